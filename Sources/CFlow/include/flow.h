@@ -26,6 +26,35 @@
  */
 typedef struct FlowHandle FlowHandle;
 
+/**
+ * Result callback type for async operations
+ */
+typedef void (*ResultCallback)(bool success, const char *result, void *context);
+
+/**
+ * Where a transform callback writes the rewritten text
+ */
+typedef struct FlowTransformOutput FlowTransformOutput;
+
+/**
+ * Callback for flow_add_transform
+ *
+ * Called with the text, the app name (NULL if unknown), the writing mode (0-3, as
+ * in flow_set_app_mode) and the registered context. To change the text, pass the
+ * result to flow_transform_output_set before returning; otherwise it is kept.
+ */
+typedef void (*TransformCallback)(const char *text,
+                                  const char *app_name,
+                                  uint8_t mode,
+                                  void *context,
+                                  struct FlowTransformOutput *output);
+
+/**
+ * Progress callback for flow_drain_pending: `completed` of the `total` jobs that were
+ * queued when the drain started have finished, successfully or not
+ */
+typedef void (*DrainProgressCallback)(size_t completed, size_t total, void *context);
+
 #ifdef __cplusplus
 extern "C" {
 #endif // __cplusplus
@@ -34,23 +63,66 @@ extern "C" {
  * Initialize the Flow engine
  *
  * Returns an opaque handle that must be passed to all other functions.
+ * A corrupt database doesn't fail init: it is backed up and replaced, see
+ * `flow_get_storage_recovery_json`.
  *
  * # Arguments
- * - `db_path` - Path to the SQLite database file, or NULL for default location
+ * - `db_path` - Path to the SQLite database file, or NULL for `flow.db` in the
+ *   data directory (`$FLOW_DATA_DIR`, or the platform's local data directory)
  *
  * # Returns
  * Opaque handle to the engine, or NULL on failure
  */
 struct FlowHandle *flow_init(const char *db_path);
 
+/**
+ * Initialize the Flow engine with a profile's database
+ *
+ * Each profile has its own corrections, shortcuts, modes and stats, stored under
+ * `profiles/<name>/` in the data directory and created on first use. A handle
+ * stays bound to its profile: to switch, call `flow_destroy` and init the other
+ * profile.
+ *
+ * # Arguments
+ * - `profile_name` - 1-64 letters, digits, '-' or '_'
+ *
+ * # Returns
+ * Opaque handle to the engine, or NULL if the name is invalid or init failed
+ */
+struct FlowHandle *flow_init_profile(const char *profile_name);
+
+/**
+ * List the profiles in the data directory
+ *
+ * # Returns
+ * JSON array of profile names, sorted, or NULL if the directory can't be read.
+ * Caller must free with `flow_free_string`.
+ */
+char *flow_list_profiles(void);
+
 /**
  * Destroy the Flow engine and free resources
+ * Background work the handle started (health checks, model loading) is aborted;
+ * the worker threads are shared with other handles and keep running. Transcriptions
+ * from flow_transcribe_async are cancelled, and their callbacks have fired by the
+ * time this returns. Open sessions are ended and their microphones released.
  */
 void flow_destroy(struct FlowHandle *handle);
 
+/**
+ * Get what happened if the database was corrupt when the handle was opened
+ * The corrupt file is moved aside and a fresh database is created, copying over
+ * every table that could still be read, so the app should tell the user.
+ * Returns JSON: {"reason": "...", "backup_path": "...", "recovered_tables": [...],
+ * "lost_tables": [...]}, or NULL if the database opened cleanly
+ * Caller must free the returned string with flow_free_string
+ */
+char *flow_get_storage_recovery_json(struct FlowHandle *handle);
+
 /**
  * Start audio recording
- * Returns true on success
+ * Returns true on success; false while a recording that reached its cap
+ * hasn't been collected with flow_stop_recording
  */
 bool flow_start_recording(struct FlowHandle *handle);
 
@@ -67,6 +139,48 @@ bool flow_start_recording(struct FlowHandle *handle);
  */
 uint64_t flow_stop_recording(struct FlowHandle *handle);
 
+/**
+ * Set the shortest recording that gets transcribed (default 300ms)
+ * Shorter recordings, like an accidental hotkey tap, return empty text without
+ * calling the transcription provider. 0 transcribes everything.
+ * Returns true on success
+ */
+bool flow_set_min_recording_ms(struct FlowHandle *handle, uint64_t min_ms);
+
+/**
+ * Set how long transcription and completion requests may take, in seconds
+ * Requests that run longer fail with a timeout error. 0 removes the limit (the default).
+ * Returns true on success
+ */
+bool flow_set_request_timeout(struct FlowHandle *handle, uint64_t timeout_secs);
+
+/**
+ * Cap how long a recording can get before it's stopped or trimmed
+ *
+ * Captured audio is kept in memory until flow_stop_recording, so a recording nobody
+ * stops would otherwise grow without bound. Applies from the next recording.
+ *
+ * # Arguments
+ * - `handle` - Engine handle
+ * - `max_secs` - Longest recording kept, in seconds (default 600)
+ * - `policy` - What happens at the cap: 0 = stop recording and keep the start
+ *   (default; flow_is_recording turns false, the microphone is released, and
+ *   flow_stop_recording still returns the audio), 1 = keep recording and keep only the
+ *   last `max_secs`
+ *
+ * # Returns
+ * true on success
+ */
+bool flow_set_max_recording(struct FlowHandle *handle, uint64_t max_secs, uint8_t policy);
+
+/**
+ * Check whether the current recording reached the length set with
+ * flow_set_max_recording
+ * Poll this while recording, or when flow_is_recording turns false without a call
+ * to flow_stop_recording. False once the recording is stopped.
+ */
+bool flow_recording_limit_reached(struct FlowHandle *handle);
+
 /**
  * Check if currently recording
  */
@@ -90,15 +204,249 @@ float flow_get_audio_level(struct FlowHandle *handle);
  */
 char *flow_transcribe(struct FlowHandle *handle, const char *app_name);
 
+/**
+ * Transcribe the recorded audio and return the result with metadata
+ *
+ * # Arguments
+ * - `handle` - Engine handle
+ * - `app_name` - Name of the current app (for mode selection), or NULL
+ *
+ * # Returns
+ * JSON object with `text`, `provider`, `transcription_ms`, `formatting_ms`,
+ * `detected_language`, `corrections_applied`, `shortcuts_triggered`, the applied
+ * `corrections` with the `corrected_text` their word positions refer to (the text
+ * before formatting, null when no corrections ran), the `edits` made to the text, as
+ * `{start, end, kind}` byte ranges with kind "shortcut", "correction", "formatting" or
+ * "transform", and the number of `repetition_loops` collapsed (caller must free with
+ * flow_free_string), or NULL on failure
+ */
+char *flow_transcribe_json(struct FlowHandle *handle, const char *app_name);
+
+/**
+ * Run the full pipeline on the given audio without recording anything
+ *
+ * Transcription, shortcuts, corrections and formatting run as in flow_transcribe_json,
+ * but nothing is written: the transcription isn't saved to the stats or history,
+ * nothing is learned, and shortcut uses and contact interactions aren't counted. The
+ * audio is used as is, so the recording buffer and the minimum recording length are
+ * ignored. For previews and for tests that don't have a microphone.
+ *
+ * # Arguments
+ * - `handle` - Engine handle
+ * - `pcm` - Mono 16-bit PCM samples
+ * - `len` - Number of samples in `pcm`
+ * - `sample_rate` - Sample rate of `pcm` in Hz
+ * - `app_name` - Name of the app to format for (for mode selection), or NULL
+ *
+ * # Returns
+ * JSON object as returned by flow_transcribe_json (caller must free with
+ * flow_free_string), or NULL on failure
+ */
+char *flow_transcribe_dry_run(struct FlowHandle *handle,
+                              const int16_t *pcm,
+                              size_t len,
+                              uint32_t sample_rate,
+                              const char *app_name);
+
+/**
+ * Transcribe the recorded audio into WebVTT captions
+ *
+ * Cues use the provider's segment timings when it reports them (OpenAI Whisper,
+ * local Whisper) and are otherwise spaced evenly over the recording. Captions are
+ * the raw transcription: shortcuts, corrections and formatting aren't applied.
+ *
+ * Unlike flow_transcribe, a recording that fails for lack of network isn't queued
+ * for flow_retry_pending, since the queue only produces processed text.
+ *
+ * # Returns
+ * WebVTT text (caller must free with flow_free_string), or NULL on failure
+ */
+char *flow_transcribe_vtt(struct FlowHandle *handle);
+
+/**
+ * Transcribe the recorded audio in the background so it can be cancelled
+ *
+ * # Arguments
+ * - `handle` - Engine handle; flow_destroy cancels the transcription and waits for
+ *   its callback
+ * - `app_name` - Name of the current app (for mode selection), or NULL
+ * - `callback` - Called once from a background thread with the processed text, or
+ *   `success` false and the error message, or "cancelled" after flow_cancel_transcription
+ * - `context` - Passed through to the callback
+ *
+ * # Returns
+ * Id to pass to flow_cancel_transcription, or 0 if there was no recorded audio (the
+ * callback is not called)
+ */
+uint64_t flow_transcribe_async(struct FlowHandle *handle,
+                               const char *app_name,
+                               ResultCallback callback,
+                               void *context);
+
+/**
+ * Cancel a transcription started with flow_transcribe_async
+ * The in-flight request is dropped and its callback fires with `success` false and
+ * "cancelled". Ids that already finished (or never existed) are ignored.
+ *
+ * # Returns
+ * true if a running transcription was cancelled
+ */
+bool flow_cancel_transcription(struct FlowHandle *handle, uint64_t id);
+
 /**
  * Retry the last transcription using cached audio
  * Returns processed text (caller must free with flow_free_string), or null on failure
  */
 char *flow_retry_last_transcription(struct FlowHandle *handle, const char *app_name);
 
+/**
+ * Retry transcriptions queued after network failures, oldest first
+ *
+ * # Arguments
+ * - `handle` - Engine handle
+ * - `callback` - Called once per attempted job with the processed text on success,
+ *   or the error message on failure (may be NULL)
+ * - `context` - Passed through to the callback
+ *
+ * Draining stops at the first network failure. Jobs that fail for any other reason
+ * stay queued for 5 attempts, or are dropped right away if their audio is unusable.
+ * Same as flow_drain_pending with one job in flight and no progress callback.
+ *
+ * # Returns
+ * Number of jobs transcribed successfully
+ */
+size_t flow_retry_pending(struct FlowHandle *handle, ResultCallback callback, void *context);
+
+/**
+ * Retry queued transcriptions with up to `max_in_flight` of them running at once
+ *
+ * # Arguments
+ * - `handle` - Engine handle
+ * - `max_in_flight` - Most jobs transcribed at the same time (at least 1)
+ * - `callback` - Called once per attempted job with the processed text on success,
+ *   or the error message on failure (may be NULL)
+ * - `progress` - Called after each finished job with the completed and total counts
+ *   (may be NULL)
+ * - `context` - Passed through to both callbacks
+ *
+ * Blocks until the queue is drained, a job fails with a network error (jobs already
+ * in flight still finish) or flow_cancel_drain is called. Jobs that weren't done
+ * stay queued, as do failed jobs with usable audio and fewer than 5 attempts. Only
+ * `max_in_flight` recordings are loaded at a time, and each one's audio is freed as
+ * soon as it's transcribed. The callbacks are never called concurrently. Only one
+ * drain can run per handle.
+ *
+ * # Returns
+ * Number of jobs transcribed successfully
+ */
+size_t flow_drain_pending(struct FlowHandle *handle,
+                          size_t max_in_flight,
+                          ResultCallback callback,
+                          DrainProgressCallback progress,
+                          void *context);
+
+/**
+ * Stop a running flow_drain_pending
+ * Jobs in flight are abandoned and, like the ones not started yet, stay queued.
+ *
+ * # Returns
+ * true if a drain was running
+ */
+bool flow_cancel_drain(struct FlowHandle *handle);
+
+/**
+ * Get the number of recordings waiting in the offline retry queue
+ */
+size_t flow_pending_count(struct FlowHandle *handle);
+
+/**
+ * Start an independent capture session, for apps that dictate into several fields
+ * Sessions record and transcribe separately from flow_start_recording and from each
+ * other. They end with flow_end_session or when the handle is destroyed.
+ *
+ * # Returns
+ * Session id (never 0)
+ */
+uint64_t flow_start_session(struct FlowHandle *handle);
+
+/**
+ * Start recording in a session
+ * Returns true on success
+ */
+bool flow_session_start_recording(struct FlowHandle *handle, uint64_t session_id);
+
+/**
+ * Stop recording in a session, keeping the audio for flow_session_transcribe
+ *
+ * # Returns
+ * Recording duration in milliseconds, or 0 on failure
+ */
+uint64_t flow_session_stop_recording(struct FlowHandle *handle, uint64_t session_id);
+
+/**
+ * Transcribe the audio recorded in a session
+ * Processing is the same as flow_transcribe: shortcuts, corrections and formatting
+ * for the given app.
+ *
+ * # Returns
+ * Processed text (caller must free with flow_free_string), or NULL on failure
+ */
+char *flow_session_transcribe(struct FlowHandle *handle, uint64_t session_id, const char *app_name);
+
+/**
+ * End a session, stopping any recording and discarding its audio
+ * Returns true if the session existed
+ */
+bool flow_end_session(struct FlowHandle *handle, uint64_t session_id);
+
+/**
+ * Register a callback that rewrites text during transcription
+ *
+ * Transforms at the same stage run in the order they were added, each getting
+ * the previous one's output. The callback runs on a background thread and must
+ * not call back into the handle.
+ *
+ * In cloud mode the worker formats the text it transcribed, so when a transform at
+ * stage 0 or 1 changes the text, the worker's formatting is dropped and the text is
+ * formatted with the completion provider chosen with flow_set_completion_provider
+ * (or left unformatted without one).
+ *
+ * # Arguments
+ * - `stage` - Runs after: 0 = transcription (before shortcuts), 1 = shortcuts,
+ *   2 = corrections (before formatting), 3 = formatting (before redaction)
+ * - `callback` - Called with each transcription's text
+ * - `context` - Passed back to the callback
+ *
+ * # Returns
+ * Id for flow_remove_transform, or 0 if the stage is invalid
+ */
+uint64_t flow_add_transform(struct FlowHandle *handle,
+                            uint8_t stage,
+                            TransformCallback callback,
+                            void *context);
+
+/**
+ * Unregister a transform added with flow_add_transform
+ * Returns true if it was registered
+ */
+bool flow_remove_transform(struct FlowHandle *handle, uint64_t id);
+
+/**
+ * Set the text a transform callback produced
+ *
+ * Only valid inside the callback, with the output it was given. The text is copied.
+ * Returns false if either pointer is NULL or the text isn't valid UTF-8.
+ */
+bool flow_transform_output_set(struct FlowTransformOutput *output, const char *text);
+
 /**
  * Add a voice shortcut
  *
+ * In cloud mode, a transcription that triggers a shortcut skips the worker's
+ * formatting, which wouldn't include the replacement, and is formatted with the
+ * completion provider chosen with flow_set_completion_provider (or left unformatted
+ * without one).
+ *
  * # Arguments
  * - `handle` - Engine handle
  * - `trigger` - Trigger phrase
@@ -115,6 +463,15 @@ bool flow_add_shortcut(struct FlowHandle *handle, const char *trigger, const cha
  */
 bool flow_remove_shortcut(struct FlowHandle *handle, const char *trigger);
 
+/**
+ * Also expand phrases that are close to a trigger, like "insert signatures" for
+ * "insert signature"
+ * `threshold` is the similarity a phrase needs (0.0-1.0, 0.85 is a good start);
+ * 0 matches exact triggers only, the default.
+ * Returns true on success
+ */
+bool flow_set_fuzzy_shortcuts(struct FlowHandle *handle, double threshold);
+
 /**
  * Get the number of shortcuts
  */
@@ -139,6 +496,55 @@ bool flow_set_app_mode(struct FlowHandle *handle, const char *app_name, uint8_t
  */
 uint8_t flow_get_app_mode(struct FlowHandle *handle, const char *app_name);
 
+/**
+ * Set the writing mode for apps without their own mode, and keep it across launches
+ * mode: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+ * Returns true on success
+ */
+bool flow_set_default_mode(struct FlowHandle *handle, uint8_t mode);
+
+/**
+ * Get the writing mode for apps without their own mode
+ * Returns: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+ */
+uint8_t flow_get_default_mode(struct FlowHandle *handle);
+
+/**
+ * Set the language dictation is formatted for, and keep it across launches
+ * The locale picks the writing mode instructions sent to the completion model and
+ * the punctuation rules applied afterwards (French spacing before ? ! : ;, Spanish
+ * ¿ and ¡), and is passed to transcription as a language hint. In cloud mode the
+ * worker formats the text, so it gets the locale with its completion request.
+ *
+ * # Arguments
+ * - `locale` - Language code such as "fr" or "es-MX", or NULL/empty to follow the
+ *   language the provider detects. Languages without their own rules use English.
+ *
+ * Returns false if the code isn't a supported language or couldn't be saved
+ */
+bool flow_set_locale(struct FlowHandle *handle, const char *locale);
+
+/**
+ * Set the completion provider and model for an app
+ *
+ * # Arguments
+ * - `handle` - Engine handle
+ * - `app_name` - Name of the app
+ * - `provider` - 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 255 = use the global provider
+ * - `model` - Model name, or NULL/empty for the provider's default
+ *
+ * Passing 255 with a NULL model clears the override.
+ * With local transcription, only apps with an override are formatted, so other
+ * apps' text never leaves the device.
+ *
+ * # Returns
+ * true on success
+ */
+bool flow_set_app_model(struct FlowHandle *handle,
+                        const char *app_name,
+                        uint8_t provider,
+                        const char *model);
+
 /**
  * Report a user edit to learn from
  *
@@ -159,6 +565,8 @@ size_t flow_correction_count(struct FlowHandle *handle);
 
 /**
  * Get all corrections as JSON
+ * Lists general corrections only; the contextual copies learned alongside them
+ * are deleted with them
  * Returns JSON array: [{"id": "...", "original": "...", "corrected": "...", "occurrences": N, "confidence": N.N}, ...]
  * Caller must free the returned string with flow_free_string
  */
@@ -176,13 +584,64 @@ bool flow_delete_correction(struct FlowHandle *handle, const char *id);
  */
 size_t flow_delete_all_corrections(struct FlowHandle *handle);
 
+/**
+ * Set the minimum similarity (0.0-1.0) for learning a word pair as a correction
+ *
+ * Lower values learn more aggressive corrections; the default is 0.7.
+ *
+ * # Returns
+ * true on success
+ */
+bool flow_set_correction_similarity_threshold(struct FlowHandle *handle, double threshold);
+
+/**
+ * Set the lowest confidence at which learned corrections are applied (0.0-1.0, default 0.55)
+ * Returns true on success
+ */
+bool flow_set_min_correction_confidence(struct FlowHandle *handle, float confidence);
+
+/**
+ * Get the minimum similarity for learning a word pair as a correction
+ */
+double flow_get_correction_similarity_threshold(struct FlowHandle *handle);
+
+/**
+ * Set how many learned corrections are kept in memory (default 10000)
+ *
+ * When there are more, the ones with the lowest confidence and least recent use
+ * are dropped from memory but kept in the database.
+ * Returns true on success
+ */
+bool flow_set_max_correction_cache_size(struct FlowHandle *handle, size_t max);
+
+/**
+ * Get how full the correction cache is
+ * Returns JSON: {"general": N, "contextual": N, "max": N}
+ * Caller must free the returned string with flow_free_string
+ */
+char *flow_get_correction_cache_json(struct FlowHandle *handle);
+
+/**
+ * Revert auto-applied corrections, restoring the words as transcribed
+ *
+ * # Arguments
+ * - `handle` - Engine handle
+ * - `text_json` - JSON object `{"text": "...", "corrections": [...]}`, where `corrections`
+ *   is the list returned by flow_transcribe_json and `text` its `corrected_text`; the
+ *   positions are word indices into that text, not the formatted output
+ *
+ * # Returns
+ * Text with the corrections undone (caller must free with flow_free_string), or NULL on error
+ */
+char *flow_revert_corrections(struct FlowHandle *handle, const char *text_json);
+
 /**
  * Validate corrections using AI (async, returns JSON)
  * Input: JSON array of {"original": "...", "corrected": "..."} pairs
  * Output: JSON array of {"original": "...", "corrected": "...", "valid": bool, "reason": "..."}
  * Caller must free the returned string with flow_free_string
  */
-char *flow_validate_corrections(struct FlowHandle *_handle, const char *corrections_json);
+char *flow_validate_corrections(struct FlowHandle *handle, const char *corrections_json);
 
 /**
  * Get total transcription time in minutes
@@ -194,6 +653,16 @@ uint64_t flow_total_transcription_minutes(struct FlowHandle *handle);
  */
 uint64_t flow_transcription_count(struct FlowHandle *handle);
 
+/**
+ * Get local usage analytics, computed on the device from stored transcriptions
+ * Returns JSON: {"total_transcriptions": N, "failed_transcriptions": N, "total_words": N,
+ * "total_duration_ms": N, "words_per_minute": F, "top_corrected_words": [{"original",
+ * "corrected", "occurrences"}], "apps": [{"app_name", "transcriptions",
+ * "total_duration_ms", "words"}], "busiest_app": "..." or null}, or NULL on error
+ * Caller must free the returned string with flow_free_string
+ */
+char *flow_get_analytics(struct FlowHandle *handle);
+
 /**
  * Free a string returned by flow functions
  */
@@ -204,6 +673,20 @@ void flow_free_string(char *s);
  */
 bool flow_is_configured(struct FlowHandle *handle);
 
+/**
+ * Verify the configured providers actually work (key valid, endpoint reachable)
+ * Runs in the background, so call it on launch rather than waiting for the first dictation.
+ * Local Whisper loads its model as part of the check, which also warms it up.
+ *
+ * # Arguments
+ * - `handle` - Engine handle
+ * - `callback` - Called once with `success` true if every provider passed, and a JSON
+ *   report `{"transcription": {"provider", "ok", "error"}, "completion": {...} or null}`.
+ *   Never called if the handle is destroyed before the check finishes.
+ * - `context` - Passed through to the callback
+ */
+void flow_health_check(struct FlowHandle *handle, ResultCallback callback, void *context);
+
 /**
  * Set the currently active app (call from Swift when app switches)
  * Returns the suggested writing mode for the app
@@ -250,6 +733,19 @@ char *flow_get_recent_transcriptions_json(struct FlowHandle *handle, size_t limi
  */
 char *flow_get_last_error(struct FlowHandle *handle);
 
+/**
+ * Get the code of the most recent error recorded on the handle
+ * Returns: 0 = none, 1 = unconfigured, 2 = network, 3 = timeout, 4 = auth,
+ * 5 = audio, 6 = storage, 7 = provider, 8 = invalid input, 9 = cancelled, 10 = internal
+ */
+int32_t flow_last_error(struct FlowHandle *handle);
+
+/**
+ * Get the message of the most recent error recorded on the handle
+ * Returns null if there is none (caller must free with flow_free_string)
+ */
+char *flow_last_error_message(struct FlowHandle *handle);
+
 /**
  * Switch completion provider (loads API key from database)
  * provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
@@ -282,11 +778,11 @@ char *flow_get_api_key(struct FlowHandle *handle, uint8_t provider);
  * Set transcription mode (local or remote)
  * use_local: true for local Whisper, false for cloud provider
  * whisper_model: Model selection (only used when use_local = true)
- *   0 = Turbo (~15MB) - quantized, ultra-fast, lowest memory
- *   1 = Fast (~39MB) - fast, lower accuracy
- *   2 = Balanced (~142MB) - good speed/accuracy balance
- *   3 = Quality (~400MB) - great accuracy, still fast [recommended]
- *   4 = Best (~750MB) - best quality available
+ *   0 = Turbo (~41MB) - quantized, ultra-fast, lowest memory
+ *   1 = Fast (~151MB) - fast, lower accuracy
+ *   2 = Balanced (~290MB) - good speed/accuracy balance
+ *   3 = Quality (~789MB) - great accuracy, still fast [recommended]
+ *   4 = Best (~1.5GB) - best quality available
  * Returns true on success, false on failure
  */
 bool flow_set_transcription_mode(struct FlowHandle *handle, bool use_local, uint8_t whisper_model);
@@ -306,6 +802,19 @@ bool flow_get_transcription_mode(struct FlowHandle *handle,
  */
 bool flow_is_model_loading(struct FlowHandle *handle);
 
+/**
+ * Load the local Whisper model and warm it up in the background, so the first
+ * dictation doesn't wait for it. Transcriptions started meanwhile share the same load.
+ * Cloud providers have nothing to load and succeed immediately.
+ *
+ * # Arguments
+ * - `handle` - Engine handle
+ * - `callback` - Called once with `success` and an error message (empty on success).
+ *   Never called if the handle is destroyed before loading finishes.
+ * - `context` - Passed through to the callback
+ */
+void flow_preload_model(struct FlowHandle *handle, ResultCallback callback, void *context);
+
 /**
  * Legacy function - prefer flow_set_transcription_mode
  * Enable local Whisper transcription with Metal + Accelerate acceleration
@@ -316,7 +825,10 @@ bool flow_enable_local_whisper(struct FlowHandle *handle, uint8_t model);
 
 /**
  * Get available Whisper models as JSON (caller must free with flow_free_string)
- * Returns JSON array with model info including id, name, description, size, and flags
+ * Returns JSON array with model info including id, name, description, download size,
+ * estimated RAM (`ram_mb`) and flags
+ * Only Turbo is quantized (8-bit tiny); 5-bit weights aren't offered since none are
+ * published
  */
 char *flow_get_whisper_models_json(void);
 
@@ -325,6 +837,13 @@ char *flow_get_whisper_models_json(void);
  */
 char *flow_get_shortcuts_json(struct FlowHandle *handle);
 
+/**
+ * Get each shortcut's trigger count and last use as JSON (caller must free with flow_free_string)
+ * Format: [{"trigger", "replacement", "use_count", "last_used_at", "enabled"}], most used first;
+ * `last_used_at` is an RFC 3339 timestamp or null if the shortcut never fired
+ */
+char *flow_shortcut_stats(struct FlowHandle *handle);
+
 /**
  * Get active contact name from Messages.app window
  * Returns C string with contact name, or null if not available
@@ -466,6 +985,122 @@ bool flow_set_openai_base_url(struct FlowHandle *handle, const char *url);
  */
 char *flow_get_openai_base_url(struct FlowHandle *handle);
 
+/**
+ * Add a word or phrase to mask in transcriptions (case-insensitive, whole words)
+ * Returns true on success
+ */
+bool flow_add_redacted_word(struct FlowHandle *handle, const char *word);
+
+/**
+ * Remove a masked word or phrase
+ * Returns true if the word was removed
+ */
+bool flow_remove_redacted_word(struct FlowHandle *handle, const char *word);
+
+/**
+ * Add a regex pattern to mask in transcriptions (case-insensitive)
+ * Returns false if the pattern is invalid
+ */
+bool flow_add_redaction_pattern(struct FlowHandle *handle, const char *pattern);
+
+/**
+ * Remove a masked regex pattern
+ * Returns true if the pattern was removed
+ */
+bool flow_remove_redaction_pattern(struct FlowHandle *handle, const char *pattern);
+
+/**
+ * Set where masking runs in the pipeline
+ *
+ * Before formatting masks right after corrections, so the completion model
+ * never sees the words. In cloud mode the worker formats server-side, so its
+ * text is masked as soon as it comes back.
+ *
+ * # Arguments
+ * - `handle` - Engine handle
+ * - `stage` - 0 = before formatting, 1 = after formatting (default)
+ *
+ * # Returns
+ * true on success
+ */
+bool flow_set_redaction_stage(struct FlowHandle *handle, uint8_t stage);
+
+/**
+ * Enable or disable collapsing runs of whitespace in the final output
+ * Newlines are always preserved. Returns true on success
+ */
+bool flow_set_normalize_whitespace(struct FlowHandle *handle, bool enabled);
+
+/**
+ * Enable or disable converting smart quotes to straight quotes in the final output
+ * Returns true on success
+ */
+bool flow_set_normalize_quotes(struct FlowHandle *handle, bool enabled);
+
+/**
+ * Enable or disable trimming trailing spaces from each line of the final output
+ * Returns true on success
+ */
+bool flow_set_trim_trailing_spaces(struct FlowHandle *handle, bool enabled);
+
+/**
+ * Enable or disable converting spoken formatting commands ("new line", "new paragraph",
+ * "bullet", "numbered item") into line breaks and list markers
+ *
+ * In cloud mode, a transcription with commands in it skips the worker's formatting,
+ * which would lose the structure, and is formatted with the completion provider
+ * chosen with flow_set_completion_provider (or left unformatted without one).
+ * Returns true on success
+ */
+bool flow_set_dictation_commands(struct FlowHandle *handle, bool enabled);
+
+/**
+ * Enable or disable dropping transcriptions that are only a known silence
+ * hallucination (e.g. "Thanks for watching!")
+ * Returns true on success
+ */
+bool flow_set_hallucination_filter(struct FlowHandle *handle, bool enabled);
+
+/**
+ * Replace the hallucination phrase list
+ * `phrases_json` is a JSON array of strings, or NULL to restore the built-in list
+ * Returns true on success
+ */
+bool flow_set_hallucination_phrases(struct FlowHandle *handle, const char *phrases_json);
+
+/**
+ * Set how many times a phrase may repeat back to back before it's collapsed as a
+ * transcription loop ("the the the the" -> "the"), 0 to keep every repeat
+ * Transcriptions with collapsed loops report them in `repetition_loops`
+ * Returns true on success
+ */
+bool flow_set_max_phrase_repeats(struct FlowHandle *handle, uint32_t max_repeats);
+
+/**
+ * Add a word or name that transcription should recognize ("Kubernetes", "FlowWhispr")
+ * Only used once the vocabulary prompt is enabled with flow_set_vocabulary_prompt
+ * Returns false if the word is blank, already in the vocabulary or couldn't be saved
+ */
+bool flow_add_vocabulary_word(struct FlowHandle *handle, const char *word);
+
+/**
+ * Remove a word from the vocabulary (ignoring case)
+ * Returns true if the word was removed
+ */
+bool flow_remove_vocabulary_word(struct FlowHandle *handle, const char *word);
+
+/**
+ * Get the vocabulary as a JSON array of strings, oldest first
+ * Caller must free the returned string with flow_free_string; null on error
+ */
+char *flow_get_vocabulary_json(struct FlowHandle *handle);
+
+/**
+ * Enable or disable priming transcription with the vocabulary (off by default)
+ * Returns true on success
+ */
+bool flow_set_vocabulary_prompt(struct FlowHandle *handle, bool enabled);
+
 #ifdef __cplusplus
 }  // extern "C"
 #endif  // __cplusplus
@@ -0,0 +1,9 @@
+-- Redaction filter terms (masked words and custom regex patterns)
+
+CREATE TABLE IF NOT EXISTS redaction_terms (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    term TEXT NOT NULL,
+    is_regex INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE(term, is_regex)
+);
@@ -0,0 +1,8 @@
+-- Per-app completion provider/model overrides
+
+CREATE TABLE IF NOT EXISTS app_model_overrides (
+    app_name TEXT PRIMARY KEY,
+    provider TEXT,
+    model TEXT,
+    updated_at TEXT NOT NULL
+);
@@ -0,0 +1,14 @@
+-- Offline queue of recordings whose cloud transcription failed with a network error
+
+CREATE TABLE IF NOT EXISTS pending_transcriptions (
+    id TEXT PRIMARY KEY,
+    audio BLOB NOT NULL,
+    sample_rate INTEGER NOT NULL,
+    app_name TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_pending_transcriptions_created_at
+    ON pending_transcriptions(created_at);
@@ -0,0 +1,3 @@
+-- When each shortcut last fired, for surfacing unused shortcuts
+
+ALTER TABLE shortcuts ADD COLUMN last_used_at TEXT;
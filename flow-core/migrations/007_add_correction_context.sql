@@ -0,0 +1,34 @@
+-- Preceding word for context-sensitive corrections ("there" -> "their" only after "fix")
+-- '' means the correction applies anywhere. SQLite can't alter a UNIQUE constraint,
+-- so the table is rebuilt to include the context in it. The rebuild runs in one
+-- transaction so a failure part way through leaves the old table untouched.
+
+BEGIN;
+
+DROP TABLE IF EXISTS corrections_new;
+
+CREATE TABLE corrections_new (
+    id TEXT PRIMARY KEY,
+    original TEXT NOT NULL,
+    corrected TEXT NOT NULL,
+    context TEXT NOT NULL DEFAULT '',
+    occurrences INTEGER NOT NULL DEFAULT 1,
+    confidence REAL NOT NULL DEFAULT 0.5,
+    source TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    observed_source TEXT,
+    UNIQUE(original, corrected, context)
+);
+
+INSERT INTO corrections_new (id, original, corrected, occurrences, confidence, source, created_at, updated_at, observed_source)
+    SELECT id, original, corrected, occurrences, confidence, source, created_at, updated_at, observed_source
+    FROM corrections;
+
+DROP TABLE corrections;
+ALTER TABLE corrections_new RENAME TO corrections;
+
+CREATE INDEX IF NOT EXISTS idx_corrections_original ON corrections(original);
+CREATE INDEX IF NOT EXISTS idx_corrections_confidence ON corrections(confidence DESC);
+
+COMMIT;
@@ -0,0 +1,8 @@
+-- Domain words and names used to prime transcription ("Kubernetes", "FlowWhispr")
+-- Stored with the user's casing, since that's what the model should produce.
+
+CREATE TABLE IF NOT EXISTS vocabulary (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    term TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
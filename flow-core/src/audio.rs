@@ -3,12 +3,29 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{Device, Sample, SampleFormat, SizedSample, Stream, StreamConfig};
 use parking_lot::Mutex;
+use std::collections::VecDeque;
 use std::sync::Arc;
-use tracing::{debug, error, info};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
 
 use crate::AudioData;
 use crate::error::{Error, Result};
 
+/// Length of each frame emitted by `AudioCapture::frame_stream`
+pub const FRAME_DURATION_MS: u32 = 100;
+
+/// Frames the stream channel holds before new ones are dropped (5 seconds of audio)
+const FRAME_CHANNEL_CAPACITY: usize = 50;
+
+/// Longest recording kept in memory unless `AudioCapture::with_max_duration` says
+/// otherwise (about 38 MB of samples at 16 kHz)
+pub const DEFAULT_MAX_RECORDING_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// Receiver for fixed-size 16-bit PCM frames
+pub type AudioFrameReceiver = mpsc::Receiver<AudioData>;
+
 /// Audio capture configuration
 #[derive(Debug, Clone)]
 pub struct AudioCaptureConfig {
@@ -36,6 +53,39 @@ pub enum CaptureState {
     Idle,
     Recording,
     Paused,
+    /// Stopped at the maximum duration under `OverflowPolicy::Stop`: the microphone
+    /// is released and the audio up to the cap is kept until `stop`
+    Full,
+}
+
+/// What a recording does when it reaches its maximum duration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Stop recording and keep the start of the recording
+    #[default]
+    Stop,
+    /// Keep recording, dropping the oldest audio so only the last
+    /// maximum duration is kept
+    DropOldest,
+}
+
+impl OverflowPolicy {
+    /// Parse a policy from its stored string form
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "stop" => Some(Self::Stop),
+            "drop_oldest" => Some(Self::DropOldest),
+            _ => None,
+        }
+    }
+
+    /// String form used for persistence
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Stop => "stop",
+            Self::DropOldest => "drop_oldest",
+        }
+    }
 }
 
 /// Handles audio capture from the default input device
@@ -46,8 +96,14 @@ pub struct AudioCapture {
     input_channels: u16,
     sample_format: SampleFormat,
     state: Arc<Mutex<CaptureState>>,
-    buffer: Arc<Mutex<Vec<f32>>>,
-    stream: Option<Stream>,
+    buffer: Arc<Mutex<CaptureBuffer>>,
+    frames: Arc<Mutex<Option<FrameSink>>>,
+    /// Frames the current (or last) frame stream dropped
+    dropped_frames: Arc<AtomicUsize>,
+    /// Shared with the stream's callback, which releases it when the recording is full
+    stream: Arc<Mutex<Option<Stream>>>,
+    max_duration: Duration,
+    overflow_policy: OverflowPolicy,
 }
 
 impl AudioCapture {
@@ -100,38 +156,83 @@ impl AudioCapture {
             input_channels,
             sample_format,
             state: Arc::new(Mutex::new(CaptureState::Idle)),
-            buffer: Arc::new(Mutex::new(Vec::new())),
-            stream: None,
+            buffer: Arc::new(Mutex::new(CaptureBuffer::default())),
+            frames: Arc::new(Mutex::new(None)),
+            dropped_frames: Arc::new(AtomicUsize::new(0)),
+            stream: Arc::new(Mutex::new(None)),
+            max_duration: DEFAULT_MAX_RECORDING_DURATION,
+            overflow_policy: OverflowPolicy::default(),
         })
     }
 
+    /// Cap how much audio is kept in memory (default `DEFAULT_MAX_RECORDING_DURATION`)
+    ///
+    /// Takes effect from the next `start`. What happens at the cap depends on the
+    /// overflow policy; `limit_reached` reports whether it was hit.
+    pub fn with_max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = max_duration;
+        self
+    }
+
+    /// Set what happens when the recording reaches its maximum duration
+    pub fn with_overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow_policy = policy;
+        self
+    }
+
+    /// Whether the current recording reached its maximum duration
+    ///
+    /// Under `OverflowPolicy::Stop` the state is also `CaptureState::Full` from then on.
+    pub fn limit_reached(&self) -> bool {
+        self.buffer.lock().overflowed
+    }
+
     /// Start recording audio
+    ///
+    /// Fails on a full recording, whose audio is kept until `stop` takes it.
     pub fn start(&mut self) -> Result<()> {
-        if *self.state.lock() == CaptureState::Recording {
-            return Ok(());
+        match *self.state.lock() {
+            CaptureState::Recording => return Ok(()),
+            CaptureState::Full => {
+                return Err(Error::Audio(
+                    "Recording reached its maximum length; stop it to collect the audio"
+                        .to_string(),
+                ));
+            }
+            CaptureState::Idle | CaptureState::Paused => {}
         }
 
         let buffer = Arc::clone(&self.buffer);
+        let frames = Arc::clone(&self.frames);
         let state = Arc::clone(&self.state);
+        let slot = Arc::clone(&self.stream);
 
         // clear buffer
-        buffer.lock().clear();
+        let max_samples =
+            (self.max_duration.as_millis() * u128::from(self.config.sample_rate) / 1000) as usize;
+        buffer
+            .lock()
+            .reset(max_samples.max(1), self.overflow_policy);
 
         let err_fn = |err| error!("Audio stream error: {}", err);
 
         let stream = match self.sample_format {
-            SampleFormat::F32 => self.build_stream::<f32>(buffer, state, err_fn)?,
-            SampleFormat::I16 => self.build_stream::<i16>(buffer, state, err_fn)?,
-            SampleFormat::U16 => self.build_stream::<u16>(buffer, state, err_fn)?,
-            SampleFormat::I24 => self.build_stream::<cpal::I24>(buffer, state, err_fn)?,
-            SampleFormat::U24 => self.build_stream::<cpal::U24>(buffer, state, err_fn)?,
-            SampleFormat::I32 => self.build_stream::<i32>(buffer, state, err_fn)?,
-            SampleFormat::U32 => self.build_stream::<u32>(buffer, state, err_fn)?,
-            SampleFormat::I8 => self.build_stream::<i8>(buffer, state, err_fn)?,
-            SampleFormat::U8 => self.build_stream::<u8>(buffer, state, err_fn)?,
-            SampleFormat::F64 => self.build_stream::<f64>(buffer, state, err_fn)?,
-            SampleFormat::I64 => self.build_stream::<i64>(buffer, state, err_fn)?,
-            SampleFormat::U64 => self.build_stream::<u64>(buffer, state, err_fn)?,
+            SampleFormat::F32 => self.build_stream::<f32>(buffer, frames, state, slot, err_fn)?,
+            SampleFormat::I16 => self.build_stream::<i16>(buffer, frames, state, slot, err_fn)?,
+            SampleFormat::U16 => self.build_stream::<u16>(buffer, frames, state, slot, err_fn)?,
+            SampleFormat::I24 => {
+                self.build_stream::<cpal::I24>(buffer, frames, state, slot, err_fn)?
+            }
+            SampleFormat::U24 => {
+                self.build_stream::<cpal::U24>(buffer, frames, state, slot, err_fn)?
+            }
+            SampleFormat::I32 => self.build_stream::<i32>(buffer, frames, state, slot, err_fn)?,
+            SampleFormat::U32 => self.build_stream::<u32>(buffer, frames, state, slot, err_fn)?,
+            SampleFormat::I8 => self.build_stream::<i8>(buffer, frames, state, slot, err_fn)?,
+            SampleFormat::U8 => self.build_stream::<u8>(buffer, frames, state, slot, err_fn)?,
+            SampleFormat::F64 => self.build_stream::<f64>(buffer, frames, state, slot, err_fn)?,
+            SampleFormat::I64 => self.build_stream::<i64>(buffer, frames, state, slot, err_fn)?,
+            SampleFormat::U64 => self.build_stream::<u64>(buffer, frames, state, slot, err_fn)?,
             _ => {
                 return Err(Error::Audio(format!(
                     "Unsupported sample format: {:?}",
@@ -144,7 +245,7 @@ impl AudioCapture {
             .play()
             .map_err(|e| Error::Audio(format!("Failed to start stream: {e}")))?;
 
-        self.stream = Some(stream);
+        *self.stream.lock() = Some(stream);
         *self.state.lock() = CaptureState::Recording;
 
         info!("Audio capture started");
@@ -156,10 +257,10 @@ impl AudioCapture {
         *self.state.lock() = CaptureState::Idle;
 
         // drop the stream to stop recording
-        self.stream = None;
+        self.stream.lock().take();
+        self.close_frame_stream();
 
-        let samples = std::mem::take(&mut *self.buffer.lock());
-        let audio_data = self.samples_to_pcm(&samples);
+        let audio_data = self.buffer.lock().take_pcm();
 
         info!("Audio capture stopped, {} bytes captured", audio_data.len());
         Ok(audio_data)
@@ -168,15 +269,48 @@ impl AudioCapture {
     /// Stop recording without draining the buffer
     pub fn stop_stream(&mut self) -> Result<()> {
         *self.state.lock() = CaptureState::Idle;
-        self.stream = None;
+        self.stream.lock().take();
+        self.close_frame_stream();
         info!("Audio capture stopped (buffer retained)");
         Ok(())
     }
 
     /// Drain buffered audio into PCM data without touching the stream
     pub fn take_buffered_audio(&mut self) -> AudioData {
-        let samples = std::mem::take(&mut *self.buffer.lock());
-        self.samples_to_pcm(&samples)
+        self.buffer.lock().take_pcm()
+    }
+
+    /// Stream captured audio as fixed-size PCM frames of `FRAME_DURATION_MS` each
+    ///
+    /// While a frame stream is open the capture buffer only keeps enough audio for
+    /// the level meter, so memory stays bounded however long the recording runs and
+    /// `stop` returns no audio. Stopping sends any trailing partial frame and closes
+    /// the channel. Frames are dropped if the receiver falls more than
+    /// `FRAME_CHANNEL_CAPACITY` frames behind; `dropped_frames` counts them.
+    pub fn frame_stream(&mut self) -> AudioFrameReceiver {
+        let frame_samples = (self.config.sample_rate * FRAME_DURATION_MS / 1000) as usize;
+        let (sender, receiver) = mpsc::channel(FRAME_CHANNEL_CAPACITY);
+
+        let sink = FrameSink::new(sender, frame_samples.max(1));
+        self.dropped_frames = Arc::clone(&sink.dropped);
+        let previous = self.frames.lock().replace(sink);
+        if let Some(previous) = previous {
+            previous.finish();
+        }
+        receiver
+    }
+
+    /// Frames the current (or last) frame stream dropped because its receiver fell behind
+    pub fn dropped_frames(&self) -> usize {
+        self.dropped_frames.load(Ordering::Relaxed)
+    }
+
+    /// Flush and close the frame stream, if one is open
+    fn close_frame_stream(&mut self) {
+        let sink = self.frames.lock().take();
+        if let Some(sink) = sink {
+            sink.finish();
+        }
     }
 
     /// Pause recording (keeps stream alive but stops buffering)
@@ -198,7 +332,7 @@ impl AudioCapture {
 
     /// Get current buffer duration in milliseconds
     pub fn buffer_duration_ms(&self) -> u64 {
-        let samples = self.buffer.lock().len();
+        let samples = self.buffer.lock().samples.len();
         (samples as u64 * 1000) / (self.config.sample_rate as u64 * self.config.channels as u64)
     }
 
@@ -210,19 +344,20 @@ impl AudioCapture {
     /// Get current audio level (RMS amplitude) from the last 50ms of audio
     /// Returns a value between 0.0 and 1.0
     pub fn current_audio_level(&self) -> f32 {
-        let buffer = self.buffer.lock();
+        let buffer = &self.buffer.lock().samples;
         if buffer.is_empty() {
             return 0.0;
         }
 
         // Calculate how many samples represent 50ms
-        let samples_per_50ms = (self.config.sample_rate as usize / 20).max(1);
+        let samples_per_50ms = level_window(self.config.sample_rate);
         let start_idx = buffer.len().saturating_sub(samples_per_50ms);
-        let recent_samples = &buffer[start_idx..];
+        let recent_samples = buffer.range(start_idx..);
+        let count = recent_samples.len();
 
         // Calculate RMS (root mean square) for perceived loudness
-        let sum_squares: f32 = recent_samples.iter().map(|&s| s * s).sum();
-        let rms = (sum_squares / recent_samples.len() as f32).sqrt();
+        let sum_squares: f32 = recent_samples.map(|&s| s * s).sum();
+        let rms = (sum_squares / count as f32).sqrt();
 
         // Amplify a bit for visual effect (typical speech is quite quiet)
         (rms * 3.0).min(1.0)
@@ -230,8 +365,10 @@ impl AudioCapture {
 
     fn build_stream<T>(
         &self,
-        buffer: Arc<Mutex<Vec<f32>>>,
+        buffer: Arc<Mutex<CaptureBuffer>>,
+        frames: Arc<Mutex<Option<FrameSink>>>,
         state: Arc<Mutex<CaptureState>>,
+        slot: Arc<Mutex<Option<Stream>>>,
         err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
     ) -> Result<Stream>
     where
@@ -240,6 +377,7 @@ impl AudioCapture {
     {
         let channels = self.input_channels as usize;
         let stream_config = self.stream_config.clone();
+        let window = level_window(self.config.sample_rate);
 
         self.device
             .build_input_stream(
@@ -249,48 +387,194 @@ impl AudioCapture {
                         return;
                     }
 
+                    let mut guard = buffer.lock();
+                    let buf = &mut guard.samples;
+                    let start = buf.len();
                     if channels == 1 {
-                        buffer
-                            .lock()
-                            .extend(data.iter().map(|sample| sample.to_sample::<f32>()));
+                        buf.extend(data.iter().map(|sample| sample.to_sample::<f32>()));
                     } else {
-                        let mut buf = buffer.lock();
                         for frame in data.chunks_exact(channels) {
                             let mut sum = 0.0f32;
                             for sample in frame {
                                 sum += sample.to_sample::<f32>();
                             }
-                            buf.push(sum / channels as f32);
+                            buf.push_back(sum / channels as f32);
                         }
                     }
+
+                    if let Some(sink) = frames.lock().as_mut() {
+                        sink.push(&buf.make_contiguous()[start..]);
+                        // the frames carry the audio now, keep only what the level meter needs
+                        let excess = buf.len().saturating_sub(window);
+                        buf.drain(..excess);
+                    } else if guard.enforce_limit() {
+                        *state.lock() = CaptureState::Full;
+                        // turn the microphone off rather than keep discarding its audio
+                        release_off_thread(&slot, &state);
+                    }
                 },
                 err_fn,
                 None,
             )
             .map_err(|e| Error::Audio(format!("Failed to build stream: {e}")))
     }
-
-    /// Convert f32 samples to 16-bit PCM bytes
-    fn samples_to_pcm(&self, samples: &[f32]) -> AudioData {
-        samples
-            .iter()
-            .flat_map(|&sample| {
-                // clamp and convert to i16
-                let clamped = sample.clamp(-1.0, 1.0);
-                let pcm = (clamped * 32767.0) as i16;
-                pcm.to_le_bytes()
-            })
-            .collect()
-    }
 }
 
 impl Drop for AudioCapture {
     fn drop(&mut self) {
         *self.state.lock() = CaptureState::Idle;
-        self.stream = None;
+        self.stream.lock().take();
+        self.close_frame_stream();
+    }
+}
+
+/// Drop what's in `slot` on a new thread, unless the capture has left `Full` by then
+///
+/// A stream's callback can't tear down its own stream, since dropping it waits for
+/// the callback to return. Checking the state keeps a stream started after a quick
+/// stop from being taken instead.
+fn release_off_thread<T: Send + 'static>(
+    slot: &Arc<Mutex<Option<T>>>,
+    state: &Arc<Mutex<CaptureState>>,
+) {
+    let slot = Arc::clone(slot);
+    let state = Arc::clone(state);
+    std::thread::spawn(move || {
+        let released = {
+            let state = state.lock();
+            if *state == CaptureState::Full {
+                slot.lock().take()
+            } else {
+                None
+            }
+        };
+        // dropped without the state lock, which the callback takes
+        if released.is_some() {
+            info!("Audio capture stopped at its maximum length (buffer retained)");
+        }
+    });
+}
+
+/// Samples recorded so far, kept under a cap so a recording nobody stops can't
+/// exhaust memory
+#[derive(Default)]
+struct CaptureBuffer {
+    samples: VecDeque<f32>,
+    max_samples: usize,
+    policy: OverflowPolicy,
+    /// Whether the cap was reached since the last `reset`
+    overflowed: bool,
+}
+
+impl CaptureBuffer {
+    /// Empty the buffer for a new recording with the given cap
+    fn reset(&mut self, max_samples: usize, policy: OverflowPolicy) {
+        self.samples.clear();
+        self.max_samples = max_samples;
+        self.policy = policy;
+        self.overflowed = false;
+    }
+
+    /// Bring the buffer back under its cap after samples were added
+    ///
+    /// Returns true if recording should stop.
+    fn enforce_limit(&mut self) -> bool {
+        let excess = self.samples.len().saturating_sub(self.max_samples);
+        if excess == 0 {
+            return false;
+        }
+        if !self.overflowed {
+            warn!(
+                "Recording reached its maximum of {} samples ({:?})",
+                self.max_samples, self.policy
+            );
+            self.overflowed = true;
+        }
+
+        match self.policy {
+            OverflowPolicy::Stop => {
+                self.samples.truncate(self.max_samples);
+                true
+            }
+            OverflowPolicy::DropOldest => {
+                self.samples.drain(..excess);
+                false
+            }
+        }
+    }
+
+    /// Take the buffered samples as PCM data
+    fn take_pcm(&mut self) -> AudioData {
+        let mut samples = std::mem::take(&mut self.samples);
+        samples_to_pcm(samples.make_contiguous())
+    }
+}
+
+/// Splits captured samples into fixed-size PCM frames and sends them down a channel
+struct FrameSink {
+    sender: mpsc::Sender<AudioData>,
+    frame_samples: usize,
+    /// Samples waiting for a full frame, always shorter than `frame_samples`
+    pending: Vec<f32>,
+    /// Shared with `AudioCapture` so callers can see the losses
+    dropped: Arc<AtomicUsize>,
+}
+
+impl FrameSink {
+    fn new(sender: mpsc::Sender<AudioData>, frame_samples: usize) -> Self {
+        Self {
+            sender,
+            frame_samples,
+            pending: Vec::with_capacity(frame_samples),
+            dropped: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Add samples, sending every completed frame without blocking the audio thread
+    fn push(&mut self, samples: &[f32]) {
+        self.pending.extend_from_slice(samples);
+
+        let complete = self.pending.len() - self.pending.len() % self.frame_samples;
+        for frame in self.pending[..complete].chunks_exact(self.frame_samples) {
+            if self.sender.try_send(samples_to_pcm(frame)).is_err() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.pending.drain(..complete);
+    }
+
+    /// Send the trailing partial frame and close the channel
+    fn finish(self) {
+        if !self.pending.is_empty() && self.sender.try_send(samples_to_pcm(&self.pending)).is_err()
+        {
+            warn!("Audio frame stream closed with a full channel, last frame dropped");
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            warn!("Audio frame stream dropped {} frames", dropped);
+        }
     }
 }
 
+/// Number of samples used for the audio level meter (50ms)
+fn level_window(sample_rate: u32) -> usize {
+    (sample_rate as usize / 20).max(1)
+}
+
+/// Convert f32 samples to 16-bit PCM bytes
+fn samples_to_pcm(samples: &[f32]) -> AudioData {
+    samples
+        .iter()
+        .flat_map(|&sample| {
+            // clamp and convert to i16
+            let clamped = sample.clamp(-1.0, 1.0);
+            let pcm = (clamped * 32767.0) as i16;
+            pcm.to_le_bytes()
+        })
+        .collect()
+}
+
 fn select_supported_config(
     ranges: &[cpal::SupportedStreamConfigRange],
     preferred_rate: u32,
@@ -408,4 +692,175 @@ mod tests {
         let half_neg = i16::from_le_bytes([pcm[4], pcm[5]]);
         assert!((half_neg + 16383).abs() < 2);
     }
+
+    /// Push `seconds` of a 440Hz tone through a sink in callback-sized chunks, calling
+    /// `after_chunk` after each one
+    fn capture_tone(
+        sink: &mut FrameSink,
+        sample_rate: u32,
+        seconds: u32,
+        mut after_chunk: impl FnMut(),
+    ) {
+        let total = (sample_rate * seconds) as usize;
+        let samples: Vec<f32> = (0..total)
+            .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / sample_rate as f32).sin() * 0.5)
+            .collect();
+
+        // odd-sized chunks, like a real device callback
+        for chunk in samples.chunks(441) {
+            sink.push(chunk);
+            assert!(sink.pending.len() < sink.frame_samples);
+            after_chunk();
+        }
+    }
+
+    #[test]
+    fn test_frame_stream_thirty_seconds() {
+        let sample_rate = 16000;
+        let frame_samples = (sample_rate * FRAME_DURATION_MS / 1000) as usize;
+        let (sender, mut receiver) = mpsc::channel(FRAME_CHANNEL_CAPACITY);
+        let mut sink = FrameSink::new(sender, frame_samples);
+        let dropped = Arc::clone(&sink.dropped);
+
+        // a receiver that keeps up, draining after every callback
+        let mut frames = 0;
+        capture_tone(&mut sink, sample_rate, 30, || {
+            while let Ok(frame) = receiver.try_recv() {
+                assert_eq!(frame.len(), frame_samples * 2);
+                frames += 1;
+            }
+        });
+        sink.finish();
+
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+        assert_eq!(frames, 300);
+        assert_eq!(
+            receiver.try_recv(),
+            Err(mpsc::error::TryRecvError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn test_frame_stream_stop_flushes_partial_frame() {
+        let (sender, mut receiver) = mpsc::channel(FRAME_CHANNEL_CAPACITY);
+        let mut sink = FrameSink::new(sender, 1600);
+
+        sink.push(&[0.25; 2000]);
+        sink.finish();
+
+        assert_eq!(receiver.try_recv().unwrap().len(), 3200);
+        assert_eq!(receiver.try_recv().unwrap().len(), 800);
+        assert_eq!(
+            receiver.try_recv(),
+            Err(mpsc::error::TryRecvError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn test_frame_stream_drops_when_receiver_stalls() {
+        let (sender, mut receiver) = mpsc::channel(FRAME_CHANNEL_CAPACITY);
+        let mut sink = FrameSink::new(sender, 1600);
+
+        // nobody reads for 30 seconds, only the channel's capacity is held
+        let dropped = Arc::clone(&sink.dropped);
+        capture_tone(&mut sink, 16000, 30, || {});
+        assert_eq!(
+            dropped.load(Ordering::Relaxed),
+            300 - FRAME_CHANNEL_CAPACITY
+        );
+        sink.finish();
+        assert_eq!(
+            dropped.load(Ordering::Relaxed),
+            300 - FRAME_CHANNEL_CAPACITY
+        );
+
+        let mut frames = 0;
+        while receiver.try_recv().is_ok() {
+            frames += 1;
+        }
+        assert_eq!(frames, FRAME_CHANNEL_CAPACITY);
+    }
+
+    /// Record `seconds` of numbered samples into a buffer capped at `max_seconds`, in
+    /// callback-sized chunks, stopping when the buffer says to
+    fn record_capped(policy: OverflowPolicy, seconds: usize, max_seconds: usize) -> CaptureBuffer {
+        let sample_rate = 1000;
+        let mut buffer = CaptureBuffer::default();
+        buffer.reset(max_seconds * sample_rate, policy);
+
+        let samples: Vec<f32> = (0..seconds * sample_rate).map(|i| i as f32).collect();
+        for chunk in samples.chunks(97) {
+            buffer.samples.extend(chunk);
+            let stop = buffer.enforce_limit();
+            assert!(buffer.samples.len() <= max_seconds * sample_rate);
+            if stop {
+                break;
+            }
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_capped_buffer_stops_at_limit() {
+        let buffer = record_capped(OverflowPolicy::Stop, 60, 5);
+
+        assert!(buffer.overflowed);
+        // the start of the recording is kept
+        assert_eq!(buffer.samples.len(), 5_000);
+        assert_eq!(buffer.samples.front(), Some(&0.0));
+        assert_eq!(buffer.samples.back(), Some(&4_999.0));
+
+        let under_cap = record_capped(OverflowPolicy::Stop, 4, 5);
+        assert!(!under_cap.overflowed);
+        assert_eq!(under_cap.samples.len(), 4_000);
+    }
+
+    #[test]
+    fn test_capped_buffer_keeps_last_audio() {
+        let mut buffer = record_capped(OverflowPolicy::DropOldest, 60, 5);
+
+        assert!(buffer.overflowed);
+        // the last five seconds are kept, in order
+        assert_eq!(buffer.samples.len(), 5_000);
+        assert_eq!(buffer.samples.front(), Some(&55_000.0));
+        assert_eq!(buffer.samples.back(), Some(&59_999.0));
+        assert_eq!(buffer.take_pcm().len(), 10_000);
+
+        buffer.reset(10, OverflowPolicy::Stop);
+        assert!(!buffer.overflowed);
+        assert!(buffer.samples.is_empty());
+    }
+
+    #[test]
+    fn test_release_off_thread_empties_slot() {
+        let value = Arc::new(());
+        let slot = Arc::new(Mutex::new(Some(Arc::clone(&value))));
+        let state = Arc::new(Mutex::new(CaptureState::Full));
+
+        release_off_thread(&slot, &state);
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while Arc::strong_count(&value) > 1 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(slot.lock().is_none());
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn test_release_off_thread_keeps_a_restarted_stream() {
+        let slot = Arc::new(Mutex::new(Some(())));
+        let state = Arc::new(Mutex::new(CaptureState::Recording));
+
+        release_off_thread(&slot, &state);
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(slot.lock().is_some());
+    }
+
+    #[test]
+    fn test_overflow_policy_round_trips() {
+        for policy in [OverflowPolicy::Stop, OverflowPolicy::DropOldest] {
+            assert_eq!(OverflowPolicy::parse(policy.as_str()), Some(policy));
+        }
+        assert_eq!(OverflowPolicy::parse("ring"), None);
+    }
 }
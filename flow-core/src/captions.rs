@@ -0,0 +1,157 @@
+//! Subtitle and caption export
+//!
+//! Formats timed transcription segments as SRT or WebVTT. Providers that report
+//! timing (OpenAI Whisper's verbose_json, local Whisper's 30 second windows) are
+//! used as-is; otherwise cues are synthesized by spreading the words evenly over
+//! the audio duration, which is rough but keeps captions roughly in sync.
+
+use crate::providers::TranscriptionSegment;
+
+/// Most words in a synthesized cue, about two lines of captions
+pub const MAX_CUE_WORDS: usize = 8;
+
+/// Format segments as SubRip (.srt) subtitles
+///
+/// ```text
+/// 1
+/// 00:00:00,000 --> 00:00:01,500
+/// Hello there.
+/// ```
+pub fn to_srt(segments: &[TranscriptionSegment]) -> String {
+    let mut srt = String::new();
+    for (i, segment) in cues(segments).enumerate() {
+        srt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            format_timestamp(segment.start_ms, ','),
+            format_timestamp(segment.end_ms, ','),
+            cue_text(&segment.text)
+        ));
+    }
+    srt
+}
+
+/// Format segments as WebVTT (.vtt) captions
+pub fn to_vtt(segments: &[TranscriptionSegment]) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for segment in cues(segments) {
+        vtt.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            format_timestamp(segment.start_ms, '.'),
+            format_timestamp(segment.end_ms, '.'),
+            cue_text(&segment.text)
+        ));
+    }
+    vtt
+}
+
+/// Split text into cues of up to `MAX_CUE_WORDS` words, breaking early at sentence
+/// ends, with each word given an equal share of `duration_ms`
+pub fn synthesize_segments(text: &str, duration_ms: u64) -> Vec<TranscriptionSegment> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let total = words.len() as u64;
+    let time_at = |word_index: usize| duration_ms * word_index as u64 / total.max(1);
+
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for (i, word) in words.iter().enumerate() {
+        let cue_len = i + 1 - start;
+        let last = i + 1 == words.len();
+        if cue_len == MAX_CUE_WORDS || word.ends_with(['.', '!', '?']) || last {
+            segments.push(TranscriptionSegment {
+                text: words[start..=i].join(" "),
+                start_ms: time_at(start),
+                end_ms: time_at(i + 1),
+                confidence: None,
+            });
+            start = i + 1;
+        }
+    }
+    segments
+}
+
+/// Segments with something to show
+fn cues(segments: &[TranscriptionSegment]) -> impl Iterator<Item = &TranscriptionSegment> {
+    segments.iter().filter(|s| !s.text.trim().is_empty())
+}
+
+/// Cue text without blank lines, which would end the cue early in both formats
+fn cue_text(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `HH:MM:SS,mmm` (SRT) or `HH:MM:SS.mmm` (WebVTT)
+fn format_timestamp(ms: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(text: &str, start_ms: u64, end_ms: u64) -> TranscriptionSegment {
+        TranscriptionSegment {
+            text: text.to_string(),
+            start_ms,
+            end_ms,
+            confidence: None,
+        }
+    }
+
+    #[test]
+    fn test_srt_format() {
+        let segments = vec![
+            segment("Hello there.", 0, 1500),
+            segment("  ", 1500, 1600),
+            segment("See you\n\nin an hour.", 3_723_004, 3_725_010),
+        ];
+
+        assert_eq!(
+            to_srt(&segments),
+            "1\n00:00:00,000 --> 00:00:01,500\nHello there.\n\n\
+             2\n01:02:03,004 --> 01:02:05,010\nSee you\nin an hour.\n\n"
+        );
+    }
+
+    #[test]
+    fn test_vtt_format() {
+        let segments = vec![segment("Hello there.", 61_000, 62_250)];
+
+        assert_eq!(
+            to_vtt(&segments),
+            "WEBVTT\n\n00:01:01.000 --> 00:01:02.250\nHello there.\n\n"
+        );
+        assert_eq!(to_vtt(&[]), "WEBVTT\n\n");
+    }
+
+    #[test]
+    fn test_synthesized_segments_are_evenly_spaced() {
+        let segments =
+            synthesize_segments("Hi. one two three four five six seven eight nine", 10_000);
+
+        let cues: Vec<_> = segments
+            .iter()
+            .map(|s| (s.text.as_str(), s.start_ms, s.end_ms))
+            .collect();
+        assert_eq!(
+            cues,
+            vec![
+                ("Hi.", 0, 1000),
+                ("one two three four five six seven eight", 1000, 9000),
+                ("nine", 9000, 10_000),
+            ]
+        );
+        assert!(synthesize_segments("   ", 5000).is_empty());
+    }
+}
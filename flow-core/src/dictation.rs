@@ -0,0 +1,287 @@
+//! Spoken formatting commands
+//!
+//! Converts dictated commands like "new line" or "bullet point" into actual line
+//! breaks and list markers. Example: "first item new line second item" ->
+//! "first item\nsecond item". A command preceded by an article or determiner
+//! ("add a new line of products") is treated as content and left alone.
+
+use crate::tokenizer::word_spans;
+
+/// A spoken formatting command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DictationCommand {
+    /// "new line" -> line break
+    NewLine,
+    /// "new paragraph" -> blank line
+    NewParagraph,
+    /// "bullet" / "bullet point" -> "- " list item
+    Bullet,
+    /// "numbered item" -> "1. ", "2. ", ... list item
+    NumberedItem,
+}
+
+/// Spoken phrases for each command, longest first so "bullet point" wins over "bullet"
+const COMMAND_PHRASES: &[(&[&str], DictationCommand)] = &[
+    (&["new", "paragraph"], DictationCommand::NewParagraph),
+    (&["new", "line"], DictationCommand::NewLine),
+    (&["newline"], DictationCommand::NewLine),
+    (&["bullet", "point"], DictationCommand::Bullet),
+    (&["numbered", "item"], DictationCommand::NumberedItem),
+    (&["bullet"], DictationCommand::Bullet),
+];
+
+/// Words that make a following command phrase read as content ("a new line", "the bullet")
+const CONTENT_MARKERS: &[&str] = &[
+    "a", "an", "the", "this", "that", "these", "those", "my", "your", "our", "their", "his", "her",
+    "its", "another", "each", "every", "any", "no", "one", "some", "first", "last",
+];
+
+/// Converts spoken formatting commands into text structure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DictationProcessor {
+    enabled: bool,
+}
+
+impl DictationProcessor {
+    /// Create an enabled processor
+    pub fn new() -> Self {
+        Self { enabled: true }
+    }
+
+    /// Create a processor that leaves text untouched
+    pub fn disabled() -> Self {
+        Self { enabled: false }
+    }
+
+    /// Check whether commands are converted
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable command conversion
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Replace spoken commands with line breaks and list markers
+    /// Returns the processed text and the commands that were applied, in order
+    pub fn process(&self, text: &str) -> (String, Vec<DictationCommand>) {
+        if !self.enabled {
+            return (text.to_string(), Vec::new());
+        }
+
+        let spans = word_spans(text);
+        let words: Vec<&str> = spans
+            .iter()
+            .map(|&(start, end)| &text[start..end])
+            .collect();
+        let mut result = String::with_capacity(text.len());
+        let mut applied = Vec::new();
+        let mut next_number = 1;
+        // byte offset of the end of the last word handled
+        let mut copied = 0;
+        let mut after_command = false;
+        let mut i = 0;
+
+        while i < words.len() {
+            let previous = i.checked_sub(1).map(|p| normalize(words[p]));
+            let is_content = previous.is_some_and(|p| CONTENT_MARKERS.contains(&p.as_str()));
+
+            let matched = if is_content {
+                None
+            } else {
+                match_command(&words[i..])
+            };
+
+            let Some((command, len)) = matched else {
+                // keep the original whitespace between words, except right after a command
+                if !after_command {
+                    result.push_str(&text[copied..spans[i].0]);
+                }
+                result.push_str(words[i]);
+                copied = spans[i].1;
+                after_command = false;
+                i += 1;
+                continue;
+            };
+
+            // drop the pause punctuation transcription puts around commands ("item, new line, ...")
+            let trimmed = result.trim_end_matches([' ', ',', ';', ':']).len();
+            result.truncate(trimmed);
+
+            match command {
+                DictationCommand::NewLine => result.push('\n'),
+                DictationCommand::NewParagraph => {
+                    result.push_str("\n\n");
+                    next_number = 1;
+                }
+                DictationCommand::Bullet => {
+                    start_list_line(&mut result);
+                    result.push_str("- ");
+                }
+                DictationCommand::NumberedItem => {
+                    start_list_line(&mut result);
+                    result.push_str(&format!("{next_number}. "));
+                    next_number += 1;
+                }
+            }
+
+            applied.push(command);
+            i += len;
+            copied = spans[i - 1].1;
+            after_command = true;
+        }
+
+        if after_command {
+            // a trailing command leaves nothing to hang the marker on
+            let trimmed = result.trim_end_matches(' ').len();
+            result.truncate(trimmed);
+        } else {
+            result.push_str(&text[copied..]);
+        }
+
+        (result, applied)
+    }
+}
+
+impl Default for DictationProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lowercase a word and strip surrounding punctuation for phrase matching
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// Match a command phrase at the start of words, returning it and its word count
+fn match_command(words: &[&str]) -> Option<(DictationCommand, usize)> {
+    COMMAND_PHRASES.iter().find_map(|(phrase, command)| {
+        let matches = phrase.len() <= words.len()
+            && phrase
+                .iter()
+                .zip(words)
+                .enumerate()
+                .all(|(j, (expected, word))| {
+                    // punctuation is only allowed after the last word of the phrase
+                    let core = if j + 1 == phrase.len() {
+                        normalize(word)
+                    } else {
+                        word.to_lowercase()
+                    };
+                    core == *expected
+                });
+        matches.then_some((*command, phrase.len()))
+    })
+}
+
+/// Put a list marker on its own line unless the text already starts one
+fn start_list_line(result: &mut String) {
+    if !result.is_empty() && !result.ends_with('\n') {
+        result.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_line() {
+        let processor = DictationProcessor::new();
+
+        let (text, applied) = processor.process("first item new line second item");
+        assert_eq!(text, "first item\nsecond item");
+        assert_eq!(applied, vec![DictationCommand::NewLine]);
+        assert_eq!(text.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_new_paragraph_with_punctuation() {
+        let processor = DictationProcessor::new();
+
+        let (text, _) = processor.process("Thanks for the update. New paragraph. See you soon.");
+        assert_eq!(text, "Thanks for the update.\n\nSee you soon.");
+
+        let (text, _) = processor.process("hello, new line, world");
+        assert_eq!(text, "hello\nworld");
+    }
+
+    #[test]
+    fn test_bullets() {
+        let processor = DictationProcessor::new();
+
+        let (text, applied) =
+            processor.process("groceries bullet point milk bullet eggs bullet point bread");
+        assert_eq!(text, "groceries\n- milk\n- eggs\n- bread");
+        assert_eq!(applied.len(), 3);
+
+        let (text, _) = processor.process("bullet first thing");
+        assert_eq!(text, "- first thing");
+    }
+
+    #[test]
+    fn test_numbered_items() {
+        let processor = DictationProcessor::new();
+
+        let (text, _) = processor.process(
+            "steps numbered item wake up numbered item code new paragraph numbered item sleep",
+        );
+        assert_eq!(text, "steps\n1. wake up\n2. code\n\n1. sleep");
+    }
+
+    #[test]
+    fn test_content_use_is_left_alone() {
+        let processor = DictationProcessor::new();
+
+        let input = "We launched a new line of products and the bullet train was late";
+        let (text, applied) = processor.process(input);
+        assert_eq!(text, input);
+        assert!(applied.is_empty());
+    }
+
+    #[test]
+    fn test_disabled_keeps_false_positive_prone_text() {
+        let processor = DictationProcessor::disabled();
+
+        let input = "Stand in the new line, then bullet point your notes";
+        let (text, applied) = processor.process(input);
+        assert_eq!(text, input);
+        assert!(applied.is_empty());
+    }
+
+    #[test]
+    fn test_case_insensitive_and_trailing_command() {
+        let processor = DictationProcessor::new();
+
+        let (text, _) = processor.process("Done New Line");
+        assert_eq!(text, "Done\n");
+    }
+
+    #[test]
+    fn test_toggle() {
+        let mut processor = DictationProcessor::new();
+        processor.set_enabled(false);
+        assert!(!processor.is_enabled());
+
+        let (text, _) = processor.process("a new line b");
+        assert_eq!(text, "a new line b");
+    }
+
+    #[test]
+    fn test_preserves_existing_whitespace() {
+        let processor = DictationProcessor::new();
+
+        let (text, _) = processor.process("Best,\nJason  new line P.S. call me ");
+        assert_eq!(text, "Best,\nJason\nP.S. call me ");
+    }
+
+    #[test]
+    fn test_empty_text() {
+        let (text, applied) = DictationProcessor::new().process("");
+        assert_eq!(text, "");
+        assert!(applied.is_empty());
+    }
+}
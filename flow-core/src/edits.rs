@@ -0,0 +1,286 @@
+//! Which parts of the output each pipeline stage changed
+//!
+//! Shortcuts, corrections and formatting all rewrite the transcription, and later
+//! stages move or replace what earlier ones wrote. `EditTracker` follows the text
+//! through every stage: each new version is diffed against the previous one word
+//! by word, unchanged words keep the stage that last wrote them and changed words
+//! are attributed to the current stage. Words are matched ignoring trailing
+//! punctuation, so a period added after a shortcut doesn't hide the shortcut. The
+//! result is a list of byte ranges in the final text, which editors can use to
+//! highlight what Flow touched.
+
+use serde::{Deserialize, Serialize};
+
+use crate::tokenizer::word_spans;
+
+/// Largest diff table before a stage is treated as one whole rewrite, which keeps
+/// long texts from taking quadratic time and memory
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// What changed a span of text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditKind {
+    /// A voice shortcut expanded its trigger
+    Shortcut,
+    /// A learned correction replaced a word
+    Correction,
+    /// Formatting commands, the completion model or style enforcement
+    Formatting,
+    /// A transform registered by the app
+    Transform,
+}
+
+/// A changed span, as byte offsets into the final text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub kind: EditKind,
+}
+
+/// Text being rewritten by the pipeline, with the stage that last changed each word
+#[derive(Debug, Clone)]
+pub struct EditTracker {
+    text: String,
+    /// Kind of each word in `word_spans(text)`, None if it came from the transcription
+    kinds: Vec<Option<EditKind>>,
+}
+
+impl EditTracker {
+    /// Start tracking from the raw transcription
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            kinds: vec![None; word_spans(text).len()],
+        }
+    }
+
+    /// The current text
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Record the next version of the text
+    ///
+    /// Words that differ from the previous version are attributed to `kind`, except
+    /// that a word which only gained or lost trailing punctuation keeps the stage
+    /// that wrote it (raw transcription words take `kind`). Stages
+    /// that aren't reported (`None`, like quote normalization) keep the kind of the
+    /// words they replaced when those all agree, so normalizing a shortcut's
+    /// replacement doesn't hide it.
+    pub fn apply(&mut self, text: &str, kind: Option<EditKind>) {
+        let old = words(&self.text);
+        let new = words(text);
+        let old_keys: Vec<&str> = old.iter().map(|word| match_key(word)).collect();
+        let new_keys: Vec<&str> = new.iter().map(|word| match_key(word)).collect();
+
+        let mut kinds = Vec::with_capacity(new.len());
+        let mut old_index = 0;
+        let mut new_index = 0;
+        for (old_match, new_match) in matching_words(&old_keys, &new_keys)
+            .into_iter()
+            .chain([(old.len(), new.len())])
+        {
+            let replaced = &self.kinds[old_index..old_match];
+            let inserted_kind = kind.or_else(|| shared_kind(replaced));
+            kinds.extend(std::iter::repeat_n(inserted_kind, new_match - new_index));
+            if let Some(&kept) = self.kinds.get(old_match) {
+                let repunctuated = old[old_match] != new[new_match];
+                kinds.push(if repunctuated { kept.or(kind) } else { kept });
+            }
+            old_index = old_match + 1;
+            new_index = new_match + 1;
+        }
+
+        self.text = text.to_string();
+        self.kinds = kinds;
+    }
+
+    /// Changed spans of the current text, with neighbouring words of the same kind
+    /// merged into one span
+    pub fn edits(&self) -> Vec<TextEdit> {
+        let mut edits: Vec<TextEdit> = Vec::new();
+        let mut previous_changed = false;
+        for (&(start, end), &kind) in word_spans(&self.text).iter().zip(&self.kinds) {
+            let Some(kind) = kind else {
+                previous_changed = false;
+                continue;
+            };
+            match edits.last_mut() {
+                Some(last) if previous_changed && last.kind == kind => last.end = end,
+                _ => edits.push(TextEdit { start, end, kind }),
+            }
+            previous_changed = true;
+        }
+        edits
+    }
+}
+
+/// The kind every changed word in `kinds` has, if they agree
+fn shared_kind(kinds: &[Option<EditKind>]) -> Option<EditKind> {
+    let mut kinds = kinds.iter().flatten();
+    let first = *kinds.next()?;
+    kinds.all(|&kind| kind == first).then_some(first)
+}
+
+/// The whitespace-separated words of text
+fn words(text: &str) -> Vec<&str> {
+    word_spans(text)
+        .into_iter()
+        .map(|(start, end)| &text[start..end])
+        .collect()
+}
+
+/// A word without its trailing punctuation, for matching ("file." and "file" match)
+fn match_key(word: &str) -> &str {
+    let key = word.trim_end_matches(|c: char| !c.is_alphanumeric());
+    if key.is_empty() { word } else { key }
+}
+
+/// Index pairs of the longest common subsequence of two word lists
+fn matching_words(old: &[&str], new: &[&str]) -> Vec<(usize, usize)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    let (rows, cols) = (old_middle.len() + 1, new_middle.len() + 1);
+    if rows * cols <= MAX_DIFF_CELLS {
+        // lengths[i][j] = LCS length of old_middle[i..] and new_middle[j..]
+        let mut lengths = vec![0u32; rows * cols];
+        for i in (0..old_middle.len()).rev() {
+            for j in (0..new_middle.len()).rev() {
+                lengths[i * cols + j] = if old_middle[i] == new_middle[j] {
+                    lengths[(i + 1) * cols + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * cols + j].max(lengths[i * cols + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < old_middle.len() && j < new_middle.len() {
+            if old_middle[i] == new_middle[j] {
+                pairs.push((prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if lengths[(i + 1) * cols + j] >= lengths[i * cols + j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+
+    let old_suffix = old.len() - suffix;
+    let new_suffix = new.len() - suffix;
+    pairs.extend((0..suffix).map(|i| (old_suffix + i, new_suffix + i)));
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(tracker: &EditTracker) -> Vec<(&str, EditKind)> {
+        tracker
+            .edits()
+            .iter()
+            .map(|e| (&tracker.text()[e.start..e.end], e.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_stages_are_attributed() {
+        let mut tracker = EditTracker::new("send teh file to my email");
+        tracker.apply(
+            "send teh file to jason@example.com",
+            Some(EditKind::Shortcut),
+        );
+        tracker.apply(
+            "send the file to jason@example.com",
+            Some(EditKind::Correction),
+        );
+        tracker.apply(
+            "Send the file to jason@example.com.",
+            Some(EditKind::Formatting),
+        );
+
+        assert_eq!(tracker.text(), "Send the file to jason@example.com.");
+        assert_eq!(
+            spans(&tracker),
+            vec![
+                ("Send", EditKind::Formatting),
+                ("the", EditKind::Correction),
+                ("jason@example.com.", EditKind::Shortcut),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_added_punctuation_keeps_earlier_stage() {
+        let mut tracker = EditTracker::new("teh meeting is at my address");
+        tracker.apply("teh meeting is at 1 Main St", Some(EditKind::Shortcut));
+        tracker.apply("the meeting is at 1 Main St", Some(EditKind::Correction));
+        tracker.apply("The meeting, is at 1 Main St.", Some(EditKind::Formatting));
+
+        assert_eq!(
+            spans(&tracker),
+            vec![
+                ("The meeting,", EditKind::Formatting),
+                ("1 Main St.", EditKind::Shortcut),
+            ]
+        );
+
+        // trailing punctuation on a corrected word stays a correction
+        let mut tracker = EditTracker::new("recieve it");
+        tracker.apply("receive it", Some(EditKind::Correction));
+        tracker.apply("receive, it", Some(EditKind::Formatting));
+        assert_eq!(spans(&tracker), vec![("receive,", EditKind::Correction)]);
+    }
+
+    #[test]
+    fn test_replacement_containing_trigger_word() {
+        let mut tracker = EditTracker::new("open my drive folder");
+        tracker.apply("open drive.example.com/me folder", Some(EditKind::Shortcut));
+
+        assert_eq!(
+            spans(&tracker),
+            vec![("drive.example.com/me", EditKind::Shortcut)]
+        );
+    }
+
+    #[test]
+    fn test_unreported_stage_keeps_kind() {
+        let mut tracker = EditTracker::new("sig please");
+        tracker.apply(
+            "Best,  \u{201C}Jason\u{201D} please",
+            Some(EditKind::Shortcut),
+        );
+        tracker.apply("Best, \"Jason\" please", None);
+
+        assert_eq!(
+            spans(&tracker),
+            vec![("Best, \"Jason\"", EditKind::Shortcut)]
+        );
+    }
+
+    #[test]
+    fn test_offsets_are_char_boundaries() {
+        let mut tracker = EditTracker::new("café naïve");
+        tracker.apply("café naïve ☕", Some(EditKind::Shortcut));
+
+        for edit in tracker.edits() {
+            assert!(tracker.text().is_char_boundary(edit.start));
+            assert!(tracker.text().is_char_boundary(edit.end));
+        }
+        assert_eq!(spans(&tracker), vec![("☕", EditKind::Shortcut)]);
+    }
+}
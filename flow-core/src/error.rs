@@ -21,11 +21,25 @@ pub enum Error {
     Storage(#[from] rusqlite::Error),
 
     #[error("Network error: {0}")]
-    Network(#[from] reqwest::Error),
+    Network(reqwest::Error),
+
+    #[error("Request timed out: {0}")]
+    Timeout(String),
+
+    #[error("Authentication failed: {0}")]
+    Auth(String),
 
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
+    #[error("Unusable provider response (HTTP {status}, {issue}): {snippet}")]
+    Provider {
+        status: u16,
+        issue: BodyIssue,
+        /// The start of the response body
+        snippet: String,
+    },
+
     #[error("Invalid configuration: {0}")]
     Config(String),
 
@@ -40,4 +54,153 @@ pub enum Error {
 
     #[error("VAD error: {0}")]
     Vad(String),
+
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+/// Why a provider's response body couldn't be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BodyIssue {
+    /// Nothing in the body, often a connection cut short by a proxy
+    Empty,
+    /// An HTML page, usually a proxy or gateway error page
+    Html,
+    /// Truncated JSON, or JSON that isn't the expected response
+    InvalidJson(String),
+}
+
+impl std::fmt::Display for BodyIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty body"),
+            Self::Html => write!(f, "HTML page instead of JSON"),
+            Self::InvalidJson(reason) => write!(f, "invalid JSON: {reason}"),
+        }
+    }
+}
+
+/// Stable error codes reported over FFI, grouped by what the user can do about them
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// No error recorded
+    None = 0,
+    /// Provider, API key or model not set up
+    Unconfigured = 1,
+    /// Server unreachable
+    Network = 2,
+    /// Request took too long
+    Timeout = 3,
+    /// API key rejected or subscription required
+    Auth = 4,
+    /// Recording failed or there was no audio
+    Audio = 5,
+    /// Database or file error
+    Storage = 6,
+    /// Provider returned an error or an unusable response
+    Provider = 7,
+    /// Bad argument from the caller
+    InvalidInput = 8,
+    /// The operation was cancelled
+    Cancelled = 9,
+    /// Anything else
+    Internal = 10,
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout(e.to_string())
+        } else {
+            Self::Network(e)
+        }
+    }
+}
+
+impl Error {
+    /// Error for a provider response with a non-success status: 401 and 403 mean
+    /// the API key was rejected, anything else is wrapped with `other`
+    pub fn from_status(
+        status: reqwest::StatusCode,
+        message: String,
+        other: fn(String) -> Self,
+    ) -> Self {
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+                Self::Auth(message)
+            }
+            _ => other(message),
+        }
+    }
+
+    /// Whether the error came from the network being unreachable (connection, timeout),
+    /// as opposed to the server rejecting the request (e.g. 401) or a bad response
+    pub fn is_network(&self) -> bool {
+        match self {
+            Self::Network(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+            Self::Timeout(_) => true,
+            _ => false,
+        }
+    }
+
+    /// The FFI error code for this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Audio(_) | Self::Vad(_) => ErrorCode::Audio,
+            Self::Transcription(_)
+            | Self::Completion(_)
+            | Self::Serialization(_)
+            | Self::Provider { .. } => ErrorCode::Provider,
+            Self::Storage(_) | Self::Io(_) => ErrorCode::Storage,
+            Self::Network(e) if e.is_timeout() => ErrorCode::Timeout,
+            Self::Network(e) => match e.status() {
+                Some(reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN) => {
+                    ErrorCode::Auth
+                }
+                Some(_) => ErrorCode::Provider,
+                None if e.is_decode() || e.is_body() => ErrorCode::Provider,
+                None => ErrorCode::Network,
+            },
+            Self::Timeout(_) => ErrorCode::Timeout,
+            Self::Auth(_) | Self::SubscriptionRequired(_) => ErrorCode::Auth,
+            Self::Config(_) => ErrorCode::InvalidInput,
+            Self::ProviderNotConfigured(_) => ErrorCode::Unconfigured,
+            Self::Cancelled => ErrorCode::Cancelled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(
+            Error::ProviderNotConfigured("OpenAI API key not set".into()).code(),
+            ErrorCode::Unconfigured
+        );
+        assert_eq!(Error::Audio("no device".into()).code(), ErrorCode::Audio);
+        assert_eq!(Error::Cancelled.code(), ErrorCode::Cancelled);
+        assert_eq!(ErrorCode::Unconfigured as i32, 1);
+    }
+
+    #[test]
+    fn test_rejected_key_is_auth() {
+        let error = Error::from_status(
+            reqwest::StatusCode::UNAUTHORIZED,
+            "bad key".into(),
+            Error::Transcription,
+        );
+        assert_eq!(error.code(), ErrorCode::Auth);
+
+        let error = Error::from_status(
+            reqwest::StatusCode::BAD_GATEWAY,
+            "upstream down".into(),
+            Error::Transcription,
+        );
+        assert!(matches!(error, Error::Transcription(_)));
+        assert_eq!(error.code(), ErrorCode::Provider);
+    }
 }
@@ -6,38 +6,63 @@
 // FFI functions necessarily work with raw pointers - this is expected behavior
 #![allow(clippy::not_unsafe_ptr_arg_deref)]
 
+use std::collections::{HashMap, VecDeque};
 use std::ffi::{CStr, CString};
+use std::future::Future;
 use std::os::raw::{c_char, c_void};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ptr;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-use parking_lot::Mutex;
-use serde::Serialize;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
 use tokio::runtime::Runtime;
-use tracing::{debug, error};
+use tokio::task::AbortHandle;
+use tracing::{debug, error, warn};
 
 use crate::apps::AppTracker;
-use crate::audio::{AudioCapture, CaptureState};
+use crate::audio::{AudioCapture, CaptureState, DEFAULT_MAX_RECORDING_DURATION, OverflowPolicy};
 use crate::contacts::{ContactClassifier, ContactInput};
-use crate::learning::LearningEngine;
+use crate::dictation::DictationProcessor;
+use crate::edits::{EditKind, EditTracker, TextEdit};
+use crate::error::ErrorCode;
+use crate::hallucination::{HallucinationFilter, RepetitionDetector};
+use crate::learning::{AppliedCorrection, LearningEngine};
+use crate::locale::Locale;
 use crate::macos_messages::MessagesDetector;
 use crate::modes::{StyleLearner, WritingMode, WritingModeEngine};
+use crate::normalizer::TextNormalizer;
+use crate::numbers::normalize_numbers;
+use crate::profiles;
 use crate::providers::{
     AutoTranscriptionProvider, CompletionProvider, GeminiCompletionProvider,
     GeminiTranscriptionProvider, LocalWhisperTranscriptionProvider, OpenAICompletionProvider,
     OpenAITranscriptionProvider, OpenRouterCompletionProvider, TranscriptionCompletionParams,
     TranscriptionProvider, TranscriptionRequest, WhisperModel,
 };
+use crate::redaction::{RedactionFilter, RedactionStage};
 use crate::shortcuts::ShortcutsEngine;
+use crate::stats::Analytics;
 use crate::storage::{
     SETTING_AUTO_REWRITING_ENABLED, SETTING_CLOUD_TRANSCRIPTION_PROVIDER,
-    SETTING_COMPLETION_PROVIDER, SETTING_GEMINI_API_KEY, SETTING_LOCAL_WHISPER_MODEL,
-    SETTING_OPENAI_API_KEY, SETTING_OPENAI_BASE_URL, SETTING_OPENROUTER_API_KEY,
-    SETTING_USE_LOCAL_TRANSCRIPTION, Storage,
+    SETTING_COMPLETION_PROVIDER, SETTING_DICTATION_COMMANDS_ENABLED,
+    SETTING_FUZZY_SHORTCUT_THRESHOLD, SETTING_GEMINI_API_KEY, SETTING_HALLUCINATION_FILTER_ENABLED,
+    SETTING_HALLUCINATION_PHRASES, SETTING_LOCAL_WHISPER_MODEL, SETTING_LOCALE,
+    SETTING_MAX_CORRECTION_CACHE_SIZE, SETTING_MAX_PHRASE_REPEATS, SETTING_MAX_RECORDING_SECS,
+    SETTING_MIN_CORRECTION_CONFIDENCE, SETTING_MIN_CORRECTION_SIMILARITY, SETTING_MIN_RECORDING_MS,
+    SETTING_NORMALIZE_QUOTES, SETTING_NORMALIZE_WHITESPACE, SETTING_OPENAI_API_KEY,
+    SETTING_OPENAI_BASE_URL, SETTING_OPENROUTER_API_KEY, SETTING_RECORDING_OVERFLOW_POLICY,
+    SETTING_REDACTION_STAGE, SETTING_REQUEST_TIMEOUT_SECS, SETTING_TRIM_TRAILING_SPACES,
+    SETTING_USE_LOCAL_TRANSCRIPTION, SETTING_VOCABULARY_PROMPT_ENABLED, Storage,
+};
+use crate::style::{enforce_locale_punctuation, enforce_style};
+use crate::transforms::{TextTransform, TransformContext, TransformRegistry, TransformStage};
+use crate::types::{
+    AppCategory, AppModelOverride, PendingTranscription, Shortcut, Transcription,
+    TranscriptionHistoryEntry, TranscriptionStatus,
 };
-use crate::types::{Shortcut, Transcription, TranscriptionHistoryEntry, TranscriptionStatus};
 
 /// Log with timestamp
 macro_rules! log_with_time {
@@ -50,18 +75,29 @@ macro_rules! log_with_time {
 }
 
 /// Opaque handle to the Flow engine
+///
+/// Settings and providers are behind locks or atomics (some inside the engines
+/// themselves), since setters can run while flow_transcribe_async or
+/// flow_drain_pending jobs are reading them.
 pub struct FlowHandle {
-    runtime: Runtime,
+    runtime: HandleRuntime,
     storage: Storage,
     audio: Mutex<Option<AudioCapture>>,
     last_audio: Mutex<Option<crate::AudioData>>,
     last_audio_sample_rate: Mutex<Option<u32>>,
-    last_error: Mutex<Option<String>>,
-    transcription: Arc<dyn TranscriptionProvider>,
-    completion: Arc<dyn CompletionProvider>,
+    last_error: Mutex<Option<(ErrorCode, String)>>,
+    transcription: RwLock<Arc<dyn TranscriptionProvider>>,
+    completion: RwLock<Arc<dyn CompletionProvider>>,
     shortcuts: ShortcutsEngine,
+    dictation: Mutex<DictationProcessor>,
     learning: LearningEngine,
-    modes: Mutex<WritingModeEngine>,
+    redaction: RedactionFilter,
+    normalizer: Mutex<TextNormalizer>,
+    /// Transforms the app registered to run between the built-in stages
+    transforms: TransformRegistry,
+    hallucinations: Mutex<HallucinationFilter>,
+    repetition: Mutex<RepetitionDetector>,
+    modes: WritingModeEngine,
     app_tracker: AppTracker,
     style_learner: Mutex<StyleLearner>,
     is_model_loading: Arc<AtomicBool>,
@@ -71,6 +107,59 @@ pub struct FlowHandle {
     /// Temporary storage for audio between stop and transcribe (ensures mic is fully released)
     pending_audio: Mutex<Option<crate::AudioData>>,
     pending_sample_rate: Mutex<Option<u32>>,
+    /// Recording length flow_stop_recording measured for the pending audio
+    pending_duration_ms: Mutex<Option<u64>>,
+    /// Recordings shorter than this are accidental taps and aren't transcribed
+    min_recording_ms: AtomicU64,
+    /// Longest recording kept in memory, and what happens when it's reached
+    max_recording: Mutex<Duration>,
+    overflow_policy: Mutex<OverflowPolicy>,
+    /// Prime transcription with the stored vocabulary
+    vocabulary_prompt_enabled: AtomicBool,
+    /// Transcription and completion requests fail after this long (None = no limit)
+    request_timeout: Mutex<Option<Duration>>,
+    /// Cancellation tokens of in-flight flow_transcribe_async calls, by id
+    transcriptions: Mutex<HashMap<u64, Arc<CancellationToken>>>,
+    next_transcription_id: AtomicU64,
+    /// Independent captures started with flow_start_session, by id
+    sessions: Mutex<HashMap<u64, Session>>,
+    next_session_id: AtomicU64,
+    /// Language set with flow_set_locale (None = use the detected language)
+    locale: Mutex<Option<Locale>>,
+    /// Cancellation token of the running flow_drain_pending, if any
+    drain: Mutex<Option<Arc<CancellationToken>>>,
+}
+
+impl FlowHandle {
+    /// Current transcription provider
+    fn transcription(&self) -> Arc<dyn TranscriptionProvider> {
+        Arc::clone(&self.transcription.read())
+    }
+
+    /// Replace the transcription provider; requests already made keep the old one
+    fn set_transcription(&self, provider: Arc<dyn TranscriptionProvider>) {
+        *self.transcription.write() = provider;
+    }
+
+    /// Current completion provider
+    fn completion(&self) -> Arc<dyn CompletionProvider> {
+        Arc::clone(&self.completion.read())
+    }
+
+    /// Replace the completion provider; requests already made keep the old one
+    fn set_completion(&self, provider: Arc<dyn CompletionProvider>) {
+        *self.completion.write() = provider;
+    }
+}
+
+/// A capture started with flow_start_session, recording independently of the
+/// handle's own capture and of other sessions
+#[derive(Default)]
+struct Session {
+    /// Live capture while recording
+    capture: Option<AudioCapture>,
+    /// Stopped recording waiting for flow_session_transcribe
+    pending: Option<PendingAudio>,
 }
 
 #[derive(Serialize)]
@@ -85,35 +174,301 @@ struct TranscriptionSummary {
     app_name: Option<String>,
 }
 
+/// Processed transcription with provider and timing metadata
+#[derive(Serialize)]
+struct TranscriptionOutcome {
+    text: String,
+    provider: String,
+    transcription_ms: u64,
+    formatting_ms: u64,
+    detected_language: Option<String>,
+    corrections_applied: usize,
+    shortcuts_triggered: usize,
+    /// Applied corrections, so the caller can revert them with flow_revert_corrections
+    corrections: Vec<AppliedCorrection>,
+    /// Text right after corrections, before formatting; `corrections` positions are
+    /// word indices into it
+    corrected_text: Option<String>,
+    /// Spans of `text` that shortcuts, corrections and formatting changed
+    edits: Vec<TextEdit>,
+    /// Phrase loops collapsed in the transcription; non-zero means it's likely garbage
+    repetition_loops: usize,
+}
+
+impl TranscriptionOutcome {
+    /// No text, for recordings that had nothing worth processing
+    fn empty(provider: &str) -> Self {
+        Self {
+            text: String::new(),
+            provider: provider.to_string(),
+            transcription_ms: 0,
+            formatting_ms: 0,
+            detected_language: None,
+            corrections_applied: 0,
+            shortcuts_triggered: 0,
+            corrections: Vec::new(),
+            corrected_text: None,
+            edits: Vec::new(),
+            repetition_loops: 0,
+        }
+    }
+}
+
+/// Input for flow_revert_corrections
+#[derive(Deserialize)]
+struct RevertCorrectionsInput {
+    text: String,
+    corrections: Vec<AppliedCorrection>,
+}
+
+/// Maximum number of recordings kept for retry after network failures
+const MAX_PENDING_TRANSCRIPTIONS: usize = 20;
+
+/// Failed retries after which a queued recording is dropped, unless the failures
+/// were the network being unreachable
+const MAX_PENDING_ATTEMPTS: u32 = 5;
+
 /// Result callback type for async operations
 pub type ResultCallback = extern "C" fn(success: bool, result: *const c_char, context: *mut c_void);
 
-fn set_last_error(handle: &FlowHandle, message: impl Into<String>) {
-    *handle.last_error.lock() = Some(message.into());
+/// Recordings shorter than this are ignored unless flow_set_min_recording_ms says otherwise
+const DEFAULT_MIN_RECORDING_MS: u64 = 300;
+
+/// Result passed to the flow_transcribe_async callback when the transcription was cancelled
+const CANCELLED_RESULT: &str = "cancelled";
+
+/// Signal for abandoning an in-flight transcription
+#[derive(Default)]
+struct CancellationToken {
+    cancelled: AtomicBool,
+    notify: tokio::sync::Notify,
 }
 
-/// Check if Whisper model files exist in the models directory
-fn check_model_files_exist(model: WhisperModel, models_dir: &std::path::Path) -> bool {
-    let (model_id, _) = model.model_id();
-    let model_name = model_id.split('/').next_back().unwrap();
+impl CancellationToken {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Run a future to completion, or drop it mid-flight (aborting any HTTP request
+    /// it owns) and return None once the token is cancelled
+    async fn run<F: Future>(&self, future: F) -> Option<F::Output> {
+        let cancelled = self.notify.notified();
+        tokio::pin!(cancelled);
+        // register before checking the flag so a cancel in between isn't missed
+        cancelled.as_mut().enable();
+        if self.is_cancelled() {
+            return None;
+        }
+
+        tokio::select! {
+            output = future => Some(output),
+            _ = cancelled => None,
+        }
+    }
+}
+
+/// Worker threads of the shared runtime. Blocking FFI calls drive their futures on the
+/// caller's thread with `block_on`, so workers only run spawned tasks and can't be starved.
+const RUNTIME_WORKER_THREADS: usize = 2;
+
+/// Runtime shared by every handle, so creating handles doesn't create thread pools
+static SHARED_RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// The process-wide runtime, built on first use
+fn shared_runtime() -> std::io::Result<&'static Runtime> {
+    if let Some(runtime) = SHARED_RUNTIME.get() {
+        return Ok(runtime);
+    }
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(RUNTIME_WORKER_THREADS)
+        .thread_name("flow-runtime")
+        .enable_all()
+        .build()?;
+    // if another thread got there first, ours is dropped and theirs is used
+    Ok(SHARED_RUNTIME.get_or_init(|| runtime))
+}
+
+/// A handle's view of the shared runtime
+///
+/// Tracks the tasks the handle spawned and aborts the ones still running when the
+/// handle is destroyed, so nothing outlives it on the shared worker threads. Jobs
+/// that block on the runtime themselves get their own thread, which is joined.
+struct HandleRuntime {
+    runtime: tokio::runtime::Handle,
+    tasks: Mutex<Vec<AbortHandle>>,
+    /// Spawned tasks that haven't finished or been dropped yet
+    active: Arc<AtomicUsize>,
+    /// Threads started with spawn_job
+    jobs: Mutex<Vec<std::thread::JoinHandle<()>>>,
+}
+
+impl HandleRuntime {
+    fn new(runtime: tokio::runtime::Handle) -> Self {
+        Self {
+            runtime,
+            tasks: Mutex::new(Vec::new()),
+            active: Arc::new(AtomicUsize::new(0)),
+            jobs: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Run a future to completion on the calling thread
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Run a future in the background on the shared worker threads
+    fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let guard = ActiveTask::new(Arc::clone(&self.active));
+        let task = self.runtime.spawn(async move {
+            let _guard = guard;
+            future.await;
+        });
+
+        let mut tasks = self.tasks.lock();
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(task.abort_handle());
+    }
+
+    /// Run a job that calls block_on itself on its own thread
+    fn spawn_job<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut jobs = self.jobs.lock();
+        jobs.retain(|job| !job.is_finished());
+        jobs.push(std::thread::spawn(job));
+    }
+
+    /// Wait for every job started with spawn_job, except the calling one
+    fn join_jobs(&self) {
+        let jobs = std::mem::take(&mut *self.jobs.lock());
+        let current = std::thread::current().id();
+        for job in jobs {
+            // a job's callback destroying the handle can't wait for itself
+            if job.thread().id() == current {
+                continue;
+            }
+            if job.join().is_err() {
+                error!("Background job panicked");
+            }
+        }
+    }
+}
+
+impl Drop for HandleRuntime {
+    fn drop(&mut self) {
+        for task in self.tasks.get_mut().drain(..) {
+            task.abort();
+        }
+    }
+}
 
-    let config_path = models_dir.join(format!("{}-config.json", model_name));
-    let tokenizer_path = models_dir.join(format!("{}-tokenizer.json", model_name));
-    let weights_path = models_dir.join(format!("{}-model.safetensors", model_name));
+/// Counts a spawned task as active until it's dropped, whether it finished or was aborted
+struct ActiveTask(Arc<AtomicUsize>);
+
+impl ActiveTask {
+    fn new(active: Arc<AtomicUsize>) -> Self {
+        active.fetch_add(1, Ordering::SeqCst);
+        Self(active)
+    }
+}
+
+impl Drop for ActiveTask {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn set_last_error(handle: &FlowHandle, code: ErrorCode, message: impl Into<String>) {
+    *handle.last_error.lock() = Some((code, message.into()));
+}
 
-    config_path.exists() && tokenizer_path.exists() && weights_path.exists()
+/// Check if Whisper model files exist in the models directory
+fn check_model_files_exist(model: WhisperModel, models_dir: &std::path::Path) -> bool {
+    model
+        .cached_files(models_dir)
+        .iter()
+        .all(|path| path.exists())
 }
 
 fn clear_last_error(handle: &FlowHandle) {
     *handle.last_error.lock() = None;
 }
 
+/// Whether dictation is going into a code editor or terminal, where identifiers and
+/// paths mustn't be "corrected"
+fn is_code_app(handle: &FlowHandle, app_name: Option<&str>) -> bool {
+    let category = match app_name {
+        Some(name) => AppCategory::from_app(name, None),
+        None => handle.app_tracker.current_category(),
+    };
+    matches!(category, AppCategory::Code | AppCategory::Terminal)
+}
+
+/// A transcription request, primed with the vocabulary if that's enabled
+fn transcription_request(
+    handle: &FlowHandle,
+    audio: crate::AudioData,
+    sample_rate: u32,
+) -> TranscriptionRequest {
+    let mut request = TranscriptionRequest::new(audio, sample_rate);
+    if let Some(locale) = *handle.locale.lock() {
+        request = request.with_language(locale.code());
+    }
+    if !handle.vocabulary_prompt_enabled.load(Ordering::SeqCst) {
+        return request;
+    }
+    match handle.storage.get_vocabulary() {
+        Ok(words) => request.with_vocabulary(&words),
+        Err(e) => {
+            error!("Failed to load vocabulary: {}", e);
+            request
+        }
+    }
+}
+
+/// Run a provider request, failing with `Error::Timeout` once the request timeout passes
+async fn with_request_timeout<T>(
+    timeout: Option<Duration>,
+    request: impl Future<Output = crate::error::Result<T>>,
+) -> crate::error::Result<T> {
+    let Some(timeout) = timeout else {
+        return request.await;
+    };
+    tokio::time::timeout(timeout, request)
+        .await
+        .unwrap_or_else(|_| {
+            Err(crate::error::Error::Timeout(format!(
+                "no response after {}s",
+                timeout.as_secs()
+            )))
+        })
+}
+
+/// `request_timeout_secs` as a timeout, where 0 means no limit
+fn request_timeout_from_secs(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
 fn estimate_duration_ms(bytes: usize, sample_rate: u32) -> u64 {
     let samples = bytes / 2;
     (samples as u64 * 1000) / sample_rate as u64
 }
 
-fn load_persisted_configuration(handle: &mut FlowHandle) {
+fn load_persisted_configuration(handle: &FlowHandle) {
     // Load all API keys
     let openai_key = handle
         .storage
@@ -190,18 +545,18 @@ fn load_persisted_configuration(handle: &mut FlowHandle) {
     match saved_completion_provider.as_deref() {
         Some("gemini") => {
             debug!("Restoring Gemini completion provider from database");
-            handle.completion = Arc::new(GeminiCompletionProvider::new(gemini_key.clone()));
+            handle.set_completion(Arc::new(GeminiCompletionProvider::new(gemini_key.clone())));
         }
         Some("openrouter") => {
             debug!("Restoring OpenRouter completion provider from database");
-            handle.completion = Arc::new(OpenRouterCompletionProvider::new(openrouter_key));
+            handle.set_completion(Arc::new(OpenRouterCompletionProvider::new(openrouter_key)));
         }
         _ => {
             debug!("Restoring OpenAI completion provider from database");
-            handle.completion = Arc::new(OpenAICompletionProvider::new(
+            handle.set_completion(Arc::new(OpenAICompletionProvider::new(
                 openai_key.clone(),
                 openai_base_url.clone(),
-            ));
+            )));
         }
     }
 
@@ -210,21 +565,21 @@ fn load_persisted_configuration(handle: &mut FlowHandle) {
         // Local whisper will be initialized by flow_set_transcription_mode
         // For now, set a placeholder that will be replaced
         debug!("Local transcription enabled, will be initialized separately");
-        handle.transcription = Arc::new(AutoTranscriptionProvider::new(None));
+        handle.set_transcription(Arc::new(AutoTranscriptionProvider::new(None)));
     } else {
         // Cloud transcription - check which provider
         match saved_cloud_transcription.as_deref() {
             Some("openai") => {
                 debug!("Restoring OpenAI transcription provider from database");
-                handle.transcription = Arc::new(OpenAITranscriptionProvider::new(
+                handle.set_transcription(Arc::new(OpenAITranscriptionProvider::new(
                     openai_key,
                     openai_base_url,
-                ));
+                )));
             }
             _ => {
                 // Default to Auto (worker handles transcription + completion)
                 debug!("Using Auto transcription provider (default)");
-                handle.transcription = Arc::new(AutoTranscriptionProvider::new(None));
+                handle.set_transcription(Arc::new(AutoTranscriptionProvider::new(None)));
             }
         }
     }
@@ -235,9 +590,12 @@ fn load_persisted_configuration(handle: &mut FlowHandle) {
 /// Initialize the Flow engine
 ///
 /// Returns an opaque handle that must be passed to all other functions.
+/// A corrupt database doesn't fail init: it is backed up and replaced, see
+/// `flow_get_storage_recovery_json`.
 ///
 /// # Arguments
-/// - `db_path` - Path to the SQLite database file, or NULL for default location
+/// - `db_path` - Path to the SQLite database file, or NULL for `flow.db` in the
+///   data directory (`$FLOW_DATA_DIR`, or the platform's local data directory)
 ///
 /// # Returns
 /// Opaque handle to the engine, or NULL on failure
@@ -245,10 +603,7 @@ fn load_persisted_configuration(handle: &mut FlowHandle) {
 pub extern "C" fn flow_init(db_path: *const c_char) -> *mut FlowHandle {
     let db_path = if db_path.is_null() {
         // default to app support directory
-        dirs::data_local_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("flow")
-            .join("flow.db")
+        profiles::default_db_path(&profiles::data_dir())
     } else {
         let path_str = match unsafe { CStr::from_ptr(db_path) }.to_str() {
             Ok(s) => s,
@@ -257,6 +612,58 @@ pub extern "C" fn flow_init(db_path: *const c_char) -> *mut FlowHandle {
         PathBuf::from(path_str)
     };
 
+    open_handle(&db_path)
+}
+
+/// Initialize the Flow engine with a profile's database
+///
+/// Each profile has its own corrections, shortcuts, modes and stats, stored under
+/// `profiles/<name>/` in the data directory and created on first use. A handle
+/// stays bound to its profile: to switch, call `flow_destroy` and init the other
+/// profile.
+///
+/// # Arguments
+/// - `profile_name` - 1-64 letters, digits, '-' or '_'
+///
+/// # Returns
+/// Opaque handle to the engine, or NULL if the name is invalid or init failed
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_init_profile(profile_name: *const c_char) -> *mut FlowHandle {
+    if profile_name.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(name) = (unsafe { CStr::from_ptr(profile_name) }).to_str() else {
+        return ptr::null_mut();
+    };
+
+    match profiles::profile_db_path(&profiles::data_dir(), name) {
+        Ok(db_path) => open_handle(&db_path),
+        Err(e) => {
+            error!("Invalid profile: {}", e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// List the profiles in the data directory
+///
+/// # Returns
+/// JSON array of profile names, sorted, or NULL if the directory can't be read.
+/// Caller must free with `flow_free_string`.
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_list_profiles() -> *mut c_char {
+    let profiles = match profiles::list_profiles(&profiles::data_dir()) {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            error!("Failed to list profiles: {}", e);
+            return ptr::null_mut();
+        }
+    };
+    string_to_c(serde_json::to_string(&profiles).unwrap_or_default())
+}
+
+/// Open the database at `db_path` and build a handle from its saved configuration
+fn open_handle(db_path: &Path) -> *mut FlowHandle {
     // ensure parent directory exists
     if let Some(parent) = db_path.parent()
         && let Err(e) = std::fs::create_dir_all(parent)
@@ -265,15 +672,15 @@ pub extern "C" fn flow_init(db_path: *const c_char) -> *mut FlowHandle {
         return ptr::null_mut();
     }
 
-    let runtime = match Runtime::new() {
-        Ok(rt) => rt,
+    let runtime = match shared_runtime() {
+        Ok(rt) => rt.handle().clone(),
         Err(e) => {
             error!("Failed to create async runtime: {}", e);
             return ptr::null_mut();
         }
     };
 
-    let storage = match Storage::open(&db_path) {
+    let storage = match Storage::open(db_path) {
         Ok(s) => s,
         Err(e) => {
             error!("Failed to open storage: {}", e);
@@ -281,36 +688,9 @@ pub extern "C" fn flow_init(db_path: *const c_char) -> *mut FlowHandle {
         }
     };
 
-    let shortcuts =
-        ShortcutsEngine::from_storage(&storage).unwrap_or_else(|_| ShortcutsEngine::new());
-    let learning = LearningEngine::from_storage(&storage).unwrap_or_else(|_| LearningEngine::new());
-    let modes = WritingModeEngine::new(WritingMode::Casual);
-    let app_tracker = AppTracker::new();
-    let style_learner = StyleLearner::new();
-    let contact_classifier = ContactClassifier::new();
-
-    let mut handle = FlowHandle {
-        runtime,
-        storage,
-        audio: Mutex::new(None),
-        last_audio: Mutex::new(None),
-        last_audio_sample_rate: Mutex::new(None),
-        last_error: Mutex::new(None),
-        transcription: Arc::new(OpenAITranscriptionProvider::new(None, None)),
-        completion: Arc::new(OpenAICompletionProvider::new(None, None)),
-        shortcuts,
-        learning,
-        modes: Mutex::new(modes),
-        app_tracker,
-        style_learner: Mutex::new(style_learner),
-        is_model_loading: Arc::new(AtomicBool::new(false)),
-        contact_classifier,
-        captured_contact: Mutex::new(None),
-        pending_audio: Mutex::new(None),
-        pending_sample_rate: Mutex::new(None),
-    };
+    let handle = new_handle(runtime, storage);
 
-    load_persisted_configuration(&mut handle);
+    load_persisted_configuration(&handle);
 
     // Load transcription mode (local vs remote Whisper)
     let use_local = handle
@@ -328,20 +708,17 @@ pub extern "C" fn flow_init(db_path: *const c_char) -> *mut FlowHandle {
             .get_setting(SETTING_LOCAL_WHISPER_MODEL)
             .ok()
             .flatten();
-        let model = WhisperModel::all()
-            .iter()
-            .find(|m| {
-                let (id, _) = m.model_id();
-                Some(id) == model_str.as_deref()
-            })
-            .copied()
-            .unwrap_or(WhisperModel::Quality);
+        let model = model_str
+            .as_deref()
+            .and_then(WhisperModel::parse)
+            .unwrap_or(WhisperModel::QUALITY);
 
         // Get models directory
         match crate::whisper_models::get_models_dir() {
             Ok(models_dir) => {
-                handle.transcription =
-                    Arc::new(LocalWhisperTranscriptionProvider::new(model, models_dir));
+                handle.set_transcription(Arc::new(LocalWhisperTranscriptionProvider::new(
+                    model, models_dir,
+                )));
                 log_with_time!("✅ [INIT] Using local Whisper model: {:?}", model);
             }
             Err(e) => {
@@ -361,10 +738,175 @@ pub extern "C" fn flow_init(db_path: *const c_char) -> *mut FlowHandle {
     Box::into_raw(Box::new(handle))
 }
 
+/// Build a handle with engines loaded from storage and default providers
+fn new_handle(runtime: tokio::runtime::Handle, storage: Storage) -> FlowHandle {
+    let shortcuts =
+        ShortcutsEngine::from_storage(&storage).unwrap_or_else(|_| ShortcutsEngine::new());
+    shortcuts.set_fuzzy_threshold(
+        storage
+            .get_setting_as::<f64>(SETTING_FUZZY_SHORTCUT_THRESHOLD)
+            .ok()
+            .flatten()
+            .filter(|&t| t > 0.0),
+    );
+    let learning = LearningEngine::from_storage(&storage).unwrap_or_else(|_| LearningEngine::new());
+    if let Some(similarity) = storage
+        .get_setting_as::<f64>(SETTING_MIN_CORRECTION_SIMILARITY)
+        .ok()
+        .flatten()
+    {
+        learning.set_min_similarity(similarity);
+    }
+    if let Some(confidence) = storage
+        .get_setting_as::<f32>(SETTING_MIN_CORRECTION_CONFIDENCE)
+        .ok()
+        .flatten()
+    {
+        learning.set_min_confidence(confidence);
+    }
+    if let Some(max) = storage
+        .get_setting_as::<usize>(SETTING_MAX_CORRECTION_CACHE_SIZE)
+        .ok()
+        .flatten()
+    {
+        learning.set_max_cache_size(max);
+    }
+    let redaction =
+        RedactionFilter::from_storage(&storage).unwrap_or_else(|_| RedactionFilter::new());
+    if let Some(stage) = storage
+        .get_setting(SETTING_REDACTION_STAGE)
+        .ok()
+        .flatten()
+        .and_then(|s| RedactionStage::parse(&s))
+    {
+        redaction.set_stage(stage);
+    }
+    let setting_enabled = |key: &str| {
+        storage
+            .get_setting(key)
+            .ok()
+            .flatten()
+            .map(|s| s == "true")
+            .unwrap_or(true)
+    };
+    let normalizer = TextNormalizer::new()
+        .with_collapse_whitespace(setting_enabled(SETTING_NORMALIZE_WHITESPACE))
+        .with_straighten_quotes(setting_enabled(SETTING_NORMALIZE_QUOTES))
+        .with_trim_trailing(setting_enabled(SETTING_TRIM_TRAILING_SPACES));
+    let mut dictation = DictationProcessor::new();
+    dictation.set_enabled(setting_enabled(SETTING_DICTATION_COMMANDS_ENABLED));
+    let mut hallucinations = HallucinationFilter::new();
+    hallucinations.set_enabled(setting_enabled(SETTING_HALLUCINATION_FILTER_ENABLED));
+    if let Some(phrases) = storage
+        .get_setting(SETTING_HALLUCINATION_PHRASES)
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_str::<Vec<String>>(&json).ok())
+    {
+        hallucinations.set_phrases(phrases);
+    }
+    let mut repetition = RepetitionDetector::default();
+    if let Some(max_repeats) = storage
+        .get_setting_as::<usize>(SETTING_MAX_PHRASE_REPEATS)
+        .ok()
+        .flatten()
+    {
+        repetition.set_max_repeats(max_repeats);
+    }
+    let min_recording_ms = storage
+        .get_setting_as::<u64>(SETTING_MIN_RECORDING_MS)
+        .ok()
+        .flatten()
+        .unwrap_or(DEFAULT_MIN_RECORDING_MS);
+    let max_recording = storage
+        .get_setting_as::<u64>(SETTING_MAX_RECORDING_SECS)
+        .ok()
+        .flatten()
+        .filter(|&secs| secs > 0)
+        .map_or(DEFAULT_MAX_RECORDING_DURATION, Duration::from_secs);
+    let overflow_policy = storage
+        .get_setting(SETTING_RECORDING_OVERFLOW_POLICY)
+        .ok()
+        .flatten()
+        .and_then(|s| OverflowPolicy::parse(&s))
+        .unwrap_or_default();
+    let request_timeout = storage
+        .get_setting_as::<u64>(SETTING_REQUEST_TIMEOUT_SECS)
+        .ok()
+        .flatten()
+        .and_then(request_timeout_from_secs);
+    // off by default: an unwanted prompt can make Whisper echo it back
+    let vocabulary_prompt_enabled = storage
+        .get_setting(SETTING_VOCABULARY_PROMPT_ENABLED)
+        .ok()
+        .flatten()
+        .is_some_and(|s| s == "true");
+    let locale = storage
+        .get_setting(SETTING_LOCALE)
+        .ok()
+        .flatten()
+        .and_then(|code| Locale::parse(&code));
+    let modes = WritingModeEngine::from_storage(&storage, WritingMode::Casual)
+        .unwrap_or_else(|_| WritingModeEngine::new(WritingMode::Casual));
+    let app_tracker = AppTracker::new();
+    let style_learner = StyleLearner::new();
+    let contact_classifier = ContactClassifier::new();
+
+    FlowHandle {
+        runtime: HandleRuntime::new(runtime),
+        storage,
+        audio: Mutex::new(None),
+        last_audio: Mutex::new(None),
+        last_audio_sample_rate: Mutex::new(None),
+        last_error: Mutex::new(None),
+        transcription: RwLock::new(Arc::new(OpenAITranscriptionProvider::new(None, None))),
+        completion: RwLock::new(Arc::new(OpenAICompletionProvider::new(None, None))),
+        shortcuts,
+        dictation: Mutex::new(dictation),
+        learning,
+        redaction,
+        normalizer: Mutex::new(normalizer),
+        transforms: TransformRegistry::new(),
+        hallucinations: Mutex::new(hallucinations),
+        repetition: Mutex::new(repetition),
+        modes,
+        app_tracker,
+        style_learner: Mutex::new(style_learner),
+        is_model_loading: Arc::new(AtomicBool::new(false)),
+        contact_classifier,
+        captured_contact: Mutex::new(None),
+        pending_audio: Mutex::new(None),
+        pending_sample_rate: Mutex::new(None),
+        pending_duration_ms: Mutex::new(None),
+        min_recording_ms: AtomicU64::new(min_recording_ms),
+        max_recording: Mutex::new(max_recording),
+        overflow_policy: Mutex::new(overflow_policy),
+        vocabulary_prompt_enabled: AtomicBool::new(vocabulary_prompt_enabled),
+        request_timeout: Mutex::new(request_timeout),
+        transcriptions: Mutex::new(HashMap::new()),
+        next_transcription_id: AtomicU64::new(1),
+        sessions: Mutex::new(HashMap::new()),
+        next_session_id: AtomicU64::new(1),
+        locale: Mutex::new(locale),
+        drain: Mutex::new(None),
+    }
+}
+
 /// Destroy the Flow engine and free resources
+/// Background work the handle started (health checks, model loading) is aborted;
+/// the worker threads are shared with other handles and keep running. Transcriptions
+/// from flow_transcribe_async are cancelled, and their callbacks have fired by the
+/// time this returns. Open sessions are ended and their microphones released.
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_destroy(handle: *mut FlowHandle) {
     if !handle.is_null() {
+        let handle_ref = unsafe { &*handle };
+        for cancel in handle_ref.transcriptions.lock().values() {
+            cancel.cancel();
+        }
+        // the jobs read the handle, so it has to outlive them
+        handle_ref.runtime.join_jobs();
+
         unsafe {
             drop(Box::from_raw(handle));
         }
@@ -372,10 +914,27 @@ pub extern "C" fn flow_destroy(handle: *mut FlowHandle) {
     }
 }
 
+/// Get what happened if the database was corrupt when the handle was opened
+/// The corrupt file is moved aside and a fresh database is created, copying over
+/// every table that could still be read, so the app should tell the user.
+/// Returns JSON: {"reason": "...", "backup_path": "...", "recovered_tables": [...],
+/// "lost_tables": [...]}, or NULL if the database opened cleanly
+/// Caller must free the returned string with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_storage_recovery_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    match handle.storage.recovery() {
+        Some(recovery) => string_to_c(serde_json::to_string(recovery).unwrap_or_default()),
+        None => ptr::null_mut(),
+    }
+}
+
 // ============ Audio ============
 
 /// Start audio recording
-/// Returns true on success
+/// Returns true on success; false while a recording that reached its cap
+/// hasn't been collected with flow_stop_recording
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_start_recording(handle: *mut FlowHandle) -> bool {
     let handle = unsafe { &*handle };
@@ -420,12 +979,12 @@ pub extern "C" fn flow_start_recording(handle: *mut FlowHandle) -> bool {
 
     // create new audio capture if needed
     if audio_lock.is_none() {
-        match AudioCapture::new() {
+        match new_capture(handle) {
             Ok(capture) => *audio_lock = Some(capture),
             Err(e) => {
                 let message = format!("Failed to create audio capture: {e}");
                 error!("{message}");
-                set_last_error(handle, message);
+                set_last_error(handle, e.code(), message);
                 return false;
             }
         }
@@ -440,12 +999,12 @@ pub extern "C" fn flow_start_recording(handle: *mut FlowHandle) -> bool {
             Err(e) => {
                 let message = format!("Failed to start recording: {e}");
                 error!("{message}");
-                set_last_error(handle, message);
+                set_last_error(handle, e.code(), message);
                 false
             }
         }
     } else {
-        set_last_error(handle, "Audio capture unavailable");
+        set_last_error(handle, ErrorCode::Audio, "Audio capture unavailable");
         false
     }
 }
@@ -466,20 +1025,13 @@ pub extern "C" fn flow_stop_recording(handle: *mut FlowHandle) -> u64 {
 
     // Take ownership of AudioCapture (removes it from the Option)
     // This causes it to be dropped after this block, releasing the CPAL device
-    if let Some(mut capture) = audio_lock.take() {
-        let duration = capture.buffer_duration_ms();
-
-        match capture.stop_stream() {
-            Ok(_) => {
-                // Extract and cache audio data before dropping AudioCapture
-                let sample_rate = capture.sample_rate();
-                let audio_data = capture.take_buffered_audio();
-
-                *handle.pending_audio.lock() = Some(audio_data);
-                *handle.pending_sample_rate.lock() = Some(sample_rate);
-
-                // AudioCapture is dropped here - CPAL device fully released
-                drop(capture);
+    if let Some(capture) = audio_lock.take() {
+        match finish_capture(capture) {
+            Ok(audio) => {
+                let duration = audio.duration_ms;
+                *handle.pending_audio.lock() = Some(audio.data);
+                *handle.pending_sample_rate.lock() = Some(audio.sample_rate);
+                *handle.pending_duration_ms.lock() = Some(duration);
 
                 clear_last_error(handle);
                 duration
@@ -487,45 +1039,191 @@ pub extern "C" fn flow_stop_recording(handle: *mut FlowHandle) -> u64 {
             Err(e) => {
                 let message = format!("Failed to stop recording: {e}");
                 error!("{message}");
-                set_last_error(handle, message);
-
-                // Still drop capture even on error
-                drop(capture);
+                set_last_error(handle, e.code(), message);
                 0
             }
         }
     } else {
-        set_last_error(handle, "Audio capture unavailable");
+        set_last_error(handle, ErrorCode::Audio, "Audio capture unavailable");
         0
     }
 }
 
-/// Check if currently recording
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_is_recording(handle: *mut FlowHandle) -> bool {
-    let handle = unsafe { &*handle };
-    let audio_lock = handle.audio.lock();
+/// Open the default microphone with the handle's recording limit
+fn new_capture(handle: &FlowHandle) -> crate::error::Result<AudioCapture> {
+    Ok(AudioCapture::new()?
+        .with_max_duration(*handle.max_recording.lock())
+        .with_overflow_policy(*handle.overflow_policy.lock()))
+}
 
-    if let Some(ref capture) = *audio_lock {
-        capture.state() == CaptureState::Recording
-    } else {
-        false
-    }
+/// Stop a capture and take what it recorded
+/// The capture is dropped either way, fully releasing the CPAL device.
+fn finish_capture(mut capture: AudioCapture) -> crate::error::Result<PendingAudio> {
+    let duration_ms = capture.buffer_duration_ms();
+    capture.stop_stream()?;
+    Ok(PendingAudio {
+        sample_rate: capture.sample_rate(),
+        data: capture.take_buffered_audio(),
+        duration_ms,
+    })
 }
 
-/// Get current audio level (RMS amplitude) from the recording
-/// Returns a value between 0.0 and 1.0, or 0.0 if not recording
+/// Set the shortest recording that gets transcribed (default 300ms)
+/// Shorter recordings, like an accidental hotkey tap, return empty text without
+/// calling the transcription provider. 0 transcribes everything.
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_audio_level(handle: *mut FlowHandle) -> f32 {
+pub extern "C" fn flow_set_min_recording_ms(handle: *mut FlowHandle, min_ms: u64) -> bool {
     let handle = unsafe { &*handle };
-    let audio_lock = handle.audio.lock();
+    handle.min_recording_ms.store(min_ms, Ordering::SeqCst);
 
-    if let Some(ref capture) = *audio_lock {
-        if capture.state() == CaptureState::Recording {
-            capture.current_audio_level()
-        } else {
-            0.0
-        }
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_MIN_RECORDING_MS, &min_ms.to_string())
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save minimum recording length: {e}"),
+        );
+        return false;
+    }
+
+    clear_last_error(handle);
+    true
+}
+
+/// Set how long transcription and completion requests may take, in seconds
+/// Requests that run longer fail with a timeout error. 0 removes the limit (the default).
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_request_timeout(handle: *mut FlowHandle, timeout_secs: u64) -> bool {
+    let handle = unsafe { &*handle };
+    *handle.request_timeout.lock() = request_timeout_from_secs(timeout_secs);
+
+    if let Err(e) = handle
+        .storage
+        .set_setting_as(SETTING_REQUEST_TIMEOUT_SECS, &timeout_secs)
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save request timeout: {e}"),
+        );
+        return false;
+    }
+
+    clear_last_error(handle);
+    true
+}
+
+/// Cap how long a recording can get before it's stopped or trimmed
+///
+/// Captured audio is kept in memory until flow_stop_recording, so a recording nobody
+/// stops would otherwise grow without bound. Applies from the next recording.
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `max_secs` - Longest recording kept, in seconds (default 600)
+/// - `policy` - What happens at the cap: 0 = stop recording and keep the start
+///   (default; flow_is_recording turns false, the microphone is released, and
+///   flow_stop_recording still returns the audio), 1 = keep recording and keep only the
+///   last `max_secs`
+///
+/// # Returns
+/// true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_max_recording(
+    handle: *mut FlowHandle,
+    max_secs: u64,
+    policy: u8,
+) -> bool {
+    let handle = unsafe { &*handle };
+
+    if max_secs == 0 {
+        set_last_error(
+            handle,
+            ErrorCode::InvalidInput,
+            "Maximum recording length must be positive",
+        );
+        return false;
+    }
+    let policy = match policy {
+        0 => OverflowPolicy::Stop,
+        1 => OverflowPolicy::DropOldest,
+        _ => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                format!("Invalid overflow policy: {policy}"),
+            );
+            return false;
+        }
+    };
+
+    let saved = handle
+        .storage
+        .set_setting_as(SETTING_MAX_RECORDING_SECS, &max_secs)
+        .and_then(|()| {
+            handle
+                .storage
+                .set_setting(SETTING_RECORDING_OVERFLOW_POLICY, policy.as_str())
+        });
+    if let Err(e) = saved {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save recording limit: {e}"),
+        );
+        return false;
+    }
+
+    *handle.max_recording.lock() = Duration::from_secs(max_secs);
+    *handle.overflow_policy.lock() = policy;
+    clear_last_error(handle);
+    true
+}
+
+/// Check whether the current recording reached the length set with
+/// flow_set_max_recording
+/// Poll this while recording, or when flow_is_recording turns false without a call
+/// to flow_stop_recording. False once the recording is stopped.
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_recording_limit_reached(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+    handle
+        .audio
+        .lock()
+        .as_ref()
+        .is_some_and(|capture| capture.limit_reached())
+}
+
+/// Check if currently recording
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_is_recording(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+    let audio_lock = handle.audio.lock();
+
+    if let Some(ref capture) = *audio_lock {
+        capture.state() == CaptureState::Recording
+    } else {
+        false
+    }
+}
+
+/// Get current audio level (RMS amplitude) from the recording
+/// Returns a value between 0.0 and 1.0, or 0.0 if not recording
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_audio_level(handle: *mut FlowHandle) -> f32 {
+    let handle = unsafe { &*handle };
+    let audio_lock = handle.audio.lock();
+
+    if let Some(ref capture) = *audio_lock {
+        if capture.state() == CaptureState::Recording {
+            capture.current_audio_level()
+        } else {
+            0.0
+        }
     } else {
         0.0
     }
@@ -533,12 +1231,135 @@ pub extern "C" fn flow_get_audio_level(handle: *mut FlowHandle) -> f32 {
 
 // ============ Transcription ============
 
+/// Build a completion provider by name using the API key saved in storage
+/// Returns None if the provider is unknown or has no API key
+fn completion_provider_from_storage(
+    storage: &Storage,
+    provider_name: &str,
+) -> Option<Arc<dyn CompletionProvider>> {
+    let setting_key = match provider_name {
+        "openai" => SETTING_OPENAI_API_KEY,
+        "gemini" => SETTING_GEMINI_API_KEY,
+        "openrouter" => SETTING_OPENROUTER_API_KEY,
+        _ => return None,
+    };
+
+    let api_key = storage
+        .get_setting(setting_key)
+        .ok()
+        .flatten()
+        .filter(|k| !k.is_empty())?;
+
+    let provider: Arc<dyn CompletionProvider> = match provider_name {
+        "openai" => {
+            let base_url = storage
+                .get_setting(SETTING_OPENAI_BASE_URL)
+                .ok()
+                .flatten()
+                .filter(|s| !s.is_empty());
+            Arc::new(OpenAICompletionProvider::new(Some(api_key), base_url))
+        }
+        "gemini" => Arc::new(GeminiCompletionProvider::new(Some(api_key))),
+        _ => Arc::new(OpenRouterCompletionProvider::new(Some(api_key))),
+    };
+
+    Some(provider)
+}
+
+/// Completion provider to format an app's text with: the provider named by its
+/// override if that has an API key, else the global one if the user picked it
+/// Returns None when neither is set up, so text is never sent to a provider the
+/// user didn't choose. Local transcriptions stay on the device unless the app has
+/// an override.
+fn completion_provider_for(
+    handle: &FlowHandle,
+    model_override: Option<&AppModelOverride>,
+    use_local_transcription: bool,
+) -> Option<Arc<dyn CompletionProvider>> {
+    if use_local_transcription && model_override.is_none() {
+        return None;
+    }
+
+    let completion_selected = handle
+        .storage
+        .get_setting(SETTING_COMPLETION_PROVIDER)
+        .ok()
+        .flatten()
+        .is_some();
+
+    model_override
+        .and_then(|o| o.provider.as_deref())
+        .and_then(|name| completion_provider_from_storage(&handle.storage, name))
+        .or_else(|| completion_selected.then(|| handle.completion()))
+        .filter(|provider| provider.is_configured())
+}
+
+/// Format text with the completion provider, honoring the app's model override
+/// Returns the text unchanged if there is no provider or the request fails
+fn format_with_completion(
+    handle: &FlowHandle,
+    provider: Option<Arc<dyn CompletionProvider>>,
+    text: String,
+    mode: WritingMode,
+    locale: Locale,
+    app_name: Option<&str>,
+    cancel: &CancellationToken,
+) -> String {
+    let Some(provider) = provider else {
+        return text;
+    };
+    if text.trim().is_empty() {
+        return text;
+    }
+
+    let request = handle
+        .modes
+        .completion_request(text.clone(), mode, app_name, &handle.storage)
+        .with_locale(locale);
+
+    let request_timeout = *handle.request_timeout.lock();
+    match handle.runtime.block_on(cancel.run(with_request_timeout(
+        request_timeout,
+        provider.complete(request),
+    ))) {
+        Some(Ok(response)) => response.text,
+        Some(Err(e)) => {
+            error!("Completion failed, using unformatted text: {}", e);
+            text
+        }
+        // the caller checks the token and discards the result
+        None => text,
+    }
+}
+
 fn transcribe_with_audio(
     handle: &FlowHandle,
     audio_data: crate::AudioData,
     sample_rate: u32,
     app_name: Option<String>,
-) -> crate::error::Result<String> {
+) -> crate::error::Result<TranscriptionOutcome> {
+    transcribe_cancellable(
+        handle,
+        audio_data,
+        sample_rate,
+        app_name,
+        &CancellationToken::new(),
+        false,
+    )
+}
+
+/// Transcribe and process audio, failing with `Error::Cancelled` if `cancel` fires first
+///
+/// A `dry_run` only reads: the transcription isn't saved to storage or history, and
+/// shortcut uses and contact interactions aren't counted.
+fn transcribe_cancellable(
+    handle: &FlowHandle,
+    audio_data: crate::AudioData,
+    sample_rate: u32,
+    app_name: Option<String>,
+    cancel: &CancellationToken,
+    dry_run: bool,
+) -> crate::error::Result<TranscriptionOutcome> {
     // Determine writing mode - use contact captured at recording start for Messages
     let mode = if let Some(ref name) = app_name {
         // Check if this is Messages.app
@@ -564,24 +1385,24 @@ fn transcribe_with_audio(
                 );
 
                 // Record the interaction
-                handle.contact_classifier.record_interaction(&contact_name);
+                if !dry_run {
+                    handle.contact_classifier.record_interaction(&contact_name);
+                }
 
                 contact_mode
             } else {
                 debug!("No contact was captured at recording start, using app default");
-                let mut modes = handle.modes.lock();
-                modes.get_mode_with_storage(name, &handle.storage)
+                handle.modes.get_mode_with_storage(name, &handle.storage)
             }
         } else {
             // Not Messages - use app-based mode
-            let mut modes = handle.modes.lock();
-            modes.get_mode_with_storage(name, &handle.storage)
+            handle.modes.get_mode_with_storage(name, &handle.storage)
         }
     } else {
         WritingMode::Casual
     };
 
-    let transcription_provider = Arc::clone(&handle.transcription);
+    let transcription_provider = handle.transcription();
     let app_context = handle.app_tracker.current_app();
 
     // Check if using local transcription
@@ -610,124 +1431,348 @@ fn transcribe_with_audio(
         WritingMode::Excited => "excited",
     };
 
+    // An app with its own model formats with it, not with the worker's model
+    let model_override = app_name.as_deref().and_then(|app| {
+        handle
+            .modes
+            .get_model_override_with_storage(app, &handle.storage)
+    });
+    let completion_provider =
+        completion_provider_for(handle, model_override.as_ref(), use_local_transcription);
+    let app_formats_itself = model_override.is_some() && completion_provider.is_some();
+
     // For cloud transcription (auto mode), worker handles everything
     // But skip completion if auto-rewriting is disabled
-    let completion_params = if !use_local_transcription && auto_rewriting_enabled {
-        log_with_time!("🚀 [RUST] Using auto mode (worker handles transcription+completion)");
-        Some(TranscriptionCompletionParams {
-            mode: mode_str.to_string(),
-            app_context: app_name.clone(),
-            shortcuts_triggered: Vec::new(),
-            voice_instruction: None, // Worker auto-detects from transcription
-        })
-    } else if !auto_rewriting_enabled {
-        log_with_time!("📝 [RUST] Auto-rewriting disabled, returning raw transcription");
-        None
-    } else {
-        None
-    };
+    let completion_params =
+        if !use_local_transcription && auto_rewriting_enabled && !app_formats_itself {
+            log_with_time!("🚀 [RUST] Using auto mode (worker handles transcription+completion)");
+            Some(TranscriptionCompletionParams {
+                mode: mode_str.to_string(),
+                app_context: app_name.clone(),
+                shortcuts_triggered: Vec::new(),
+                voice_instruction: None, // Worker auto-detects from transcription
+                locale: handle.locale.lock().map(|locale| locale.code().to_string()),
+            })
+        } else if !auto_rewriting_enabled {
+            log_with_time!("📝 [RUST] Auto-rewriting disabled, returning raw transcription");
+            None
+        } else {
+            None
+        };
 
     // Perform transcription
-    let transcription = handle.runtime.block_on(async {
-        let mut request = TranscriptionRequest::new(audio_data, sample_rate);
-        if let Some(params) = completion_params {
-            request = request.with_completion(params);
+    let request_timeout = *handle.request_timeout.lock();
+    let transcription_start = Instant::now();
+    let transcription = handle
+        .runtime
+        .block_on(cancel.run(async {
+            let mut request = transcription_request(handle, audio_data, sample_rate);
+            if let Some(params) = completion_params {
+                request = request.with_completion(params);
+            }
+            with_request_timeout(request_timeout, transcription_provider.transcribe(request)).await
+        }))
+        .ok_or(crate::error::Error::Cancelled)??;
+    let transcription_ms = transcription_start.elapsed().as_millis() as u64;
+
+    // Silence: there's nothing to process, and formatting an empty or filler
+    // transcription only invites the completion model to make text up
+    if transcription.text.trim().is_empty()
+        || handle
+            .hallucinations
+            .lock()
+            .is_hallucination(&transcription.text)
+    {
+        log_with_time!(
+            "🔇 [RUST] No speech in transcription ({:?}), skipping processing",
+            transcription.text
+        );
+        return Ok(TranscriptionOutcome {
+            transcription_ms,
+            detected_language: transcription.language,
+            ..TranscriptionOutcome::empty(transcription_provider.name())
+        });
+    }
+
+    // Format for the language that was spoken unless the user picked one
+    let locale = handle
+        .locale
+        .lock()
+        .unwrap_or_else(|| Locale::from_detected(transcription.language.as_deref()));
+
+    // Collapse phrase loops before they waste completion tokens
+    let collapsed = handle.repetition.lock().collapse(&transcription.text);
+    if collapsed.loops > 0 {
+        log_with_time!(
+            "🔁 [RUST] Collapsed {} repetition loop(s) in transcription",
+            collapsed.loops
+        );
+    }
+
+    // Run the app's transforms registered for a stage, if any
+    let mut edits = EditTracker::new(&collapsed.text);
+    let run_transforms = |stage: TransformStage, text: String, edits: &mut EditTracker| {
+        if handle.transforms.is_empty() {
+            return text;
         }
-        transcription_provider.transcribe(request).await
-    })?;
+        let ctx = TransformContext {
+            app_name: app_name.as_deref(),
+            mode,
+            stage,
+        };
+        let transformed = handle.transforms.apply(&text, &ctx);
+        edits.apply(&transformed, Some(EditKind::Transform));
+        transformed
+    };
+    let raw_text = run_transforms(
+        TransformStage::AfterTranscription,
+        collapsed.text.clone(),
+        &mut edits,
+    );
+    // the worker formats the transcript it heard, so text changed before formatting
+    // has to be formatted here instead
+    let mut changed_before_formatting = raw_text != collapsed.text;
 
     // Process shortcuts (always applied) and corrections (only if auto-rewriting enabled)
-    let (text_with_shortcuts, triggered) = handle.shortcuts.process(&transcription.text);
+    let (text_with_shortcuts, triggered) = handle.shortcuts.process(&raw_text);
+    edits.apply(&text_with_shortcuts, Some(EditKind::Shortcut));
+    // a replacement only reaches the output (and is only worth counting) if the text
+    // is formatted here
+    changed_before_formatting |= !triggered.is_empty();
+    for shortcut in triggered.iter().filter(|_| !dry_run) {
+        if let Err(e) = handle.storage.increment_shortcut_use(&shortcut.trigger) {
+            error!("Failed to record shortcut use: {}", e);
+        }
+    }
+
+    // Turn spoken formatting commands ("new line", "bullet") into structure
+    let (text_with_shortcuts, commands) = handle.dictation.lock().process(&text_with_shortcuts);
+    edits.apply(&text_with_shortcuts, Some(EditKind::Formatting));
+    changed_before_formatting |= !commands.is_empty();
+    let transformed = run_transforms(
+        TransformStage::AfterShortcuts,
+        text_with_shortcuts.clone(),
+        &mut edits,
+    );
+    changed_before_formatting |= transformed != text_with_shortcuts;
+    let text_with_shortcuts = transformed;
+
+    // Mask redacted words before any formatting pass (and the completion model) sees them
+    let redact_before_formatting = |text: String, edits: &mut EditTracker| {
+        let redacted = handle
+            .redaction
+            .apply_at(RedactionStage::BeforeFormatting, &text);
+        if matches!(redacted, std::borrow::Cow::Borrowed(_)) {
+            return text;
+        }
+        let redacted = redacted.into_owned();
+        edits.apply(&redacted, None);
+        redacted
+    };
 
     // Determine final processed text based on auto-rewriting setting
+    let mut corrections = Vec::new();
+    let mut corrected_text = None;
+    let mut formatting_ms = 0;
     let processed_text = if !auto_rewriting_enabled {
         // Auto-rewriting disabled: return transcription with shortcuts only (no corrections, no AI)
         log_with_time!(
             "📝 [RUST] Auto-rewriting disabled - returning text with shortcuts only: {} chars",
             text_with_shortcuts.len()
         );
-        text_with_shortcuts
-    } else if let Some(completed_text) = transcription.completed_text {
-        // Worker completion available (cloud mode with auto-rewriting)
+        let text_with_shortcuts = redact_before_formatting(text_with_shortcuts, &mut edits);
+        run_transforms(
+            TransformStage::AfterCorrections,
+            text_with_shortcuts,
+            &mut edits,
+        )
+    } else if let Some(completed_text) = transcription
+        .completed_text
+        .filter(|_| !changed_before_formatting)
+    {
+        // Worker completion available (cloud mode with auto-rewriting). The worker
+        // formats server-side, so this is the first point the text can be masked.
         log_with_time!(
             "✅ [RUST/AI] Worker completion received - Output: {} chars",
             completed_text.len()
         );
-        completed_text
+        edits.apply(&completed_text, Some(EditKind::Formatting));
+        let completed_text = redact_before_formatting(completed_text, &mut edits);
+        run_transforms(TransformStage::AfterCorrections, completed_text, &mut edits)
     } else {
-        // Local transcription mode or cloud without completion - apply corrections
-        let (text_with_corrections, _applied) =
-            handle.learning.apply_corrections(&text_with_shortcuts);
+        // Local transcription mode, cloud without completion, text the worker's formatting
+        // would lose, or an app with its own model - apply corrections, then format if a
+        // completion provider is set up
+        let (text_with_corrections, applied) = if is_code_app(handle, app_name.as_deref()) {
+            handle
+                .learning
+                .apply_corrections_outside_code(&text_with_shortcuts)
+        } else {
+            handle.learning.apply_corrections(&text_with_shortcuts)
+        };
+        corrections = applied;
+        corrected_text = Some(text_with_corrections.clone());
+        edits.apply(&text_with_corrections, Some(EditKind::Correction));
+        let text_with_corrections = redact_before_formatting(text_with_corrections, &mut edits);
+        let text_with_corrections = run_transforms(
+            TransformStage::AfterCorrections,
+            text_with_corrections,
+            &mut edits,
+        );
         log_with_time!(
             "📝 [RUST] Local transcription mode - using corrected text: {} chars",
             text_with_corrections.len()
         );
-        text_with_corrections
+        let formatting_start = Instant::now();
+        let formatted = format_with_completion(
+            handle,
+            completion_provider,
+            text_with_corrections,
+            mode,
+            locale,
+            app_name.as_deref(),
+            cancel,
+        );
+        formatting_ms = formatting_start.elapsed().as_millis() as u64;
+        edits.apply(&formatted, Some(EditKind::Formatting));
+        formatted
     };
 
-    // Suppress unused warning for triggered shortcuts (used by worker)
-    let _ = triggered;
-
-    let mut record = Transcription::new(
-        transcription.text,
-        processed_text.clone(),
-        transcription.confidence.unwrap_or(0.0),
-        transcription.duration_ms,
-    );
-    if let Some(context) = app_context {
-        record.app_context = Some(context);
-    }
-    if let Err(e) = handle.storage.save_transcription(&record) {
-        error!("Failed to save transcription: {}", e);
+    // don't save or return anything the user already abandoned
+    if cancel.is_cancelled() {
+        return Err(crate::error::Error::Cancelled);
     }
 
-    let mut history = TranscriptionHistoryEntry::success(
-        record.raw_text.clone(),
-        processed_text.clone(),
-        record.duration_ms,
-    );
-    history.app_context = record.app_context.clone();
-    if let Err(e) = handle.storage.save_history_entry(&history) {
-        error!("Failed to save transcription history: {}", e);
+    // The completion model doesn't always follow the mode, so enforce what can be checked,
+    // including how numbers and units are written (English number words only) and the
+    // language's punctuation
+    let processed_text = if auto_rewriting_enabled {
+        let processed_text = if locale == Locale::English {
+            normalize_numbers(&processed_text, mode)
+        } else {
+            processed_text
+        };
+        let styled = enforce_locale_punctuation(&enforce_style(&processed_text, mode), locale);
+        edits.apply(&styled, Some(EditKind::Formatting));
+        styled
+    } else {
+        processed_text
+    };
+    let processed_text =
+        run_transforms(TransformStage::AfterFormatting, processed_text, &mut edits);
+
+    // Normalize the output, then mask redacted words set to run on the final text
+    let processed_text = handle.normalizer.lock().normalize(&processed_text);
+    let processed_text = handle
+        .redaction
+        .apply_at(RedactionStage::AfterFormatting, &processed_text)
+        .into_owned();
+    edits.apply(&processed_text, None);
+
+    if !dry_run {
+        let mut record = Transcription::new(
+            transcription.text,
+            processed_text.clone(),
+            transcription.confidence.unwrap_or(0.0),
+            transcription.duration_ms,
+        );
+        if let Some(context) = app_context {
+            record.app_context = Some(context);
+        }
+        if let Err(e) = handle.storage.save_transcription(&record) {
+            error!("Failed to save transcription: {}", e);
+        }
+
+        let mut history = TranscriptionHistoryEntry::success(
+            record.raw_text.clone(),
+            processed_text.clone(),
+            record.duration_ms,
+        );
+        history.app_context = record.app_context.clone();
+        if let Err(e) = handle.storage.save_history_entry(&history) {
+            error!("Failed to save transcription history: {}", e);
+        }
     }
 
-    Ok(processed_text)
+    Ok(TranscriptionOutcome {
+        text: processed_text,
+        provider: transcription_provider.name().to_string(),
+        transcription_ms,
+        formatting_ms,
+        detected_language: transcription.language,
+        corrections_applied: corrections.len(),
+        shortcuts_triggered: triggered.len(),
+        corrections,
+        corrected_text,
+        edits: edits.edits(),
+        repetition_loops: collapsed.loops,
+    })
 }
 
-/// Transcribe the recorded audio and process it
-///
-/// # Arguments
-/// - `handle` - Engine handle
-/// - `app_name` - Name of the current app (for mode selection), or NULL
-///
-/// # Returns
-/// Processed text (caller must free with flow_free_string), or NULL on failure
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_transcribe(handle: *mut FlowHandle, app_name: *const c_char) -> *mut c_char {
-    let handle = unsafe { &*handle };
+/// Audio captured by flow_stop_recording, waiting to be transcribed
+struct PendingAudio {
+    data: crate::AudioData,
+    sample_rate: u32,
+    duration_ms: u64,
+}
 
+/// Take the audio captured by flow_stop_recording
+/// Returns None if there is none (error is recorded on the handle)
+fn take_pending_audio(handle: &FlowHandle) -> Option<PendingAudio> {
     // Get cached audio data (don't touch handle.audio at all)
     // This ensures the microphone device was already released by flow_stop_recording
-    let (audio_data, sample_rate) = {
-        let audio_data = handle.pending_audio.lock().take();
-        let sample_rate = handle.pending_sample_rate.lock().take();
+    let audio_data = handle.pending_audio.lock().take();
+    let sample_rate = handle.pending_sample_rate.lock().take();
 
-        match (audio_data, sample_rate) {
-            (Some(data), Some(rate)) => (data, rate),
-            _ => {
-                set_last_error(
-                    handle,
-                    "No audio data pending - must call stop_recording first",
-                );
-                return ptr::null_mut();
-            }
+    let (audio_data, sample_rate) = match (audio_data, sample_rate) {
+        (Some(data), Some(rate)) => (data, rate),
+        _ => {
+            set_last_error(
+                handle,
+                ErrorCode::Audio,
+                "No audio data pending - must call stop_recording first",
+            );
+            return None;
         }
     };
 
     if audio_data.is_empty() {
-        set_last_error(handle, "No audio captured");
-        return ptr::null_mut();
+        set_last_error(handle, ErrorCode::Audio, "No audio captured");
+        return None;
+    }
+
+    let duration_ms = handle
+        .pending_duration_ms
+        .lock()
+        .take()
+        .unwrap_or_else(|| estimate_duration_ms(audio_data.len(), sample_rate));
+    Some(PendingAudio {
+        data: audio_data,
+        sample_rate,
+        duration_ms,
+    })
+}
+
+/// Whether a recording is too short to be anything but an accidental hotkey tap
+fn is_accidental_tap(handle: &FlowHandle, audio: &PendingAudio) -> bool {
+    let min_recording_ms = handle.min_recording_ms.load(Ordering::SeqCst);
+    if audio.duration_ms < min_recording_ms {
+        debug!(
+            "Ignoring {}ms recording (minimum {}ms)",
+            audio.duration_ms, min_recording_ms
+        );
+        return true;
     }
+    false
+}
+
+/// Transcribe the pending audio captured by flow_stop_recording
+/// Returns None on failure (error is recorded on the handle)
+fn transcribe_pending(
+    handle: &FlowHandle,
+    app_name: *const c_char,
+) -> Option<TranscriptionOutcome> {
+    let audio = take_pending_audio(handle)?;
 
     // get app name
     let app = if !app_name.is_null() {
@@ -739,58 +1784,168 @@ pub extern "C" fn flow_transcribe(handle: *mut FlowHandle, app_name: *const c_ch
         None
     };
 
-    let duration_ms = estimate_duration_ms(audio_data.len(), sample_rate);
+    run_pending_transcription(handle, audio, app, &CancellationToken::new()).ok()
+}
+
+/// Transcribe a recording taken from the pending slot, recording failures in history
+/// and queueing the audio for retry on network errors
+fn run_pending_transcription(
+    handle: &FlowHandle,
+    audio: PendingAudio,
+    app: Option<String>,
+    cancel: &CancellationToken,
+) -> crate::error::Result<TranscriptionOutcome> {
+    if is_accidental_tap(handle, &audio) {
+        *handle.captured_contact.lock() = None;
+        clear_last_error(handle);
+        return Ok(TranscriptionOutcome::empty(handle.transcription().name()));
+    }
+
+    let PendingAudio {
+        data: audio_data,
+        sample_rate,
+        duration_ms,
+    } = audio;
     *handle.last_audio.lock() = Some(audio_data.clone());
     *handle.last_audio_sample_rate.lock() = Some(sample_rate);
-    let result = transcribe_with_audio(handle, audio_data, sample_rate, app);
+    let result =
+        transcribe_cancellable(handle, audio_data, sample_rate, app.clone(), cancel, false);
 
     // Clear the captured contact after transcription (whether success or failure)
     *handle.captured_contact.lock() = None;
 
-    match result {
-        Ok(text) => {
+    match &result {
+        Ok(_) => {
             clear_last_error(handle);
             *handle.last_audio.lock() = None;
             *handle.last_audio_sample_rate.lock() = None;
-            match CString::new(text) {
-                Ok(cstr) => cstr.into_raw(),
-                Err(_) => ptr::null_mut(),
-            }
+        }
+        Err(crate::error::Error::Cancelled) => {
+            // the user abandoned it, so it isn't a failure worth keeping in history
+            debug!("Transcription cancelled");
+            set_last_error(handle, ErrorCode::Cancelled, "Transcription cancelled");
         }
         Err(e) => {
             let message = format!("Transcription failed: {e}");
             error!("{message}");
-            set_last_error(handle, message.clone());
+            set_last_error(handle, e.code(), message.clone());
+
+            // Keep the recording for flow_retry_pending if we're offline
+            if e.is_network()
+                && let Some(audio) = handle.last_audio.lock().clone()
+            {
+                let mut job = PendingTranscription::new(audio, sample_rate, app);
+                job.last_error = Some(message.clone());
+                match handle
+                    .storage
+                    .enqueue_pending_transcription(&job, MAX_PENDING_TRANSCRIPTIONS)
+                {
+                    Ok(evicted) => {
+                        debug!("Queued recording for retry (evicted {} oldest)", evicted)
+                    }
+                    Err(e) => error!("Failed to queue recording for retry: {}", e),
+                }
+            }
+
             let mut history = TranscriptionHistoryEntry::failure(message, duration_ms);
             history.app_context = handle.app_tracker.current_app();
             if let Err(e) = handle.storage.save_history_entry(&history) {
                 error!("Failed to save transcription history: {}", e);
             }
-            ptr::null_mut()
         }
     }
+    result
 }
 
-/// Retry the last transcription using cached audio
-/// Returns processed text (caller must free with flow_free_string), or null on failure
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_retry_last_transcription(
-    handle: *mut FlowHandle,
-    app_name: *const c_char,
-) -> *mut c_char {
-    let handle = unsafe { &*handle };
-    let (audio_data, sample_rate) = {
-        let last_audio = handle.last_audio.lock();
-        let last_sample_rate = handle.last_audio_sample_rate.lock();
-        match last_audio.as_ref() {
-            Some(data) => (data.clone(), last_sample_rate.unwrap_or(16_000)),
-            None => {
-                set_last_error(handle, "No previous recording to retry");
-                return ptr::null_mut();
-            }
-        }
+/// Transcribe the recorded audio and process it
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `app_name` - Name of the current app (for mode selection), or NULL
+///
+/// # Returns
+/// Processed text (caller must free with flow_free_string), or NULL on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_transcribe(handle: *mut FlowHandle, app_name: *const c_char) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    match transcribe_pending(handle, app_name) {
+        Some(outcome) => string_to_c(outcome.text),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Transcribe the recorded audio and return the result with metadata
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `app_name` - Name of the current app (for mode selection), or NULL
+///
+/// # Returns
+/// JSON object with `text`, `provider`, `transcription_ms`, `formatting_ms`,
+/// `detected_language`, `corrections_applied`, `shortcuts_triggered`, the applied
+/// `corrections` with the `corrected_text` their word positions refer to (the text
+/// before formatting, null when no corrections ran), the `edits` made to the text, as
+/// `{start, end, kind}` byte ranges with kind "shortcut", "correction", "formatting" or
+/// "transform", and the number of `repetition_loops` collapsed (caller must free with
+/// flow_free_string), or NULL on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_transcribe_json(
+    handle: *mut FlowHandle,
+    app_name: *const c_char,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let Some(outcome) = transcribe_pending(handle, app_name) else {
+        return ptr::null_mut();
     };
 
+    string_to_c(serde_json::to_string(&outcome).unwrap_or_default())
+}
+
+/// Run the full pipeline on the given audio without recording anything
+///
+/// Transcription, shortcuts, corrections and formatting run as in flow_transcribe_json,
+/// but nothing is written: the transcription isn't saved to the stats or history,
+/// nothing is learned, and shortcut uses and contact interactions aren't counted. The
+/// audio is used as is, so the recording buffer and the minimum recording length are
+/// ignored. For previews and for tests that don't have a microphone.
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `pcm` - Mono 16-bit PCM samples
+/// - `len` - Number of samples in `pcm`
+/// - `sample_rate` - Sample rate of `pcm` in Hz
+/// - `app_name` - Name of the app to format for (for mode selection), or NULL
+///
+/// # Returns
+/// JSON object as returned by flow_transcribe_json (caller must free with
+/// flow_free_string), or NULL on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_transcribe_dry_run(
+    handle: *mut FlowHandle,
+    pcm: *const i16,
+    len: usize,
+    sample_rate: u32,
+    app_name: *const c_char,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    if pcm.is_null() || len == 0 {
+        set_last_error(handle, ErrorCode::Audio, "No audio provided");
+        return ptr::null_mut();
+    }
+    if sample_rate == 0 {
+        set_last_error(
+            handle,
+            ErrorCode::InvalidInput,
+            "Sample rate must be positive",
+        );
+        return ptr::null_mut();
+    }
+
+    let samples = unsafe { std::slice::from_raw_parts(pcm, len) };
+    let audio_data: crate::AudioData = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
     let app = if !app_name.is_null() {
         unsafe { CStr::from_ptr(app_name) }
             .to_str()
@@ -800,838 +1955,2267 @@ pub extern "C" fn flow_retry_last_transcription(
         None
     };
 
-    let duration_ms = estimate_duration_ms(audio_data.len(), sample_rate);
-    let result = transcribe_with_audio(handle, audio_data, sample_rate, app);
-
-    match result {
-        Ok(text) => {
+    match transcribe_cancellable(
+        handle,
+        audio_data,
+        sample_rate,
+        app,
+        &CancellationToken::new(),
+        true,
+    ) {
+        Ok(outcome) => {
             clear_last_error(handle);
-            *handle.last_audio.lock() = None;
-            *handle.last_audio_sample_rate.lock() = None;
-            match CString::new(text) {
-                Ok(cstr) => cstr.into_raw(),
-                Err(_) => ptr::null_mut(),
-            }
+            string_to_c(serde_json::to_string(&outcome).unwrap_or_default())
         }
         Err(e) => {
             let message = format!("Transcription failed: {e}");
             error!("{message}");
-            set_last_error(handle, message.clone());
-            let mut history = TranscriptionHistoryEntry::failure(message, duration_ms);
-            history.app_context = handle.app_tracker.current_app();
-            if let Err(e) = handle.storage.save_history_entry(&history) {
-                error!("Failed to save transcription history: {}", e);
-            }
+            set_last_error(handle, e.code(), message);
             ptr::null_mut()
         }
     }
 }
 
-// ============ Shortcuts ============
-
-/// Add a voice shortcut
+/// Transcribe the recorded audio into WebVTT captions
 ///
-/// # Arguments
-/// - `handle` - Engine handle
-/// - `trigger` - Trigger phrase
-/// - `replacement` - Replacement text
+/// Cues use the provider's segment timings when it reports them (OpenAI Whisper,
+/// local Whisper) and are otherwise spaced evenly over the recording. Captions are
+/// the raw transcription: shortcuts, corrections and formatting aren't applied.
+///
+/// Unlike flow_transcribe, a recording that fails for lack of network isn't queued
+/// for flow_retry_pending, since the queue only produces processed text.
 ///
 /// # Returns
-/// true on success
+/// WebVTT text (caller must free with flow_free_string), or NULL on failure
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_add_shortcut(
-    handle: *mut FlowHandle,
-    trigger: *const c_char,
-    replacement: *const c_char,
-) -> bool {
-    if trigger.is_null() || replacement.is_null() {
-        return false;
-    }
-
+pub extern "C" fn flow_transcribe_vtt(handle: *mut FlowHandle) -> *mut c_char {
     let handle = unsafe { &*handle };
 
-    let trigger_str = match unsafe { CStr::from_ptr(trigger) }.to_str() {
-        Ok(s) => s.to_string(),
-        Err(_) => return false,
-    };
-
-    let replacement_str = match unsafe { CStr::from_ptr(replacement) }.to_str() {
-        Ok(s) => s.to_string(),
-        Err(_) => return false,
+    let Some(audio) = take_pending_audio(handle) else {
+        return ptr::null_mut();
     };
-
-    let shortcut = Shortcut::new(trigger_str, replacement_str);
-
-    if let Err(e) = handle.storage.save_shortcut(&shortcut) {
-        error!("Failed to save shortcut: {}", e);
-        return false;
-    }
-
-    handle.shortcuts.add_shortcut(shortcut);
-    true
-}
-
-/// Remove a voice shortcut
-/// Returns true on success
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_remove_shortcut(handle: *mut FlowHandle, trigger: *const c_char) -> bool {
-    if trigger.is_null() {
-        return false;
+    if is_accidental_tap(handle, &audio) {
+        clear_last_error(handle);
+        return string_to_c(crate::captions::to_vtt(&[]));
     }
 
-    let handle = unsafe { &*handle };
-
-    let trigger_str = match unsafe { CStr::from_ptr(trigger) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
+    let request = transcription_request(handle, audio.data, audio.sample_rate);
+    let request_timeout = *handle.request_timeout.lock();
+    let transcription = handle.transcription();
+    let response = match handle.runtime.block_on(with_request_timeout(
+        request_timeout,
+        transcription.transcribe(request),
+    )) {
+        Ok(response) => response,
+        Err(e) => {
+            let message = format!("Transcription failed: {e}");
+            error!("{message}");
+            set_last_error(handle, e.code(), message);
+            return ptr::null_mut();
+        }
     };
+    clear_last_error(handle);
 
-    handle.shortcuts.remove_shortcut(trigger_str);
-    true
+    let vtt = if handle
+        .hallucinations
+        .lock()
+        .is_hallucination(&response.text)
+    {
+        crate::captions::to_vtt(&[])
+    } else {
+        response.to_vtt()
+    };
+    string_to_c(vtt)
 }
 
-/// Get the number of shortcuts
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_shortcut_count(handle: *mut FlowHandle) -> usize {
-    let handle = unsafe { &*handle };
-    handle.shortcuts.count()
-}
+/// Engine handle shared with a background transcription thread
+struct HandlePtr(*const FlowHandle);
 
-// ============ Writing Modes ============
+// SAFETY: FlowHandle is used from arbitrary caller threads already, and the threads it
+// is sent to are joined before it's freed (scoped, or by flow_destroy)
+unsafe impl Send for HandlePtr {}
 
-/// Set the writing mode for an app
+/// Transcribe the recorded audio in the background so it can be cancelled
 ///
 /// # Arguments
-/// - `handle` - Engine handle
-/// - `app_name` - Name of the app
-/// - `mode` - Writing mode (0=Formal, 1=Casual, 2=VeryCasual, 3=Excited)
+/// - `handle` - Engine handle; flow_destroy cancels the transcription and waits for
+///   its callback
+/// - `app_name` - Name of the current app (for mode selection), or NULL
+/// - `callback` - Called once from a background thread with the processed text, or
+///   `success` false and the error message, or "cancelled" after flow_cancel_transcription
+/// - `context` - Passed through to the callback
 ///
 /// # Returns
-/// true on success
+/// Id to pass to flow_cancel_transcription, or 0 if there was no recorded audio (the
+/// callback is not called)
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_set_app_mode(
+pub extern "C" fn flow_transcribe_async(
     handle: *mut FlowHandle,
     app_name: *const c_char,
-    mode: u8,
-) -> bool {
-    if app_name.is_null() {
-        return false;
-    }
-
-    let handle = unsafe { &*handle };
+    callback: ResultCallback,
+    context: *mut c_void,
+) -> u64 {
+    let handle_ref = unsafe { &*handle };
 
-    let app = match unsafe { CStr::from_ptr(app_name) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
+    let Some(audio) = take_pending_audio(handle_ref) else {
+        return 0;
     };
-
-    let writing_mode = match mode {
-        0 => WritingMode::Formal,
-        1 => WritingMode::Casual,
-        2 => WritingMode::VeryCasual,
-        3 => WritingMode::Excited,
-        _ => return false,
+    let app = if !app_name.is_null() {
+        unsafe { CStr::from_ptr(app_name) }
+            .to_str()
+            .ok()
+            .map(String::from)
+    } else {
+        None
     };
 
-    let mut modes = handle.modes.lock();
-    if let Err(e) = modes.set_mode_with_storage(app, writing_mode, &handle.storage) {
-        error!("Failed to save app mode: {}", e);
-        return false;
-    }
+    let id = handle_ref
+        .next_transcription_id
+        .fetch_add(1, Ordering::Relaxed);
+    let cancel = Arc::new(CancellationToken::new());
+    handle_ref
+        .transcriptions
+        .lock()
+        .insert(id, Arc::clone(&cancel));
+
+    let handle = HandlePtr(handle);
+    let context = CallbackContext(context);
+    handle_ref.runtime.spawn_job(move || {
+        // move the whole wrappers in, not just their raw pointer fields
+        let (handle, context) = (handle, context);
+        let handle = unsafe { &*handle.0 };
+
+        let result = run_pending_transcription(handle, audio, app, &cancel);
+        handle.transcriptions.lock().remove(&id);
+
+        let (success, message) = match result {
+            Ok(outcome) => (true, outcome.text),
+            Err(crate::error::Error::Cancelled) => (false, CANCELLED_RESULT.to_string()),
+            Err(e) => (false, format!("Transcription failed: {e}")),
+        };
+        let message = c_string(message);
+        callback(success, message.as_ptr(), context.0);
+    });
 
-    true
+    id
 }
 
-/// Get the writing mode for an app
-/// Returns: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+/// Cancel a transcription started with flow_transcribe_async
+/// The in-flight request is dropped and its callback fires with `success` false and
+/// "cancelled". Ids that already finished (or never existed) are ignored.
+///
+/// # Returns
+/// true if a running transcription was cancelled
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_app_mode(handle: *mut FlowHandle, app_name: *const c_char) -> u8 {
-    if app_name.is_null() {
-        return 1; // default to casual
+pub extern "C" fn flow_cancel_transcription(handle: *mut FlowHandle, id: u64) -> bool {
+    let handle = unsafe { &*handle };
+
+    match handle.transcriptions.lock().remove(&id) {
+        Some(cancel) => {
+            cancel.cancel();
+            true
+        }
+        None => false,
     }
+}
 
+/// Retry the last transcription using cached audio
+/// Returns processed text (caller must free with flow_free_string), or null on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_retry_last_transcription(
+    handle: *mut FlowHandle,
+    app_name: *const c_char,
+) -> *mut c_char {
     let handle = unsafe { &*handle };
+    let (audio_data, sample_rate) = {
+        let last_audio = handle.last_audio.lock();
+        let last_sample_rate = handle.last_audio_sample_rate.lock();
+        match last_audio.as_ref() {
+            Some(data) => (data.clone(), last_sample_rate.unwrap_or(16_000)),
+            None => {
+                set_last_error(handle, ErrorCode::Audio, "No previous recording to retry");
+                return ptr::null_mut();
+            }
+        }
+    };
 
-    let app = match unsafe { CStr::from_ptr(app_name) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return 1,
+    let app = if !app_name.is_null() {
+        unsafe { CStr::from_ptr(app_name) }
+            .to_str()
+            .ok()
+            .map(String::from)
+    } else {
+        None
     };
 
-    let mut modes = handle.modes.lock();
-    let mode = modes.get_mode_with_storage(app, &handle.storage);
+    let duration_ms = estimate_duration_ms(audio_data.len(), sample_rate);
+    let result = transcribe_with_audio(handle, audio_data, sample_rate, app);
 
-    match mode {
-        WritingMode::Formal => 0,
-        WritingMode::Casual => 1,
-        WritingMode::VeryCasual => 2,
-        WritingMode::Excited => 3,
+    match result {
+        Ok(outcome) => {
+            clear_last_error(handle);
+            *handle.last_audio.lock() = None;
+            *handle.last_audio_sample_rate.lock() = None;
+            string_to_c(outcome.text)
+        }
+        Err(e) => {
+            let message = format!("Transcription failed: {e}");
+            error!("{message}");
+            set_last_error(handle, e.code(), message.clone());
+            let mut history = TranscriptionHistoryEntry::failure(message, duration_ms);
+            history.app_context = handle.app_tracker.current_app();
+            if let Err(e) = handle.storage.save_history_entry(&history) {
+                error!("Failed to save transcription history: {}", e);
+            }
+            ptr::null_mut()
+        }
     }
 }
 
-// ============ Learning ============
-
-/// Report a user edit to learn from
+/// Retry transcriptions queued after network failures, oldest first
 ///
 /// # Arguments
 /// - `handle` - Engine handle
-/// - `original` - Original transcribed text
-/// - `edited` - Text after user edits
+/// - `callback` - Called once per attempted job with the processed text on success,
+///   or the error message on failure (may be NULL)
+/// - `context` - Passed through to the callback
+///
+/// Draining stops at the first network failure. Jobs that fail for any other reason
+/// stay queued for 5 attempts, or are dropped right away if their audio is unusable.
+/// Same as flow_drain_pending with one job in flight and no progress callback.
 ///
 /// # Returns
-/// true on success
+/// Number of jobs transcribed successfully
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_learn_from_edit(
+pub extern "C" fn flow_retry_pending(
     handle: *mut FlowHandle,
-    original: *const c_char,
-    edited: *const c_char,
-) -> bool {
-    if original.is_null() || edited.is_null() {
-        return false;
-    }
-
-    let handle = unsafe { &*handle };
-
-    let original_str = match unsafe { CStr::from_ptr(original) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
+    callback: Option<ResultCallback>,
+    context: *mut c_void,
+) -> usize {
+    flow_drain_pending(handle, 1, callback, None, context)
+}
 
-    let edited_str = match unsafe { CStr::from_ptr(edited) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
+/// Progress callback for flow_drain_pending: `completed` of the `total` jobs that were
+/// queued when the drain started have finished, successfully or not
+pub type DrainProgressCallback =
+    extern "C" fn(completed: usize, total: usize, context: *mut c_void);
+
+/// Results of a drain, reported to the caller as jobs finish
+struct DrainProgress {
+    total: usize,
+    completed: usize,
+    succeeded: usize,
+    callback: Option<ResultCallback>,
+    progress: Option<DrainProgressCallback>,
+    context: CallbackContext,
+}
 
-    match handle
-        .learning
-        .learn_from_edit(original_str, edited_str, &handle.storage)
-    {
-        Ok(learned) => {
-            debug!("Learned {} corrections from edit", learned.len());
-            true
+impl DrainProgress {
+    /// Count a finished job, passing its result (if it was attempted) to the callbacks
+    fn finish(&mut self, result: Option<(bool, String)>) {
+        self.completed += 1;
+        if let Some((success, message)) = result {
+            if success {
+                self.succeeded += 1;
+            }
+            if let Some(callback) = self.callback {
+                let message = c_string(message);
+                callback(success, message.as_ptr(), self.context.0);
+            }
         }
-        Err(e) => {
-            error!("Failed to learn from edit: {}", e);
-            false
+        if let Some(progress) = self.progress {
+            progress(self.completed, self.total, self.context.0);
         }
     }
 }
 
-/// Get the number of learned corrections
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_correction_count(handle: *mut FlowHandle) -> usize {
-    let handle = unsafe { &*handle };
-    handle.learning.cache_size()
-}
-
-/// Get all corrections as JSON
-/// Returns JSON array: [{"id": "...", "original": "...", "corrected": "...", "occurrences": N, "confidence": N.N}, ...]
-/// Caller must free the returned string with flow_free_string
+/// Retry queued transcriptions with up to `max_in_flight` of them running at once
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `max_in_flight` - Most jobs transcribed at the same time (at least 1)
+/// - `callback` - Called once per attempted job with the processed text on success,
+///   or the error message on failure (may be NULL)
+/// - `progress` - Called after each finished job with the completed and total counts
+///   (may be NULL)
+/// - `context` - Passed through to both callbacks
+///
+/// Blocks until the queue is drained, a job fails with a network error (jobs already
+/// in flight still finish) or flow_cancel_drain is called. Jobs that weren't done
+/// stay queued, as do failed jobs with usable audio and fewer than 5 attempts. Only
+/// `max_in_flight` recordings are loaded at a time, and each one's audio is freed as
+/// soon as it's transcribed. The callbacks are never called concurrently. Only one
+/// drain can run per handle.
+///
+/// # Returns
+/// Number of jobs transcribed successfully
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_corrections_json(handle: *mut FlowHandle) -> *mut c_char {
-    let handle = unsafe { &*handle };
+pub extern "C" fn flow_drain_pending(
+    handle: *mut FlowHandle,
+    max_in_flight: usize,
+    callback: Option<ResultCallback>,
+    progress: Option<DrainProgressCallback>,
+    context: *mut c_void,
+) -> usize {
+    let handle_ref = unsafe { &*handle };
+
+    if max_in_flight == 0 {
+        set_last_error(
+            handle_ref,
+            ErrorCode::InvalidInput,
+            "max_in_flight must be at least 1",
+        );
+        return 0;
+    }
 
-    let corrections = match handle.storage.get_all_corrections() {
-        Ok(c) => c,
+    let ids = match handle_ref.storage.get_pending_transcription_ids() {
+        Ok(ids) => ids,
         Err(e) => {
-            error!("Failed to get corrections: {}", e);
-            return ptr::null_mut();
+            set_last_error(
+                handle_ref,
+                e.code(),
+                format!("Failed to load pending transcriptions: {e}"),
+            );
+            return 0;
         }
     };
 
-    let json_array: Vec<serde_json::Value> = corrections
-        .into_iter()
-        .map(|c| {
-            serde_json::json!({
-                "id": c.id.to_string(),
-                "original": c.original,
-                "corrected": c.corrected,
-                "occurrences": c.occurrences,
-                "confidence": c.confidence,
-                "source": format!("{:?}", c.source),
-                "created_at": c.created_at.to_rfc3339(),
-                "updated_at": c.updated_at.to_rfc3339(),
-            })
-        })
-        .collect();
+    let cancel = Arc::new(CancellationToken::new());
+    {
+        let mut drain = handle_ref.drain.lock();
+        if drain.is_some() {
+            set_last_error(
+                handle_ref,
+                ErrorCode::InvalidInput,
+                "Pending transcriptions are already being retried",
+            );
+            return 0;
+        }
+        *drain = Some(Arc::clone(&cancel));
+    }
 
-    match CString::new(serde_json::to_string(&json_array).unwrap_or_default()) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => ptr::null_mut(),
+    let workers = max_in_flight.min(ids.len());
+    let queue = Mutex::new(VecDeque::from(ids));
+    let progress = Mutex::new(DrainProgress {
+        total: queue.lock().len(),
+        completed: 0,
+        succeeded: 0,
+        callback,
+        progress,
+        context: CallbackContext(context),
+    });
+    // set on a network failure: we're offline again, so starting more jobs is pointless
+    let offline = AtomicBool::new(false);
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            let handle = HandlePtr(handle);
+            let (queue, progress, offline, cancel) = (&queue, &progress, &offline, &*cancel);
+            scope.spawn(move || {
+                let handle = handle;
+                let handle = unsafe { &*handle.0 };
+                drain_worker(handle, queue, progress, offline, cancel);
+            });
+        }
+    });
+
+    *handle_ref.drain.lock() = None;
+    let progress = progress.into_inner();
+    debug!(
+        "Retried pending transcriptions: {} of {} succeeded, {} left undrained",
+        progress.succeeded,
+        progress.total,
+        progress.total - progress.completed
+    );
+    progress.succeeded
+}
+
+/// Take jobs off the drain queue and transcribe them one at a time until it's empty,
+/// cancelled or offline
+fn drain_worker(
+    handle: &FlowHandle,
+    queue: &Mutex<VecDeque<uuid::Uuid>>,
+    progress: &Mutex<DrainProgress>,
+    offline: &AtomicBool,
+    cancel: &CancellationToken,
+) {
+    loop {
+        if cancel.is_cancelled() || offline.load(Ordering::SeqCst) {
+            return;
+        }
+        let Some(id) = queue.lock().pop_front() else {
+            return;
+        };
+
+        let job = match handle.storage.get_pending_transcription(&id) {
+            Ok(Some(job)) => job,
+            // removed since the drain started
+            Ok(None) => {
+                progress.lock().finish(None);
+                continue;
+            }
+            Err(e) => {
+                error!("Failed to load pending transcription {}: {}", id, e);
+                progress.lock().finish(None);
+                continue;
+            }
+        };
+
+        let duration_ms = estimate_duration_ms(job.audio.len(), job.sample_rate);
+        // the audio moves into the transcription and is freed when it returns
+        match transcribe_cancellable(
+            handle,
+            job.audio,
+            job.sample_rate,
+            job.app_name,
+            cancel,
+            false,
+        ) {
+            Ok(outcome) => {
+                if let Err(e) = handle.storage.delete_pending_transcription(&id) {
+                    error!("Failed to remove pending transcription: {}", e);
+                }
+                progress.lock().finish(Some((true, outcome.text)));
+            }
+            // left in the queue for the next drain
+            Err(crate::error::Error::Cancelled) => return,
+            // a rejected key, rate limit or server error can clear up, so the job is
+            // kept for a few more drains; only audio that can never work is dropped
+            Err(e)
+                if e.is_network()
+                    || (job.attempts + 1 < MAX_PENDING_ATTEMPTS
+                        && !matches!(e.code(), ErrorCode::Audio | ErrorCode::InvalidInput)) =>
+            {
+                if e.is_network() {
+                    offline.store(true, Ordering::SeqCst);
+                }
+                let message = format!("Transcription failed: {e}");
+                if let Err(e) = handle
+                    .storage
+                    .record_pending_transcription_failure(&id, &message)
+                {
+                    error!("Failed to update pending transcription: {}", e);
+                }
+                progress.lock().finish(Some((false, message)));
+            }
+            Err(e) => {
+                let message = format!("Transcription failed: {e}");
+                error!("Dropping pending transcription {}: {}", id, message);
+                if let Err(e) = handle.storage.delete_pending_transcription(&id) {
+                    error!("Failed to remove pending transcription: {}", e);
+                }
+                let history = TranscriptionHistoryEntry::failure(message.clone(), duration_ms);
+                if let Err(e) = handle.storage.save_history_entry(&history) {
+                    error!("Failed to save transcription history: {}", e);
+                }
+                progress.lock().finish(Some((false, message)));
+            }
+        }
     }
 }
 
-/// Delete a correction by ID
-/// Returns true if the correction was deleted, false if not found or on error
+/// Stop a running flow_drain_pending
+/// Jobs in flight are abandoned and, like the ones not started yet, stay queued.
+///
+/// # Returns
+/// true if a drain was running
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_delete_correction(handle: *mut FlowHandle, id: *const c_char) -> bool {
-    if id.is_null() {
-        return false;
+pub extern "C" fn flow_cancel_drain(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+
+    match handle.drain.lock().as_ref() {
+        Some(cancel) => {
+            cancel.cancel();
+            true
+        }
+        None => false,
     }
+}
 
+/// Get the number of recordings waiting in the offline retry queue
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_pending_count(handle: *mut FlowHandle) -> usize {
     let handle = unsafe { &*handle };
+    handle
+        .storage
+        .get_pending_transcription_count()
+        .unwrap_or(0)
+}
 
-    let id_str = match unsafe { CStr::from_ptr(id) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
+// ============ Sessions ============
 
-    let uuid = match uuid::Uuid::parse_str(id_str) {
-        Ok(u) => u,
-        Err(_) => {
-            error!("Invalid UUID: {}", id_str);
-            return false;
+/// Start an independent capture session, for apps that dictate into several fields
+/// Sessions record and transcribe separately from flow_start_recording and from each
+/// other. They end with flow_end_session or when the handle is destroyed.
+///
+/// # Returns
+/// Session id (never 0)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_start_session(handle: *mut FlowHandle) -> u64 {
+    let handle = unsafe { &*handle };
+    let id = handle.next_session_id.fetch_add(1, Ordering::Relaxed);
+    handle.sessions.lock().insert(id, Session::default());
+    debug!("Started session {}", id);
+    id
+}
+
+/// Run `f` on a session, recording an error if the id is unknown
+fn with_session<T>(
+    handle: &FlowHandle,
+    session_id: u64,
+    f: impl FnOnce(&mut Session) -> T,
+) -> Option<T> {
+    match handle.sessions.lock().get_mut(&session_id) {
+        Some(session) => Some(f(session)),
+        None => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                format!("Unknown session id {session_id}"),
+            );
+            None
         }
-    };
+    }
+}
 
-    // First, get the correction to find the original word (for cache removal)
-    if let Ok(corrections) = handle.storage.get_all_corrections() {
-        if let Some(correction) = corrections.iter().find(|c| c.id == uuid) {
-            let original = correction.original.clone();
+/// Start recording in a session
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_session_start_recording(handle: *mut FlowHandle, session_id: u64) -> bool {
+    let handle = unsafe { &*handle };
 
-            // Delete from storage
-            match handle.storage.delete_correction(&uuid) {
-                Ok(deleted) => {
-                    if deleted {
-                        // Remove from cache
-                        handle.learning.remove_from_cache(&original);
-                        debug!(
-                            "Deleted correction: {} -> {}",
-                            original, correction.corrected
-                        );
-                    }
-                    deleted
-                }
-                Err(e) => {
-                    error!("Failed to delete correction: {}", e);
-                    false
-                }
-            }
-        } else {
+    let started = with_session(handle, session_id, |session| {
+        let capture = match session.capture.as_mut() {
+            Some(capture) => capture,
+            None => session.capture.insert(new_capture(handle)?),
+        };
+        capture.start()
+    });
+
+    match started {
+        Some(Ok(())) => {
+            clear_last_error(handle);
+            true
+        }
+        Some(Err(e)) => {
+            let message = format!("Failed to start recording in session {session_id}: {e}");
+            error!("{message}");
+            set_last_error(handle, e.code(), message);
             false
         }
-    } else {
-        false
+        None => false,
     }
 }
 
-/// Delete all corrections
-/// Returns the number of corrections deleted
+/// Stop recording in a session, keeping the audio for flow_session_transcribe
+///
+/// # Returns
+/// Recording duration in milliseconds, or 0 on failure
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_delete_all_corrections(handle: *mut FlowHandle) -> usize {
+pub extern "C" fn flow_session_stop_recording(handle: *mut FlowHandle, session_id: u64) -> u64 {
     let handle = unsafe { &*handle };
 
-    match handle.storage.delete_all_corrections() {
-        Ok(count) => {
-            // Clear the cache
-            handle.learning.clear_cache();
-            debug!("Deleted all {} corrections", count);
-            count
+    let stopped = with_session(handle, session_id, |session| {
+        let capture = session.capture.take()?;
+        Some(finish_capture(capture).map(|audio| {
+            let duration = audio.duration_ms;
+            session.pending = Some(audio);
+            duration
+        }))
+    });
+
+    match stopped {
+        Some(Some(Ok(duration))) => {
+            clear_last_error(handle);
+            duration
         }
-        Err(e) => {
-            error!("Failed to delete all corrections: {}", e);
+        Some(Some(Err(e))) => {
+            let message = format!("Failed to stop recording in session {session_id}: {e}");
+            error!("{message}");
+            set_last_error(handle, e.code(), message);
+            0
+        }
+        Some(None) => {
+            set_last_error(
+                handle,
+                ErrorCode::Audio,
+                format!("Session {session_id} is not recording"),
+            );
             0
         }
+        None => 0,
     }
 }
 
-/// Validate corrections using AI (async, returns JSON)
-/// Input: JSON array of {"original": "...", "corrected": "..."} pairs
-/// Output: JSON array of {"original": "...", "corrected": "...", "valid": bool, "reason": "..."}
-/// Caller must free the returned string with flow_free_string
+/// Transcribe the audio recorded in a session
+/// Processing is the same as flow_transcribe: shortcuts, corrections and formatting
+/// for the given app.
+///
+/// # Returns
+/// Processed text (caller must free with flow_free_string), or NULL on failure
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_validate_corrections(
-    _handle: *mut FlowHandle,
-    corrections_json: *const c_char,
+pub extern "C" fn flow_session_transcribe(
+    handle: *mut FlowHandle,
+    session_id: u64,
+    app_name: *const c_char,
 ) -> *mut c_char {
-    if corrections_json.is_null() {
-        return ptr::null_mut();
-    }
+    let handle = unsafe { &*handle };
 
-    let json_str = match unsafe { CStr::from_ptr(corrections_json) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
+    let Some(pending) = with_session(handle, session_id, |session| session.pending.take()) else {
+        return ptr::null_mut();
     };
-
-    // Parse input JSON
-    let pairs: Vec<crate::providers::CorrectionPair> = match serde_json::from_str(json_str) {
-        Ok(p) => p,
-        Err(e) => {
-            error!("Failed to parse corrections JSON: {}", e);
+    let audio = match pending {
+        Some(audio) if !audio.data.is_empty() => audio,
+        Some(_) => {
+            set_last_error(handle, ErrorCode::Audio, "No audio captured");
             return ptr::null_mut();
         }
-    };
-
-    // Run async validation
-    let runtime = match tokio::runtime::Runtime::new() {
-        Ok(rt) => rt,
-        Err(e) => {
-            error!("Failed to create tokio runtime: {}", e);
+        None => {
+            set_last_error(
+                handle,
+                ErrorCode::Audio,
+                format!(
+                    "No audio pending in session {session_id} - must call flow_session_stop_recording first"
+                ),
+            );
             return ptr::null_mut();
         }
     };
 
-    let results = match runtime.block_on(crate::providers::validate_corrections(pairs)) {
-        Ok(r) => r,
-        Err(e) => {
-            error!("Validation failed: {}", e);
-            return ptr::null_mut();
-        }
+    let app = if app_name.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(app_name) }
+            .to_str()
+            .ok()
+            .map(String::from)
     };
 
-    // Return as JSON
-    match CString::new(serde_json::to_string(&results).unwrap_or_default()) {
-        Ok(cstr) => cstr.into_raw(),
+    match run_pending_transcription(handle, audio, app, &CancellationToken::new()) {
+        Ok(outcome) => string_to_c(outcome.text),
         Err(_) => ptr::null_mut(),
     }
 }
 
-// ============ Stats ============
-
-/// Get total transcription time in minutes
+/// End a session, stopping any recording and discarding its audio
+/// Returns true if the session existed
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_total_transcription_minutes(handle: *mut FlowHandle) -> u64 {
+pub extern "C" fn flow_end_session(handle: *mut FlowHandle, session_id: u64) -> bool {
     let handle = unsafe { &*handle };
-    handle
-        .storage
-        .get_total_transcription_time_ms()
-        .unwrap_or(0)
-        / 60000
-}
 
-/// Get total transcription count
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_transcription_count(handle: *mut FlowHandle) -> u64 {
-    let handle = unsafe { &*handle };
-    handle.storage.get_transcription_count().unwrap_or(0)
+    // dropping the session drops its capture, releasing the microphone
+    if handle.sessions.lock().remove(&session_id).is_none() {
+        set_last_error(
+            handle,
+            ErrorCode::InvalidInput,
+            format!("Unknown session id {session_id}"),
+        );
+        return false;
+    }
+
+    debug!("Ended session {}", session_id);
+    clear_last_error(handle);
+    true
 }
 
-// ============ Utilities ============
+// ============ Transforms ============
 
-/// Free a string returned by flow functions
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_free_string(s: *mut c_char) {
-    if !s.is_null() {
-        unsafe {
-            drop(CString::from_raw(s));
-        }
-    }
+/// Where a transform callback writes the rewritten text
+pub struct FlowTransformOutput {
+    text: Option<String>,
 }
 
-/// Check if the transcription provider is configured
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_is_configured(handle: *mut FlowHandle) -> bool {
-    let handle = unsafe { &*handle };
+/// Callback for flow_add_transform
+///
+/// Called with the text, the app name (NULL if unknown), the writing mode (0-3, as
+/// in flow_set_app_mode) and the registered context. To change the text, pass the
+/// result to flow_transform_output_set before returning; otherwise it is kept.
+pub type TransformCallback = extern "C" fn(
+    text: *const c_char,
+    app_name: *const c_char,
+    mode: u8,
+    context: *mut c_void,
+    output: *mut FlowTransformOutput,
+);
+
+/// A transform implemented by the caller
+struct CallbackTransform {
+    callback: TransformCallback,
+    context: CallbackContext,
+}
 
-    // Auto provider handles both transcription and completion internally via the worker,
-    // so we don't need a separate completion provider configured
-    if handle.transcription.name() == "Auto (Cloud)" {
-        return handle.transcription.is_configured();
-    }
+// SAFETY: the context is only passed back to the callback, which flow_add_transform
+// requires to be callable from any thread
+unsafe impl Sync for CallbackTransform {}
 
-    handle.transcription.is_configured() && handle.completion.is_configured()
+impl TextTransform for CallbackTransform {
+    fn transform(&self, text: &str, ctx: &TransformContext<'_>) -> String {
+        let c_text = c_string(text);
+        let app_name = ctx.app_name.map(c_string);
+        let mode = match ctx.mode {
+            WritingMode::Formal => 0,
+            WritingMode::Casual => 1,
+            WritingMode::VeryCasual => 2,
+            WritingMode::Excited => 3,
+        };
+        let mut output = FlowTransformOutput { text: None };
+        (self.callback)(
+            c_text.as_ptr(),
+            app_name.as_ref().map_or(ptr::null(), |name| name.as_ptr()),
+            mode,
+            self.context.0,
+            &mut output,
+        );
+        output.text.unwrap_or_else(|| text.to_string())
+    }
 }
 
-// ============ App Tracking ============
-
-/// Set the currently active app (call from Swift when app switches)
-/// Returns the suggested writing mode for the app
+/// Register a callback that rewrites text during transcription
+///
+/// Transforms at the same stage run in the order they were added, each getting
+/// the previous one's output. The callback runs on a background thread and must
+/// not call back into the handle.
+///
+/// In cloud mode the worker formats the text it transcribed, so when a transform at
+/// stage 0 or 1 changes the text, the worker's formatting is dropped and the text is
+/// formatted with the completion provider chosen with flow_set_completion_provider
+/// (or left unformatted without one).
+///
+/// # Arguments
+/// - `stage` - Runs after: 0 = transcription (before shortcuts), 1 = shortcuts,
+///   2 = corrections (before formatting), 3 = formatting (before redaction)
+/// - `callback` - Called with each transcription's text
+/// - `context` - Passed back to the callback
+///
+/// # Returns
+/// Id for flow_remove_transform, or 0 if the stage is invalid
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_set_active_app(
+pub extern "C" fn flow_add_transform(
     handle: *mut FlowHandle,
-    app_name: *const c_char,
-    bundle_id: *const c_char,
-    window_title: *const c_char,
-) -> u8 {
-    if app_name.is_null() {
-        return 1; // default to casual
-    }
-
+    stage: u8,
+    callback: TransformCallback,
+    context: *mut c_void,
+) -> u64 {
     let handle = unsafe { &*handle };
 
-    let name = match unsafe { CStr::from_ptr(app_name) }.to_str() {
-        Ok(s) => s.to_string(),
-        Err(_) => return 1,
-    };
-
-    let bid = if bundle_id.is_null() {
-        None
-    } else {
-        unsafe { CStr::from_ptr(bundle_id) }
-            .to_str()
-            .ok()
-            .map(String::from)
-    };
-
-    let title = if window_title.is_null() {
-        None
-    } else {
-        unsafe { CStr::from_ptr(window_title) }
-            .to_str()
-            .ok()
-            .map(String::from)
+    let stage = match stage {
+        0 => TransformStage::AfterTranscription,
+        1 => TransformStage::AfterShortcuts,
+        2 => TransformStage::AfterCorrections,
+        3 => TransformStage::AfterFormatting,
+        _ => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                format!("Invalid transform stage: {stage}"),
+            );
+            return 0;
+        }
     };
 
-    let _context = handle.app_tracker.set_active_app(name, bid, title);
-
-    // return suggested mode
-    match handle.app_tracker.suggested_mode() {
-        WritingMode::Formal => 0,
-        WritingMode::Casual => 1,
-        WritingMode::VeryCasual => 2,
-        WritingMode::Excited => 3,
-    }
+    let id = handle.transforms.register(
+        stage,
+        CallbackTransform {
+            callback,
+            context: CallbackContext(context),
+        },
+    );
+    clear_last_error(handle);
+    id
 }
 
-/// Get the current app's category
-/// Returns: 0=Email, 1=Slack, 2=Code, 3=Documents, 4=Social, 5=Browser, 6=Terminal, 7=Unknown
+/// Unregister a transform added with flow_add_transform
+/// Returns true if it was registered
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_app_category(handle: *mut FlowHandle) -> u8 {
+pub extern "C" fn flow_remove_transform(handle: *mut FlowHandle, id: u64) -> bool {
     let handle = unsafe { &*handle };
-
-    use crate::types::AppCategory;
-    match handle.app_tracker.current_category() {
-        AppCategory::Email => 0,
-        AppCategory::Slack => 1,
-        AppCategory::Code => 2,
-        AppCategory::Documents => 3,
-        AppCategory::Social => 4,
-        AppCategory::Browser => 5,
-        AppCategory::Terminal => 6,
-        AppCategory::Unknown => 7,
-    }
+    handle.transforms.remove(id)
 }
 
-/// Get current app name (caller must free with flow_free_string)
+/// Set the text a transform callback produced
+///
+/// Only valid inside the callback, with the output it was given. The text is copied.
+/// Returns false if either pointer is NULL or the text isn't valid UTF-8.
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_current_app(handle: *mut FlowHandle) -> *mut c_char {
-    let handle = unsafe { &*handle };
-
-    match handle.app_tracker.current_app() {
-        Some(ctx) => match CString::new(ctx.app_name) {
-            Ok(cstr) => cstr.into_raw(),
-            Err(_) => ptr::null_mut(),
-        },
-        None => ptr::null_mut(),
+pub extern "C" fn flow_transform_output_set(
+    output: *mut FlowTransformOutput,
+    text: *const c_char,
+) -> bool {
+    if output.is_null() || text.is_null() {
+        return false;
     }
+    let Ok(text) = (unsafe { CStr::from_ptr(text) }).to_str() else {
+        return false;
+    };
+    unsafe { &mut *output }.text = Some(text.to_string());
+    true
 }
 
-// ============ Style Learning ============
+// ============ Shortcuts ============
 
-/// Report edited text to learn user's style for current app
+/// Add a voice shortcut
+///
+/// In cloud mode, a transcription that triggers a shortcut skips the worker's
+/// formatting, which wouldn't include the replacement, and is formatted with the
+/// completion provider chosen with flow_set_completion_provider (or left unformatted
+/// without one).
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `trigger` - Trigger phrase
+/// - `replacement` - Replacement text
+///
+/// # Returns
+/// true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_learn_style(handle: *mut FlowHandle, edited_text: *const c_char) -> bool {
-    if edited_text.is_null() {
+pub extern "C" fn flow_add_shortcut(
+    handle: *mut FlowHandle,
+    trigger: *const c_char,
+    replacement: *const c_char,
+) -> bool {
+    let handle = unsafe { &*handle };
+
+    if trigger.is_null() || replacement.is_null() {
+        set_last_error(
+            handle,
+            ErrorCode::InvalidInput,
+            "Trigger or replacement cannot be null",
+        );
         return false;
     }
 
-    let handle = unsafe { &*handle };
-
-    let text = match unsafe { CStr::from_ptr(edited_text) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
+    let trigger_str = match unsafe { CStr::from_ptr(trigger) }.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in trigger");
+            return false;
+        }
     };
 
-    let app_name = match handle.app_tracker.current_app() {
-        Some(ctx) => ctx.app_name,
-        None => return false,
+    let replacement_str = match unsafe { CStr::from_ptr(replacement) }.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                "Invalid UTF-8 in replacement",
+            );
+            return false;
+        }
     };
 
-    let mut learner = handle.style_learner.lock();
-    learner.observe_with_storage(&app_name, text, &handle.storage);
+    let shortcut = Shortcut::new(trigger_str, replacement_str);
+
+    if let Err(e) = handle.storage.save_shortcut(&shortcut) {
+        let message = format!("Failed to save shortcut: {e}");
+        error!("{message}");
+        set_last_error(handle, e.code(), message);
+        return false;
+    }
 
+    handle.shortcuts.add_shortcut(shortcut);
     true
 }
 
-/// Get suggested mode based on learned style for current app
-/// Returns: 0=Formal, 1=Casual, 2=VeryCasual, 3=Excited, 255=no suggestion
+/// Remove a voice shortcut
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_style_suggestion(handle: *mut FlowHandle) -> u8 {
+pub extern "C" fn flow_remove_shortcut(handle: *mut FlowHandle, trigger: *const c_char) -> bool {
     let handle = unsafe { &*handle };
 
-    let app_name = match handle.app_tracker.current_app() {
-        Some(ctx) => ctx.app_name,
-        None => return 255,
+    if trigger.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "Trigger cannot be null");
+        return false;
+    }
+
+    let trigger_str = match unsafe { CStr::from_ptr(trigger) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in trigger");
+            return false;
+        }
     };
 
-    let learner = handle.style_learner.lock();
-    match learner.suggest_mode(&app_name) {
-        Some(suggestion) => match suggestion.suggested_mode {
-            WritingMode::Formal => 0,
-            WritingMode::Casual => 1,
-            WritingMode::VeryCasual => 2,
-            WritingMode::Excited => 3,
-        },
-        None => 255,
-    }
+    handle.shortcuts.remove_shortcut(trigger_str);
+    true
 }
 
-// ============ Extended Stats ============
-
-/// Get user stats as JSON (caller must free with flow_free_string)
+/// Also expand phrases that are close to a trigger, like "insert signatures" for
+/// "insert signature"
+/// `threshold` is the similarity a phrase needs (0.0-1.0, 0.85 is a good start);
+/// 0 matches exact triggers only, the default.
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_stats_json(handle: *mut FlowHandle) -> *mut c_char {
+pub extern "C" fn flow_set_fuzzy_shortcuts(handle: *mut FlowHandle, threshold: f64) -> bool {
     let handle = unsafe { &*handle };
 
-    let stats = serde_json::json!({
-        "total_transcriptions": handle.storage.get_transcription_count().unwrap_or(0),
-        "total_duration_ms": handle.storage.get_total_transcription_time_ms().unwrap_or(0),
-        "total_words_dictated": handle.storage.get_total_words_dictated().unwrap_or(0),
-        "shortcut_count": handle.shortcuts.count(),
-        "correction_count": handle.learning.cache_size(),
-    });
+    if !threshold.is_finite() {
+        set_last_error(
+            handle,
+            ErrorCode::InvalidInput,
+            "Fuzzy shortcut threshold must be a finite number",
+        );
+        return false;
+    }
 
-    match CString::new(stats.to_string()) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => ptr::null_mut(),
+    let threshold = threshold.clamp(0.0, 1.0);
+    handle
+        .shortcuts
+        .set_fuzzy_threshold((threshold > 0.0).then_some(threshold));
+
+    if let Err(e) = handle
+        .storage
+        .set_setting_as(SETTING_FUZZY_SHORTCUT_THRESHOLD, &threshold)
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save fuzzy shortcut threshold: {e}"),
+        );
+        return false;
     }
+
+    clear_last_error(handle);
+    true
 }
 
-/// Get recent transcriptions as JSON (caller must free with flow_free_string)
+/// Get the number of shortcuts
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_recent_transcriptions_json(
+pub extern "C" fn flow_shortcut_count(handle: *mut FlowHandle) -> usize {
+    let handle = unsafe { &*handle };
+    handle.shortcuts.count()
+}
+
+// ============ Writing Modes ============
+
+/// Set the writing mode for an app
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `app_name` - Name of the app
+/// - `mode` - Writing mode (0=Formal, 1=Casual, 2=VeryCasual, 3=Excited)
+///
+/// # Returns
+/// true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_app_mode(
     handle: *mut FlowHandle,
-    limit: usize,
-) -> *mut c_char {
+    app_name: *const c_char,
+    mode: u8,
+) -> bool {
     let handle = unsafe { &*handle };
-    let transcriptions = match handle.storage.get_recent_history(limit) {
-        Ok(items) => items,
-        Err(e) => {
-            error!("Failed to load transcriptions: {}", e);
-            return ptr::null_mut();
+
+    if app_name.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "App_name cannot be null");
+        return false;
+    }
+
+    let app = match unsafe { CStr::from_ptr(app_name) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in app_name");
+            return false;
         }
     };
 
-    let summaries: Vec<TranscriptionSummary> = transcriptions
-        .into_iter()
-        .map(|item| TranscriptionSummary {
-            id: item.id.to_string(),
-            status: item.status,
-            text: item.text,
-            raw_text: item.raw_text,
-            error: item.error,
-            duration_ms: item.duration_ms,
-            created_at: item.created_at.to_rfc3339(),
-            app_name: item.app_context.map(|ctx| ctx.app_name),
-        })
-        .collect();
-
-    let json = match serde_json::to_string(&summaries) {
-        Ok(value) => value,
-        Err(e) => {
-            error!("Failed to serialize transcriptions: {}", e);
-            return ptr::null_mut();
+    let writing_mode = match mode {
+        0 => WritingMode::Formal,
+        1 => WritingMode::Casual,
+        2 => WritingMode::VeryCasual,
+        3 => WritingMode::Excited,
+        _ => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid writing mode");
+            return false;
         }
     };
 
-    match CString::new(json) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => ptr::null_mut(),
+    if let Err(e) = handle
+        .modes
+        .set_mode_with_storage(app, writing_mode, &handle.storage)
+    {
+        let message = format!("Failed to save app mode: {e}");
+        error!("{message}");
+        set_last_error(handle, e.code(), message);
+        return false;
     }
+
+    true
 }
 
-/// Get the last error message (caller must free with flow_free_string)
+/// Get the writing mode for an app
+/// Returns: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_last_error(handle: *mut FlowHandle) -> *mut c_char {
+pub extern "C" fn flow_get_app_mode(handle: *mut FlowHandle, app_name: *const c_char) -> u8 {
     let handle = unsafe { &*handle };
-    let message = handle.last_error.lock().clone();
-    match message {
-        Some(text) => match CString::new(text) {
-            Ok(cstr) => cstr.into_raw(),
-            Err(_) => ptr::null_mut(),
-        },
-        None => ptr::null_mut(),
+
+    if app_name.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "App_name cannot be null");
+        return 1; // default to casual
     }
-}
 
-// ============ Provider Configuration ============
+    let app = match unsafe { CStr::from_ptr(app_name) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in app_name");
+            return 1;
+        }
+    };
 
-/// Switch completion provider (loads API key from database)
-/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
-/// Returns true if provider was switched successfully
+    let mode = handle.modes.get_mode_with_storage(app, &handle.storage);
+
+    match mode {
+        WritingMode::Formal => 0,
+        WritingMode::Casual => 1,
+        WritingMode::VeryCasual => 2,
+        WritingMode::Excited => 3,
+    }
+}
+
+/// Set the writing mode for apps without their own mode, and keep it across launches
+/// mode: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_switch_completion_provider(handle: *mut FlowHandle, provider: u8) -> bool {
-    let handle = unsafe { &mut *handle };
+pub extern "C" fn flow_set_default_mode(handle: *mut FlowHandle, mode: u8) -> bool {
+    let handle = unsafe { &*handle };
 
-    let (setting_key, provider_name) = match provider {
-        0 => (SETTING_OPENAI_API_KEY, "openai"),
-        1 => (SETTING_GEMINI_API_KEY, "gemini"),
-        2 => (SETTING_OPENROUTER_API_KEY, "openrouter"),
+    let writing_mode = match mode {
+        0 => WritingMode::Formal,
+        1 => WritingMode::Casual,
+        2 => WritingMode::VeryCasual,
+        3 => WritingMode::Excited,
         _ => {
-            set_last_error(handle, "Invalid provider");
-            return false;
-        }
-    };
-
-    // Load the API key from the database
-    let api_key = match handle.storage.get_setting(setting_key) {
-        Ok(Some(key)) if !key.is_empty() => key,
-        Ok(Some(_)) | Ok(None) => {
-            let message = format!("No API key configured for {}", provider_name);
-            error!("{message}");
-            set_last_error(handle, message);
-            return false;
-        }
-        Err(e) => {
-            let message = format!("Failed to load API key for {}: {}", provider_name, e);
-            error!("{message}");
-            set_last_error(handle, message);
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid writing mode");
             return false;
         }
     };
 
-    // Save the provider preference
     if let Err(e) = handle
-        .storage
-        .set_setting(SETTING_COMPLETION_PROVIDER, provider_name)
+        .modes
+        .set_default_mode_with_storage(writing_mode, &handle.storage)
     {
-        let message = format!("Failed to save completion provider: {e}");
+        let message = format!("Failed to save default mode: {e}");
         error!("{message}");
-        set_last_error(handle, message);
+        set_last_error(handle, e.code(), message);
         return false;
     }
 
-    // Initialize the provider
-    match provider {
-        0 => {
-            let base_url = handle
-                .storage
-                .get_setting(SETTING_OPENAI_BASE_URL)
-                .ok()
-                .flatten()
-                .filter(|s| !s.is_empty());
-            handle.transcription = Arc::new(OpenAITranscriptionProvider::new(
-                Some(api_key.clone()),
-                base_url.clone(),
-            ));
-            handle.completion =
-                Arc::new(OpenAICompletionProvider::new(Some(api_key), base_url));
-            debug!("Switched completion provider to OpenAI");
-        }
-        1 => {
-            handle.transcription =
-                Arc::new(GeminiTranscriptionProvider::new(Some(api_key.clone())));
-            handle.completion = Arc::new(GeminiCompletionProvider::new(Some(api_key)));
-            debug!("Switched completion provider to Gemini");
+    clear_last_error(handle);
+    true
+}
+
+/// Get the writing mode for apps without their own mode
+/// Returns: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_default_mode(handle: *mut FlowHandle) -> u8 {
+    let handle = unsafe { &*handle };
+
+    match handle.modes.default_mode() {
+        WritingMode::Formal => 0,
+        WritingMode::Casual => 1,
+        WritingMode::VeryCasual => 2,
+        WritingMode::Excited => 3,
+    }
+}
+
+/// Set the language dictation is formatted for, and keep it across launches
+/// The locale picks the writing mode instructions sent to the completion model and
+/// the punctuation rules applied afterwards (French spacing before ? ! : ;, Spanish
+/// ¿ and ¡), and is passed to transcription as a language hint. In cloud mode the
+/// worker formats the text, so it gets the locale with its completion request.
+///
+/// # Arguments
+/// - `locale` - Language code such as "fr" or "es-MX", or NULL/empty to follow the
+///   language the provider detects. Languages without their own rules use English.
+///
+/// Returns false if the code isn't a supported language or couldn't be saved
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_locale(handle: *mut FlowHandle, locale: *const c_char) -> bool {
+    let handle = unsafe { &*handle };
+
+    let code = if locale.is_null() {
+        ""
+    } else {
+        match unsafe { CStr::from_ptr(locale) }.to_str() {
+            Ok(s) => s.trim(),
+            Err(_) => {
+                set_last_error(handle, ErrorCode::InvalidInput, "Invalid locale string");
+                return false;
+            }
         }
-        2 => {
-            // OpenRouter only handles completion, keep existing transcription provider
-            handle.completion = Arc::new(OpenRouterCompletionProvider::new(Some(api_key)));
-            debug!("Switched completion provider to OpenRouter");
+    };
+
+    let (locale, result) = if code.is_empty() {
+        (None, handle.storage.delete_setting(SETTING_LOCALE))
+    } else {
+        match Locale::parse(code) {
+            Some(locale) => (
+                Some(locale),
+                handle.storage.set_setting(SETTING_LOCALE, locale.code()),
+            ),
+            None => {
+                set_last_error(
+                    handle,
+                    ErrorCode::InvalidInput,
+                    format!("Unsupported locale '{code}'"),
+                );
+                return false;
+            }
         }
-        _ => unreachable!(),
+    };
+
+    if let Err(e) = result {
+        set_last_error(handle, e.code(), format!("Failed to save locale: {e}"));
+        return false;
     }
+    *handle.locale.lock() = locale;
 
     clear_last_error(handle);
     true
 }
 
-/// Set completion provider with API key (saves both)
-/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
-/// api_key: The API key for the provider
+/// Set the completion provider and model for an app
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `app_name` - Name of the app
+/// - `provider` - 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 255 = use the global provider
+/// - `model` - Model name, or NULL/empty for the provider's default
+///
+/// Passing 255 with a NULL model clears the override.
+/// With local transcription, only apps with an override are formatted, so other
+/// apps' text never leaves the device.
+///
+/// # Returns
+/// true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_set_completion_provider(
+pub extern "C" fn flow_set_app_model(
     handle: *mut FlowHandle,
+    app_name: *const c_char,
     provider: u8,
-    api_key: *const c_char,
+    model: *const c_char,
 ) -> bool {
-    if api_key.is_null() {
+    let handle = unsafe { &*handle };
+
+    if app_name.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "App_name cannot be null");
         return false;
     }
 
-    let handle = unsafe { &mut *handle };
-
-    let key = match unsafe { CStr::from_ptr(api_key) }.to_str() {
-        Ok(s) => s.to_string(),
-        Err(_) => return false,
+    let app_name_str = match unsafe { CStr::from_ptr(app_name) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in app_name");
+            return false;
+        }
     };
 
-    match provider {
-        0 => {
-            if let Err(e) = handle.storage.set_setting(SETTING_OPENAI_API_KEY, &key) {
-                let message = format!("Failed to save OpenAI API key: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
-                return false;
-            }
-            if let Err(e) = handle
-                .storage
-                .set_setting(SETTING_COMPLETION_PROVIDER, "openai")
-            {
-                let message = format!("Failed to save completion provider: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
-                return false;
-            }
-            let base_url = handle
-                .storage
-                .get_setting(SETTING_OPENAI_BASE_URL)
-                .ok()
-                .flatten()
-                .filter(|s| !s.is_empty());
-            handle.transcription = Arc::new(OpenAITranscriptionProvider::new(
-                Some(key.clone()),
-                base_url.clone(),
-            ));
-            handle.completion = Arc::new(OpenAICompletionProvider::new(Some(key), base_url));
-            debug!("Set completion provider to OpenAI");
-        }
-        1 => {
-            if let Err(e) = handle.storage.set_setting(SETTING_GEMINI_API_KEY, &key) {
-                let message = format!("Failed to save Gemini API key: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
-                return false;
-            }
-            if let Err(e) = handle
-                .storage
-                .set_setting(SETTING_COMPLETION_PROVIDER, "gemini")
-            {
-                let message = format!("Failed to save completion provider: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
-                return false;
-            }
-            handle.transcription = Arc::new(GeminiTranscriptionProvider::new(Some(key.clone())));
-            handle.completion = Arc::new(GeminiCompletionProvider::new(Some(key)));
-            debug!("Set completion provider to Gemini");
-        }
-        2 => {
-            if let Err(e) = handle.storage.set_setting(SETTING_OPENROUTER_API_KEY, &key) {
-                let message = format!("Failed to save OpenRouter API key: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
-                return false;
-            }
-            if let Err(e) = handle
-                .storage
-                .set_setting(SETTING_COMPLETION_PROVIDER, "openrouter")
-            {
-                let message = format!("Failed to save completion provider: {e}");
-                error!("{message}");
-                set_last_error(handle, message);
+    let model_str = if model.is_null() {
+        None
+    } else {
+        match unsafe { CStr::from_ptr(model) }.to_str() {
+            Ok(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+            Err(_) => {
+                set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in model");
                 return false;
             }
-            // OpenRouter only handles completion, keep transcription provider as-is
-            handle.completion = Arc::new(OpenRouterCompletionProvider::new(Some(key)));
-            debug!("Set completion provider to OpenRouter");
         }
-        _ => return false,
-    }
+    };
 
-    true
-}
+    let provider_name = match provider {
+        0 => Some("openai".to_string()),
+        1 => Some("gemini".to_string()),
+        2 => Some("openrouter".to_string()),
+        255 => None,
+        _ => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid provider");
+            return false;
+        }
+    };
 
-/// Get the current completion provider name
-/// Returns: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 255 = Unknown
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_get_completion_provider(handle: *mut FlowHandle) -> u8 {
-    let handle = unsafe { &*handle };
+    let result = if provider_name.is_none() && model_str.is_none() {
+        handle
+            .modes
+            .clear_model_override_with_storage(app_name_str, &handle.storage)
+    } else {
+        let model_override = AppModelOverride {
+            provider: provider_name,
+            model: model_str,
+        };
+        handle
+            .modes
+            .set_model_override_with_storage(app_name_str, model_override, &handle.storage)
+    };
 
-    match handle.completion.name() {
-        "OpenAI GPT" => 0,
-        "Gemini" => 1,
-        "OpenRouter" => 2,
-        _ => 255,
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            set_last_error(handle, e.code(), format!("Failed to save app model: {e}"));
+            false
+        }
+    }
+}
+
+// ============ Learning ============
+
+/// Report a user edit to learn from
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `original` - Original transcribed text
+/// - `edited` - Text after user edits
+///
+/// # Returns
+/// true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_learn_from_edit(
+    handle: *mut FlowHandle,
+    original: *const c_char,
+    edited: *const c_char,
+) -> bool {
+    let handle = unsafe { &*handle };
+
+    if original.is_null() || edited.is_null() {
+        set_last_error(
+            handle,
+            ErrorCode::InvalidInput,
+            "Original or edited cannot be null",
+        );
+        return false;
+    }
+
+    let original_str = match unsafe { CStr::from_ptr(original) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in original");
+            return false;
+        }
+    };
+
+    let edited_str = match unsafe { CStr::from_ptr(edited) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in edited");
+            return false;
+        }
+    };
+
+    match handle
+        .learning
+        .learn_from_edit(original_str, edited_str, &handle.storage)
+    {
+        Ok(learned) => {
+            debug!("Learned {} corrections from edit", learned.len());
+            true
+        }
+        Err(e) => {
+            let message = format!("Failed to learn from edit: {e}");
+            error!("{message}");
+            set_last_error(handle, e.code(), message);
+            false
+        }
+    }
+}
+
+/// Get the number of learned corrections
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_correction_count(handle: *mut FlowHandle) -> usize {
+    let handle = unsafe { &*handle };
+    handle.learning.cache_size()
+}
+
+/// Get all corrections as JSON
+/// Lists general corrections only; the contextual copies learned alongside them
+/// are deleted with them
+/// Returns JSON array: [{"id": "...", "original": "...", "corrected": "...", "occurrences": N, "confidence": N.N}, ...]
+/// Caller must free the returned string with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_corrections_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let corrections = match handle.storage.get_all_corrections() {
+        Ok(c) => c,
+        Err(e) => {
+            let message = format!("Failed to get corrections: {e}");
+            error!("{message}");
+            set_last_error(handle, e.code(), message);
+            return ptr::null_mut();
+        }
+    };
+
+    let json_array: Vec<serde_json::Value> = corrections
+        .into_iter()
+        .filter(|c| c.context.is_none())
+        .map(|c| {
+            serde_json::json!({
+                "id": c.id.to_string(),
+                "original": c.original,
+                "corrected": c.corrected,
+                "occurrences": c.occurrences,
+                "confidence": c.confidence,
+                "source": format!("{:?}", c.source),
+                "created_at": c.created_at.to_rfc3339(),
+                "updated_at": c.updated_at.to_rfc3339(),
+            })
+        })
+        .collect();
+
+    string_to_c(serde_json::to_string(&json_array).unwrap_or_default())
+}
+
+/// Delete a correction by ID
+/// Returns true if the correction was deleted, false if not found or on error
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_delete_correction(handle: *mut FlowHandle, id: *const c_char) -> bool {
+    let handle = unsafe { &*handle };
+
+    if id.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "Id cannot be null");
+        return false;
+    }
+
+    let id_str = match unsafe { CStr::from_ptr(id) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in id");
+            return false;
+        }
+    };
+
+    let uuid = match uuid::Uuid::parse_str(id_str) {
+        Ok(u) => u,
+        Err(_) => {
+            let message = format!("Invalid UUID: {id_str}");
+            error!("{message}");
+            set_last_error(handle, ErrorCode::InvalidInput, message);
+            return false;
+        }
+    };
+
+    // First, get the correction to find the original word (for cache removal)
+    if let Ok(corrections) = handle.storage.get_all_corrections() {
+        if let Some(correction) = corrections.iter().find(|c| c.id == uuid) {
+            let original = correction.original.clone();
+
+            // Delete from storage
+            match handle.storage.delete_correction(&uuid) {
+                Ok(deleted) => {
+                    if deleted {
+                        // Remove from cache, along with the contextual copies storage dropped
+                        match &correction.context {
+                            Some(previous) => handle
+                                .learning
+                                .remove_contextual_from_cache(previous, &original),
+                            None => {
+                                handle.learning.remove_from_cache(&original);
+                                for sibling in corrections.iter().filter(|c| {
+                                    c.original == original && c.corrected == correction.corrected
+                                }) {
+                                    if let Some(previous) = &sibling.context {
+                                        handle
+                                            .learning
+                                            .remove_contextual_from_cache(previous, &original);
+                                    }
+                                }
+                            }
+                        }
+                        debug!(
+                            "Deleted correction: {} -> {}",
+                            original, correction.corrected
+                        );
+                    }
+                    deleted
+                }
+                Err(e) => {
+                    let message = format!("Failed to delete correction: {e}");
+                    error!("{message}");
+                    set_last_error(handle, e.code(), message);
+                    false
+                }
+            }
+        } else {
+            false
+        }
+    } else {
+        false
+    }
+}
+
+/// Delete all corrections
+/// Returns the number of corrections deleted
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_delete_all_corrections(handle: *mut FlowHandle) -> usize {
+    let handle = unsafe { &*handle };
+
+    match handle.storage.delete_all_corrections() {
+        Ok(count) => {
+            // Clear the cache
+            handle.learning.clear_cache();
+            debug!("Deleted all {} corrections", count);
+            count
+        }
+        Err(e) => {
+            error!("Failed to delete all corrections: {}", e);
+            0
+        }
+    }
+}
+
+/// Set the minimum similarity (0.0-1.0) for learning a word pair as a correction
+///
+/// Lower values learn more aggressive corrections; the default is 0.7.
+///
+/// # Returns
+/// true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_correction_similarity_threshold(
+    handle: *mut FlowHandle,
+    threshold: f64,
+) -> bool {
+    let handle = unsafe { &*handle };
+
+    if !threshold.is_finite() {
+        set_last_error(
+            handle,
+            ErrorCode::InvalidInput,
+            "Similarity threshold must be a finite number",
+        );
+        return false;
+    }
+
+    handle.learning.set_min_similarity(threshold);
+    let similarity = handle.learning.config().min_similarity;
+
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_MIN_CORRECTION_SIMILARITY, &similarity.to_string())
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save similarity threshold: {}", e),
+        );
+        return false;
+    }
+
+    debug!("Correction similarity threshold set to: {}", similarity);
+    clear_last_error(handle);
+    true
+}
+
+/// Set the lowest confidence at which learned corrections are applied (0.0-1.0, default 0.55)
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_min_correction_confidence(
+    handle: *mut FlowHandle,
+    confidence: f32,
+) -> bool {
+    let handle = unsafe { &*handle };
+
+    if !confidence.is_finite() {
+        set_last_error(
+            handle,
+            ErrorCode::InvalidInput,
+            "Confidence must be a finite number",
+        );
+        return false;
+    }
+
+    handle.learning.set_min_confidence(confidence);
+    let confidence = confidence.clamp(0.0, 1.0);
+
+    if let Err(e) = handle
+        .storage
+        .set_setting_as(SETTING_MIN_CORRECTION_CONFIDENCE, &confidence)
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save correction confidence: {e}"),
+        );
+        return false;
+    }
+
+    clear_last_error(handle);
+    true
+}
+
+/// Get the minimum similarity for learning a word pair as a correction
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_correction_similarity_threshold(handle: *mut FlowHandle) -> f64 {
+    let handle = unsafe { &*handle };
+    handle.learning.config().min_similarity
+}
+
+/// Set how many learned corrections are kept in memory (default 10000)
+///
+/// When there are more, the ones with the lowest confidence and least recent use
+/// are dropped from memory but kept in the database.
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_max_correction_cache_size(handle: *mut FlowHandle, max: usize) -> bool {
+    let handle = unsafe { &*handle };
+
+    handle.learning.set_max_cache_size(max);
+
+    if let Err(e) = handle
+        .storage
+        .set_setting_as(SETTING_MAX_CORRECTION_CACHE_SIZE, &max)
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save correction cache size: {e}"),
+        );
+        return false;
+    }
+
+    clear_last_error(handle);
+    true
+}
+
+/// Get how full the correction cache is
+/// Returns JSON: {"general": N, "contextual": N, "max": N}
+/// Caller must free the returned string with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_correction_cache_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    string_to_c(serde_json::to_string(&handle.learning.cache_occupancy()).unwrap_or_default())
+}
+
+/// Revert auto-applied corrections, restoring the words as transcribed
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `text_json` - JSON object `{"text": "...", "corrections": [...]}`, where `corrections`
+///   is the list returned by flow_transcribe_json and `text` its `corrected_text`; the
+///   positions are word indices into that text, not the formatted output
+///
+/// # Returns
+/// Text with the corrections undone (caller must free with flow_free_string), or NULL on error
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_revert_corrections(
+    handle: *mut FlowHandle,
+    text_json: *const c_char,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    if text_json.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "Revert input is null");
+        return ptr::null_mut();
+    }
+
+    let json_str = match unsafe { CStr::from_ptr(text_json) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                "Invalid revert input string",
+            );
+            return ptr::null_mut();
+        }
+    };
+
+    let input: RevertCorrectionsInput = match serde_json::from_str(json_str) {
+        Ok(input) => input,
+        Err(e) => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                format!("Failed to parse revert input JSON: {}", e),
+            );
+            return ptr::null_mut();
+        }
+    };
+
+    let reverted = handle
+        .learning
+        .revert_corrections(&input.text, &input.corrections);
+    clear_last_error(handle);
+
+    string_to_c(reverted)
+}
+
+/// Validate corrections using AI (async, returns JSON)
+/// Input: JSON array of {"original": "...", "corrected": "..."} pairs
+/// Output: JSON array of {"original": "...", "corrected": "...", "valid": bool, "reason": "..."}
+/// Caller must free the returned string with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_validate_corrections(
+    handle: *mut FlowHandle,
+    corrections_json: *const c_char,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    if corrections_json.is_null() {
+        set_last_error(
+            handle,
+            ErrorCode::InvalidInput,
+            "Corrections JSON cannot be null",
+        );
+        return ptr::null_mut();
+    }
+
+    let json_str = match unsafe { CStr::from_ptr(corrections_json) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                "Invalid UTF-8 in corrections JSON",
+            );
+            return ptr::null_mut();
+        }
+    };
+
+    // Parse input JSON
+    let pairs: Vec<crate::providers::CorrectionPair> = match serde_json::from_str(json_str) {
+        Ok(p) => p,
+        Err(e) => {
+            let message = format!("Failed to parse corrections JSON: {e}");
+            error!("{message}");
+            set_last_error(handle, ErrorCode::InvalidInput, message);
+            return ptr::null_mut();
+        }
+    };
+
+    let results = match handle
+        .runtime
+        .block_on(crate::providers::validate_corrections(pairs))
+    {
+        Ok(r) => r,
+        Err(e) => {
+            let message = format!("Validation failed: {e}");
+            error!("{message}");
+            set_last_error(handle, e.code(), message);
+            return ptr::null_mut();
+        }
+    };
+
+    // Return as JSON
+    string_to_c(serde_json::to_string(&results).unwrap_or_default())
+}
+
+// ============ Stats ============
+
+/// Get total transcription time in minutes
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_total_transcription_minutes(handle: *mut FlowHandle) -> u64 {
+    let handle = unsafe { &*handle };
+    handle
+        .storage
+        .get_total_transcription_time_ms()
+        .unwrap_or(0)
+        / 60000
+}
+
+/// Get total transcription count
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_transcription_count(handle: *mut FlowHandle) -> u64 {
+    let handle = unsafe { &*handle };
+    handle.storage.get_transcription_count().unwrap_or(0)
+}
+
+/// Get local usage analytics, computed on the device from stored transcriptions
+/// Returns JSON: {"total_transcriptions": N, "failed_transcriptions": N, "total_words": N,
+/// "total_duration_ms": N, "words_per_minute": F, "top_corrected_words": [{"original",
+/// "corrected", "occurrences"}], "apps": [{"app_name", "transcriptions",
+/// "total_duration_ms", "words"}], "busiest_app": "..." or null}, or NULL on error
+/// Caller must free the returned string with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_analytics(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    match Analytics::from_storage(&handle.storage) {
+        Ok(analytics) => {
+            clear_last_error(handle);
+            string_to_c(serde_json::to_string(&analytics).unwrap_or_default())
+        }
+        Err(e) => {
+            set_last_error(
+                handle,
+                e.code(),
+                format!("Failed to compute analytics: {e}"),
+            );
+            ptr::null_mut()
+        }
+    }
+}
+
+// ============ Utilities ============
+
+/// Build a C string from text, removing interior NUL bytes
+///
+/// A C string ends at its first NUL, so `CString::new` rejects text containing one.
+/// Provider output occasionally has a stray NUL; dropping it keeps the rest of the
+/// text instead of losing all of it.
+fn c_string(text: impl Into<String>) -> CString {
+    CString::new(text.into()).unwrap_or_else(|e| {
+        let mut bytes = e.into_vec();
+        let len = bytes.len();
+        bytes.retain(|&b| b != 0);
+        warn!(
+            "Removed {} NUL byte(s) from text returned over FFI",
+            len - bytes.len()
+        );
+        CString::new(bytes).unwrap_or_default()
+    })
+}
+
+/// Hand text to the caller, who must free it with `flow_free_string`
+fn string_to_c(text: impl Into<String>) -> *mut c_char {
+    c_string(text).into_raw()
+}
+
+/// Free a string returned by flow functions
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        unsafe {
+            drop(CString::from_raw(s));
+        }
+    }
+}
+
+/// Check if the transcription provider is configured
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_is_configured(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+    let transcription = handle.transcription();
+
+    // Auto provider handles both transcription and completion internally via the worker,
+    // so we don't need a separate completion provider configured
+    if transcription.includes_completion() {
+        return transcription.is_configured();
+    }
+
+    transcription.is_configured() && handle.completion().is_configured()
+}
+
+/// Caller-provided callback context, handed back on the runtime thread
+struct CallbackContext(*mut c_void);
+
+// SAFETY: the pointer is never dereferenced here, only passed back to the caller's callback
+unsafe impl Send for CallbackContext {}
+
+/// Health of a single provider
+#[derive(Serialize)]
+struct ProviderHealth {
+    provider: String,
+    ok: bool,
+    error: Option<String>,
+}
+
+impl ProviderHealth {
+    fn from_result(provider: &str, result: crate::error::Result<()>) -> Self {
+        Self {
+            provider: provider.to_string(),
+            ok: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// Result of flow_health_check
+#[derive(Serialize)]
+struct HealthReport {
+    transcription: ProviderHealth,
+    /// None when the transcription provider also handles completion
+    completion: Option<ProviderHealth>,
+}
+
+/// Verify the configured providers actually work (key valid, endpoint reachable)
+/// Runs in the background, so call it on launch rather than waiting for the first dictation.
+/// Local Whisper loads its model as part of the check, which also warms it up.
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `callback` - Called once with `success` true if every provider passed, and a JSON
+///   report `{"transcription": {"provider", "ok", "error"}, "completion": {...} or null}`.
+///   Never called if the handle is destroyed before the check finishes.
+/// - `context` - Passed through to the callback
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_health_check(
+    handle: *mut FlowHandle,
+    callback: ResultCallback,
+    context: *mut c_void,
+) {
+    let handle = unsafe { &*handle };
+
+    let transcription = handle.transcription();
+    // Auto provider handles completion through the same worker request
+    let completion = (!transcription.includes_completion()).then(|| handle.completion());
+    let context = CallbackContext(context);
+
+    handle.runtime.spawn(async move {
+        // move the whole wrapper in, not just its raw pointer field
+        let context = context;
+        let transcription_health =
+            ProviderHealth::from_result(transcription.name(), transcription.health_check().await);
+        let completion_health = match completion {
+            Some(provider) => Some(ProviderHealth::from_result(
+                provider.name(),
+                provider.health_check().await,
+            )),
+            None => None,
+        };
+
+        let success =
+            transcription_health.ok && completion_health.as_ref().is_none_or(|health| health.ok);
+        let report = HealthReport {
+            transcription: transcription_health,
+            completion: completion_health,
+        };
+        debug!("Provider health check finished (healthy: {})", success);
+
+        let json = serde_json::to_string(&report).unwrap_or_default();
+        let json = c_string(json);
+        callback(success, json.as_ptr(), context.0);
+    });
+}
+
+// ============ App Tracking ============
+
+/// Set the currently active app (call from Swift when app switches)
+/// Returns the suggested writing mode for the app
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_active_app(
+    handle: *mut FlowHandle,
+    app_name: *const c_char,
+    bundle_id: *const c_char,
+    window_title: *const c_char,
+) -> u8 {
+    let handle = unsafe { &*handle };
+
+    if app_name.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "App_name cannot be null");
+        return 1; // default to casual
+    }
+
+    let name = match unsafe { CStr::from_ptr(app_name) }.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in app_name");
+            return 1;
+        }
+    };
+
+    let bid = if bundle_id.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(bundle_id) }
+            .to_str()
+            .ok()
+            .map(String::from)
+    };
+
+    let title = if window_title.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(window_title) }
+            .to_str()
+            .ok()
+            .map(String::from)
+    };
+
+    let _context = handle.app_tracker.set_active_app(name, bid, title);
+
+    // return suggested mode
+    match handle.app_tracker.suggested_mode() {
+        WritingMode::Formal => 0,
+        WritingMode::Casual => 1,
+        WritingMode::VeryCasual => 2,
+        WritingMode::Excited => 3,
+    }
+}
+
+/// Get the current app's category
+/// Returns: 0=Email, 1=Slack, 2=Code, 3=Documents, 4=Social, 5=Browser, 6=Terminal, 7=Unknown
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_app_category(handle: *mut FlowHandle) -> u8 {
+    let handle = unsafe { &*handle };
+
+    match handle.app_tracker.current_category() {
+        AppCategory::Email => 0,
+        AppCategory::Slack => 1,
+        AppCategory::Code => 2,
+        AppCategory::Documents => 3,
+        AppCategory::Social => 4,
+        AppCategory::Browser => 5,
+        AppCategory::Terminal => 6,
+        AppCategory::Unknown => 7,
+    }
+}
+
+/// Get current app name (caller must free with flow_free_string)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_current_app(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    match handle.app_tracker.current_app() {
+        Some(ctx) => string_to_c(ctx.app_name),
+        None => ptr::null_mut(),
+    }
+}
+
+// ============ Style Learning ============
+
+/// Report edited text to learn user's style for current app
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_learn_style(handle: *mut FlowHandle, edited_text: *const c_char) -> bool {
+    let handle = unsafe { &*handle };
+
+    if edited_text.is_null() {
+        set_last_error(
+            handle,
+            ErrorCode::InvalidInput,
+            "Edited_text cannot be null",
+        );
+        return false;
+    }
+
+    let text = match unsafe { CStr::from_ptr(edited_text) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                "Invalid UTF-8 in edited_text",
+            );
+            return false;
+        }
+    };
+
+    let app_name = match handle.app_tracker.current_app() {
+        Some(ctx) => ctx.app_name,
+        None => return false,
+    };
+
+    let mut learner = handle.style_learner.lock();
+    learner.observe_with_storage(&app_name, text, &handle.storage);
+
+    true
+}
+
+/// Get suggested mode based on learned style for current app
+/// Returns: 0=Formal, 1=Casual, 2=VeryCasual, 3=Excited, 255=no suggestion
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_style_suggestion(handle: *mut FlowHandle) -> u8 {
+    let handle = unsafe { &*handle };
+
+    let app_name = match handle.app_tracker.current_app() {
+        Some(ctx) => ctx.app_name,
+        None => return 255,
+    };
+
+    let learner = handle.style_learner.lock();
+    match learner.suggest_mode(&app_name) {
+        Some(suggestion) => match suggestion.suggested_mode {
+            WritingMode::Formal => 0,
+            WritingMode::Casual => 1,
+            WritingMode::VeryCasual => 2,
+            WritingMode::Excited => 3,
+        },
+        None => 255,
+    }
+}
+
+// ============ Extended Stats ============
+
+/// Get user stats as JSON (caller must free with flow_free_string)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_stats_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let stats = serde_json::json!({
+        "total_transcriptions": handle.storage.get_transcription_count().unwrap_or(0),
+        "total_duration_ms": handle.storage.get_total_transcription_time_ms().unwrap_or(0),
+        "total_words_dictated": handle.storage.get_total_words_dictated().unwrap_or(0),
+        "shortcut_count": handle.shortcuts.count(),
+        "correction_count": handle.learning.cache_size(),
+    });
+
+    string_to_c(stats.to_string())
+}
+
+/// Get recent transcriptions as JSON (caller must free with flow_free_string)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_recent_transcriptions_json(
+    handle: *mut FlowHandle,
+    limit: usize,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    let transcriptions = match handle.storage.get_recent_history(limit) {
+        Ok(items) => items,
+        Err(e) => {
+            let message = format!("Failed to load transcriptions: {e}");
+            error!("{message}");
+            set_last_error(handle, e.code(), message);
+            return ptr::null_mut();
+        }
+    };
+
+    let summaries: Vec<TranscriptionSummary> = transcriptions
+        .into_iter()
+        .map(|item| TranscriptionSummary {
+            id: item.id.to_string(),
+            status: item.status,
+            text: item.text,
+            raw_text: item.raw_text,
+            error: item.error,
+            duration_ms: item.duration_ms,
+            created_at: item.created_at.to_rfc3339(),
+            app_name: item.app_context.map(|ctx| ctx.app_name),
+        })
+        .collect();
+
+    let json = match serde_json::to_string(&summaries) {
+        Ok(value) => value,
+        Err(e) => {
+            let message = format!("Failed to serialize transcriptions: {e}");
+            error!("{message}");
+            set_last_error(handle, ErrorCode::Internal, message);
+            return ptr::null_mut();
+        }
+    };
+
+    string_to_c(json)
+}
+
+/// Get the last error message (caller must free with flow_free_string)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_last_error(handle: *mut FlowHandle) -> *mut c_char {
+    flow_last_error_message(handle)
+}
+
+/// Get the code of the most recent error recorded on the handle
+/// Returns: 0 = none, 1 = unconfigured, 2 = network, 3 = timeout, 4 = auth,
+/// 5 = audio, 6 = storage, 7 = provider, 8 = invalid input, 9 = cancelled, 10 = internal
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_last_error(handle: *mut FlowHandle) -> i32 {
+    let handle = unsafe { &*handle };
+    let code = handle.last_error.lock().as_ref().map(|(code, _)| *code);
+    code.unwrap_or(ErrorCode::None) as i32
+}
+
+/// Get the message of the most recent error recorded on the handle
+/// Returns null if there is none (caller must free with flow_free_string)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_last_error_message(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    let message = handle
+        .last_error
+        .lock()
+        .as_ref()
+        .map(|(_, message)| message.clone());
+    match message {
+        Some(text) => string_to_c(text),
+        None => ptr::null_mut(),
+    }
+}
+
+// ============ Provider Configuration ============
+
+/// Switch completion provider (loads API key from database)
+/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
+/// Returns true if provider was switched successfully
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_switch_completion_provider(handle: *mut FlowHandle, provider: u8) -> bool {
+    let handle = unsafe { &*handle };
+
+    let (setting_key, provider_name) = match provider {
+        0 => (SETTING_OPENAI_API_KEY, "openai"),
+        1 => (SETTING_GEMINI_API_KEY, "gemini"),
+        2 => (SETTING_OPENROUTER_API_KEY, "openrouter"),
+        _ => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid provider");
+            return false;
+        }
+    };
+
+    // Load the API key from the database
+    let api_key = match handle.storage.get_setting(setting_key) {
+        Ok(Some(key)) if !key.is_empty() => key,
+        Ok(Some(_)) | Ok(None) => {
+            let message = format!("No API key configured for {}", provider_name);
+            error!("{message}");
+            set_last_error(handle, ErrorCode::Unconfigured, message);
+            return false;
+        }
+        Err(e) => {
+            let message = format!("Failed to load API key for {}: {}", provider_name, e);
+            error!("{message}");
+            set_last_error(handle, e.code(), message);
+            return false;
+        }
+    };
+
+    // Save the provider preference
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_COMPLETION_PROVIDER, provider_name)
+    {
+        let message = format!("Failed to save completion provider: {e}");
+        error!("{message}");
+        set_last_error(handle, e.code(), message);
+        return false;
+    }
+
+    // Initialize the provider
+    match provider {
+        0 => {
+            let base_url = handle
+                .storage
+                .get_setting(SETTING_OPENAI_BASE_URL)
+                .ok()
+                .flatten()
+                .filter(|s| !s.is_empty());
+            handle.set_transcription(Arc::new(OpenAITranscriptionProvider::new(
+                Some(api_key.clone()),
+                base_url.clone(),
+            )));
+            handle.set_completion(Arc::new(OpenAICompletionProvider::new(
+                Some(api_key),
+                base_url,
+            )));
+            debug!("Switched completion provider to OpenAI");
+        }
+        1 => {
+            handle.set_transcription(Arc::new(GeminiTranscriptionProvider::new(Some(
+                api_key.clone(),
+            ))));
+            handle.set_completion(Arc::new(GeminiCompletionProvider::new(Some(api_key))));
+            debug!("Switched completion provider to Gemini");
+        }
+        2 => {
+            // OpenRouter only handles completion, keep existing transcription provider
+            handle.set_completion(Arc::new(OpenRouterCompletionProvider::new(Some(api_key))));
+            debug!("Switched completion provider to OpenRouter");
+        }
+        _ => unreachable!(),
+    }
+
+    clear_last_error(handle);
+    true
+}
+
+/// Set completion provider with API key (saves both)
+/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
+/// api_key: The API key for the provider
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_completion_provider(
+    handle: *mut FlowHandle,
+    provider: u8,
+    api_key: *const c_char,
+) -> bool {
+    let handle = unsafe { &*handle };
+
+    if api_key.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "Api_key cannot be null");
+        return false;
+    }
+
+    let key = match unsafe { CStr::from_ptr(api_key) }.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in api_key");
+            return false;
+        }
+    };
+
+    match provider {
+        0 => {
+            if let Err(e) = handle.storage.set_setting(SETTING_OPENAI_API_KEY, &key) {
+                let message = format!("Failed to save OpenAI API key: {e}");
+                error!("{message}");
+                set_last_error(handle, e.code(), message);
+                return false;
+            }
+            if let Err(e) = handle
+                .storage
+                .set_setting(SETTING_COMPLETION_PROVIDER, "openai")
+            {
+                let message = format!("Failed to save completion provider: {e}");
+                error!("{message}");
+                set_last_error(handle, e.code(), message);
+                return false;
+            }
+            let base_url = handle
+                .storage
+                .get_setting(SETTING_OPENAI_BASE_URL)
+                .ok()
+                .flatten()
+                .filter(|s| !s.is_empty());
+            handle.set_transcription(Arc::new(OpenAITranscriptionProvider::new(
+                Some(key.clone()),
+                base_url.clone(),
+            )));
+            handle.set_completion(Arc::new(OpenAICompletionProvider::new(Some(key), base_url)));
+            debug!("Set completion provider to OpenAI");
+        }
+        1 => {
+            if let Err(e) = handle.storage.set_setting(SETTING_GEMINI_API_KEY, &key) {
+                let message = format!("Failed to save Gemini API key: {e}");
+                error!("{message}");
+                set_last_error(handle, e.code(), message);
+                return false;
+            }
+            if let Err(e) = handle
+                .storage
+                .set_setting(SETTING_COMPLETION_PROVIDER, "gemini")
+            {
+                let message = format!("Failed to save completion provider: {e}");
+                error!("{message}");
+                set_last_error(handle, e.code(), message);
+                return false;
+            }
+            handle.set_transcription(Arc::new(GeminiTranscriptionProvider::new(Some(
+                key.clone(),
+            ))));
+            handle.set_completion(Arc::new(GeminiCompletionProvider::new(Some(key))));
+            debug!("Set completion provider to Gemini");
+        }
+        2 => {
+            if let Err(e) = handle.storage.set_setting(SETTING_OPENROUTER_API_KEY, &key) {
+                let message = format!("Failed to save OpenRouter API key: {e}");
+                error!("{message}");
+                set_last_error(handle, e.code(), message);
+                return false;
+            }
+            if let Err(e) = handle
+                .storage
+                .set_setting(SETTING_COMPLETION_PROVIDER, "openrouter")
+            {
+                let message = format!("Failed to save completion provider: {e}");
+                error!("{message}");
+                set_last_error(handle, e.code(), message);
+                return false;
+            }
+            // OpenRouter only handles completion, keep transcription provider as-is
+            handle.set_completion(Arc::new(OpenRouterCompletionProvider::new(Some(key))));
+            debug!("Set completion provider to OpenRouter");
+        }
+        _ => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid provider");
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Get the current completion provider name
+/// Returns: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 255 = Unknown
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_completion_provider(handle: *mut FlowHandle) -> u8 {
+    let handle = unsafe { &*handle };
+
+    match handle.completion().name() {
+        "OpenAI GPT" => 0,
+        "Gemini" => 1,
+        "OpenRouter" => 2,
+        _ => 255,
     }
 }
 
@@ -1642,870 +4226,2928 @@ fn mask_api_key(key: &str) -> String {
         return String::new();
     }
 
-    // For OpenAI keys (sk-...)
-    if key.starts_with("sk-") {
-        return "sk-••••••••".to_string();
+    // For OpenAI keys (sk-...)
+    if key.starts_with("sk-") {
+        return "sk-••••••••".to_string();
+    }
+    // For Gemini keys (AI...)
+    if key.starts_with("AI") {
+        return "AI••••••••".to_string();
+    }
+    // For other keys, just show dots
+    "••••••••".to_string()
+}
+
+/// Get API key for a specific provider in masked form
+/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
+/// Returns null if no key is set, or a masked version like "sk-••••••••"
+/// Caller must free the returned string with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_api_key(handle: *mut FlowHandle, provider: u8) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let setting_key = match provider {
+        0 => SETTING_OPENAI_API_KEY,
+        1 => SETTING_GEMINI_API_KEY,
+        2 => SETTING_OPENROUTER_API_KEY,
+        _ => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid provider");
+            return ptr::null_mut();
+        }
+    };
+
+    match handle.storage.get_setting(setting_key) {
+        Ok(Some(key)) => {
+            let masked = mask_api_key(&key);
+            string_to_c(masked)
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Set transcription mode (local or remote)
+/// use_local: true for local Whisper, false for cloud provider
+/// whisper_model: Model selection (only used when use_local = true)
+///   0 = Turbo (~41MB) - quantized, ultra-fast, lowest memory
+///   1 = Fast (~151MB) - fast, lower accuracy
+///   2 = Balanced (~290MB) - good speed/accuracy balance
+///   3 = Quality (~789MB) - great accuracy, still fast [recommended]
+///   4 = Best (~1.5GB) - best quality available
+/// Returns true on success, false on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_transcription_mode(
+    handle: *mut FlowHandle,
+    use_local: bool,
+    whisper_model: u8,
+) -> bool {
+    let handle = unsafe { &*handle };
+
+    // Save setting to database
+    if let Err(e) = handle.storage.set_setting(
+        SETTING_USE_LOCAL_TRANSCRIPTION,
+        if use_local { "true" } else { "false" },
+    ) {
+        let message = format!("Failed to save transcription mode: {}", e);
+        error!("{}", message);
+        set_last_error(handle, e.code(), message);
+        return false;
+    }
+
+    if use_local {
+        // Local Whisper transcription
+        let Some(&model) = WhisperModel::all().get(whisper_model as usize) else {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                "Invalid Whisper model selection (0-4)",
+            );
+            return false;
+        };
+
+        // Save model choice using canonical name
+        let model_name = model.as_str();
+        if let Err(e) = handle
+            .storage
+            .set_setting(SETTING_LOCAL_WHISPER_MODEL, model_name)
+        {
+            let message = format!("Failed to save Whisper model: {}", e);
+            error!("{}", message);
+            set_last_error(handle, e.code(), message);
+            return false;
+        }
+
+        // Get models directory
+        let models_dir = match crate::whisper_models::get_models_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                let message = format!("Failed to get models directory: {}", e);
+                error!("{}", message);
+                set_last_error(handle, e.code(), message);
+                return false;
+            }
+        };
+
+        // Check if model files already exist
+        let files_exist = check_model_files_exist(model, &models_dir);
+
+        // Set loading flag if this will require downloading
+        if !files_exist {
+            handle.is_model_loading.store(true, Ordering::SeqCst);
+            debug!(
+                "Model files not found, will download (~{}MB)",
+                model.size_mb()
+            );
+        }
+
+        // Create provider
+        let provider = Arc::new(LocalWhisperTranscriptionProvider::new(model, models_dir));
+
+        // Trigger model download/load asynchronously
+        let provider_clone = Arc::clone(&provider);
+        let loading_flag = Arc::clone(&handle.is_model_loading);
+        let should_clear_flag = !files_exist;
+
+        handle.runtime.spawn(async move {
+            if let Err(e) = provider_clone.preload().await {
+                error!("Failed to load Whisper model: {}", e);
+            }
+            // Clear loading flag when done (only if we set it)
+            if should_clear_flag {
+                loading_flag.store(false, Ordering::SeqCst);
+                debug!("Model loading completed");
+            }
+        });
+
+        handle.set_transcription(provider);
+        debug!("Enabled local Whisper transcription with {:?} model", model);
+    } else {
+        // Remote transcription - use the cloud transcription provider setting
+        let cloud_provider = match handle
+            .storage
+            .get_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER)
+        {
+            Ok(Some(name)) => name,
+            _ => "auto".to_string(), // default to Auto
+        };
+
+        match cloud_provider.as_str() {
+            "openai" => {
+                if let Ok(Some(key)) = handle.storage.get_setting(SETTING_OPENAI_API_KEY) {
+                    let base_url = handle
+                        .storage
+                        .get_setting(SETTING_OPENAI_BASE_URL)
+                        .ok()
+                        .flatten()
+                        .filter(|s| !s.is_empty());
+                    handle.set_transcription(Arc::new(OpenAITranscriptionProvider::new(
+                        Some(key),
+                        base_url,
+                    )));
+                    debug!("Enabled OpenAI remote transcription");
+                } else {
+                    set_last_error(
+                        handle,
+                        ErrorCode::Unconfigured,
+                        "OpenAI API key not configured",
+                    );
+                    return false;
+                }
+            }
+            _ => {
+                // Default to Auto (worker handles transcription + completion)
+                handle.set_transcription(Arc::new(AutoTranscriptionProvider::new(None)));
+                debug!("Enabled Auto transcription (worker handles everything)");
+            }
+        }
+    }
+
+    true
+}
+
+/// Get current transcription mode settings
+/// Returns use_local flag and whisper_model (0-4) via out parameters
+/// Returns false on database error, true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_transcription_mode(
+    handle: *mut FlowHandle,
+    out_use_local: *mut bool,
+    out_whisper_model: *mut u8,
+) -> bool {
+    let handle = unsafe { &*handle };
+
+    // Read use_local setting from database
+    let use_local = match handle.storage.get_setting(SETTING_USE_LOCAL_TRANSCRIPTION) {
+        Ok(Some(value)) => value == "true",
+        Ok(None) => false, // Default to remote if not set
+        Err(e) => {
+            let message = format!("Failed to read transcription mode: {e}");
+            error!("{message}");
+            set_last_error(handle, e.code(), message);
+            return false;
+        }
+    };
+
+    // Read whisper model setting from database
+    let whisper_model = if use_local {
+        match handle.storage.get_setting(SETTING_LOCAL_WHISPER_MODEL) {
+            Ok(Some(model_str)) => {
+                // Convert model name to its preset index
+                WhisperModel::all()
+                    .iter()
+                    .position(|m| m.as_str() == model_str)
+                    .unwrap_or(2) as u8 // Default to Balanced
+            }
+            Ok(None) => 1, // Default to Balanced
+            Err(e) => {
+                let message = format!("Failed to read Whisper model: {e}");
+                error!("{message}");
+                set_last_error(handle, e.code(), message);
+                return false;
+            }
+        }
+    } else {
+        1 // Default value when using remote transcription
+    };
+
+    // Write to out parameters
+    unsafe {
+        *out_use_local = use_local;
+        *out_whisper_model = whisper_model;
+    }
+
+    true
+}
+
+/// Check if a Whisper model is currently being downloaded/initialized
+/// Returns true if model download/initialization is in progress
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_is_model_loading(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+    handle.is_model_loading.load(Ordering::SeqCst)
+}
+
+/// Load the local Whisper model and warm it up in the background, so the first
+/// dictation doesn't wait for it. Transcriptions started meanwhile share the same load.
+/// Cloud providers have nothing to load and succeed immediately.
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `callback` - Called once with `success` and an error message (empty on success).
+///   Never called if the handle is destroyed before loading finishes.
+/// - `context` - Passed through to the callback
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_preload_model(
+    handle: *mut FlowHandle,
+    callback: ResultCallback,
+    context: *mut c_void,
+) {
+    let handle = unsafe { &*handle };
+
+    let transcription = handle.transcription();
+    let context = CallbackContext(context);
+
+    handle.runtime.spawn(async move {
+        // move the whole wrapper in, not just its raw pointer field
+        let context = context;
+        let (success, message) = match transcription.warm_up().await {
+            Ok(()) => {
+                debug!("{} ready", transcription.name());
+                (true, String::new())
+            }
+            Err(e) => {
+                error!("Failed to preload {}: {}", transcription.name(), e);
+                (false, e.to_string())
+            }
+        };
+        let message = c_string(message);
+        callback(success, message.as_ptr(), context.0);
+    });
+}
+
+/// Legacy function - prefer flow_set_transcription_mode
+/// Enable local Whisper transcription with Metal + Accelerate acceleration
+/// model: 0=Turbo, 1=Fast, 2=Balanced, 3=Quality, 4=Best
+/// Returns true on success, false on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_enable_local_whisper(handle: *mut FlowHandle, model: u8) -> bool {
+    flow_set_transcription_mode(handle, true, model)
+}
+
+/// Get available Whisper models as JSON (caller must free with flow_free_string)
+/// Returns JSON array with model info including id, name, description, download size,
+/// estimated RAM (`ram_mb`) and flags
+/// Only Turbo is quantized (8-bit tiny); 5-bit weights aren't offered since none are
+/// published
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_whisper_models_json() -> *mut c_char {
+    let models: Vec<serde_json::Value> = WhisperModel::all()
+        .iter()
+        .enumerate()
+        .map(|(id, model)| {
+            serde_json::json!({
+                "id": id,
+                "name": model.as_str(),
+                "description": model.description(),
+                "size_mb": model.size_mb(),
+                "ram_mb": model.estimated_ram_mb(),
+                "is_quantized": model.is_quantized(),
+                "is_distilled": model.is_distilled(),
+            })
+        })
+        .collect();
+
+    let json = serde_json::to_string(&models).unwrap_or_else(|_| "[]".to_string());
+    string_to_c(json)
+}
+
+/// Get all shortcuts as JSON (caller must free with flow_free_string)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_shortcuts_json(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let shortcuts: Vec<serde_json::Value> = handle
+        .shortcuts
+        .get_all()
+        .iter()
+        .map(|s| {
+            serde_json::json!({
+                "trigger": s.trigger,
+                "replacement": s.replacement,
+                "use_count": s.use_count,
+                "enabled": s.enabled,
+            })
+        })
+        .collect();
+
+    string_to_c(serde_json::to_string(&shortcuts).unwrap_or_default())
+}
+
+/// Get each shortcut's trigger count and last use as JSON (caller must free with flow_free_string)
+/// Format: [{"trigger", "replacement", "use_count", "last_used_at", "enabled"}], most used first;
+/// `last_used_at` is an RFC 3339 timestamp or null if the shortcut never fired
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_shortcut_stats(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let mut shortcuts = match handle.storage.get_all_shortcuts() {
+        Ok(shortcuts) => shortcuts,
+        Err(e) => {
+            set_last_error(
+                handle,
+                e.code(),
+                format!("Failed to load shortcut stats: {}", e),
+            );
+            return ptr::null_mut();
+        }
+    };
+    shortcuts.sort_by_key(|s| std::cmp::Reverse(s.use_count));
+
+    let stats: Vec<serde_json::Value> = shortcuts
+        .iter()
+        .map(|s| {
+            serde_json::json!({
+                "trigger": s.trigger,
+                "replacement": s.replacement,
+                "use_count": s.use_count,
+                "last_used_at": s.last_used_at.map(|t| t.to_rfc3339()),
+                "enabled": s.enabled,
+            })
+        })
+        .collect();
+
+    string_to_c(serde_json::to_string(&stats).unwrap_or_default())
+}
+
+// ============ Contact Categorization ============
+
+/// Get active contact name from Messages.app window
+/// Returns C string with contact name, or null if not available
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_active_messages_contact(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    match MessagesDetector::get_active_contact() {
+        Ok(Some(name)) => string_to_c(name),
+        Ok(None) => ptr::null_mut(),
+        Err(e) => {
+            set_last_error(
+                handle,
+                e.code(),
+                format!("Failed to get active contact: {}", e),
+            );
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Classify a contact given name and organization
+/// Returns JSON string with category
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_classify_contact(
+    handle: *mut FlowHandle,
+    name: *const c_char,
+    organization: *const c_char,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    let name_str = unsafe {
+        if name.is_null() {
+            set_last_error(handle, ErrorCode::InvalidInput, "Name cannot be null");
+            return ptr::null_mut();
+        }
+        match CStr::from_ptr(name).to_str() {
+            Ok(s) => s,
+            Err(_) => {
+                set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in name");
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let org_str = unsafe {
+        if organization.is_null() {
+            String::new()
+        } else {
+            match CStr::from_ptr(organization).to_str() {
+                Ok(s) => s.to_string(),
+                Err(_) => String::new(),
+            }
+        }
+    };
+
+    let input = ContactInput {
+        name: name_str.to_string(),
+        organization: org_str,
+    };
+
+    let category = handle.contact_classifier.classify(&input);
+
+    let result = serde_json::json!({
+        "name": name_str,
+        "category": category,
+    });
+
+    string_to_c(result.to_string())
+}
+
+/// Classify multiple contacts from JSON array
+/// Input format: [{"name": "...", "organization": "..."}]
+/// Output format: {"ContactName": "category", ...}
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_classify_contacts_batch(
+    handle: *mut FlowHandle,
+    contacts_json: *const c_char,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    let json_str = unsafe {
+        if contacts_json.is_null() {
+            set_last_error(handle, ErrorCode::InvalidInput, "JSON cannot be null");
+            return ptr::null_mut();
+        }
+        match CStr::from_ptr(contacts_json).to_str() {
+            Ok(s) => s,
+            Err(_) => {
+                set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in JSON");
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let inputs: Vec<ContactInput> = match serde_json::from_str(json_str) {
+        Ok(i) => i,
+        Err(e) => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                format!("Invalid JSON: {}", e),
+            );
+            return ptr::null_mut();
+        }
+    };
+
+    let result_json = handle.contact_classifier.classify_batch_json(&inputs);
+
+    string_to_c(result_json)
+}
+
+/// Record interaction with a contact (updates frequency)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_record_contact_interaction(handle: *mut FlowHandle, name: *const c_char) {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    let name_str = unsafe {
+        if name.is_null() {
+            set_last_error(handle, ErrorCode::InvalidInput, "Name cannot be null");
+            return;
+        }
+        match CStr::from_ptr(name).to_str() {
+            Ok(s) => s,
+            Err(_) => {
+                set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in name");
+                return;
+            }
+        }
+    };
+
+    handle.contact_classifier.record_interaction(name_str);
+}
+
+/// Get frequent contacts as JSON array
+/// Returns: [{"name": "...", "category": "...", "frequency": N}, ...]
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_frequent_contacts(handle: *mut FlowHandle, limit: u32) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    let contacts = handle
+        .contact_classifier
+        .get_frequent_contacts(limit as usize);
+
+    let result: Vec<serde_json::Value> = contacts
+        .iter()
+        .map(|c| {
+            serde_json::json!({
+                "name": c.name,
+                "category": c.category,
+                "frequency": c.frequency,
+                "organization": c.organization,
+            })
+        })
+        .collect();
+
+    string_to_c(serde_json::to_string(&result).unwrap_or_default())
+}
+
+/// Get suggested writing mode for a contact category
+/// Returns: 0=Formal, 1=Casual, 2=VeryCasual, 3=Excited
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_writing_mode_for_category(
+    handle: *mut FlowHandle,
+    category: u32,
+) -> u32 {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    use crate::types::ContactCategory;
+
+    let contact_category = match category {
+        0 => ContactCategory::Professional,
+        1 => ContactCategory::CloseFamily,
+        2 => ContactCategory::CasualPeer,
+        3 => ContactCategory::Partner,
+        4 => ContactCategory::FormalNeutral,
+        _ => ContactCategory::FormalNeutral,
+    };
+
+    let writing_mode = contact_category.suggested_writing_mode();
+
+    match writing_mode {
+        WritingMode::Formal => 0,
+        WritingMode::Casual => 1,
+        WritingMode::VeryCasual => 2,
+        WritingMode::Excited => 3,
+    }
+}
+
+// ============ Cloud Transcription Provider ============
+
+/// Set cloud transcription provider (saves preference)
+/// provider: 0 = OpenAI, 1 = Auto (default)
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_cloud_transcription_provider(
+    handle: *mut FlowHandle,
+    provider: u8,
+) -> bool {
+    let handle = unsafe { &*handle };
+
+    let provider_name = match provider {
+        0 => "openai",
+        1 => "auto",
+        _ => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                "Invalid cloud transcription provider (0=OpenAI, 1=Auto)",
+            );
+            return false;
+        }
+    };
+
+    // Save the provider preference
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER, provider_name)
+    {
+        let message = format!("Failed to save cloud transcription provider: {e}");
+        error!("{message}");
+        set_last_error(handle, e.code(), message);
+        return false;
+    }
+
+    clear_last_error(handle);
+    true
+}
+
+/// Get the current cloud transcription provider
+/// Returns: 0 = OpenAI, 1 = Auto (default)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_cloud_transcription_provider(handle: *mut FlowHandle) -> u8 {
+    let handle = unsafe { &*handle };
+
+    match handle
+        .storage
+        .get_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER)
+    {
+        Ok(Some(name)) => match name.as_str() {
+            "openai" => 0,
+            "auto" => 1,
+            _ => 1, // default to Auto
+        },
+        _ => 1, // default to Auto
+    }
+}
+
+// ============ Auto-Rewriting Setting ============
+
+/// Set whether auto-rewriting is enabled
+/// When disabled, transcriptions are returned as-is (with shortcuts only, no corrections or AI)
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `enabled` - Whether auto-rewriting should be enabled
+///
+/// # Returns
+/// true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_auto_rewriting_enabled(handle: *mut FlowHandle, enabled: bool) -> bool {
+    let handle = unsafe { &*handle };
+
+    let value = if enabled { "true" } else { "false" };
+
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_AUTO_REWRITING_ENABLED, value)
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save auto-rewriting setting: {}", e),
+        );
+        return false;
+    }
+
+    debug!("Auto-rewriting set to: {}", enabled);
+    true
+}
+
+/// Get whether auto-rewriting is enabled
+///
+/// # Arguments
+/// - `handle` - Engine handle
+///
+/// # Returns
+/// true if auto-rewriting is enabled, false otherwise (default: true)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_auto_rewriting_enabled(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+
+    handle
+        .storage
+        .get_setting(SETTING_AUTO_REWRITING_ENABLED)
+        .ok()
+        .flatten()
+        .map(|s| s == "true")
+        .unwrap_or(true) // default to enabled
+}
+
+// ============ Alignment and Edit Detection ============
+
+/// Align original and edited text, extract correction candidates
+/// Returns JSON with alignment result (caller must free with flow_free_string)
+/// JSON format:
+/// {
+///   "steps": [...],
+///   "word_edit_vector": "MMSMM",
+///   "punct_edit_vector": "ZZZZ",
+///   "corrections": [["original", "corrected"], ...]
+/// }
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_align_and_extract_corrections(
+    original: *const c_char,
+    edited: *const c_char,
+) -> *mut c_char {
+    if original.is_null() || edited.is_null() {
+        return ptr::null_mut();
+    }
+
+    let original_str = match unsafe { CStr::from_ptr(original) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let edited_str = match unsafe { CStr::from_ptr(edited) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let json = crate::alignment::align_and_extract_corrections_json(original_str, edited_str);
+
+    string_to_c(json)
+}
+
+/// Get dictionary context for ASR prompting
+/// Returns JSON array of high-confidence learned words (caller must free with flow_free_string)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_dictionary_context(handle: *mut FlowHandle, limit: u32) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let words = handle
+        .storage
+        .get_dictionary_context(limit as usize)
+        .unwrap_or_default();
+
+    let json = serde_json::to_string(&words).unwrap_or_else(|_| "[]".to_string());
+
+    string_to_c(json)
+}
+
+/// Save edit analytics for tracking alignment patterns
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_save_edit_analytics(
+    handle: *mut FlowHandle,
+    word_edit_vector: *const c_char,
+    punct_edit_vector: *const c_char,
+    original_text: *const c_char,
+    edited_text: *const c_char,
+) -> bool {
+    let handle = unsafe { &*handle };
+
+    if word_edit_vector.is_null() {
+        set_last_error(
+            handle,
+            ErrorCode::InvalidInput,
+            "Word_edit_vector cannot be null",
+        );
+        return false;
+    }
+
+    let word_vec = match unsafe { CStr::from_ptr(word_edit_vector) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                "Invalid UTF-8 in word_edit_vector",
+            );
+            return false;
+        }
+    };
+
+    let punct_vec = if punct_edit_vector.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(punct_edit_vector) }.to_str().ok()
+    };
+
+    let original = if original_text.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(original_text) }.to_str().ok()
+    };
+
+    let edited = if edited_text.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(edited_text) }.to_str().ok()
+    };
+
+    handle
+        .storage
+        .save_edit_analytics(None, word_vec, punct_vec, original, edited)
+        .is_ok()
+}
+
+/// Save a learned words session for undo functionality
+/// words_json: JSON array of strings ["word1", "word2", ...]
+/// Returns session ID (or -1 on error)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_save_learned_words_session(
+    handle: *mut FlowHandle,
+    words_json: *const c_char,
+) -> i64 {
+    let handle = unsafe { &*handle };
+
+    let json_str = match unsafe { CStr::from_ptr(words_json) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                "Invalid UTF-8 in words_json",
+            );
+            return -1;
+        }
+    };
+
+    let words: Vec<String> = match serde_json::from_str(json_str) {
+        Ok(w) => w,
+        Err(e) => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                format!("Invalid learned words JSON: {e}"),
+            );
+            return -1;
+        }
+    };
+
+    handle
+        .storage
+        .save_learned_words_session(&words)
+        .unwrap_or(-1)
+}
+
+/// Undo the most recent learned words session
+/// Removes the corrections and marks session as used
+/// Returns true if undo was performed
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_undo_learned_words(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+
+    let Some((session_id, words)) = handle.storage.get_undoable_learned_words().ok().flatten()
+    else {
+        return false;
+    };
+
+    // Delete each learned word
+    for word in &words {
+        let _ = handle.storage.delete_correction_by_word(word);
+        // Also remove from learning engine cache
+        handle.learning.remove_from_cache(word);
+    }
+
+    // Mark session as used
+    let _ = handle.storage.mark_learned_words_used(session_id);
+
+    debug!("Undid learned words session {}: {:?}", session_id, words);
+    true
+}
+
+/// Get the most recent undoable learned words as JSON
+/// Returns JSON array of strings (caller must free with flow_free_string)
+/// Returns null if no undoable session exists
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_undoable_learned_words(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    let Some((_, words)) = handle.storage.get_undoable_learned_words().ok().flatten() else {
+        return ptr::null_mut();
+    };
+
+    let json = serde_json::to_string(&words).unwrap_or_else(|_| "[]".to_string());
+
+    string_to_c(json)
+}
+
+// ============ OpenAI Base URL ============
+
+/// Set a custom OpenAI-compatible base URL for transcription and completion
+/// Pass an empty string to reset to the default (https://api.openai.com/v1)
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_openai_base_url(handle: *mut FlowHandle, url: *const c_char) -> bool {
+    let handle = unsafe { &*handle };
+
+    let url_str = if url.is_null() {
+        String::new()
+    } else {
+        match unsafe { CStr::from_ptr(url) }.to_str() {
+            Ok(s) => s.trim().to_string(),
+            Err(_) => {
+                set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in url");
+                return false;
+            }
+        }
+    };
+
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_OPENAI_BASE_URL, &url_str)
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save OpenAI base URL: {e}"),
+        );
+        return false;
+    }
+
+    // Update the live transcription provider only when currently using cloud OpenAI
+    let use_local = handle
+        .storage
+        .get_setting(SETTING_USE_LOCAL_TRANSCRIPTION)
+        .ok()
+        .flatten()
+        .map(|s| s == "true")
+        .unwrap_or(false);
+
+    let cloud_provider = handle
+        .storage
+        .get_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "auto".to_string());
+
+    if !use_local && cloud_provider == "openai" {
+        let api_key = handle
+            .storage
+            .get_setting(SETTING_OPENAI_API_KEY)
+            .ok()
+            .flatten();
+        let base_url = if url_str.is_empty() {
+            None
+        } else {
+            Some(url_str)
+        };
+        handle.set_transcription(Arc::new(OpenAITranscriptionProvider::new(
+            api_key, base_url,
+        )));
+    }
+
+    clear_last_error(handle);
+    true
+}
+
+/// Get the currently configured custom OpenAI base URL
+/// Returns null if using the default (https://api.openai.com/v1)
+/// Caller must free the returned string with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_openai_base_url(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+
+    match handle.storage.get_setting(SETTING_OPENAI_BASE_URL) {
+        Ok(Some(url)) if !url.is_empty() => string_to_c(url),
+        _ => ptr::null_mut(),
+    }
+}
+
+// ============ Redaction ============
+
+/// Add a word or phrase to mask in transcriptions (case-insensitive, whole words)
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_add_redacted_word(handle: *mut FlowHandle, word: *const c_char) -> bool {
+    let handle = unsafe { &*handle };
+
+    if word.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "Word cannot be null");
+        return false;
+    }
+
+    let word_str = match unsafe { CStr::from_ptr(word) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in word");
+            return false;
+        }
+    };
+
+    if let Err(e) = handle.redaction.add_word(word_str) {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to add redacted word: {e}"),
+        );
+        return false;
+    }
+
+    if let Err(e) = handle.storage.add_redacted_word(word_str) {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save redacted word: {e}"),
+        );
+        return false;
+    }
+
+    clear_last_error(handle);
+    true
+}
+
+/// Remove a masked word or phrase
+/// Returns true if the word was removed
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_remove_redacted_word(handle: *mut FlowHandle, word: *const c_char) -> bool {
+    let handle = unsafe { &*handle };
+
+    if word.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "Word cannot be null");
+        return false;
+    }
+
+    let word_str = match unsafe { CStr::from_ptr(word) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in word");
+            return false;
+        }
+    };
+
+    if let Err(e) = handle.redaction.remove_word(word_str) {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to remove redacted word: {e}"),
+        );
+        return false;
     }
-    // For Gemini keys (AI...)
-    if key.starts_with("AI") {
-        return "AI••••••••".to_string();
+
+    match handle.storage.remove_redacted_word(word_str) {
+        Ok(removed) => {
+            clear_last_error(handle);
+            removed
+        }
+        Err(e) => {
+            set_last_error(
+                handle,
+                e.code(),
+                format!("Failed to delete redacted word: {e}"),
+            );
+            false
+        }
     }
-    // For other keys, just show dots
-    "••••••••".to_string()
 }
 
-/// Get API key for a specific provider in masked form
-/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
-/// Returns null if no key is set, or a masked version like "sk-••••••••"
-/// Caller must free the returned string with flow_free_string
+/// Add a regex pattern to mask in transcriptions (case-insensitive)
+/// Returns false if the pattern is invalid
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_api_key(handle: *mut FlowHandle, provider: u8) -> *mut c_char {
+pub extern "C" fn flow_add_redaction_pattern(
+    handle: *mut FlowHandle,
+    pattern: *const c_char,
+) -> bool {
     let handle = unsafe { &*handle };
 
-    let setting_key = match provider {
-        0 => SETTING_OPENAI_API_KEY,
-        1 => SETTING_GEMINI_API_KEY,
-        2 => SETTING_OPENROUTER_API_KEY,
-        _ => return ptr::null_mut(),
-    };
+    if pattern.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "Pattern cannot be null");
+        return false;
+    }
 
-    match handle.storage.get_setting(setting_key) {
-        Ok(Some(key)) => {
-            let masked = mask_api_key(&key);
-            CString::new(masked).unwrap().into_raw()
+    let pattern_str = match unsafe { CStr::from_ptr(pattern) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in pattern");
+            return false;
         }
-        _ => ptr::null_mut(),
+    };
+
+    if let Err(e) = handle.redaction.add_pattern(pattern_str) {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to add redaction pattern: {e}"),
+        );
+        return false;
+    }
+
+    if let Err(e) = handle.storage.add_redaction_pattern(pattern_str) {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save redaction pattern: {e}"),
+        );
+        return false;
     }
+
+    clear_last_error(handle);
+    true
 }
 
-/// Set transcription mode (local or remote)
-/// use_local: true for local Whisper, false for cloud provider
-/// whisper_model: Model selection (only used when use_local = true)
-///   0 = Turbo (~15MB) - quantized, ultra-fast, lowest memory
-///   1 = Fast (~39MB) - fast, lower accuracy
-///   2 = Balanced (~142MB) - good speed/accuracy balance
-///   3 = Quality (~400MB) - great accuracy, still fast [recommended]
-///   4 = Best (~750MB) - best quality available
-/// Returns true on success, false on failure
+/// Remove a masked regex pattern
+/// Returns true if the pattern was removed
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_set_transcription_mode(
+pub extern "C" fn flow_remove_redaction_pattern(
     handle: *mut FlowHandle,
-    use_local: bool,
-    whisper_model: u8,
+    pattern: *const c_char,
 ) -> bool {
-    let handle = unsafe { &mut *handle };
+    let handle = unsafe { &*handle };
 
-    // Save setting to database
-    if let Err(e) = handle.storage.set_setting(
-        SETTING_USE_LOCAL_TRANSCRIPTION,
-        if use_local { "true" } else { "false" },
-    ) {
-        let message = format!("Failed to save transcription mode: {}", e);
-        error!("{}", message);
-        set_last_error(handle, message);
+    if pattern.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "Pattern cannot be null");
         return false;
     }
 
-    if use_local {
-        // Local Whisper transcription
-        let model = match whisper_model {
-            0 => WhisperModel::Turbo,
-            1 => WhisperModel::Fast,
-            2 => WhisperModel::Balanced,
-            3 => WhisperModel::Quality,
-            4 => WhisperModel::Best,
-            _ => {
-                set_last_error(handle, "Invalid Whisper model selection (0-4)");
-                return false;
-            }
-        };
-
-        // Save model choice using canonical name
-        let model_name = model.as_str();
-        if let Err(e) = handle
-            .storage
-            .set_setting(SETTING_LOCAL_WHISPER_MODEL, model_name)
-        {
-            let message = format!("Failed to save Whisper model: {}", e);
-            error!("{}", message);
-            set_last_error(handle, message);
+    let pattern_str = match unsafe { CStr::from_ptr(pattern) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in pattern");
             return false;
         }
+    };
 
-        // Get models directory
-        let models_dir = match crate::whisper_models::get_models_dir() {
-            Ok(dir) => dir,
-            Err(e) => {
-                let message = format!("Failed to get models directory: {}", e);
-                error!("{}", message);
-                set_last_error(handle, message);
-                return false;
-            }
-        };
+    if let Err(e) = handle.redaction.remove_pattern(pattern_str) {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to remove redaction pattern: {e}"),
+        );
+        return false;
+    }
 
-        // Check if model files already exist
-        let files_exist = check_model_files_exist(model, &models_dir);
+    match handle.storage.remove_redaction_pattern(pattern_str) {
+        Ok(removed) => {
+            clear_last_error(handle);
+            removed
+        }
+        Err(e) => {
+            set_last_error(
+                handle,
+                e.code(),
+                format!("Failed to delete redaction pattern: {e}"),
+            );
+            false
+        }
+    }
+}
 
-        // Set loading flag if this will require downloading
-        if !files_exist {
-            handle.is_model_loading.store(true, Ordering::SeqCst);
-            debug!(
-                "Model files not found, will download (~{}MB)",
-                model.size_mb()
+/// Set where masking runs in the pipeline
+///
+/// Before formatting masks right after corrections, so the completion model
+/// never sees the words. In cloud mode the worker formats server-side, so its
+/// text is masked as soon as it comes back.
+///
+/// # Arguments
+/// - `handle` - Engine handle
+/// - `stage` - 0 = before formatting, 1 = after formatting (default)
+///
+/// # Returns
+/// true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_redaction_stage(handle: *mut FlowHandle, stage: u8) -> bool {
+    let handle = unsafe { &*handle };
+
+    let stage = match stage {
+        0 => RedactionStage::BeforeFormatting,
+        1 => RedactionStage::AfterFormatting,
+        _ => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                format!("Invalid redaction stage: {stage}"),
             );
+            return false;
         }
+    };
 
-        // Create provider
-        let provider = Arc::new(LocalWhisperTranscriptionProvider::new(model, models_dir));
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_REDACTION_STAGE, stage.as_str())
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save redaction stage: {e}"),
+        );
+        return false;
+    }
 
-        // Trigger model download/load asynchronously
-        let provider_clone = Arc::clone(&provider);
-        let loading_flag = Arc::clone(&handle.is_model_loading);
-        let should_clear_flag = !files_exist;
+    handle.redaction.set_stage(stage);
+    clear_last_error(handle);
+    true
+}
 
-        handle.runtime.spawn(async move {
-            if let Err(e) = provider_clone.load_model().await {
-                error!("Failed to load Whisper model: {}", e);
-            }
-            // Clear loading flag when done (only if we set it)
-            if should_clear_flag {
-                loading_flag.store(false, Ordering::SeqCst);
-                debug!("Model loading completed");
-            }
-        });
+// ============ Output Normalization ============
 
-        handle.transcription = provider;
-        debug!("Enabled local Whisper transcription with {:?} model", model);
-    } else {
-        // Remote transcription - use the cloud transcription provider setting
-        let cloud_provider = match handle
-            .storage
-            .get_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER)
-        {
-            Ok(Some(name)) => name,
-            _ => "auto".to_string(), // default to Auto
-        };
+/// Persist a normalization rule toggle
+fn save_normalizer_setting(handle: &FlowHandle, key: &str, enabled: bool) -> bool {
+    let value = if enabled { "true" } else { "false" };
 
-        match cloud_provider.as_str() {
-            "openai" => {
-                if let Ok(Some(key)) = handle.storage.get_setting(SETTING_OPENAI_API_KEY) {
-                    let base_url = handle
-                        .storage
-                        .get_setting(SETTING_OPENAI_BASE_URL)
-                        .ok()
-                        .flatten()
-                        .filter(|s| !s.is_empty());
-                    handle.transcription =
-                        Arc::new(OpenAITranscriptionProvider::new(Some(key), base_url));
-                    debug!("Enabled OpenAI remote transcription");
-                } else {
-                    set_last_error(handle, "OpenAI API key not configured");
-                    return false;
-                }
-            }
-            _ => {
-                // Default to Auto (worker handles transcription + completion)
-                handle.transcription = Arc::new(AutoTranscriptionProvider::new(None));
-                debug!("Enabled Auto transcription (worker handles everything)");
-            }
-        }
+    if let Err(e) = handle.storage.set_setting(key, value) {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save normalization setting: {e}"),
+        );
+        return false;
+    }
+
+    clear_last_error(handle);
+    true
+}
+
+/// Enable or disable collapsing runs of whitespace in the final output
+/// Newlines are always preserved. Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_normalize_whitespace(handle: *mut FlowHandle, enabled: bool) -> bool {
+    let handle = unsafe { &*handle };
+    handle.normalizer.lock().collapse_whitespace = enabled;
+    save_normalizer_setting(handle, SETTING_NORMALIZE_WHITESPACE, enabled)
+}
+
+/// Enable or disable converting smart quotes to straight quotes in the final output
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_normalize_quotes(handle: *mut FlowHandle, enabled: bool) -> bool {
+    let handle = unsafe { &*handle };
+    handle.normalizer.lock().straighten_quotes = enabled;
+    save_normalizer_setting(handle, SETTING_NORMALIZE_QUOTES, enabled)
+}
+
+/// Enable or disable trimming trailing spaces from each line of the final output
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_trim_trailing_spaces(handle: *mut FlowHandle, enabled: bool) -> bool {
+    let handle = unsafe { &*handle };
+    handle.normalizer.lock().trim_trailing = enabled;
+    save_normalizer_setting(handle, SETTING_TRIM_TRAILING_SPACES, enabled)
+}
+
+// ============ Dictation Commands ============
+
+/// Enable or disable converting spoken formatting commands ("new line", "new paragraph",
+/// "bullet", "numbered item") into line breaks and list markers
+///
+/// In cloud mode, a transcription with commands in it skips the worker's formatting,
+/// which would lose the structure, and is formatted with the completion provider
+/// chosen with flow_set_completion_provider (or left unformatted without one).
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_dictation_commands(handle: *mut FlowHandle, enabled: bool) -> bool {
+    let handle = unsafe { &*handle };
+    handle.dictation.lock().set_enabled(enabled);
+
+    let value = if enabled { "true" } else { "false" };
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_DICTATION_COMMANDS_ENABLED, value)
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save dictation setting: {e}"),
+        );
+        return false;
+    }
+
+    clear_last_error(handle);
+    true
+}
+
+// ============ Hallucination Filter ============
+
+/// Enable or disable dropping transcriptions that are only a known silence
+/// hallucination (e.g. "Thanks for watching!")
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_hallucination_filter(handle: *mut FlowHandle, enabled: bool) -> bool {
+    let handle = unsafe { &*handle };
+    handle.hallucinations.lock().set_enabled(enabled);
+
+    let value = if enabled { "true" } else { "false" };
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_HALLUCINATION_FILTER_ENABLED, value)
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save hallucination filter setting: {e}"),
+        );
+        return false;
     }
 
+    clear_last_error(handle);
     true
 }
 
-/// Get current transcription mode settings
-/// Returns use_local flag and whisper_model (0-4) via out parameters
-/// Returns false on database error, true on success
+/// Replace the hallucination phrase list
+/// `phrases_json` is a JSON array of strings, or NULL to restore the built-in list
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_transcription_mode(
+pub extern "C" fn flow_set_hallucination_phrases(
     handle: *mut FlowHandle,
-    out_use_local: *mut bool,
-    out_whisper_model: *mut u8,
+    phrases_json: *const c_char,
 ) -> bool {
     let handle = unsafe { &*handle };
 
-    // Read use_local setting from database
-    let use_local = match handle.storage.get_setting(SETTING_USE_LOCAL_TRANSCRIPTION) {
-        Ok(Some(value)) => value == "true",
-        Ok(None) => false, // Default to remote if not set
-        Err(e) => {
-            error!("Failed to read transcription mode: {}", e);
+    if phrases_json.is_null() {
+        handle
+            .hallucinations
+            .lock()
+            .set_phrases(crate::hallucination::DEFAULT_HALLUCINATION_PHRASES);
+        if let Err(e) = handle.storage.delete_setting(SETTING_HALLUCINATION_PHRASES) {
+            set_last_error(
+                handle,
+                e.code(),
+                format!("Failed to reset hallucination phrases: {e}"),
+            );
             return false;
         }
-    };
+        clear_last_error(handle);
+        return true;
+    }
 
-    // Read whisper model setting from database
-    let whisper_model = if use_local {
-        match handle.storage.get_setting(SETTING_LOCAL_WHISPER_MODEL) {
-            Ok(Some(model_str)) => {
-                // Convert model name to enum
-                let model = WhisperModel::all()
-                    .iter()
-                    .find(|m| m.as_str() == model_str)
-                    .copied()
-                    .unwrap_or(WhisperModel::Balanced); // Default to Balanced
-
-                // Convert enum to u8
-                match model {
-                    WhisperModel::Turbo => 0,
-                    WhisperModel::Fast => 1,
-                    WhisperModel::Balanced => 2,
-                    WhisperModel::Quality => 3,
-                    WhisperModel::Best => 4,
-                }
-            }
-            Ok(None) => 1, // Default to Balanced
-            Err(e) => {
-                error!("Failed to read Whisper model: {}", e);
-                return false;
-            }
+    let json = match unsafe { CStr::from_ptr(phrases_json) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                "Invalid UTF-8 in hallucination phrases",
+            );
+            return false;
+        }
+    };
+    let phrases: Vec<String> = match serde_json::from_str(json) {
+        Ok(phrases) => phrases,
+        Err(e) => {
+            set_last_error(
+                handle,
+                ErrorCode::InvalidInput,
+                format!("Invalid hallucination phrases: {e}"),
+            );
+            return false;
         }
-    } else {
-        1 // Default value when using remote transcription
     };
 
-    // Write to out parameters
-    unsafe {
-        *out_use_local = use_local;
-        *out_whisper_model = whisper_model;
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_HALLUCINATION_PHRASES, json)
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save hallucination phrases: {e}"),
+        );
+        return false;
     }
+    handle.hallucinations.lock().set_phrases(phrases);
 
+    clear_last_error(handle);
     true
 }
 
-/// Check if a Whisper model is currently being downloaded/initialized
-/// Returns true if model download/initialization is in progress
+/// Set how many times a phrase may repeat back to back before it's collapsed as a
+/// transcription loop ("the the the the" -> "the"), 0 to keep every repeat
+/// Transcriptions with collapsed loops report them in `repetition_loops`
+/// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_is_model_loading(handle: *mut FlowHandle) -> bool {
+pub extern "C" fn flow_set_max_phrase_repeats(handle: *mut FlowHandle, max_repeats: u32) -> bool {
     let handle = unsafe { &*handle };
-    handle.is_model_loading.load(Ordering::SeqCst)
-}
-
-/// Legacy function - prefer flow_set_transcription_mode
-/// Enable local Whisper transcription with Metal + Accelerate acceleration
-/// model: 0=Turbo, 1=Fast, 2=Balanced, 3=Quality, 4=Best
-/// Returns true on success, false on failure
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_enable_local_whisper(handle: *mut FlowHandle, model: u8) -> bool {
-    flow_set_transcription_mode(handle, true, model)
-}
+    handle
+        .repetition
+        .lock()
+        .set_max_repeats(max_repeats as usize);
 
-/// Get available Whisper models as JSON (caller must free with flow_free_string)
-/// Returns JSON array with model info including id, name, description, size, and flags
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_get_whisper_models_json() -> *mut c_char {
-    let models: Vec<serde_json::Value> = WhisperModel::all()
-        .iter()
-        .enumerate()
-        .map(|(id, model)| {
-            serde_json::json!({
-                "id": id,
-                "name": model.as_str(),
-                "description": model.description(),
-                "size_mb": model.size_mb(),
-                "is_quantized": model.is_quantized(),
-                "is_distilled": model.is_distilled(),
-            })
-        })
-        .collect();
+    if let Err(e) = handle
+        .storage
+        .set_setting_as(SETTING_MAX_PHRASE_REPEATS, &max_repeats)
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save repetition threshold: {e}"),
+        );
+        return false;
+    }
 
-    let json = serde_json::to_string(&models).unwrap_or_else(|_| "[]".to_string());
-    CString::new(json).unwrap().into_raw()
+    clear_last_error(handle);
+    true
 }
 
-/// Get all shortcuts as JSON (caller must free with flow_free_string)
+// ============ Vocabulary ============
+
+/// Add a word or name that transcription should recognize ("Kubernetes", "FlowWhispr")
+/// Only used once the vocabulary prompt is enabled with flow_set_vocabulary_prompt
+/// Returns false if the word is blank, already in the vocabulary or couldn't be saved
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_shortcuts_json(handle: *mut FlowHandle) -> *mut c_char {
+pub extern "C" fn flow_add_vocabulary_word(handle: *mut FlowHandle, word: *const c_char) -> bool {
     let handle = unsafe { &*handle };
 
-    let shortcuts: Vec<serde_json::Value> = handle
-        .shortcuts
-        .get_all()
-        .iter()
-        .map(|s| {
-            serde_json::json!({
-                "trigger": s.trigger,
-                "replacement": s.replacement,
-                "use_count": s.use_count,
-                "enabled": s.enabled,
-            })
-        })
-        .collect();
+    if word.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "Word cannot be null");
+        return false;
+    }
 
-    match CString::new(serde_json::to_string(&shortcuts).unwrap_or_default()) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => ptr::null_mut(),
+    let word_str = match unsafe { CStr::from_ptr(word) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in word");
+            return false;
+        }
+    };
+
+    match handle.storage.add_vocabulary_word(word_str) {
+        Ok(added) => {
+            clear_last_error(handle);
+            added
+        }
+        Err(e) => {
+            set_last_error(
+                handle,
+                e.code(),
+                format!("Failed to save vocabulary word: {e}"),
+            );
+            false
+        }
     }
 }
 
-// ============ Contact Categorization ============
-
-/// Get active contact name from Messages.app window
-/// Returns C string with contact name, or null if not available
-/// Caller must free with flow_free_string
+/// Remove a word from the vocabulary (ignoring case)
+/// Returns true if the word was removed
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_active_messages_contact(handle: *mut FlowHandle) -> *mut c_char {
+pub extern "C" fn flow_remove_vocabulary_word(
+    handle: *mut FlowHandle,
+    word: *const c_char,
+) -> bool {
     let handle = unsafe { &*handle };
-    clear_last_error(handle);
 
-    match MessagesDetector::get_active_contact() {
-        Ok(Some(name)) => match CString::new(name) {
-            Ok(cstr) => cstr.into_raw(),
-            Err(_) => {
-                set_last_error(handle, "Invalid UTF-8 in contact name");
-                ptr::null_mut()
-            }
-        },
-        Ok(None) => ptr::null_mut(),
+    if word.is_null() {
+        set_last_error(handle, ErrorCode::InvalidInput, "Word cannot be null");
+        return false;
+    }
+
+    let word_str = match unsafe { CStr::from_ptr(word) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error(handle, ErrorCode::InvalidInput, "Invalid UTF-8 in word");
+            return false;
+        }
+    };
+
+    match handle.storage.remove_vocabulary_word(word_str) {
+        Ok(removed) => {
+            clear_last_error(handle);
+            removed
+        }
         Err(e) => {
-            set_last_error(handle, format!("Failed to get active contact: {}", e));
-            ptr::null_mut()
+            set_last_error(
+                handle,
+                e.code(),
+                format!("Failed to delete vocabulary word: {e}"),
+            );
+            false
         }
     }
 }
 
-/// Classify a contact given name and organization
-/// Returns JSON string with category
-/// Caller must free with flow_free_string
+/// Get the vocabulary as a JSON array of strings, oldest first
+/// Caller must free the returned string with flow_free_string; null on error
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_classify_contact(
-    handle: *mut FlowHandle,
-    name: *const c_char,
-    organization: *const c_char,
-) -> *mut c_char {
+pub extern "C" fn flow_get_vocabulary_json(handle: *mut FlowHandle) -> *mut c_char {
     let handle = unsafe { &*handle };
-    clear_last_error(handle);
 
-    let name_str = unsafe {
-        if name.is_null() {
-            set_last_error(handle, "Name cannot be null");
+    let words = match handle.storage.get_vocabulary() {
+        Ok(words) => words,
+        Err(e) => {
+            set_last_error(handle, e.code(), format!("Failed to load vocabulary: {e}"));
             return ptr::null_mut();
         }
-        match CStr::from_ptr(name).to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                set_last_error(handle, "Invalid UTF-8 in name");
-                return ptr::null_mut();
-            }
-        }
     };
 
-    let org_str = unsafe {
-        if organization.is_null() {
-            String::new()
-        } else {
-            match CStr::from_ptr(organization).to_str() {
-                Ok(s) => s.to_string(),
-                Err(_) => String::new(),
-            }
+    string_to_c(serde_json::to_string(&words).unwrap_or_default())
+}
+
+/// Enable or disable priming transcription with the vocabulary (off by default)
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_vocabulary_prompt(handle: *mut FlowHandle, enabled: bool) -> bool {
+    let handle = unsafe { &*handle };
+    handle
+        .vocabulary_prompt_enabled
+        .store(enabled, Ordering::SeqCst);
+
+    let value = if enabled { "true" } else { "false" };
+    if let Err(e) = handle
+        .storage
+        .set_setting(SETTING_VOCABULARY_PROMPT_ENABLED, value)
+    {
+        set_last_error(
+            handle,
+            e.code(),
+            format!("Failed to save vocabulary setting: {e}"),
+        );
+        return false;
+    }
+
+    clear_last_error(handle);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::providers::{MockCompletionProvider, MockTranscriptionProvider};
+    use crate::types::{Correction, CorrectionSource};
+
+    fn take_string(ptr: *mut c_char) -> String {
+        assert!(!ptr.is_null());
+        unsafe { CString::from_raw(ptr) }.into_string().unwrap()
+    }
+
+    #[test]
+    fn test_transcribe_json_reports_applied_corrections() {
+        let storage = Storage::in_memory().unwrap();
+        storage
+            .set_setting(SETTING_USE_LOCAL_TRANSCRIPTION, "true")
+            .unwrap();
+        let mut correction = Correction::new(
+            "teh".to_string(),
+            "the".to_string(),
+            CorrectionSource::UserEdit,
+        );
+        correction.confidence = 0.95;
+        storage.save_correction(&correction).unwrap();
+
+        let handle = new_handle(shared_runtime().unwrap().handle().clone(), storage);
+        handle.set_transcription(Arc::new(
+            MockTranscriptionProvider::returning("teh cat sat on teh mat").with_language("en"),
+        ));
+        handle
+            .learning
+            .reload_from_storage(&handle.storage)
+            .unwrap();
+        *handle.pending_audio.lock() = Some(vec![0; 32_000]);
+        *handle.pending_sample_rate.lock() = Some(16_000);
+
+        let handle = Box::into_raw(Box::new(handle));
+        let json = take_string(flow_transcribe_json(handle, ptr::null()));
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["text"], "the cat sat on the mat");
+        assert_eq!(value["provider"], "mock");
+        assert_eq!(value["detected_language"], "en");
+        assert_eq!(value["corrections_applied"], 2);
+        assert_eq!(value["shortcuts_triggered"], 0);
+        assert!(value["transcription_ms"].is_u64());
+        assert!(value["formatting_ms"].is_u64());
+
+        assert_eq!(value["corrected_text"], "the cat sat on the mat");
+        let input = serde_json::json!({
+            "text": value["corrected_text"],
+            "corrections": value["corrections"],
+        })
+        .to_string();
+        let input = CString::new(input).unwrap();
+        let reverted = take_string(flow_revert_corrections(handle, input.as_ptr()));
+        assert_eq!(reverted, "teh cat sat on teh mat");
+        unsafe { drop(Box::from_raw(handle)) };
+    }
+
+    #[test]
+    fn test_transcribe_json_reports_edit_ranges() {
+        let storage = Storage::in_memory().unwrap();
+        storage
+            .set_setting(SETTING_USE_LOCAL_TRANSCRIPTION, "true")
+            .unwrap();
+        let mut correction = Correction::new(
+            "teh".to_string(),
+            "the".to_string(),
+            CorrectionSource::UserEdit,
+        );
+        correction.confidence = 0.95;
+        storage.save_correction(&correction).unwrap();
+
+        let handle = new_handle(shared_runtime().unwrap().handle().clone(), storage);
+        handle.set_transcription(Arc::new(MockTranscriptionProvider::returning(
+            "teh café notes are in my drive folder",
+        )));
+        handle
+            .learning
+            .reload_from_storage(&handle.storage)
+            .unwrap();
+        handle.shortcuts.add_shortcut(Shortcut::new(
+            "my drive".to_string(),
+            "drive.example.com/flow".to_string(),
+        ));
+        *handle.pending_audio.lock() = Some(vec![0; 32_000]);
+        *handle.pending_sample_rate.lock() = Some(16_000);
+
+        let handle = Box::into_raw(Box::new(handle));
+        let json = take_string(flow_transcribe_json(handle, ptr::null()));
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let text = value["text"].as_str().unwrap();
+        assert_eq!(text, "the café notes are in drive.example.com/flow folder");
+        let edits: Vec<(&str, &str)> = value["edits"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edit| {
+                let start = edit["start"].as_u64().unwrap() as usize;
+                let end = edit["end"].as_u64().unwrap() as usize;
+                (&text[start..end], edit["kind"].as_str().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            edits,
+            vec![
+                ("the", "correction"),
+                ("drive.example.com/flow", "shortcut")
+            ]
+        );
+        unsafe { drop(Box::from_raw(handle)) };
+    }
+
+    #[test]
+    fn test_transcribe_counts_triggered_shortcuts() {
+        let storage = Storage::in_memory().unwrap();
+        storage
+            .set_setting(SETTING_USE_LOCAL_TRANSCRIPTION, "true")
+            .unwrap();
+        let handle = new_handle(shared_runtime().unwrap().handle().clone(), storage);
+        handle.set_transcription(Arc::new(MockTranscriptionProvider::returning(
+            "find me on my linkedin",
+        )));
+        *handle.pending_audio.lock() = Some(vec![0; 32_000]);
+        *handle.pending_sample_rate.lock() = Some(16_000);
+        let handle = Box::into_raw(Box::new(handle));
+
+        let trigger = CString::new("my linkedin").unwrap();
+        let replacement = CString::new("jsn.cam/li").unwrap();
+        assert!(flow_add_shortcut(
+            handle,
+            trigger.as_ptr(),
+            replacement.as_ptr()
+        ));
+        let unused = CString::new("my email").unwrap();
+        assert!(flow_add_shortcut(handle, unused.as_ptr(), trigger.as_ptr()));
+
+        let text = take_string(flow_transcribe(handle, ptr::null()));
+        assert_eq!(text, "find me on jsn.cam/li");
+
+        let stats: serde_json::Value =
+            serde_json::from_str(&take_string(flow_shortcut_stats(handle))).unwrap();
+        assert_eq!(stats[0]["trigger"], "my linkedin");
+        assert_eq!(stats[0]["use_count"], 1);
+        assert!(stats[0]["last_used_at"].is_string());
+        assert_eq!(stats[1]["trigger"], "my email");
+        assert_eq!(stats[1]["use_count"], 0);
+        assert!(stats[1]["last_used_at"].is_null());
+        unsafe { drop(Box::from_raw(handle)) };
+    }
+
+    #[test]
+    fn test_shortcuts_replace_worker_formatting() {
+        let worker = Arc::new(
+            MockTranscriptionProvider::returning("find me on my linkedin")
+                .with_completed_text("Find me on my LinkedIn."),
+        );
+        let handle = handle_with_worker(worker);
+        let trigger = CString::new("my linkedin").unwrap();
+        let replacement = CString::new("jsn.cam/li").unwrap();
+        assert!(flow_add_shortcut(
+            handle,
+            trigger.as_ptr(),
+            replacement.as_ptr()
+        ));
+
+        let json = take_string(flow_transcribe_json(handle, ptr::null()));
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["text"], "find me on jsn.cam/li");
+        assert_eq!(value["shortcuts_triggered"], 1);
+        let stats: serde_json::Value =
+            serde_json::from_str(&take_string(flow_shortcut_stats(handle))).unwrap();
+        assert_eq!(stats[0]["use_count"], 1);
+        flow_destroy(handle);
+    }
+
+    #[test]
+    fn test_transcribe_json_without_pending_audio() {
+        let handle = Box::into_raw(Box::new(new_handle(
+            shared_runtime().unwrap().handle().clone(),
+            Storage::in_memory().unwrap(),
+        )));
+
+        assert!(flow_transcribe_json(handle, ptr::null()).is_null());
+        unsafe { drop(Box::from_raw(handle)) };
+    }
+
+    /// Provider that takes a while, so drained jobs overlap
+    fn slow_provider() -> MockTranscriptionProvider {
+        MockTranscriptionProvider::returning("drained").with_latency(Duration::from_millis(30))
+    }
+
+    fn queue_jobs(handle: *mut FlowHandle, count: usize) {
+        let storage = &unsafe { &*handle }.storage;
+        for _ in 0..count {
+            let job = PendingTranscription::new(vec![0; 32_000], 16_000, None);
+            storage
+                .enqueue_pending_transcription(&job, MAX_PENDING_TRANSCRIPTIONS)
+                .unwrap();
         }
-    };
+    }
+
+    extern "C" fn collect_progress(completed: usize, total: usize, context: *mut c_void) {
+        let progress = unsafe { &mut *(context as *mut Vec<(usize, usize)>) };
+        progress.push((completed, total));
+    }
+
+    /// A real connection error, since nothing listens on port 1
+    fn connection_error() -> Error {
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        shared_runtime()
+            .unwrap()
+            .block_on(client.get("http://127.0.0.1:1/").send())
+            .unwrap_err()
+            .into()
+    }
+
+    extern "C" fn collect_results(success: bool, result: *const c_char, context: *mut c_void) {
+        let results = unsafe { &mut *(context as *mut Vec<(bool, String)>) };
+        let text = unsafe { CStr::from_ptr(result) }
+            .to_string_lossy()
+            .into_owned();
+        results.push((success, text));
+    }
+
+    fn handle_with_provider(provider: Arc<dyn TranscriptionProvider>) -> *mut FlowHandle {
+        let storage = Storage::in_memory().unwrap();
+        storage
+            .set_setting(SETTING_USE_LOCAL_TRANSCRIPTION, "true")
+            .unwrap();
+        let handle = new_handle(shared_runtime().unwrap().handle().clone(), storage);
+        handle.set_transcription(provider);
+        *handle.pending_audio.lock() = Some(vec![0; 32_000]);
+        *handle.pending_sample_rate.lock() = Some(16_000);
+        Box::into_raw(Box::new(handle))
+    }
+
+    /// Handle in cloud mode, where `worker` transcribes and formats in one request
+    fn handle_with_worker(worker: Arc<dyn TranscriptionProvider>) -> *mut FlowHandle {
+        let handle = new_handle(
+            shared_runtime().unwrap().handle().clone(),
+            Storage::in_memory().unwrap(),
+        );
+        handle.set_transcription(worker);
+        *handle.pending_audio.lock() = Some(vec![0; 32_000]);
+        *handle.pending_sample_rate.lock() = Some(16_000);
+        Box::into_raw(Box::new(handle))
+    }
+
+    /// Make `provider` the completion provider, as if the user had picked it
+    fn select_completion(handle: *mut FlowHandle, provider: Arc<dyn CompletionProvider>) {
+        let handle = unsafe { &*handle };
+        handle
+            .storage
+            .set_setting(SETTING_COMPLETION_PROVIDER, "openai")
+            .unwrap();
+        handle.set_completion(provider);
+    }
+
+    #[test]
+    fn test_locale_formats_french_punctuation() {
+        let handle = handle_with_provider(Arc::new(
+            MockTranscriptionProvider::returning("tu viens ce soir?").with_language("en"),
+        ));
+        let locale = CString::new("fr-FR").unwrap();
+        assert!(flow_set_locale(handle, locale.as_ptr()));
+        assert_eq!(
+            take_string(flow_transcribe(handle, ptr::null())),
+            "tu viens ce soir\u{202F}?"
+        );
+
+        // back to the detected language, which is English here
+        assert!(flow_set_locale(handle, ptr::null()));
+        let handle_ref = unsafe { &*handle };
+        *handle_ref.pending_audio.lock() = Some(vec![0; 32_000]);
+        *handle_ref.pending_sample_rate.lock() = Some(16_000);
+        assert_eq!(
+            take_string(flow_transcribe(handle, ptr::null())),
+            "tu viens ce soir?"
+        );
+
+        let unsupported = CString::new("tlh").unwrap();
+        assert!(!flow_set_locale(handle, unsupported.as_ptr()));
+        flow_destroy(handle);
+    }
 
-    let input = ContactInput {
-        name: name_str.to_string(),
-        organization: org_str,
-    };
+    #[test]
+    fn test_before_formatting_redaction_hides_words_from_the_model() {
+        let handle = handle_with_worker(Arc::new(MockTranscriptionProvider::returning(
+            "the door code is hunter two",
+        )));
+        let completion = Arc::new(MockCompletionProvider::returning("The door code is ***."));
+        select_completion(handle, completion.clone());
+
+        assert!(flow_set_redaction_stage(handle, 0));
+        let word = CString::new("hunter").unwrap();
+        assert!(flow_add_redacted_word(handle, word.as_ptr()));
+
+        take_string(flow_transcribe(handle, ptr::null()));
+        let requests = completion.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(!requests[0].text.contains("hunter"), "{}", requests[0].text);
+        flow_destroy(handle);
+    }
 
-    let category = handle.contact_classifier.classify(&input);
+    #[test]
+    fn test_local_text_is_only_formatted_for_apps_with_a_model() {
+        let handle = handle_with_provider(Arc::new(MockTranscriptionProvider::returning(
+            "meet me at noon",
+        )));
+        let completion = Arc::new(MockCompletionProvider::returning("Meet me at noon."));
+        let transcribe_in = |app: Option<&CStr>| {
+            let handle_ref = unsafe { &*handle };
+            *handle_ref.pending_audio.lock() = Some(vec![0; 32_000]);
+            *handle_ref.pending_sample_rate.lock() = Some(16_000);
+            take_string(flow_transcribe(
+                handle,
+                app.map_or(ptr::null(), CStr::as_ptr),
+            ))
+        };
 
-    let result = serde_json::json!({
-        "name": name_str,
-        "category": category,
-    });
+        // a provider picked for cloud mode doesn't make local text leave the device
+        select_completion(handle, completion.clone());
+        assert_eq!(transcribe_in(None), "meet me at noon");
+        completion.assert_calls(0);
 
-    match CString::new(result.to_string()) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => {
-            set_last_error(handle, "Failed to serialize result");
-            ptr::null_mut()
-        }
+        let mail = CString::new("Mail").unwrap();
+        let model = CString::new("gpt-4o").unwrap();
+        assert!(flow_set_app_model(
+            handle,
+            mail.as_ptr(),
+            255,
+            model.as_ptr()
+        ));
+        assert_eq!(transcribe_in(Some(&mail)), "Meet me at noon.");
+        completion.assert_calls(1);
+        assert_eq!(
+            transcribe_in(Some(&CString::new("Slack").unwrap())),
+            "meet me at noon"
+        );
+        completion.assert_calls(1);
+        flow_destroy(handle);
     }
-}
 
-/// Classify multiple contacts from JSON array
-/// Input format: [{"name": "...", "organization": "..."}]
-/// Output format: {"ContactName": "category", ...}
-/// Caller must free with flow_free_string
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_classify_contacts_batch(
-    handle: *mut FlowHandle,
-    contacts_json: *const c_char,
-) -> *mut c_char {
-    let handle = unsafe { &*handle };
-    clear_last_error(handle);
+    #[test]
+    fn test_cloud_text_is_only_formatted_by_a_chosen_provider() {
+        // a cloud provider that doesn't format, so the text is formatted here
+        let handle = handle_with_worker(Arc::new(MockTranscriptionProvider::returning(
+            "meet me at noon",
+        )));
+        let completion = Arc::new(MockCompletionProvider::returning("Meet me at noon."));
+        // the default provider every handle starts with, never picked by the user
+        unsafe { &*handle }.set_completion(completion.clone());
+
+        assert_eq!(
+            transcribe_text(handle, "meet me at noon"),
+            "meet me at noon"
+        );
+        completion.assert_calls(0);
 
-    let json_str = unsafe {
-        if contacts_json.is_null() {
-            set_last_error(handle, "JSON cannot be null");
-            return ptr::null_mut();
-        }
-        match CStr::from_ptr(contacts_json).to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                set_last_error(handle, "Invalid UTF-8 in JSON");
-                return ptr::null_mut();
-            }
-        }
-    };
+        select_completion(handle, completion.clone());
+        assert_eq!(
+            transcribe_text(handle, "meet me at noon"),
+            "Meet me at noon."
+        );
+        completion.assert_calls(1);
+        flow_destroy(handle);
+    }
 
-    let inputs: Vec<ContactInput> = match serde_json::from_str(json_str) {
-        Ok(i) => i,
-        Err(e) => {
-            set_last_error(handle, format!("Invalid JSON: {}", e));
-            return ptr::null_mut();
-        }
-    };
+    #[test]
+    fn test_app_model_override_replaces_worker_formatting() {
+        // cloud mode, where the worker formats unless the app has its own model
+        let handle = new_handle(
+            shared_runtime().unwrap().handle().clone(),
+            Storage::in_memory().unwrap(),
+        );
+        let worker = Arc::new(MockTranscriptionProvider::returning("send the report"));
+        handle.set_transcription(worker.clone());
+        let handle = Box::into_raw(Box::new(handle));
+        let completion = Arc::new(MockCompletionProvider::returning("Send the report."));
+        select_completion(handle, completion.clone());
+
+        let mail = CString::new("Mail").unwrap();
+        let model = CString::new("gpt-4o").unwrap();
+        assert!(flow_set_app_model(
+            handle,
+            mail.as_ptr(),
+            255,
+            model.as_ptr()
+        ));
+        let transcribe_in = |app: &CStr| {
+            let handle_ref = unsafe { &*handle };
+            *handle_ref.pending_audio.lock() = Some(vec![0; 32_000]);
+            *handle_ref.pending_sample_rate.lock() = Some(16_000);
+            take_string(flow_transcribe(handle, app.as_ptr()))
+        };
 
-    let result_json = handle.contact_classifier.classify_batch_json(&inputs);
+        assert_eq!(transcribe_in(&mail), "Send the report.");
+        assert!(worker.requests()[0].completion.is_none());
+        let requests = completion.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].model.as_deref(), Some("gpt-4o"));
 
-    match CString::new(result_json) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => {
-            set_last_error(handle, "Failed to create result string");
-            ptr::null_mut()
-        }
+        // other apps are still formatted by the worker
+        transcribe_in(&CString::new("Slack").unwrap());
+        assert!(worker.requests()[1].completion.is_some());
+        flow_destroy(handle);
     }
-}
 
-/// Record interaction with a contact (updates frequency)
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_record_contact_interaction(handle: *mut FlowHandle, name: *const c_char) {
-    let handle = unsafe { &*handle };
-    clear_last_error(handle);
+    #[test]
+    fn test_locale_is_sent_to_the_worker() {
+        // cloud mode, where the provider formats the text itself
+        let handle = new_handle(
+            shared_runtime().unwrap().handle().clone(),
+            Storage::in_memory().unwrap(),
+        );
+        let provider = Arc::new(MockTranscriptionProvider::returning("bonjour"));
+        handle.set_transcription(provider.clone());
+        *handle.pending_audio.lock() = Some(vec![0; 32_000]);
+        *handle.pending_sample_rate.lock() = Some(16_000);
+        let handle = Box::into_raw(Box::new(handle));
+
+        let locale = CString::new("fr").unwrap();
+        assert!(flow_set_locale(handle, locale.as_ptr()));
+        take_string(flow_transcribe(handle, ptr::null()));
+
+        let requests = provider.requests();
+        assert_eq!(requests[0].language.as_deref(), Some("fr"));
+        let completion = requests[0].completion.as_ref().unwrap();
+        assert_eq!(completion.locale.as_deref(), Some("fr"));
+        flow_destroy(handle);
+    }
 
-    let name_str = unsafe {
-        if name.is_null() {
-            return;
-        }
-        match CStr::from_ptr(name).to_str() {
-            Ok(s) => s,
-            Err(_) => return,
-        }
-    };
+    #[test]
+    fn test_transcription_with_nul_byte_is_returned() {
+        let handle = handle_with_provider(Arc::new(MockTranscriptionProvider::returning(
+            "hello\0 world",
+        )));
 
-    handle.contact_classifier.record_interaction(name_str);
-}
+        let text = take_string(flow_transcribe(handle, ptr::null()));
+        assert_eq!(text, "hello world");
 
-/// Get frequent contacts as JSON array
-/// Returns: [{"name": "...", "category": "...", "frequency": N}, ...]
-/// Caller must free with flow_free_string
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_get_frequent_contacts(handle: *mut FlowHandle, limit: u32) -> *mut c_char {
-    let handle = unsafe { &*handle };
-    clear_last_error(handle);
+        assert_eq!(c_string("a\0b\0").as_bytes(), b"ab");
+        assert_eq!(c_string("plain").as_bytes(), b"plain");
+        flow_destroy(handle);
+    }
 
-    let contacts = handle
-        .contact_classifier
-        .get_frequent_contacts(limit as usize);
+    #[test]
+    fn test_transcription_loops_are_collapsed_and_reported() {
+        let handle = handle_with_provider(Arc::new(MockTranscriptionProvider::returning(
+            "send it to the the the the the the the the team",
+        )));
+
+        let json = take_string(flow_transcribe_json(handle, ptr::null()));
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["text"], "send it to the team");
+        assert_eq!(value["repetition_loops"], 1);
+
+        assert!(flow_set_max_phrase_repeats(handle, 0));
+        let handle_ref = unsafe { &*handle };
+        *handle_ref.pending_audio.lock() = Some(vec![0; 32_000]);
+        *handle_ref.pending_sample_rate.lock() = Some(16_000);
+        let json = take_string(flow_transcribe_json(handle, ptr::null()));
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value["text"],
+            "send it to the the the the the the the the team"
+        );
+        assert_eq!(value["repetition_loops"], 0);
+        flow_destroy(handle);
+    }
 
-    let result: Vec<serde_json::Value> = contacts
-        .iter()
-        .map(|c| {
-            serde_json::json!({
-                "name": c.name,
-                "category": c.category,
-                "frequency": c.frequency,
-                "organization": c.organization,
-            })
-        })
-        .collect();
+    extern "C" fn glossary_transform(
+        text: *const c_char,
+        _app_name: *const c_char,
+        _mode: u8,
+        _context: *mut c_void,
+        output: *mut FlowTransformOutput,
+    ) {
+        let text = unsafe { CStr::from_ptr(text) }.to_str().unwrap();
+        let replaced = CString::new(text.replace("k eights", "Kubernetes")).unwrap();
+        assert!(flow_transform_output_set(output, replaced.as_ptr()));
+    }
 
-    match CString::new(serde_json::to_string(&result).unwrap_or_default()) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => {
-            set_last_error(handle, "Failed to serialize contacts");
-            ptr::null_mut()
-        }
+    /// Records the text it sees in the Vec<String> behind `context`, and keeps it
+    extern "C" fn recording_transform(
+        text: *const c_char,
+        _app_name: *const c_char,
+        _mode: u8,
+        context: *mut c_void,
+        _output: *mut FlowTransformOutput,
+    ) {
+        let seen = unsafe { &*(context as *const Mutex<Vec<String>>) };
+        let text = unsafe { CStr::from_ptr(text) }.to_str().unwrap();
+        seen.lock().push(text.to_string());
     }
-}
 
-/// Get suggested writing mode for a contact category
-/// Returns: 0=Formal, 1=Casual, 2=VeryCasual, 3=Excited
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_get_writing_mode_for_category(
-    handle: *mut FlowHandle,
-    category: u32,
-) -> u32 {
-    let handle = unsafe { &*handle };
-    clear_last_error(handle);
+    #[test]
+    fn test_transforms_run_in_order_in_pipeline() {
+        let handle = handle_with_provider(Arc::new(MockTranscriptionProvider::returning(
+            "deploy it to k eights",
+        )));
+        let seen: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        let seen_ptr = &seen as *const Mutex<Vec<String>> as *mut c_void;
+
+        let glossary = flow_add_transform(handle, 2, glossary_transform, ptr::null_mut());
+        let recorder = flow_add_transform(handle, 2, recording_transform, seen_ptr);
+        assert!(glossary > 0 && recorder > glossary);
+        assert_eq!(
+            flow_add_transform(handle, 9, glossary_transform, ptr::null_mut()),
+            0
+        );
 
-    use crate::types::ContactCategory;
+        let json = take_string(flow_transcribe_json(handle, ptr::null()));
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(value["text"].as_str().unwrap().contains("Kubernetes"));
+        // the second transform saw the first one's output
+        assert_eq!(*seen.lock(), vec!["deploy it to Kubernetes".to_string()]);
+        assert!(
+            value["edits"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|edit| edit["kind"] == "transform")
+        );
 
-    let contact_category = match category {
-        0 => ContactCategory::Professional,
-        1 => ContactCategory::CloseFamily,
-        2 => ContactCategory::CasualPeer,
-        3 => ContactCategory::Partner,
-        4 => ContactCategory::FormalNeutral,
-        _ => ContactCategory::FormalNeutral,
-    };
+        assert!(flow_remove_transform(handle, glossary));
+        assert!(!flow_remove_transform(handle, glossary));
+        assert!(flow_remove_transform(handle, recorder));
+        flow_destroy(handle);
+    }
 
-    let writing_mode = contact_category.suggested_writing_mode();
+    #[test]
+    fn test_early_transforms_replace_worker_formatting() {
+        let worker = Arc::new(
+            MockTranscriptionProvider::returning("deploy it to k eights")
+                .with_completed_text("Deploy it to k eights."),
+        );
+        let handle = handle_with_worker(worker.clone());
+        let transcribe = || {
+            let handle_ref = unsafe { &*handle };
+            *handle_ref.pending_audio.lock() = Some(vec![0; 32_000]);
+            *handle_ref.pending_sample_rate.lock() = Some(16_000);
+            take_string(flow_transcribe(handle, ptr::null()))
+        };
 
-    match writing_mode {
-        WritingMode::Formal => 0,
-        WritingMode::Casual => 1,
-        WritingMode::VeryCasual => 2,
-        WritingMode::Excited => 3,
+        assert_eq!(transcribe(), "Deploy it to k eights.");
+
+        // the worker never saw the glossary's output, so its formatting is dropped
+        let glossary = flow_add_transform(handle, 0, glossary_transform, ptr::null_mut());
+        assert_eq!(transcribe(), "deploy it to Kubernetes");
+        let completion = Arc::new(MockCompletionProvider::returning(
+            "Deploy it to Kubernetes.",
+        ));
+        select_completion(handle, completion.clone());
+        assert_eq!(transcribe(), "Deploy it to Kubernetes.");
+        assert_eq!(completion.requests()[0].text, "deploy it to Kubernetes");
+
+        // a transform that leaves the text alone keeps the worker's formatting
+        assert!(flow_remove_transform(handle, glossary));
+        let seen: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        let seen_ptr = &seen as *const Mutex<Vec<String>> as *mut c_void;
+        flow_add_transform(handle, 1, recording_transform, seen_ptr);
+        assert_eq!(transcribe(), "Deploy it to k eights.");
+        worker.assert_calls(4);
+        flow_destroy(handle);
     }
-}
 
-// ============ Cloud Transcription Provider ============
+    #[test]
+    fn test_dictation_commands_replace_worker_formatting() {
+        let worker = Arc::new(
+            MockTranscriptionProvider::returning("milk new line eggs")
+                .with_completed_text("Milk new line eggs."),
+        );
+        let handle = handle_with_worker(worker);
 
-/// Set cloud transcription provider (saves preference)
-/// provider: 0 = OpenAI, 1 = Auto (default)
-/// Returns true on success
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_set_cloud_transcription_provider(
-    handle: *mut FlowHandle,
-    provider: u8,
-) -> bool {
-    let handle = unsafe { &mut *handle };
+        assert_eq!(
+            take_string(flow_transcribe(handle, ptr::null())),
+            "milk\neggs"
+        );
 
-    let provider_name = match provider {
-        0 => "openai",
-        1 => "auto",
-        _ => {
-            set_last_error(
-                handle,
-                "Invalid cloud transcription provider (0=OpenAI, 1=Auto)",
+        assert!(flow_set_dictation_commands(handle, false));
+        let handle_ref = unsafe { &*handle };
+        *handle_ref.pending_audio.lock() = Some(vec![0; 32_000]);
+        *handle_ref.pending_sample_rate.lock() = Some(16_000);
+        assert_eq!(
+            take_string(flow_transcribe(handle, ptr::null())),
+            "Milk new line eggs."
+        );
+        flow_destroy(handle);
+    }
+
+    #[test]
+    fn test_vocabulary_is_added_to_prompt_when_enabled() {
+        let provider = Arc::new(MockTranscriptionProvider::returning("ok"));
+        let handle = handle_with_provider(provider.clone());
+        let add = |word: &str| {
+            let word = CString::new(word).unwrap();
+            flow_add_vocabulary_word(handle, word.as_ptr())
+        };
+        assert!(add("Kubernetes"));
+        assert!(add("FlowWhispr"));
+        assert!(!add("kubernetes"));
+        assert_eq!(
+            take_string(flow_get_vocabulary_json(handle)),
+            r#"["Kubernetes","FlowWhispr"]"#
+        );
+
+        // unused until enabled
+        take_string(flow_transcribe(handle, ptr::null()));
+        assert!(flow_set_vocabulary_prompt(handle, true));
+        let handle_ref = unsafe { &*handle };
+        *handle_ref.pending_audio.lock() = Some(vec![0; 32_000]);
+        *handle_ref.pending_sample_rate.lock() = Some(16_000);
+        take_string(flow_transcribe(handle, ptr::null()));
+
+        let prompts: Vec<Option<String>> = provider
+            .requests()
+            .into_iter()
+            .map(|request| request.prompt)
+            .collect();
+        assert_eq!(
+            prompts,
+            vec![None, Some("Kubernetes, FlowWhispr.".to_string())]
+        );
+        flow_destroy(handle);
+    }
+
+    #[test]
+    fn test_profiles_keep_separate_corrections() {
+        let data_dir = std::env::temp_dir().join(format!("flow_profiles_{}", uuid::Uuid::new_v4()));
+        let work_db = profiles::profile_db_path(&data_dir, "work").unwrap();
+        let personal_db = profiles::profile_db_path(&data_dir, "personal").unwrap();
+        let has_correction = |handle: *mut FlowHandle, original: &str| {
+            unsafe { &*handle }
+                .storage
+                .get_all_corrections()
+                .unwrap()
+                .iter()
+                .any(|c| c.original == original)
+        };
+
+        let work = open_handle(&work_db);
+        let personal = open_handle(&personal_db);
+        assert!(!work.is_null() && !personal.is_null());
+        for (handle, original, corrected) in
+            [(work, "kubernets", "Kubernetes"), (personal, "teh", "the")]
+        {
+            let correction = Correction::new(
+                original.to_string(),
+                corrected.to_string(),
+                CorrectionSource::UserEdit,
             );
-            return false;
+            unsafe { &*handle }
+                .storage
+                .save_correction(&correction)
+                .unwrap();
         }
-    };
+        assert!(has_correction(work, "kubernets"));
+        assert!(!has_correction(work, "teh"));
+        assert!(has_correction(personal, "teh"));
+        assert!(!has_correction(personal, "kubernets"));
+        flow_destroy(work);
+        flow_destroy(personal);
+
+        // switching back to a profile reopens its own database
+        let work = open_handle(&work_db);
+        assert!(has_correction(work, "kubernets"));
+        assert!(!has_correction(work, "teh"));
+        flow_destroy(work);
+
+        assert_eq!(
+            profiles::list_profiles(&data_dir).unwrap(),
+            vec!["personal", "work"]
+        );
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
 
-    // Save the provider preference
-    if let Err(e) = handle
-        .storage
-        .set_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER, provider_name)
-    {
-        let message = format!("Failed to save cloud transcription provider: {e}");
-        error!("{message}");
-        set_last_error(handle, message);
-        return false;
+    #[test]
+    fn test_new_handle_loads_persisted_globals() {
+        let storage = Storage::in_memory().unwrap();
+        storage.set_default_mode(WritingMode::Formal).unwrap();
+        storage
+            .set_setting(SETTING_REQUEST_TIMEOUT_SECS, "20")
+            .unwrap();
+
+        let handle = Box::into_raw(Box::new(new_handle(
+            shared_runtime().unwrap().handle().clone(),
+            storage,
+        )));
+        let handle_ref = unsafe { &*handle };
+        assert_eq!(flow_get_default_mode(handle), 0);
+        assert_eq!(
+            *handle_ref.request_timeout.lock(),
+            Some(Duration::from_secs(20))
+        );
+
+        assert!(flow_set_default_mode(handle, 2));
+        assert!(!flow_set_default_mode(handle, 9));
+        assert_eq!(
+            handle_ref.storage.get_default_mode().unwrap(),
+            Some(WritingMode::VeryCasual)
+        );
+        assert_eq!(
+            handle_ref.modes.get_mode("Unknown App"),
+            WritingMode::VeryCasual
+        );
+
+        let timed_out = handle_ref.runtime.block_on(with_request_timeout(
+            Some(Duration::from_millis(10)),
+            std::future::pending::<crate::error::Result<()>>(),
+        ));
+        assert_eq!(timed_out.unwrap_err().code(), ErrorCode::Timeout);
+        flow_destroy(handle);
     }
 
-    clear_last_error(handle);
-    true
-}
+    #[test]
+    fn test_sessions_transcribe_independently() {
+        let provider = Arc::new(
+            MockTranscriptionProvider::new()
+                .with_response("second session")
+                .with_response("first session"),
+        );
+        let handle = handle_with_provider(provider.clone());
+        let first = flow_start_session(handle);
+        let second = flow_start_session(handle);
+        assert_ne!(first, second);
+
+        // stand-ins for what each session's microphone recorded
+        let record = |session_id: u64, seconds: usize| {
+            let handle_ref = unsafe { &*handle };
+            handle_ref
+                .sessions
+                .lock()
+                .get_mut(&session_id)
+                .unwrap()
+                .pending = Some(PendingAudio {
+                data: vec![1; seconds * 32_000],
+                sample_rate: 16_000,
+                duration_ms: seconds as u64 * 1000,
+            });
+        };
+        record(first, 1);
+        record(second, 3);
 
-/// Get the current cloud transcription provider
-/// Returns: 0 = OpenAI, 1 = Auto (default)
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_get_cloud_transcription_provider(handle: *mut FlowHandle) -> u8 {
-    let handle = unsafe { &*handle };
+        assert_eq!(
+            take_string(flow_session_transcribe(handle, second, ptr::null())),
+            "second session"
+        );
+        assert_eq!(
+            take_string(flow_session_transcribe(handle, first, ptr::null())),
+            "first session"
+        );
+        let durations: Vec<u64> = provider
+            .requests()
+            .iter()
+            .map(|request| estimate_duration_ms(request.audio.len(), request.sample_rate))
+            .collect();
+        assert_eq!(durations, vec![3000, 1000]);
+        // each session's audio is used once
+        assert!(flow_session_transcribe(handle, first, ptr::null()).is_null());
+        assert_eq!(flow_last_error(handle), ErrorCode::Audio as i32);
+
+        // the handle's own pending audio is untouched
+        assert_eq!(
+            unsafe { &*handle }
+                .pending_audio
+                .lock()
+                .as_ref()
+                .map(Vec::len),
+            Some(32_000)
+        );
 
-    match handle
-        .storage
-        .get_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER)
-    {
-        Ok(Some(name)) => match name.as_str() {
-            "openai" => 0,
-            "auto" => 1,
-            _ => 1, // default to Auto
-        },
-        _ => 1, // default to Auto
+        assert!(flow_end_session(handle, first));
+        assert!(!flow_end_session(handle, first));
+        flow_destroy(handle);
     }
-}
 
-// ============ Auto-Rewriting Setting ============
+    #[test]
+    fn test_unknown_session_is_rejected() {
+        let handle = handle_with_provider(Arc::new(MockTranscriptionProvider::new()));
+
+        assert!(!flow_session_start_recording(handle, 42));
+        assert_eq!(flow_last_error(handle), ErrorCode::InvalidInput as i32);
+        assert_eq!(flow_session_stop_recording(handle, 42), 0);
+        assert!(flow_session_transcribe(handle, 42, ptr::null()).is_null());
+        assert_eq!(
+            take_string(flow_last_error_message(handle)),
+            "Unknown session id 42"
+        );
 
-/// Set whether auto-rewriting is enabled
-/// When disabled, transcriptions are returned as-is (with shortcuts only, no corrections or AI)
-///
-/// # Arguments
-/// - `handle` - Engine handle
-/// - `enabled` - Whether auto-rewriting should be enabled
-///
-/// # Returns
-/// true on success
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_set_auto_rewriting_enabled(handle: *mut FlowHandle, enabled: bool) -> bool {
-    let handle = unsafe { &mut *handle };
+        let session = flow_start_session(handle);
+        assert_eq!(flow_session_stop_recording(handle, session), 0);
+        assert_eq!(flow_last_error(handle), ErrorCode::Audio as i32);
+        flow_destroy(handle);
+    }
 
-    let value = if enabled { "true" } else { "false" };
+    #[test]
+    fn test_unconfigured_provider_sets_error_code() {
+        // fails like a cloud provider with no API key
+        let handle = handle_with_provider(Arc::new(
+            MockTranscriptionProvider::new().unconfigured().with_error(
+                Error::ProviderNotConfigured("OpenAI API key not set".to_string()),
+            ),
+        ));
+        assert_eq!(flow_last_error(handle), ErrorCode::None as i32);
+        assert!(flow_last_error_message(handle).is_null());
+
+        assert!(flow_transcribe(handle, ptr::null()).is_null());
+        assert_eq!(flow_last_error(handle), ErrorCode::Unconfigured as i32);
+        let message = take_string(flow_last_error_message(handle));
+        assert!(message.contains("OpenAI API key not set"), "{message}");
+
+        // the next failure replaces it
+        assert!(!flow_add_shortcut(handle, ptr::null(), ptr::null()));
+        assert_eq!(flow_last_error(handle), ErrorCode::InvalidInput as i32);
+
+        flow_destroy(handle);
+    }
 
-    if let Err(e) = handle
-        .storage
-        .set_setting(SETTING_AUTO_REWRITING_ENABLED, value)
-    {
-        set_last_error(
+    #[test]
+    fn test_network_failure_is_queued_and_retried() {
+        // offline for the first two tries
+        let provider = Arc::new(
+            MockTranscriptionProvider::new()
+                .with_error(connection_error())
+                .with_error(connection_error())
+                .with_fallback("back online"),
+        );
+        let handle = handle_with_provider(provider.clone());
+
+        assert!(flow_transcribe(handle, ptr::null()).is_null());
+        assert_eq!(flow_pending_count(handle), 1);
+
+        // still offline: the job stays queued with its attempt recorded
+        let mut results: Vec<(bool, String)> = Vec::new();
+        let context = &mut results as *mut Vec<(bool, String)> as *mut c_void;
+        assert_eq!(
+            flow_retry_pending(handle, Some(collect_results), context),
+            0
+        );
+        assert_eq!(flow_pending_count(handle), 1);
+        let jobs = unsafe { &*handle }
+            .storage
+            .get_pending_transcriptions()
+            .unwrap();
+        assert_eq!(jobs[0].attempts, 1);
+
+        assert_eq!(
+            flow_retry_pending(handle, Some(collect_results), context),
+            1
+        );
+        assert_eq!(flow_pending_count(handle), 0);
+
+        assert_eq!(results.len(), 2);
+        assert!(!results[0].0);
+        assert_eq!(results[1], (true, "back online".to_string()));
+        provider.assert_calls(3);
+        unsafe { drop(Box::from_raw(handle)) };
+    }
+
+    #[test]
+    fn test_drain_respects_max_in_flight() {
+        let provider = Arc::new(slow_provider());
+        let handle = handle_with_provider(provider.clone());
+        queue_jobs(handle, 6);
+
+        let mut progress: Vec<(usize, usize)> = Vec::new();
+        let context = &mut progress as *mut Vec<(usize, usize)> as *mut c_void;
+        assert_eq!(
+            flow_drain_pending(handle, 2, None, Some(collect_progress), context),
+            6
+        );
+
+        assert_eq!(provider.max_in_flight(), 2);
+        assert_eq!(flow_pending_count(handle), 0);
+        assert_eq!(progress, (1..=6).map(|done| (done, 6)).collect::<Vec<_>>());
+        assert_eq!(
+            flow_drain_pending(handle, 0, None, None, ptr::null_mut()),
+            0
+        );
+        flow_destroy(handle);
+    }
+
+    extern "C" fn cancel_after_two(completed: usize, _total: usize, context: *mut c_void) {
+        if completed == 2 {
+            assert!(flow_cancel_drain(context as *mut FlowHandle));
+        }
+    }
+
+    #[test]
+    fn test_cancelled_drain_leaves_remainder_queued() {
+        let handle = handle_with_provider(Arc::new(slow_provider()));
+        queue_jobs(handle, 6);
+
+        let drained = flow_drain_pending(
             handle,
-            format!("Failed to save auto-rewriting setting: {}", e),
+            2,
+            None,
+            Some(cancel_after_two),
+            handle as *mut c_void,
+        );
+
+        // the job running alongside the second one may finish before the cancel lands
+        assert!((2..=3).contains(&drained), "drained {drained}");
+        assert_eq!(flow_pending_count(handle), 6 - drained);
+        assert!(!flow_cancel_drain(handle));
+
+        // the rest go through on the next drain
+        assert_eq!(
+            flow_drain_pending(handle, 2, None, None, ptr::null_mut()),
+            6 - drained
         );
-        return false;
+        assert_eq!(flow_pending_count(handle), 0);
+        flow_destroy(handle);
     }
 
-    debug!("Auto-rewriting set to: {}", enabled);
-    true
-}
+    #[test]
+    fn test_non_network_failure_is_not_queued() {
+        let handle = handle_with_provider(Arc::new(
+            MockTranscriptionProvider::new()
+                .with_error(Error::Transcription("401 Unauthorized".to_string())),
+        ));
+
+        assert!(flow_transcribe(handle, ptr::null()).is_null());
+        assert_eq!(flow_pending_count(handle), 0);
+        assert_eq!(flow_retry_pending(handle, None, ptr::null_mut()), 0);
+        unsafe { drop(Box::from_raw(handle)) };
+    }
 
-/// Get whether auto-rewriting is enabled
-///
-/// # Arguments
-/// - `handle` - Engine handle
-///
-/// # Returns
-/// true if auto-rewriting is enabled, false otherwise (default: true)
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_get_auto_rewriting_enabled(handle: *mut FlowHandle) -> bool {
-    let handle = unsafe { &*handle };
+    #[test]
+    fn test_rejected_retry_stays_queued() {
+        // the key was changed while the job waited, then fixed
+        let provider = Arc::new(
+            MockTranscriptionProvider::new()
+                .with_error(Error::Auth("401 Unauthorized".to_string()))
+                .with_fallback("key fixed"),
+        );
+        let handle = handle_with_provider(provider.clone());
+        queue_jobs(handle, 1);
 
-    handle
-        .storage
-        .get_setting(SETTING_AUTO_REWRITING_ENABLED)
-        .ok()
-        .flatten()
-        .map(|s| s == "true")
-        .unwrap_or(true) // default to enabled
-}
+        assert_eq!(flow_retry_pending(handle, None, ptr::null_mut()), 0);
+        assert_eq!(flow_pending_count(handle), 1);
+        let jobs = unsafe { &*handle }
+            .storage
+            .get_pending_transcriptions()
+            .unwrap();
+        assert_eq!(jobs[0].attempts, 1);
+        assert!(jobs[0].last_error.as_deref().unwrap().contains("401"));
+
+        assert_eq!(flow_retry_pending(handle, None, ptr::null_mut()), 1);
+        assert_eq!(flow_pending_count(handle), 0);
+        unsafe { drop(Box::from_raw(handle)) };
+    }
 
-// ============ Alignment and Edit Detection ============
+    #[test]
+    fn test_failing_job_is_dropped_after_max_attempts() {
+        let mut provider = MockTranscriptionProvider::new();
+        for _ in 0..MAX_PENDING_ATTEMPTS {
+            provider = provider.with_error(Error::Transcription("503 Unavailable".to_string()));
+        }
+        let handle = handle_with_provider(Arc::new(provider));
+        queue_jobs(handle, 1);
 
-/// Align original and edited text, extract correction candidates
-/// Returns JSON with alignment result (caller must free with flow_free_string)
-/// JSON format:
-/// {
-///   "steps": [...],
-///   "word_edit_vector": "MMSMM",
-///   "punct_edit_vector": "ZZZZ",
-///   "corrections": [["original", "corrected"], ...]
-/// }
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_align_and_extract_corrections(
-    original: *const c_char,
-    edited: *const c_char,
-) -> *mut c_char {
-    if original.is_null() || edited.is_null() {
-        return ptr::null_mut();
+        for _ in 1..MAX_PENDING_ATTEMPTS {
+            assert_eq!(flow_retry_pending(handle, None, ptr::null_mut()), 0);
+            assert_eq!(flow_pending_count(handle), 1);
+        }
+        assert_eq!(flow_retry_pending(handle, None, ptr::null_mut()), 0);
+        assert_eq!(flow_pending_count(handle), 0);
+        unsafe { drop(Box::from_raw(handle)) };
     }
 
-    let original_str = match unsafe { CStr::from_ptr(original) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
-    };
+    #[test]
+    fn test_unusable_audio_is_dropped_from_queue() {
+        let handle = handle_with_provider(Arc::new(
+            MockTranscriptionProvider::new()
+                .with_error(Error::Audio("Unsupported sample rate".to_string())),
+        ));
+        queue_jobs(handle, 1);
+
+        assert_eq!(flow_retry_pending(handle, None, ptr::null_mut()), 0);
+        assert_eq!(flow_pending_count(handle), 0);
+        unsafe { drop(Box::from_raw(handle)) };
+    }
 
-    let edited_str = match unsafe { CStr::from_ptr(edited) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
-    };
+    fn transcribe_text(handle: *mut FlowHandle, text: &str) -> String {
+        let handle_ref = unsafe { &*handle };
+        handle_ref.set_transcription(Arc::new(MockTranscriptionProvider::returning(text)));
+        *handle_ref.pending_audio.lock() = Some(vec![0; 32_000]);
+        *handle_ref.pending_sample_rate.lock() = Some(16_000);
+        take_string(flow_transcribe(handle, ptr::null()))
+    }
 
-    let json = crate::alignment::align_and_extract_corrections_json(original_str, edited_str);
+    #[test]
+    fn test_silent_transcription_returns_empty() {
+        let handle = handle_with_worker(Arc::new(MockTranscriptionProvider::new()));
+        // ignores its input, like a model inventing text
+        select_completion(
+            handle,
+            Arc::new(MockCompletionProvider::returning(
+                "Thanks for your message!",
+            )),
+        );
 
-    match CString::new(json) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => ptr::null_mut(),
+        assert_eq!(transcribe_text(handle, ""), "");
+        assert_eq!(transcribe_text(handle, "  \n\t "), "");
+
+        let storage = &unsafe { &*handle }.storage;
+        assert_eq!(storage.get_transcription_count().unwrap(), 0);
+        unsafe { drop(Box::from_raw(handle)) };
     }
-}
 
-/// Get dictionary context for ASR prompting
-/// Returns JSON array of high-confidence learned words (caller must free with flow_free_string)
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_get_dictionary_context(handle: *mut FlowHandle, limit: u32) -> *mut c_char {
-    let handle = unsafe { &*handle };
+    #[test]
+    fn test_configured_hallucination_is_suppressed() {
+        let handle = handle_with_worker(Arc::new(MockTranscriptionProvider::new()));
+        // ignores its input, like a model inventing text
+        select_completion(
+            handle,
+            Arc::new(MockCompletionProvider::returning(
+                "Thanks for your message!",
+            )),
+        );
 
-    let words = handle
-        .storage
-        .get_dictionary_context(limit as usize)
-        .unwrap_or_default();
+        let phrases = CString::new(r#"["Thanks for listening."]"#).unwrap();
+        assert!(flow_set_hallucination_phrases(handle, phrases.as_ptr()));
+        assert_eq!(transcribe_text(handle, "thanks for listening"), "");
+        let storage = &unsafe { &*handle }.storage;
+        assert_eq!(storage.get_transcription_count().unwrap(), 0);
+
+        // with the filter off the phrase is processed like any other text
+        assert!(flow_set_hallucination_filter(handle, false));
+        assert_eq!(
+            transcribe_text(handle, "thanks for listening"),
+            "Thanks for your message!"
+        );
+        assert_eq!(storage.get_transcription_count().unwrap(), 1);
+        unsafe { drop(Box::from_raw(handle)) };
+    }
 
-    let json = serde_json::to_string(&words).unwrap_or_else(|_| "[]".to_string());
+    type ResultSender = std::sync::mpsc::Sender<(bool, String)>;
 
-    match CString::new(json) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => ptr::null_mut(),
+    /// Callback context owning a sender, so the callback thread never touches the
+    /// test's stack after the result arrives
+    fn result_channel() -> (*mut c_void, std::sync::mpsc::Receiver<(bool, String)>) {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let context = Box::into_raw(Box::new(sender)) as *mut c_void;
+        (context, receiver)
     }
-}
-
-/// Save edit analytics for tracking alignment patterns
-/// Returns true on success
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_save_edit_analytics(
-    handle: *mut FlowHandle,
-    word_edit_vector: *const c_char,
-    punct_edit_vector: *const c_char,
-    original_text: *const c_char,
-    edited_text: *const c_char,
-) -> bool {
-    let handle = unsafe { &*handle };
 
-    if word_edit_vector.is_null() {
-        return false;
+    extern "C" fn send_result(success: bool, result: *const c_char, context: *mut c_void) {
+        let sender = unsafe { Box::from_raw(context as *mut ResultSender) };
+        let report = unsafe { CStr::from_ptr(result) }
+            .to_string_lossy()
+            .into_owned();
+        sender.send((success, report)).unwrap();
     }
 
-    let word_vec = match unsafe { CStr::from_ptr(word_edit_vector) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
+    fn run_health_check(handle: *mut FlowHandle) -> (bool, serde_json::Value) {
+        let (context, receiver) = result_channel();
+        flow_health_check(handle, send_result, context);
 
-    let punct_vec = if punct_edit_vector.is_null() {
-        None
-    } else {
-        unsafe { CStr::from_ptr(punct_edit_vector) }.to_str().ok()
-    };
+        let (success, report) = receiver
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .unwrap();
+        (success, serde_json::from_str(&report).unwrap())
+    }
 
-    let original = if original_text.is_null() {
-        None
-    } else {
-        unsafe { CStr::from_ptr(original_text) }.to_str().ok()
-    };
+    #[test]
+    fn test_health_check_fails_independently_of_transcribe() {
+        // the health check is the first request, and the only one that fails
+        let handle = handle_with_provider(Arc::new(
+            MockTranscriptionProvider::new()
+                .with_error(Error::Config("401 invalid api key".to_string()))
+                .with_fallback("still works"),
+        ));
+        unsafe { &*handle }.set_completion(Arc::new(MockCompletionProvider::returning("ok")));
+
+        let (success, report) = run_health_check(handle);
+        assert!(!success);
+        assert_eq!(report["transcription"]["provider"], "mock");
+        assert_eq!(report["transcription"]["ok"], false);
+        assert!(
+            report["transcription"]["error"]
+                .as_str()
+                .unwrap()
+                .contains("401")
+        );
+        assert_eq!(report["completion"]["ok"], true);
 
-    let edited = if edited_text.is_null() {
-        None
-    } else {
-        unsafe { CStr::from_ptr(edited_text) }.to_str().ok()
-    };
+        // dictation itself still goes through
+        assert_eq!(
+            take_string(flow_transcribe(handle, ptr::null())),
+            "still works"
+        );
+        unsafe { drop(Box::from_raw(handle)) };
+    }
 
-    handle
-        .storage
-        .save_edit_analytics(None, word_vec, punct_vec, original, edited)
-        .is_ok()
-}
+    #[test]
+    fn test_health_check_default_uses_provider_requests() {
+        let handle = handle_with_provider(Arc::new(MockTranscriptionProvider::returning("ok")));
+        unsafe { &*handle }.set_completion(Arc::new(MockCompletionProvider::returning("ok")));
+
+        let (success, report) = run_health_check(handle);
+        assert!(success);
+        assert_eq!(report["transcription"]["ok"], true);
+        assert!(report["transcription"]["error"].is_null());
+        assert_eq!(report["completion"]["provider"], "mock");
+        unsafe { drop(Box::from_raw(handle)) };
+    }
 
-/// Save a learned words session for undo functionality
-/// words_json: JSON array of strings ["word1", "word2", ...]
-/// Returns session ID (or -1 on error)
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_save_learned_words_session(
-    handle: *mut FlowHandle,
-    words_json: *const c_char,
-) -> i64 {
-    let handle = unsafe { &*handle };
+    #[test]
+    fn test_preload_model_reports_warm_up_result() {
+        let handle = handle_with_provider(Arc::new(MockTranscriptionProvider::returning("ok")));
+        let (context, receiver) = result_channel();
+        flow_preload_model(handle, send_result, context);
+        let (success, message) = receiver
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .unwrap();
+        assert!(success);
+        assert_eq!(message, "");
+        unsafe { drop(Box::from_raw(handle)) };
+    }
 
-    let json_str = match unsafe { CStr::from_ptr(words_json) }.to_str() {
-        Ok(s) => s,
-        Err(_) => return -1,
-    };
+    fn transcribe_async(
+        handle: *mut FlowHandle,
+    ) -> (u64, std::sync::mpsc::Receiver<(bool, String)>) {
+        let (context, receiver) = result_channel();
+        let id = flow_transcribe_async(handle, ptr::null(), send_result, context);
+        (id, receiver)
+    }
 
-    let words: Vec<String> = match serde_json::from_str(json_str) {
-        Ok(w) => w,
-        Err(_) => return -1,
-    };
+    #[test]
+    fn test_cancel_drops_in_flight_transcription() {
+        // a request that never finishes
+        let provider = Arc::new(
+            MockTranscriptionProvider::new()
+                .with_delayed_response(Duration::from_secs(3600), "never"),
+        );
+        let handle = handle_with_provider(provider.clone());
 
-    handle
-        .storage
-        .save_learned_words_session(&words)
-        .unwrap_or(-1)
-}
+        let (id, receiver) = transcribe_async(handle);
+        assert_ne!(id, 0);
+        while provider.calls() == 0 {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        // ours, the handle's, and the in-flight request's
+        assert_eq!(Arc::strong_count(&provider), 3);
+
+        assert!(flow_cancel_transcription(handle, id));
+        let (success, result) = receiver
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .unwrap();
+        assert!(!success);
+        assert_eq!(result, CANCELLED_RESULT);
+        // the request was torn down along with its hold on the provider
+        assert_eq!(Arc::strong_count(&provider), 2);
+
+        // cancelled work isn't kept as a failure
+        let storage = &unsafe { &*handle }.storage;
+        assert!(storage.get_recent_history(10).unwrap().is_empty());
+        assert!(!flow_cancel_transcription(handle, id));
+        unsafe { drop(Box::from_raw(handle)) };
+    }
 
-/// Undo the most recent learned words session
-/// Removes the corrections and marks session as used
-/// Returns true if undo was performed
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_undo_learned_words(handle: *mut FlowHandle) -> bool {
-    let handle = unsafe { &*handle };
+    #[test]
+    fn test_destroy_cancels_and_waits_for_async_transcriptions() {
+        let provider = Arc::new(
+            MockTranscriptionProvider::new()
+                .with_delayed_response(Duration::from_secs(3600), "never"),
+        );
+        let handle = handle_with_provider(provider.clone());
 
-    let Some((session_id, words)) = handle.storage.get_undoable_learned_words().ok().flatten()
-    else {
-        return false;
-    };
+        let (id, receiver) = transcribe_async(handle);
+        assert_ne!(id, 0);
+        while provider.calls() == 0 {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
 
-    // Delete each learned word
-    for word in &words {
-        let _ = handle.storage.delete_correction_by_word(word);
-        // Also remove from learning engine cache
-        handle.learning.remove_from_cache(word);
+        flow_destroy(handle);
+        // the callback already fired, and nothing still holds the provider
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            (false, CANCELLED_RESULT.to_string())
+        );
+        assert_eq!(Arc::strong_count(&provider), 1);
     }
 
-    // Mark session as used
-    let _ = handle.storage.mark_learned_words_used(session_id);
+    #[test]
+    fn test_cancel_after_completion_is_noop() {
+        let handle =
+            handle_with_provider(Arc::new(MockTranscriptionProvider::returning("all done")));
+
+        let (id, receiver) = transcribe_async(handle);
+        let (success, result) = receiver
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .unwrap();
+        assert!(success);
+        assert_eq!(result, "all done");
+
+        assert!(!flow_cancel_transcription(handle, id));
+        assert!(!flow_cancel_transcription(handle, 12345));
+        unsafe { drop(Box::from_raw(handle)) };
+    }
 
-    debug!("Undid learned words session {}: {:?}", session_id, words);
-    true
-}
+    #[test]
+    fn test_transcribe_async_without_pending_audio() {
+        let handle = Box::into_raw(Box::new(new_handle(
+            shared_runtime().unwrap().handle().clone(),
+            Storage::in_memory().unwrap(),
+        )));
+        let (context, _receiver) = result_channel();
 
-/// Get the most recent undoable learned words as JSON
-/// Returns JSON array of strings (caller must free with flow_free_string)
-/// Returns null if no undoable session exists
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_get_undoable_learned_words(handle: *mut FlowHandle) -> *mut c_char {
-    let handle = unsafe { &*handle };
+        assert_eq!(
+            flow_transcribe_async(handle, ptr::null(), send_result, context),
+            0
+        );
+        // the callback never fired, so the context is still ours
+        unsafe { drop(Box::from_raw(context as *mut ResultSender)) };
+        unsafe { drop(Box::from_raw(handle)) };
+    }
 
-    let Some((_, words)) = handle.storage.get_undoable_learned_words().ok().flatten() else {
-        return ptr::null_mut();
-    };
+    #[test]
+    fn test_handles_share_runtime_and_abort_tasks_on_destroy() {
+        let runtime = shared_runtime().unwrap();
+        let mut counters = Vec::new();
+
+        for _ in 0..20 {
+            let handle = Box::into_raw(Box::new(new_handle(
+                runtime.handle().clone(),
+                Storage::in_memory().unwrap(),
+            )));
+            let handle_runtime = &unsafe { &*handle }.runtime;
+            handle_runtime.spawn(std::future::pending());
+            counters.push(Arc::clone(&handle_runtime.active));
+            flow_destroy(handle);
+        }
 
-    let json = serde_json::to_string(&words).unwrap_or_else(|_| "[]".to_string());
+        // no pool per handle
+        assert_eq!(runtime.metrics().num_workers(), RUNTIME_WORKER_THREADS);
 
-    match CString::new(json) {
-        Ok(cstr) => cstr.into_raw(),
-        Err(_) => ptr::null_mut(),
+        // aborted tasks are dropped on the worker threads shortly after
+        let deadline = Instant::now() + std::time::Duration::from_secs(5);
+        while counters.iter().any(|c| c.load(Ordering::SeqCst) > 0) && Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(counters.iter().all(|c| c.load(Ordering::SeqCst) == 0));
     }
-}
 
-// ============ OpenAI Base URL ============
+    #[test]
+    fn test_transcribe_vtt_synthesizes_timing() {
+        let handle = handle_with_provider(Arc::new(MockTranscriptionProvider::returning(
+            "Hello there. General Kenobi",
+        )));
+
+        let vtt = take_string(flow_transcribe_vtt(handle));
+        assert_eq!(
+            vtt,
+            "WEBVTT\n\n00:00:00.000 --> 00:00:00.500\nHello there.\n\n\
+             00:00:00.500 --> 00:00:01.000\nGeneral Kenobi\n\n"
+        );
+        // the pending audio was used up
+        assert!(flow_transcribe_vtt(handle).is_null());
+        unsafe { drop(Box::from_raw(handle)) };
+    }
 
-/// Set a custom OpenAI-compatible base URL for transcription and completion
-/// Pass an empty string to reset to the default (https://api.openai.com/v1)
-/// Returns true on success
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_set_openai_base_url(handle: *mut FlowHandle, url: *const c_char) -> bool {
-    let handle = unsafe { &mut *handle };
+    #[test]
+    fn test_transcribe_vtt_times_out() {
+        let handle = handle_with_provider(Arc::new(
+            MockTranscriptionProvider::new()
+                .with_delayed_response(Duration::from_secs(3600), "never"),
+        ));
+        *unsafe { &*handle }.request_timeout.lock() = Some(Duration::from_millis(50));
+
+        assert!(flow_transcribe_vtt(handle).is_null());
+        assert_eq!(flow_last_error(handle), ErrorCode::Timeout as i32);
+        assert_eq!(flow_pending_count(handle), 0);
+        unsafe { drop(Box::from_raw(handle)) };
+    }
 
-    let url_str = if url.is_null() {
-        String::new()
-    } else {
-        match unsafe { CStr::from_ptr(url) }.to_str() {
-            Ok(s) => s.trim().to_string(),
-            Err(_) => return false,
+    #[test]
+    fn test_short_recording_skips_provider() {
+        let provider = Arc::new(MockTranscriptionProvider::returning("hello"));
+        let handle = handle_with_provider(provider.clone());
+
+        // a 50ms tap, as measured by flow_stop_recording
+        {
+            let handle = unsafe { &*handle };
+            *handle.pending_audio.lock() = Some(vec![0; 1_600]);
+            *handle.pending_sample_rate.lock() = Some(16_000);
+            *handle.pending_duration_ms.lock() = Some(50);
         }
-    };
+        assert_eq!(take_string(flow_transcribe(handle, ptr::null())), "");
+        provider.assert_calls(0);
+        let storage = &unsafe { &*handle }.storage;
+        assert!(storage.get_recent_history(10).unwrap().is_empty());
 
-    if let Err(e) = handle
-        .storage
-        .set_setting(SETTING_OPENAI_BASE_URL, &url_str)
-    {
-        set_last_error(handle, format!("Failed to save OpenAI base URL: {e}"));
-        return false;
+        unsafe { drop(Box::from_raw(handle)) };
     }
 
-    // Update the live transcription provider only when currently using cloud OpenAI
-    let use_local = handle
-        .storage
-        .get_setting(SETTING_USE_LOCAL_TRANSCRIPTION)
-        .ok()
-        .flatten()
-        .map(|s| s == "true")
-        .unwrap_or(false);
+    #[test]
+    fn test_recording_above_minimum_is_transcribed() {
+        let provider = Arc::new(MockTranscriptionProvider::returning("hello"));
+        let handle = handle_with_provider(provider.clone());
 
-    let cloud_provider = handle
-        .storage
-        .get_setting(SETTING_CLOUD_TRANSCRIPTION_PROVIDER)
-        .ok()
-        .flatten()
-        .unwrap_or_else(|| "auto".to_string());
+        // the default handle has a second of audio pending
+        assert_eq!(take_string(flow_transcribe(handle, ptr::null())), "hello");
+        provider.assert_calls(1);
 
-    if !use_local && cloud_provider == "openai" {
-        let api_key = handle
-            .storage
-            .get_setting(SETTING_OPENAI_API_KEY)
-            .ok()
-            .flatten();
-        let base_url = if url_str.is_empty() {
-            None
-        } else {
-            Some(url_str)
+        // raising the minimum past the recording skips it
+        assert!(flow_set_min_recording_ms(handle, 2_000));
+        {
+            let handle = unsafe { &*handle };
+            *handle.pending_audio.lock() = Some(vec![0; 32_000]);
+            *handle.pending_sample_rate.lock() = Some(16_000);
+        }
+        assert_eq!(take_string(flow_transcribe(handle, ptr::null())), "");
+        provider.assert_calls(1);
+        assert_eq!(
+            unsafe { &*handle }
+                .storage
+                .get_setting(SETTING_MIN_RECORDING_MS)
+                .unwrap()
+                .as_deref(),
+            Some("2000")
+        );
+
+        unsafe { drop(Box::from_raw(handle)) };
+    }
+
+    #[test]
+    fn test_dry_run_records_nothing() {
+        let handle = handle_with_provider(Arc::new(MockTranscriptionProvider::returning(
+            "gonna share my linkedin",
+        )));
+        let trigger = CString::new("my linkedin").unwrap();
+        let replacement = CString::new("jsn.cam/li").unwrap();
+        assert!(flow_add_shortcut(
+            handle,
+            trigger.as_ptr(),
+            replacement.as_ptr()
+        ));
+        let occurrences = |handle: *mut FlowHandle| {
+            unsafe { &*handle }
+                .storage
+                .get_all_corrections()
+                .unwrap()
+                .into_iter()
+                .map(|c| (c.original, c.occurrences))
+                .collect::<Vec<_>>()
         };
-        handle.transcription = Arc::new(OpenAITranscriptionProvider::new(api_key, base_url));
+        let before = occurrences(handle);
+
+        let pcm = vec![0i16; 16_000];
+        let outcome: serde_json::Value = serde_json::from_str(&take_string(
+            flow_transcribe_dry_run(handle, pcm.as_ptr(), pcm.len(), 16_000, ptr::null()),
+        ))
+        .unwrap();
+        assert!(outcome["text"].as_str().unwrap().contains("jsn.cam/li"));
+        assert_eq!(outcome["corrections_applied"], 1);
+        assert_eq!(outcome["shortcuts_triggered"], 1);
+
+        assert_eq!(flow_transcription_count(handle), 0);
+        assert_eq!(occurrences(handle), before);
+        let stats: serde_json::Value =
+            serde_json::from_str(&take_string(flow_shortcut_stats(handle))).unwrap();
+        assert_eq!(stats[0]["use_count"], 0);
+        let storage = &unsafe { &*handle }.storage;
+        assert!(storage.get_recent_history(10).unwrap().is_empty());
+        // the recording buffer is left for the next real transcription
+        assert!(unsafe { &*handle }.pending_audio.lock().is_some());
+
+        assert!(flow_transcribe_dry_run(handle, ptr::null(), 0, 16_000, ptr::null()).is_null());
+        assert_eq!(flow_last_error(handle), ErrorCode::Audio as i32);
+
+        // a real transcription of the same audio is recorded
+        take_string(flow_transcribe(handle, ptr::null()));
+        assert_eq!(flow_transcription_count(handle), 1);
+
+        unsafe { drop(Box::from_raw(handle)) };
     }
 
-    clear_last_error(handle);
-    true
-}
+    #[test]
+    fn test_max_recording_setting() {
+        let storage = Storage::in_memory().unwrap();
+        let handle = Box::into_raw(Box::new(new_handle(
+            shared_runtime().unwrap().handle().clone(),
+            storage,
+        )));
+        {
+            let handle = unsafe { &*handle };
+            assert_eq!(*handle.max_recording.lock(), DEFAULT_MAX_RECORDING_DURATION);
+            assert_eq!(*handle.overflow_policy.lock(), OverflowPolicy::Stop);
+        }
 
-/// Get the currently configured custom OpenAI base URL
-/// Returns null if using the default (https://api.openai.com/v1)
-/// Caller must free the returned string with flow_free_string
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_get_openai_base_url(handle: *mut FlowHandle) -> *mut c_char {
-    let handle = unsafe { &*handle };
+        assert!(flow_set_max_recording(handle, 30, 1));
+        assert!(!flow_set_max_recording(handle, 0, 0));
+        assert!(!flow_set_max_recording(handle, 30, 2));
+        assert_eq!(flow_last_error(handle), ErrorCode::InvalidInput as i32);
+        // nothing is recording
+        assert!(!flow_recording_limit_reached(handle));
+
+        let handle = unsafe { Box::from_raw(handle) };
+        assert_eq!(*handle.max_recording.lock(), Duration::from_secs(30));
+        assert_eq!(*handle.overflow_policy.lock(), OverflowPolicy::DropOldest);
+        assert_eq!(
+            handle
+                .storage
+                .get_setting(SETTING_RECORDING_OVERFLOW_POLICY)
+                .unwrap()
+                .as_deref(),
+            Some("drop_oldest")
+        );
 
-    match handle.storage.get_setting(SETTING_OPENAI_BASE_URL) {
-        Ok(Some(url)) if !url.is_empty() => match CString::new(url) {
-            Ok(cstr) => cstr.into_raw(),
-            Err(_) => ptr::null_mut(),
-        },
-        _ => ptr::null_mut(),
+        // the limit is loaded when the handle is opened
+        let storage = Storage::in_memory().unwrap();
+        storage
+            .set_setting_as(SETTING_MAX_RECORDING_SECS, &90u64)
+            .unwrap();
+        let reopened = new_handle(shared_runtime().unwrap().handle().clone(), storage);
+        assert_eq!(*reopened.max_recording.lock(), Duration::from_secs(90));
+        assert_eq!(*reopened.overflow_policy.lock(), OverflowPolicy::Stop);
     }
 }
@@ -0,0 +1,309 @@
+//! Silence hallucination filter and repetition loop detection
+//!
+//! Given silence, Whisper often transcribes a stock phrase from its training data
+//! ("Thanks for watching!") instead of nothing. A transcription made up entirely of
+//! one of these phrases is treated as empty. Matching is on the whole text, ignoring
+//! case and punctuation, so dictation that merely contains one of them is kept.
+//!
+//! On noisy or looping audio Whisper can also get stuck repeating a short phrase
+//! ("the the the the the"). `RepetitionDetector` collapses such loops to a single
+//! occurrence so they don't reach the completion model.
+
+/// Phrases Whisper commonly produces for silent or near-silent audio
+///
+/// Short replies such as "thank you" or "bye" are hallucinated too, but they're also
+/// what people dictate into chat apps, so they're left for users to add themselves.
+pub const DEFAULT_HALLUCINATION_PHRASES: &[&str] = &[
+    "thanks for watching",
+    "thank you for watching",
+    "thanks for watching and see you next time",
+    "please subscribe",
+    "like and subscribe",
+    "subtitles by the amara org community",
+];
+
+/// Times a phrase may repeat back to back before it counts as a loop
+///
+/// High enough that laughter and emphasis ("ha ha ha ha", "no no no no") survive;
+/// real transcription loops tend to run much longer.
+pub const DEFAULT_MAX_PHRASE_REPEATS: usize = 6;
+
+/// Longest phrase, in words, checked for loops
+const MAX_LOOP_PHRASE_WORDS: usize = 6;
+
+/// Suppresses transcriptions that are just a known silence hallucination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HallucinationFilter {
+    enabled: bool,
+    /// Normalized phrases
+    phrases: Vec<String>,
+}
+
+impl HallucinationFilter {
+    /// Create an enabled filter with the default phrases
+    pub fn new() -> Self {
+        let mut filter = Self {
+            enabled: true,
+            phrases: Vec::new(),
+        };
+        filter.set_phrases(DEFAULT_HALLUCINATION_PHRASES);
+        filter
+    }
+
+    /// Create a filter with the default phrases that lets everything through
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Self::new()
+        }
+    }
+
+    /// Check whether transcriptions are filtered
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable filtering
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Replace the phrase list; blank phrases are ignored
+    pub fn set_phrases<S: AsRef<str>>(&mut self, phrases: impl IntoIterator<Item = S>) {
+        self.phrases = phrases
+            .into_iter()
+            .map(|p| normalize(p.as_ref()))
+            .filter(|p| !p.is_empty())
+            .collect();
+        self.phrases.sort();
+        self.phrases.dedup();
+    }
+
+    /// The phrase list, normalized
+    pub fn phrases(&self) -> &[String] {
+        &self.phrases
+    }
+
+    /// Whether text is nothing but a known hallucination phrase
+    pub fn is_hallucination(&self, text: &str) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let text = normalize(text);
+        !text.is_empty() && self.phrases.contains(&text)
+    }
+}
+
+impl Default for HallucinationFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Collapses a phrase repeated back to back more often than anyone would say it
+///
+/// "very very good" is fine, "the the the the the" is a loop. Phrases of up to six
+/// words are checked, ignoring case and punctuation, and the shortest repeating
+/// phrase wins, so "go go go go go" collapses to "go" rather than "go go".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepetitionDetector {
+    /// Most back-to-back repeats kept as-is, 0 to disable detection
+    max_repeats: usize,
+}
+
+/// Text with repetition loops collapsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollapsedRepetition {
+    pub text: String,
+    /// Number of loops that were collapsed
+    pub loops: usize,
+}
+
+impl RepetitionDetector {
+    /// Create a detector allowing `max_repeats` back-to-back repeats (0 disables it)
+    pub fn new(max_repeats: usize) -> Self {
+        Self { max_repeats }
+    }
+
+    /// Most back-to-back repeats kept as-is, 0 if detection is off
+    pub fn max_repeats(&self) -> usize {
+        self.max_repeats
+    }
+
+    /// Change how many back-to-back repeats are allowed (0 disables detection)
+    pub fn set_max_repeats(&mut self, max_repeats: usize) {
+        self.max_repeats = max_repeats;
+    }
+
+    /// Collapse every phrase repeated more than `max_repeats` times in a row to
+    /// its first occurrence, keeping the punctuation that ended the loop
+    pub fn collapse(&self, text: &str) -> CollapsedRepetition {
+        let spans = crate::tokenizer::word_spans(text);
+        if self.max_repeats == 0 || spans.len() <= self.max_repeats {
+            return CollapsedRepetition {
+                text: text.to_string(),
+                loops: 0,
+            };
+        }
+        let words: Vec<String> = spans
+            .iter()
+            .map(|&(start, end)| normalize(&text[start..end]))
+            .collect();
+
+        let mut result = String::with_capacity(text.len());
+        let mut copied = 0;
+        let mut loops = 0;
+        let mut i = 0;
+        while i < words.len() {
+            let Some((len, count)) = self.find_loop(&words, i) else {
+                i += 1;
+                continue;
+            };
+            // keep the first occurrence, minus its trailing punctuation, and end it
+            // the way the loop ended ("the the the the." -> "the.")
+            let first_end = spans[i + len - 1].1;
+            let last_end = spans[i + len * count - 1].1;
+            let first = &text[..first_end];
+            result.push_str(&first[copied..first.trim_end_matches(is_trailing).len()]);
+            let last_word = &text[spans[i + len * count - 1].0..last_end];
+            result.push_str(&last_word[last_word.trim_end_matches(is_trailing).len()..]);
+            copied = last_end;
+            loops += 1;
+            i += len * count;
+        }
+        result.push_str(&text[copied..]);
+
+        CollapsedRepetition {
+            text: result,
+            loops,
+        }
+    }
+
+    /// The shortest phrase starting at `start` that repeats more than allowed, as
+    /// (words in the phrase, times it occurs in a row)
+    fn find_loop(&self, words: &[String], start: usize) -> Option<(usize, usize)> {
+        for len in 1..=MAX_LOOP_PHRASE_WORDS {
+            let phrase = words.get(start..start + len)?;
+            if phrase.iter().any(String::is_empty) {
+                return None;
+            }
+            let count = 1 + words[start + len..]
+                .chunks_exact(len)
+                .take_while(|chunk| *chunk == phrase)
+                .count();
+            if count > self.max_repeats {
+                return Some((len, count));
+            }
+        }
+        None
+    }
+}
+
+impl Default for RepetitionDetector {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_PHRASE_REPEATS)
+    }
+}
+
+/// Punctuation that can end a word
+fn is_trailing(c: char) -> bool {
+    !c.is_alphanumeric()
+}
+
+/// Lowercase, drop punctuation and collapse whitespace ("Thank you." -> "thank you")
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric() || *c == '\'')
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_phrases_match_whole_text() {
+        let filter = HallucinationFilter::new();
+
+        assert!(filter.is_hallucination("  THANKS FOR WATCHING!  "));
+        assert!(!filter.is_hallucination("Thanks for watching the kids."));
+        assert!(!filter.is_hallucination(""));
+
+        // short replies are real dictation
+        for reply in ["Thank you.", "Thank you very much!", "You", "Bye!"] {
+            assert!(!filter.is_hallucination(reply), "{reply}");
+        }
+    }
+
+    #[test]
+    fn test_custom_phrases() {
+        let mut filter = HallucinationFilter::new();
+        filter.set_phrases(["Merci.", "  ", "merci"]);
+
+        assert_eq!(filter.phrases(), ["merci"]);
+        assert!(filter.is_hallucination("merci!"));
+        assert!(!filter.is_hallucination("Thanks for watching!"));
+    }
+
+    #[test]
+    fn test_repetition_loop_is_collapsed() {
+        let detector = RepetitionDetector::default();
+
+        let collapsed = detector.collapse("the the the the the the the the");
+        assert_eq!(collapsed.text, "the");
+        assert_eq!(collapsed.loops, 1);
+
+        let collapsed = detector.collapse(
+            "So I said, thank you, thank you, thank you, thank you, thank you, thank you, \
+             thank you. Then left",
+        );
+        assert_eq!(collapsed.text, "So I said, thank you. Then left");
+        assert_eq!(collapsed.loops, 1);
+
+        assert_eq!(detector.collapse("Go go go go go go go!").text, "Go!");
+    }
+
+    #[test]
+    fn test_repetition_under_threshold_is_kept() {
+        let detector = RepetitionDetector::default();
+
+        for text in [
+            "that was very very good",
+            "no no no, not that one",
+            "ha ha ha ha ha ha",
+            "I had had enough",
+            "",
+        ] {
+            let collapsed = detector.collapse(text);
+            assert_eq!(collapsed.text, text);
+            assert_eq!(collapsed.loops, 0);
+        }
+
+        // a lower threshold catches shorter runs, 0 turns detection off
+        assert_eq!(
+            RepetitionDetector::new(2).collapse("no no no, stop").text,
+            "no, stop"
+        );
+        assert_eq!(
+            RepetitionDetector::new(0)
+                .collapse("the the the the the")
+                .text,
+            "the the the the the"
+        );
+    }
+
+    #[test]
+    fn test_disabled_keeps_everything() {
+        let filter = HallucinationFilter::disabled();
+
+        assert!(!filter.is_enabled());
+        assert!(!filter.is_hallucination("Thanks for watching!"));
+    }
+}
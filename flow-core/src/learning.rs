@@ -2,15 +2,25 @@
 //!
 //! Learns from user corrections when they edit transcribed text.
 //! Uses Jaro-Winkler similarity for fuzzy matching and logarithmic confidence scaling.
+//!
+//! Each edit is stored both on its own and together with the word before it. A pair
+//! learned in both directions ("there" -> "their" and "their" -> "there") is a homophone,
+//! so it's only applied after the preceding words it was learned with.
+//!
+//! Words the user joins ("to gether" -> "together") or splits ("alot" -> "a lot") are
+//! learned as a correction between the two spellings, and applied over two adjacent
+//! words at a time.
 
+use chrono::{DateTime, Utc};
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use strsim::jaro_winkler;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::error::Result;
 use crate::storage::Storage;
-use crate::types::{Correction, CorrectionSource};
+use crate::tokenizer::{Tokenizer, WhitespaceTokenizer};
+use crate::types::{ConfidenceCurve, Correction, CorrectionSource};
 
 /// Minimum similarity threshold for considering a word pair as a typo correction
 const MIN_SIMILARITY: f64 = 0.7;
@@ -21,18 +31,111 @@ const MIN_AUTO_APPLY_CONFIDENCE: f32 = 0.55;
 /// Maximum word length difference to consider a correction (set to 1 for exact wrong words like "there"/"their")
 const MAX_LENGTH_DIFF: usize = 1;
 
+/// Minimum similarity for two words to be paired up during alignment
+const MIN_ALIGNMENT_SIMILARITY: f64 = 0.5;
+
+/// Default cap on cached corrections, general and contextual combined
+pub const DEFAULT_MAX_CACHE_SIZE: usize = 10_000;
+
+/// Days after which a correction's recency halves its cache score
+const RECENCY_HALF_LIFE_DAYS: f64 = 30.0;
+
+/// Tunable thresholds that control how aggressively corrections are learned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LearningConfig {
+    /// Minimum Jaro-Winkler similarity for a word pair to count as a typo correction
+    pub min_similarity: f64,
+    /// Minimum Jaro-Winkler similarity for two words to be aligned as a pair
+    pub min_alignment_similarity: f64,
+    /// Maximum word length difference to consider a correction
+    pub max_length_diff: usize,
+    /// How confidence grows with the number of times a correction is seen
+    pub confidence_curve: ConfidenceCurve,
+}
+
+impl LearningConfig {
+    /// Set the minimum similarity for learning a correction (clamped to 0.0-1.0)
+    pub fn with_min_similarity(mut self, similarity: f64) -> Self {
+        self.min_similarity = similarity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the minimum similarity for aligning two words (clamped to 0.0-1.0)
+    pub fn with_min_alignment_similarity(mut self, similarity: f64) -> Self {
+        self.min_alignment_similarity = similarity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the maximum word length difference for learning a correction
+    pub fn with_max_length_diff(mut self, max_length_diff: usize) -> Self {
+        self.max_length_diff = max_length_diff;
+        self
+    }
+
+    /// Set the curve mapping occurrence counts to confidence
+    pub fn with_confidence_curve(mut self, curve: ConfidenceCurve) -> Self {
+        self.confidence_curve = curve;
+        self
+    }
+}
+
+impl Default for LearningConfig {
+    fn default() -> Self {
+        Self {
+            min_similarity: MIN_SIMILARITY,
+            min_alignment_similarity: MIN_ALIGNMENT_SIMILARITY,
+            max_length_diff: MAX_LENGTH_DIFF,
+            confidence_curve: ConfidenceCurve::default(),
+        }
+    }
+}
+
 /// Engine for learning and applying typo corrections
 pub struct LearningEngine {
     /// In-memory cache of high-confidence corrections (original -> corrected)
     corrections: RwLock<HashMap<String, CachedCorrection>>,
+    /// Corrections that only apply after a given word ((previous, original) -> corrected)
+    contextual: RwLock<HashMap<(String, String), CachedCorrection>>,
     /// Minimum confidence for auto-applying corrections
-    min_confidence: f32,
+    min_confidence: RwLock<f32>,
+    /// Thresholds used when learning from edits
+    config: RwLock<LearningConfig>,
+    /// Splits text into the tokens corrections are learned and applied on
+    tokenizer: Box<dyn Tokenizer>,
+    /// Most corrections kept in the caches, see `cache_score`
+    max_cache_size: RwLock<usize>,
 }
 
 #[derive(Debug, Clone)]
 struct CachedCorrection {
     corrected: String,
     confidence: f32,
+    /// When the correction was last learned, for eviction
+    updated_at: DateTime<Utc>,
+}
+
+impl CachedCorrection {
+    /// How much a correction is worth keeping: its confidence, halved for every
+    /// 30 days since it was last learned
+    ///
+    /// A correction confirmed yesterday outranks an equally confident one nobody has
+    /// made in months, and a fresh but barely confirmed correction doesn't push out
+    /// an established one.
+    fn cache_score(&self, now: DateTime<Utc>) -> f64 {
+        let age_days = (now - self.updated_at).num_seconds().max(0) as f64 / 86_400.0;
+        f64::from(self.confidence) * 0.5f64.powf(age_days / RECENCY_HALF_LIFE_DAYS)
+    }
+}
+
+/// How full the correction caches are
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CacheOccupancy {
+    /// Corrections that apply everywhere
+    pub general: usize,
+    /// Corrections that only apply after a given word
+    pub contextual: usize,
+    /// Cap on the two combined
+    pub max: usize,
 }
 
 impl LearningEngine {
@@ -40,26 +143,46 @@ impl LearningEngine {
     pub fn new() -> Self {
         Self {
             corrections: RwLock::new(HashMap::new()),
-            min_confidence: MIN_AUTO_APPLY_CONFIDENCE,
+            contextual: RwLock::new(HashMap::new()),
+            min_confidence: RwLock::new(MIN_AUTO_APPLY_CONFIDENCE),
+            config: RwLock::new(LearningConfig::default()),
+            tokenizer: Box::new(WhitespaceTokenizer),
+            max_cache_size: RwLock::new(DEFAULT_MAX_CACHE_SIZE),
         }
     }
 
+    /// Cap the number of cached corrections (see `set_max_cache_size`)
+    pub fn with_max_cache_size(mut self, max: usize) -> Self {
+        self.max_cache_size = RwLock::new(max);
+        self
+    }
+
+    /// Use a custom learning configuration
+    pub fn with_config(mut self, config: LearningConfig) -> Self {
+        self.config = RwLock::new(config);
+        self
+    }
+
+    /// Use a different tokenizer, e.g. `CjkTokenizer` for languages without spaces
+    pub fn with_tokenizer(mut self, tokenizer: impl Tokenizer + 'static) -> Self {
+        self.tokenizer = Box::new(tokenizer);
+        self
+    }
+
     /// Create engine and load corrections from storage
     pub fn from_storage(storage: &Storage) -> Result<Self> {
         let engine = Self::new();
+        // merge rows split by casing ("the" vs "The") before loading; the duplicates
+        // only cost cache slots, so a failed merge shouldn't stop the corrections loading
+        if let Err(e) = storage.dedupe_corrections() {
+            warn!("Failed to merge duplicate corrections: {}", e);
+        }
         let corrections = storage.get_corrections(MIN_AUTO_APPLY_CONFIDENCE)?;
 
-        let mut cache = engine.corrections.write();
         for correction in corrections {
-            cache.insert(
-                correction.original.to_lowercase(),
-                CachedCorrection {
-                    corrected: correction.corrected,
-                    confidence: correction.confidence,
-                },
-            );
+            engine.cache_correction(correction);
         }
-        drop(cache);
+        engine.evict_over_capacity();
 
         info!(
             "Loaded {} corrections into learning engine",
@@ -69,9 +192,49 @@ impl LearningEngine {
         Ok(engine)
     }
 
+    /// Get the minimum confidence threshold for auto-applying corrections
+    pub fn min_confidence(&self) -> f32 {
+        *self.min_confidence.read()
+    }
+
     /// Set the minimum confidence threshold for auto-applying corrections
-    pub fn set_min_confidence(&mut self, confidence: f32) {
-        self.min_confidence = confidence.clamp(0.0, 1.0);
+    pub fn set_min_confidence(&self, confidence: f32) {
+        *self.min_confidence.write() = confidence.clamp(0.0, 1.0);
+    }
+
+    /// Cap the number of cached corrections, general and contextual combined
+    ///
+    /// When there are more, only the ones with the highest score are kept: confidence
+    /// halved for every 30 days since the correction was last learned. Evicted
+    /// corrections stay in storage and come back if they're learned again.
+    pub fn set_max_cache_size(&self, max: usize) {
+        *self.max_cache_size.write() = max;
+        self.evict_over_capacity();
+    }
+
+    /// How many corrections are cached, and the cap
+    pub fn cache_occupancy(&self) -> CacheOccupancy {
+        CacheOccupancy {
+            general: self.corrections.read().len(),
+            contextual: self.contextual.read().len(),
+            max: *self.max_cache_size.read(),
+        }
+    }
+
+    /// Get the current learning configuration
+    pub fn config(&self) -> LearningConfig {
+        *self.config.read()
+    }
+
+    /// Replace the learning configuration
+    pub fn set_config(&self, config: LearningConfig) {
+        *self.config.write() = config;
+    }
+
+    /// Set the minimum similarity for learning a correction
+    pub fn set_min_similarity(&self, similarity: f64) {
+        let mut config = self.config.write();
+        *config = config.with_min_similarity(similarity);
     }
 
     /// Learn from a before/after text comparison
@@ -82,51 +245,89 @@ impl LearningEngine {
         edited: &str,
         storage: &Storage,
     ) -> Result<Vec<LearnedCorrection>> {
-        let original_words: Vec<&str> = original.split_whitespace().collect();
-        let edited_words: Vec<&str> = edited.split_whitespace().collect();
+        let original_words = self.tokenizer.tokens(original);
+        let edited_words = self.tokenizer.tokens(edited);
 
+        let config = self.config();
         let mut learned = Vec::new();
 
         // use edit distance alignment to find corresponding words
-        let pairs = align_words(&original_words, &edited_words);
+        let alignments = align_word_indices(
+            &original_words,
+            &edited_words,
+            config.min_alignment_similarity,
+            self.tokenizer.as_ref(),
+        );
+
+        for alignment in alignments {
+            let (orig_idx, edit_idx) = match alignment {
+                Alignment::Word(orig_idx, edit_idx) => (orig_idx, edit_idx),
+                Alignment::Joined(orig_idx, edit_idx) => {
+                    let orig = original_words[orig_idx..orig_idx + 2].join(" ");
+                    learned.extend(self.learn_word_boundary(
+                        &orig,
+                        edited_words[edit_idx],
+                        storage,
+                    )?);
+                    continue;
+                }
+                Alignment::Split(orig_idx, edit_idx) => {
+                    let edit = edited_words[edit_idx..edit_idx + 2].join(" ");
+                    learned.extend(self.learn_word_boundary(
+                        original_words[orig_idx],
+                        &edit,
+                        storage,
+                    )?);
+                    continue;
+                }
+            };
+            let orig = original_words[orig_idx];
+            let edit = edited_words[edit_idx];
 
-        for (orig, edit) in pairs {
             // skip if same
             if orig.eq_ignore_ascii_case(edit) {
                 continue;
             }
 
+            // skip words the user just swapped around ("form from" -> "from form")
+            if is_adjacent_swap(orig, edit, &original_words, &edited_words) {
+                debug!("Skipping reordered words: '{}' <-> '{}'", orig, edit);
+                continue;
+            }
+
             // check if this looks like a typo correction (high similarity)
-            let similarity = jaro_winkler(orig, edit);
+            let similarity = self.tokenizer.similarity(orig, edit);
 
-            if similarity >= MIN_SIMILARITY {
+            if similarity >= config.min_similarity {
                 // check length difference
                 let len_diff = (orig.len() as isize - edit.len() as isize).unsigned_abs();
-                if len_diff > MAX_LENGTH_DIFF {
+                if len_diff > config.max_length_diff {
                     continue;
                 }
 
                 // this looks like a typo correction
-                let mut correction = Correction::new(
+                let correction = Correction::new(
                     orig.to_lowercase(),
                     edit.to_string(),
                     CorrectionSource::UserEdit,
                 );
 
-                // save or update in storage (will increment occurrences if exists)
-                storage.save_correction(&correction)?;
-
-                // update cache if confidence is high enough
-                correction.update_confidence();
-                if correction.confidence >= self.min_confidence {
-                    let mut cache = self.corrections.write();
-                    cache.insert(
+                // remember the preceding word too, in case this turns out to be a homophone
+                let previous = orig_idx
+                    .checked_sub(1)
+                    .map(|i| strip_punctuation(original_words[i]).1)
+                    .filter(|word| !word.is_empty());
+                let contextual = previous.map(|word| {
+                    Correction::new(
                         correction.original.clone(),
-                        CachedCorrection {
-                            corrected: correction.corrected.clone(),
-                            confidence: correction.confidence,
-                        },
-                    );
+                        correction.corrected.clone(),
+                        CorrectionSource::UserEdit,
+                    )
+                    .with_context(word)
+                });
+
+                for correction in std::iter::once(correction).chain(contextual) {
+                    self.record_correction(correction, storage)?;
                 }
 
                 debug!(
@@ -141,35 +342,173 @@ impl LearningEngine {
                 });
             }
         }
+        self.evict_over_capacity();
 
         Ok(learned)
     }
 
+    /// Learn a word the user joined ("to gether" -> "together") or split
+    /// ("alot" -> "a lot"), stored as a correction between the two spellings
+    ///
+    /// Surrounding punctuation is dropped, so "gether," still learns "to gether".
+    fn learn_word_boundary(
+        &self,
+        original: &str,
+        edited: &str,
+        storage: &Storage,
+    ) -> Result<Option<LearnedCorrection>> {
+        let (_, orig_core, _) = strip_punctuation(original);
+        let (_, edit_core, _) = strip_punctuation(edited);
+        if orig_core.is_empty() || edit_core.is_empty() {
+            return Ok(None);
+        }
+
+        let correction = Correction::new(
+            orig_core.to_lowercase(),
+            edit_core.to_string(),
+            CorrectionSource::UserEdit,
+        );
+        self.record_correction(correction, storage)?;
+
+        debug!("Learned word boundary: '{}' -> '{}'", orig_core, edit_core);
+
+        Ok(Some(LearnedCorrection {
+            original: original.to_string(),
+            corrected: edited.to_string(),
+            // the letters match exactly, only the spaces moved
+            similarity: 1.0,
+        }))
+    }
+
+    /// Save or update a correction in storage (incrementing its occurrences if it
+    /// exists), and cache it if it's now confident enough
+    fn record_correction(&self, mut correction: Correction, storage: &Storage) -> Result<()> {
+        let curve = self.config().confidence_curve;
+        correction.occurrences = storage.save_correction_with_curve(&correction, curve)?;
+
+        correction.update_confidence_with(curve);
+        if correction.confidence >= self.min_confidence() {
+            self.cache_correction(correction);
+        }
+        Ok(())
+    }
+
     /// Apply learned corrections to text
     /// Only applies corrections above the confidence threshold
+    /// A correction learned after a specific preceding word wins over the general one
     pub fn apply_corrections(&self, text: &str) -> (String, Vec<AppliedCorrection>) {
+        self.apply(text, false)
+    }
+
+    /// Apply learned corrections to prose, leaving code alone
+    ///
+    /// For code editors and terminals: words inside backtick spans and tokens that
+    /// look like code (`recieveBuffer`, `recieve_buffer`, `src/recieve.rs`, URLs,
+    /// `recieve()`) are never corrected, even when a correction for them was learned.
+    pub fn apply_corrections_outside_code(&self, text: &str) -> (String, Vec<AppliedCorrection>) {
+        self.apply(text, true)
+    }
+
+    fn apply(&self, text: &str, skip_code: bool) -> (String, Vec<AppliedCorrection>) {
         let cache = self.corrections.read();
+        let contextual = self.contextual.read();
+        let min_confidence = self.min_confidence();
 
-        if cache.is_empty() {
+        if cache.is_empty() && contextual.is_empty() {
             return (text.to_string(), Vec::new());
         }
 
-        let words: Vec<&str> = text.split_whitespace().collect();
+        let spans = self.tokenizer.spans(text);
 
-        if words.is_empty() {
+        if spans.is_empty() {
             return (text.to_string(), Vec::new());
         }
 
         let mut applied = Vec::with_capacity(4);
-        let mut result_words: Vec<String> = Vec::with_capacity(words.len());
+        let mut result = String::with_capacity(text.len());
+        let mut copied = 0;
+        let mut previous = String::new();
+        let mut in_backticks = false;
+        // set when a word was joined with the one before it
+        let mut joined_into_previous = false;
+
+        for (i, &(start, end)) in spans.iter().enumerate() {
+            if std::mem::take(&mut joined_into_previous) {
+                continue;
+            }
 
-        for (i, word) in words.iter().enumerate() {
+            // keep the original whitespace (including newlines) between words
+            result.push_str(&text[copied..start]);
+            copied = end;
+
+            let word = &text[start..end];
             let (prefix, core, suffix) = strip_punctuation(word);
-            let core_lower = core.to_lowercase();
+            let previous_word = std::mem::replace(&mut previous, core.to_lowercase());
+            let core_lower = &previous;
+
+            if skip_code {
+                // a word with a backtick opens, closes or is inside a code span
+                let backticks = word.matches('`').count();
+                let in_code = in_backticks || backticks > 0;
+                if backticks % 2 == 1 {
+                    in_backticks = !in_backticks;
+                }
+                if in_code || is_code_like(word) {
+                    result.push_str(word);
+                    continue;
+                }
+            }
 
-            if let Some(correction) = cache.get(&core_lower)
-                && correction.confidence >= self.min_confidence
-            {
+            // two words learned as one ("to gether" -> "together") take precedence,
+            // unless punctuation or code separates them
+            let joined = spans.get(i + 1).and_then(|&(next_start, next_end)| {
+                let next = &text[next_start..next_end];
+                let (next_prefix, next_core, next_suffix) = strip_punctuation(next);
+                if core.is_empty()
+                    || next_core.is_empty()
+                    || !suffix.is_empty()
+                    || !next_prefix.is_empty()
+                    || (skip_code && (next.contains('`') || is_code_like(next)))
+                {
+                    return None;
+                }
+                cache
+                    .get(&format!("{core_lower} {}", next_core.to_lowercase()))
+                    .filter(|c| c.confidence >= min_confidence)
+                    .map(|c| (c, next_core, next_suffix, next_end))
+            });
+
+            if let Some((correction, next_core, next_suffix, next_end)) = joined {
+                let corrected = match_case(&correction.corrected, core);
+
+                applied.push(AppliedCorrection {
+                    original: text[start + prefix.len()..next_end - next_suffix.len()].to_string(),
+                    corrected: corrected.clone(),
+                    confidence: correction.confidence,
+                    position: i,
+                });
+
+                result.push_str(prefix);
+                result.push_str(&corrected);
+                result.push_str(next_suffix);
+                copied = next_end;
+                previous = next_core.to_lowercase();
+                joined_into_previous = true;
+                continue;
+            }
+
+            let in_context = contextual
+                .get(&(previous_word, core_lower.clone()))
+                .filter(|c| c.confidence >= min_confidence);
+            let general = || {
+                cache
+                    .get(core_lower)
+                    .filter(|c| c.confidence >= min_confidence)
+                    // homophones ("there" <-> "their") only change in a learned context
+                    .filter(|c| !is_homophone(&cache, core_lower, &c.corrected))
+            };
+
+            if let Some(correction) = in_context.or_else(general) {
                 let corrected = match_case(&correction.corrected, core);
 
                 applied.push(AppliedCorrection {
@@ -179,17 +518,14 @@ impl LearningEngine {
                     position: i,
                 });
 
-                let mut full = String::with_capacity(prefix.len() + corrected.len() + suffix.len());
-                full.push_str(prefix);
-                full.push_str(&corrected);
-                full.push_str(suffix);
-                result_words.push(full);
+                result.push_str(prefix);
+                result.push_str(&corrected);
+                result.push_str(suffix);
             } else {
-                result_words.push(word.to_string());
+                result.push_str(word);
             }
         }
-
-        let result = result_words.join(" ");
+        result.push_str(&text[copied..]);
 
         if !applied.is_empty() {
             debug!("Applied {} corrections to text", applied.len());
@@ -198,6 +534,57 @@ impl LearningEngine {
         (result, applied)
     }
 
+    /// Undo corrections returned by `apply_corrections`, restoring the original words
+    ///
+    /// Positions are token indices into the uncorrected text, so corrections that
+    /// split or joined words shift later positions. Words that no longer match
+    /// their correction (e.g. the text was edited since) are left untouched.
+    pub fn revert_corrections(&self, text: &str, applied: &[AppliedCorrection]) -> String {
+        if applied.is_empty() {
+            return text.to_string();
+        }
+
+        let spans = self.tokenizer.spans(text);
+        let mut sorted: Vec<&AppliedCorrection> = applied.iter().collect();
+        sorted.sort_by_key(|c| c.position);
+
+        let mut result = String::with_capacity(text.len());
+        let mut copied = 0;
+        // difference between word indices in the corrected text and the original
+        let mut shift: isize = 0;
+
+        for correction in sorted {
+            let Some(first) = correction.position.checked_add_signed(shift) else {
+                continue;
+            };
+            let count = self.tokenizer.spans(&correction.corrected).len().max(1);
+            let original_count = self.tokenizer.spans(&correction.original).len().max(1);
+            shift += count as isize - original_count as isize;
+            if first + count > spans.len() || spans[first].0 < copied {
+                continue;
+            }
+
+            let start = spans[first].0;
+            let end = spans[first + count - 1].1;
+            result.push_str(&text[copied..start]);
+            copied = end;
+
+            let segment = &text[start..end];
+            match segment.find(&correction.corrected) {
+                Some(idx) => {
+                    result.push_str(&segment[..idx]);
+                    result.push_str(&correction.original);
+                    result.push_str(&segment[idx + correction.corrected.len()..]);
+                }
+                None => result.push_str(segment),
+            }
+        }
+        result.push_str(&text[copied..]);
+
+        debug!("Reverted {} corrections in text", applied.len());
+        result
+    }
+
     /// Check if we have a correction for a word
     pub fn has_correction(&self, word: &str) -> bool {
         let cache = self.corrections.read();
@@ -209,7 +596,7 @@ impl LearningEngine {
         let cache = self.corrections.read();
         cache
             .get(&word.to_lowercase())
-            .filter(|c| c.confidence >= self.min_confidence)
+            .filter(|c| c.confidence >= self.min_confidence())
             .map(|c| c.corrected.clone())
     }
 
@@ -225,6 +612,7 @@ impl LearningEngine {
     /// Clear all cached corrections
     pub fn clear_cache(&self) {
         self.corrections.write().clear();
+        self.contextual.write().clear();
     }
 
     /// Get the number of cached corrections
@@ -237,29 +625,92 @@ impl LearningEngine {
         self.corrections.write().remove(&original.to_lowercase());
     }
 
+    /// Remove a correction that only applies after `previous_word` from the cache
+    pub fn remove_contextual_from_cache(&self, previous_word: &str, original: &str) {
+        self.contextual
+            .write()
+            .remove(&(previous_word.to_lowercase(), original.to_lowercase()));
+    }
+
     /// Reload corrections from storage (useful after deleting)
     pub fn reload_from_storage(
         &self,
         storage: &crate::storage::Storage,
     ) -> crate::error::Result<()> {
-        let corrections = storage.get_corrections(self.min_confidence)?;
+        let corrections = storage.get_corrections(self.min_confidence())?;
 
-        let mut cache = self.corrections.write();
-        cache.clear();
+        self.clear_cache();
         for correction in corrections {
-            cache.insert(
-                correction.original.to_lowercase(),
-                CachedCorrection {
-                    corrected: correction.corrected,
-                    confidence: correction.confidence,
-                },
-            );
+            self.cache_correction(correction);
         }
+        self.evict_over_capacity();
 
-        info!("Reloaded {} corrections into learning engine", cache.len());
+        info!(
+            "Reloaded {} corrections into learning engine",
+            self.corrections.read().len()
+        );
 
         Ok(())
     }
+
+    /// Put a correction in the general or contextual cache depending on its context
+    fn cache_correction(&self, correction: Correction) {
+        let original = correction.original.to_lowercase();
+        let cached = CachedCorrection {
+            corrected: correction.corrected,
+            confidence: correction.confidence,
+            updated_at: correction.updated_at,
+        };
+        match correction.context {
+            Some(previous) => {
+                self.contextual
+                    .write()
+                    .insert((previous.to_lowercase(), original), cached);
+            }
+            None => {
+                self.corrections.write().insert(original, cached);
+            }
+        }
+    }
+
+    /// Drop the lowest scoring corrections until the caches fit `max_cache_size`
+    fn evict_over_capacity(&self) {
+        let mut general = self.corrections.write();
+        let mut contextual = self.contextual.write();
+        let excess = (general.len() + contextual.len()).saturating_sub(*self.max_cache_size.read());
+        if excess == 0 {
+            return;
+        }
+
+        // None for general corrections, the previous word for contextual ones
+        let now = Utc::now();
+        let mut ranked: Vec<(f64, Option<&String>, &String)> =
+            general
+                .iter()
+                .map(|(original, c)| (c.cache_score(now), None, original))
+                .chain(contextual.iter().map(|((previous, original), c)| {
+                    (c.cache_score(now), Some(previous), original)
+                }))
+                .collect();
+        // lowest score first; ties go by key so eviction is deterministic
+        ranked.sort_by(|a, b| {
+            a.0.total_cmp(&b.0)
+                .then_with(|| (a.1, a.2).cmp(&(b.1, b.2)))
+        });
+
+        let evicted: Vec<(Option<String>, String)> = ranked
+            .into_iter()
+            .take(excess)
+            .map(|(_, previous, original)| (previous.cloned(), original.clone()))
+            .collect();
+        for (previous, original) in evicted {
+            match previous {
+                Some(previous) => contextual.remove(&(previous, original)),
+                None => general.remove(&original),
+            };
+        }
+        debug!("Evicted {} corrections from the cache", excess);
+    }
 }
 
 impl Default for LearningEngine {
@@ -277,16 +728,53 @@ pub struct LearnedCorrection {
 }
 
 /// A correction that was applied to text
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppliedCorrection {
     pub original: String,
     pub corrected: String,
+    #[serde(default)]
     pub confidence: f32,
+    /// Word index in the text the correction was applied to
     pub position: usize,
 }
 
-/// Align words from two texts using a simple diff algorithm
-fn align_words<'a>(original: &[&'a str], edited: &[&'a str]) -> Vec<(&'a str, &'a str)> {
+/// Words matched up between an original and an edited text, by token index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alignment {
+    /// `original[.0]` corresponds to `edited[.1]`
+    Word(usize, usize),
+    /// `original[.0]` and the word after it were joined into `edited[.1]`
+    Joined(usize, usize),
+    /// `original[.0]` was split into `edited[.1]` and the word after it
+    Split(usize, usize),
+}
+
+/// Align words from two texts using a simple diff algorithm, keeping the
+/// word-for-word pairs
+#[cfg(test)]
+fn align_words<'a>(
+    original: &[&'a str],
+    edited: &[&'a str],
+    min_similarity: f64,
+    tokenizer: &dyn Tokenizer,
+) -> Vec<(&'a str, &'a str)> {
+    align_word_indices(original, edited, min_similarity, tokenizer)
+        .into_iter()
+        .filter_map(|alignment| match alignment {
+            Alignment::Word(orig_idx, edit_idx) => Some((original[orig_idx], edited[edit_idx])),
+            Alignment::Joined(..) | Alignment::Split(..) => None,
+        })
+        .collect()
+}
+
+/// Like `align_words`, but returns the index of each word in its text, including
+/// words that were joined or split
+fn align_word_indices(
+    original: &[&str],
+    edited: &[&str],
+    min_similarity: f64,
+    tokenizer: &dyn Tokenizer,
+) -> Vec<Alignment> {
     if original.is_empty() || edited.is_empty() {
         return Vec::new();
     }
@@ -303,29 +791,50 @@ fn align_words<'a>(original: &[&'a str], edited: &[&'a str]) -> Vec<(&'a str, &'
 
         // skip the Jaro-Winkler call if the strings already match
         if orig.eq_ignore_ascii_case(edit) {
-            pairs.push((orig, edit));
+            pairs.push(Alignment::Word(orig_idx, edit_idx));
             orig_idx += 1;
             edit_idx += 1;
             continue;
         }
 
+        // the same letters with a space removed ("to gether" -> "together") or added
+        // ("alot" -> "a lot")
+        if original
+            .get(orig_idx + 1)
+            .is_some_and(|next| is_joined(orig, next, edit))
+        {
+            pairs.push(Alignment::Joined(orig_idx, edit_idx));
+            orig_idx += 2;
+            edit_idx += 1;
+            continue;
+        }
+        if edited
+            .get(edit_idx + 1)
+            .is_some_and(|next| is_joined(edit, next, orig))
+        {
+            pairs.push(Alignment::Split(orig_idx, edit_idx));
+            orig_idx += 1;
+            edit_idx += 2;
+            continue;
+        }
+
         // if they're similar enough, consider them a pair
-        let sim = jaro_winkler(orig, edit);
-        if sim >= 0.5 {
-            pairs.push((orig, edit));
+        let sim = tokenizer.similarity(orig, edit);
+        if sim >= min_similarity {
+            pairs.push(Alignment::Word(orig_idx, edit_idx));
             orig_idx += 1;
             edit_idx += 1;
         } else {
             // check if the original word was deleted (next edit word matches next orig word better)
             let skip_orig = if orig_idx + 1 < original.len() {
-                jaro_winkler(original[orig_idx + 1], edit) > sim
+                tokenizer.similarity(original[orig_idx + 1], edit) > sim
             } else {
                 false
             };
 
             // check if a word was inserted (current orig matches next edit word better)
             let skip_edit = if edit_idx + 1 < edited.len() {
-                jaro_winkler(orig, edited[edit_idx + 1]) > sim
+                tokenizer.similarity(orig, edited[edit_idx + 1]) > sim
             } else {
                 false
             };
@@ -345,6 +854,42 @@ fn align_words<'a>(original: &[&'a str], edited: &[&'a str]) -> Vec<(&'a str, &'
     pairs
 }
 
+/// Whether `joined` is `first` and `second` written as one word, ignoring case
+fn is_joined(first: &str, second: &str, joined: &str) -> bool {
+    joined.len() == first.len() + second.len()
+        && joined
+            .to_lowercase()
+            .strip_prefix(&first.to_lowercase())
+            .is_some_and(|rest| rest == second.to_lowercase())
+}
+
+/// Whether `word` -> `corrected` was also learned the other way round
+fn is_homophone(cache: &HashMap<String, CachedCorrection>, word: &str, corrected: &str) -> bool {
+    let corrected = corrected.to_lowercase();
+    corrected != word
+        && cache
+            .get(&corrected)
+            .is_some_and(|reverse| reverse.corrected.to_lowercase() == word)
+}
+
+/// Check whether an aligned pair is two adjacent words that traded places,
+/// i.e. one text has "a b" where the other has "b a"
+fn is_adjacent_swap(orig: &str, edit: &str, original: &[&str], edited: &[&str]) -> bool {
+    let same = |a: &str, b: &str| {
+        strip_punctuation(a)
+            .1
+            .eq_ignore_ascii_case(strip_punctuation(b).1)
+    };
+    let has_bigram = |words: &[&str], first: &str, second: &str| {
+        words
+            .windows(2)
+            .any(|w| same(w[0], first) && same(w[1], second))
+    };
+
+    (has_bigram(original, orig, edit) && has_bigram(edited, edit, orig))
+        || (has_bigram(original, edit, orig) && has_bigram(edited, orig, edit))
+}
+
 /// Split a word into (leading_punctuation, core_word, trailing_punctuation).
 /// e.g. "\"teh,\"" -> ("\"", "teh", ",\"")
 #[inline]
@@ -359,6 +904,41 @@ fn strip_punctuation(word: &str) -> (&str, &str, &str) {
     (&word[..start], &word[start..end], &word[end..])
 }
 
+/// Whether a word looks like code rather than prose: identifiers with inner capitals
+/// or underscores, paths, URLs, emails, dotted names (`config.toml`) and calls
+fn is_code_like(word: &str) -> bool {
+    let (_, core, suffix) = strip_punctuation(word);
+    if core.is_empty() {
+        return false;
+    }
+    if suffix.starts_with("()") {
+        return true;
+    }
+    if core.contains([
+        '_', '/', '\\', '(', ')', '[', ']', '{', '}', '<', '>', '=', '@',
+    ]) {
+        return true;
+    }
+
+    // camelCase and PascalCase: a capital straight after a lowercase letter
+    let chars: Vec<char> = core.chars().collect();
+    if chars
+        .windows(2)
+        .any(|pair| pair[0].is_lowercase() && pair[1].is_uppercase())
+    {
+        return true;
+    }
+
+    // file names and member access, but not abbreviations ("e.g") or numbers ("3.5")
+    let parts: Vec<&str> = core.split('.').collect();
+    parts.len() > 1
+        && parts.iter().all(|part| !part.is_empty())
+        && parts.last().is_some_and(|last| last.chars().count() > 1)
+        && !parts
+            .iter()
+            .all(|part| part.chars().all(|c| c.is_ascii_digit()))
+}
+
 /// Try to match the case pattern of the original word
 fn match_case(corrected: &str, original: &str) -> String {
     if original.is_empty() || corrected.is_empty() {
@@ -389,6 +969,8 @@ fn match_case(corrected: &str, original: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tokenizer::CjkTokenizer;
+    use strsim::jaro_winkler;
 
     #[test]
     fn test_apply_corrections() {
@@ -402,6 +984,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "the".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
 
@@ -410,6 +993,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "receive".to_string(),
                     confidence: 0.9,
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -432,7 +1016,12 @@ mod tests {
         let original = vec!["I", "recieve", "teh", "mail"];
         let edited = vec!["I", "receive", "the", "mail"];
 
-        let pairs = align_words(&original, &edited);
+        let pairs = align_words(
+            &original,
+            &edited,
+            MIN_ALIGNMENT_SIMILARITY,
+            &WhitespaceTokenizer,
+        );
 
         assert_eq!(pairs.len(), 4);
         assert_eq!(pairs[1], ("recieve", "receive"));
@@ -452,7 +1041,7 @@ mod tests {
 
     #[test]
     fn test_confidence_below_threshold() {
-        let mut engine = LearningEngine::new();
+        let engine = LearningEngine::new();
         engine.set_min_confidence(0.9);
 
         // add a low-confidence correction
@@ -463,6 +1052,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "bar".to_string(),
                     confidence: 0.5, // below threshold
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -505,7 +1095,12 @@ mod tests {
         let original = vec!["I", "the", "mail"];
         let edited = vec!["I", "received", "the", "mail"];
 
-        let pairs = align_words(&original, &edited);
+        let pairs = align_words(
+            &original,
+            &edited,
+            MIN_ALIGNMENT_SIMILARITY,
+            &WhitespaceTokenizer,
+        );
 
         // alignment should handle insertion gracefully
         // the algorithm should skip "received" and align remaining words
@@ -518,7 +1113,12 @@ mod tests {
         let original = vec!["I", "really", "love", "mail"];
         let edited = vec!["I", "love", "mail"];
 
-        let pairs = align_words(&original, &edited);
+        let pairs = align_words(
+            &original,
+            &edited,
+            MIN_ALIGNMENT_SIMILARITY,
+            &WhitespaceTokenizer,
+        );
 
         // should handle deletion and still align remaining words
         assert!(!pairs.is_empty());
@@ -530,7 +1130,12 @@ mod tests {
         let original = vec!["hello", "world"];
         let edited = vec!["foo", "bar", "baz"];
 
-        let pairs = align_words(&original, &edited);
+        let pairs = align_words(
+            &original,
+            &edited,
+            MIN_ALIGNMENT_SIMILARITY,
+            &WhitespaceTokenizer,
+        );
 
         // should handle gracefully even if no good matches
         // the algorithm may still produce pairs based on position
@@ -543,15 +1148,30 @@ mod tests {
         let empty: Vec<&str> = vec![];
 
         // empty original
-        let pairs = align_words(&empty, &["hello"]);
+        let pairs = align_words(
+            &empty,
+            &["hello"],
+            MIN_ALIGNMENT_SIMILARITY,
+            &WhitespaceTokenizer,
+        );
         assert!(pairs.is_empty());
 
         // empty edited
-        let pairs = align_words(&["hello"], &empty);
+        let pairs = align_words(
+            &["hello"],
+            &empty,
+            MIN_ALIGNMENT_SIMILARITY,
+            &WhitespaceTokenizer,
+        );
         assert!(pairs.is_empty());
 
         // both empty
-        let pairs = align_words(&empty, &empty);
+        let pairs = align_words(
+            &empty,
+            &empty,
+            MIN_ALIGNMENT_SIMILARITY,
+            &WhitespaceTokenizer,
+        );
         assert!(pairs.is_empty());
     }
 
@@ -565,6 +1185,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "the".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -584,6 +1205,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "the".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -614,6 +1236,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "AAA".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
             cache.insert(
@@ -621,6 +1244,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "BBB".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -642,6 +1266,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "the".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -655,7 +1280,7 @@ mod tests {
 
     #[test]
     fn test_get_correction() {
-        let mut engine = LearningEngine::new();
+        let engine = LearningEngine::new();
         engine.set_min_confidence(0.5);
 
         {
@@ -665,6 +1290,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "the".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
             cache.insert(
@@ -672,6 +1298,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "HIGH".to_string(),
                     confidence: 0.3, // below threshold
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -692,6 +1319,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "AAA".to_string(),
                     confidence: 0.9,
+                    updated_at: Utc::now(),
                 },
             );
             cache.insert(
@@ -699,6 +1327,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "BBB".to_string(),
                     confidence: 0.8,
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -717,6 +1346,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "the".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -736,6 +1366,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "the".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
             cache.insert(
@@ -743,6 +1374,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "receive".to_string(),
                     confidence: 0.9,
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -760,23 +1392,23 @@ mod tests {
 
     #[test]
     fn test_set_min_confidence_clamp() {
-        let mut engine = LearningEngine::new();
+        let engine = LearningEngine::new();
 
         engine.set_min_confidence(-0.5);
-        assert_eq!(engine.min_confidence, 0.0);
+        assert_eq!(engine.min_confidence(), 0.0);
 
         engine.set_min_confidence(1.5);
-        assert_eq!(engine.min_confidence, 1.0);
+        assert_eq!(engine.min_confidence(), 1.0);
 
         engine.set_min_confidence(0.7);
-        assert_eq!(engine.min_confidence, 0.7);
+        assert_eq!(engine.min_confidence(), 0.7);
     }
 
     #[test]
     fn test_default_impl() {
         let engine = LearningEngine::default();
         assert_eq!(engine.cache_size(), 0);
-        assert_eq!(engine.min_confidence, MIN_AUTO_APPLY_CONFIDENCE);
+        assert_eq!(engine.min_confidence(), MIN_AUTO_APPLY_CONFIDENCE);
     }
 
     #[test]
@@ -840,6 +1472,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "the".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -858,6 +1491,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "the".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -871,7 +1505,12 @@ mod tests {
         let original = vec!["hello"];
         let edited = vec!["hallo"];
 
-        let pairs = align_words(&original, &edited);
+        let pairs = align_words(
+            &original,
+            &edited,
+            MIN_ALIGNMENT_SIMILARITY,
+            &WhitespaceTokenizer,
+        );
         assert_eq!(pairs.len(), 1);
         assert_eq!(pairs[0], ("hello", "hallo"));
     }
@@ -880,7 +1519,12 @@ mod tests {
     fn test_align_words_same_text() {
         let words = vec!["I", "love", "rust"];
 
-        let pairs = align_words(&words, &words);
+        let pairs = align_words(
+            &words,
+            &words,
+            MIN_ALIGNMENT_SIMILARITY,
+            &WhitespaceTokenizer,
+        );
         assert_eq!(pairs.len(), 3);
         assert_eq!(pairs[0], ("I", "I"));
         assert_eq!(pairs[1], ("love", "love"));
@@ -897,6 +1541,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "the".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -917,6 +1562,7 @@ mod tests {
                 CachedCorrection {
                     corrected: "the".to_string(),
                     confidence: 0.95,
+                    updated_at: Utc::now(),
                 },
             );
         }
@@ -925,4 +1571,648 @@ mod tests {
         assert_eq!(result, "I saw the, cat");
         assert_eq!(applied.len(), 1);
     }
+
+    #[test]
+    fn test_revert_corrections_roundtrip() {
+        let engine = LearningEngine::new();
+        {
+            let mut cache = engine.corrections.write();
+            for (original, corrected) in [("teh", "the"), ("recieve", "receive")] {
+                cache.insert(
+                    original.to_string(),
+                    CachedCorrection {
+                        corrected: corrected.to_string(),
+                        confidence: 0.95,
+                        updated_at: Utc::now(),
+                    },
+                );
+            }
+        }
+
+        for input in [
+            "I will recieve teh package",
+            "Teh cat, teh dog.",
+            "(teh) RECIEVE it",
+            "first line teh\n\n  second  recieve ",
+            "nothing to fix here",
+        ] {
+            let (corrected, applied) = engine.apply_corrections(input);
+            assert_eq!(engine.revert_corrections(&corrected, &applied), input);
+        }
+    }
+
+    #[test]
+    fn test_revert_corrections_with_length_changes() {
+        let engine = LearningEngine::new();
+        {
+            let mut cache = engine.corrections.write();
+            cache.insert(
+                "alot".to_string(),
+                CachedCorrection {
+                    corrected: "a lot".to_string(),
+                    confidence: 0.95,
+                    updated_at: Utc::now(),
+                },
+            );
+            cache.insert(
+                "teh".to_string(),
+                CachedCorrection {
+                    corrected: "the".to_string(),
+                    confidence: 0.95,
+                    updated_at: Utc::now(),
+                },
+            );
+        }
+
+        let input = "thanks alot, teh team did alot";
+        let (corrected, applied) = engine.apply_corrections(input);
+        assert_eq!(corrected, "thanks a lot, the team did a lot");
+        assert_eq!(applied.len(), 3);
+
+        assert_eq!(engine.revert_corrections(&corrected, &applied), input);
+    }
+
+    #[test]
+    fn test_revert_corrections_skips_edited_words() {
+        let engine = LearningEngine::new();
+        let applied = vec![AppliedCorrection {
+            original: "teh".to_string(),
+            corrected: "the".to_string(),
+            confidence: 0.95,
+            position: 1,
+        }];
+
+        // user replaced "the" after the correction was applied
+        assert_eq!(
+            engine.revert_corrections("saw a cat", &applied),
+            "saw a cat"
+        );
+        assert_eq!(engine.revert_corrections("", &[]), "");
+    }
+
+    #[test]
+    fn test_sigmoid_curve_is_less_eager_than_logarithmic() {
+        let logarithmic = ConfidenceCurve::Logarithmic
+            .occurrences_to_reach(MIN_AUTO_APPLY_CONFIDENCE, 100)
+            .unwrap();
+        let sigmoid = ConfidenceCurve::sigmoid()
+            .occurrences_to_reach(MIN_AUTO_APPLY_CONFIDENCE, 100)
+            .unwrap();
+
+        assert_eq!(logarithmic, 1);
+        assert_eq!(sigmoid, 4);
+        assert!(sigmoid > logarithmic);
+    }
+
+    #[test]
+    fn test_confidence_curves_documented_values() {
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+
+        assert!(close(ConfidenceCurve::Logarithmic.confidence(1), 0.62));
+        assert!(close(ConfidenceCurve::Logarithmic.confidence(5), 0.76));
+        assert!(close(ConfidenceCurve::linear().confidence(1), 0.30));
+        assert!(close(ConfidenceCurve::linear().confidence(4), 0.60));
+        assert!(close(ConfidenceCurve::sigmoid().confidence(3), 0.50));
+        assert!(close(ConfidenceCurve::sigmoid().confidence(5), 0.88));
+
+        // every curve is capped
+        for curve in [
+            ConfidenceCurve::Logarithmic,
+            ConfidenceCurve::linear(),
+            ConfidenceCurve::sigmoid(),
+        ] {
+            assert!(curve.confidence(1000) <= 0.99);
+        }
+    }
+
+    #[test]
+    fn test_engine_uses_configured_curve() {
+        let storage = Storage::in_memory().unwrap();
+        let engine = LearningEngine::new().with_config(
+            LearningConfig::default().with_confidence_curve(ConfidenceCurve::sigmoid()),
+        );
+
+        // sigmoid needs four sightings before auto-applying
+        for _ in 0..3 {
+            engine
+                .learn_from_edit("I recieve mail", "I receive mail", &storage)
+                .unwrap();
+            assert!(!engine.has_correction("recieve"));
+        }
+        engine
+            .learn_from_edit("I recieve mail", "I receive mail", &storage)
+            .unwrap();
+        assert_eq!(
+            engine.get_correction("recieve"),
+            Some("receive".to_string())
+        );
+    }
+
+    #[test]
+    fn test_learning_config_defaults() {
+        let config = LearningConfig::default();
+        assert_eq!(config.min_similarity, MIN_SIMILARITY);
+        assert_eq!(config.min_alignment_similarity, MIN_ALIGNMENT_SIMILARITY);
+        assert_eq!(config.max_length_diff, MAX_LENGTH_DIFF);
+
+        let engine = LearningEngine::new();
+        assert_eq!(engine.config(), config);
+    }
+
+    #[test]
+    fn test_learning_config_builder_clamps() {
+        let config = LearningConfig::default()
+            .with_min_similarity(1.5)
+            .with_min_alignment_similarity(-0.2)
+            .with_max_length_diff(3);
+
+        assert_eq!(config.min_similarity, 1.0);
+        assert_eq!(config.min_alignment_similarity, 0.0);
+        assert_eq!(config.max_length_diff, 3);
+    }
+
+    #[test]
+    fn test_default_length_diff_rejects_longer_correction() {
+        // "colour" -> "color" is only one character apart, so the default already learns it;
+        // "programme" -> "program" is two apart and needs a looser length limit
+        let storage = Storage::in_memory().unwrap();
+        let engine = LearningEngine::new();
+
+        let learned = engine
+            .learn_from_edit("the colour programme", "the color program", &storage)
+            .unwrap();
+
+        assert_eq!(learned.len(), 1);
+        assert_eq!(learned[0].original, "colour");
+        assert_eq!(learned[0].corrected, "color");
+    }
+
+    #[test]
+    fn test_raised_length_diff_learns_longer_correction() {
+        let storage = Storage::in_memory().unwrap();
+        let engine =
+            LearningEngine::new().with_config(LearningConfig::default().with_max_length_diff(2));
+
+        let learned = engine
+            .learn_from_edit("the colour programme", "the color program", &storage)
+            .unwrap();
+
+        assert_eq!(learned.len(), 2);
+        assert_eq!(learned[0].corrected, "color");
+        assert_eq!(learned[1].original, "programme");
+        assert_eq!(learned[1].corrected, "program");
+    }
+
+    #[test]
+    fn test_min_similarity_setter_rejects_loose_pairs() {
+        let storage = Storage::in_memory().unwrap();
+        let engine = LearningEngine::new();
+        engine.set_min_similarity(0.99);
+
+        let learned = engine
+            .learn_from_edit("I recieve mail", "I receive mail", &storage)
+            .unwrap();
+
+        assert!(learned.is_empty());
+        assert_eq!(engine.config().min_similarity, 0.99);
+    }
+
+    #[test]
+    fn test_word_swap_is_not_learned() {
+        let storage = Storage::in_memory().unwrap();
+        let engine = LearningEngine::new();
+
+        // "form"/"from" are similar enough to pass as a typo fix on their own
+        assert!(jaro_winkler("form", "from") >= MIN_SIMILARITY);
+
+        let learned = engine
+            .learn_from_edit(
+                "please send the form from home",
+                "please send the from form home",
+                &storage,
+            )
+            .unwrap();
+        assert!(learned.is_empty());
+
+        let learned = engine
+            .learn_from_edit("the quick brown fox", "the brown quick fox", &storage)
+            .unwrap();
+        assert!(learned.is_empty());
+        let stored = storage.get_all_corrections().unwrap();
+        assert!(
+            stored
+                .iter()
+                .all(|c| !["form", "from", "quick", "brown"].contains(&c.original.as_str()))
+        );
+    }
+
+    #[test]
+    fn test_typo_in_reordered_sentence_is_learned() {
+        let storage = Storage::in_memory().unwrap();
+        let engine = LearningEngine::new();
+
+        let learned = engine
+            .learn_from_edit(
+                "I recieve the form from home",
+                "I receive the from form home",
+                &storage,
+            )
+            .unwrap();
+
+        assert_eq!(learned.len(), 1);
+        assert_eq!(learned[0].original, "recieve");
+        assert_eq!(learned[0].corrected, "receive");
+    }
+
+    #[test]
+    fn test_from_storage_dedupes_corrections() {
+        let storage = Storage::in_memory().unwrap();
+        for corrected in ["the", "The"] {
+            let correction = Correction::new(
+                "teh".to_string(),
+                corrected.to_string(),
+                CorrectionSource::UserEdit,
+            );
+            storage.save_correction(&correction).unwrap();
+        }
+
+        let engine = LearningEngine::from_storage(&storage).unwrap();
+
+        let rows: Vec<_> = storage
+            .get_all_corrections()
+            .unwrap()
+            .into_iter()
+            .filter(|c| c.original == "teh")
+            .collect();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].occurrences, 2);
+        assert!(engine.has_correction("teh"));
+    }
+
+    #[test]
+    fn test_default_tokenizer_keeps_english_behavior() {
+        let storage = Storage::in_memory().unwrap();
+        let engine = LearningEngine::new();
+        let explicit = LearningEngine::new().with_tokenizer(WhitespaceTokenizer);
+
+        let learned = engine
+            .learn_from_edit("I recieve mail", "I receive mail", &storage)
+            .unwrap();
+        let learned_explicit = explicit
+            .learn_from_edit("I recieve mail", "I receive mail", &storage)
+            .unwrap();
+        assert_eq!(learned.len(), 1);
+        assert_eq!(learned_explicit.len(), 1);
+        assert_eq!(learned[0].corrected, learned_explicit[0].corrected);
+
+        let (text, applied) = engine.apply_corrections("Recieve  mail,\nrecieve it");
+        assert_eq!(text, "Receive  mail,\nreceive it");
+        assert_eq!(applied[1].position, 2);
+
+        // whitespace tokenization treats unspaced text as one word, so the
+        // correction only ever matches that exact sentence
+        let learned = engine
+            .learn_from_edit("我们明天在见", "我们明天再见", &storage)
+            .unwrap();
+        assert_eq!(learned[0].original, "我们明天在见");
+        let (text, applied) = engine.apply_corrections("那就明天在见吧。");
+        assert_eq!(text, "那就明天在见吧。");
+        assert!(applied.is_empty());
+    }
+
+    #[test]
+    fn test_cjk_tokenizer_learns_single_character_correction() {
+        let storage = Storage::in_memory().unwrap();
+        let engine = LearningEngine::new().with_tokenizer(CjkTokenizer);
+
+        let learned = engine
+            .learn_from_edit("我们明天在见", "我们明天再见", &storage)
+            .unwrap();
+        assert_eq!(learned.len(), 1);
+        assert_eq!(learned[0].original, "在");
+        assert_eq!(learned[0].corrected, "再");
+
+        let (text, applied) = engine.apply_corrections("那就明天在见吧。");
+        assert_eq!(text, "那就明天再见吧。");
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].position, 4);
+        assert_eq!(
+            engine.revert_corrections(&text, &applied),
+            "那就明天在见吧。"
+        );
+    }
+
+    #[test]
+    fn test_cache_keeps_top_scoring_corrections() {
+        let storage = Storage::in_memory().unwrap();
+        storage.delete_all_corrections().unwrap();
+        let now = Utc::now();
+        // (original, occurrences, days since last learned)
+        let rows = [
+            ("aaa", 20, 0),   // confident and recent: kept
+            ("bbb", 20, 120), // confident but stale: score / 16
+            ("ccc", 4, 0),    // less confident, recent: kept
+            ("ddd", 20, 10),  // confident, fairly recent: kept
+            ("eee", 3, 45),   // barely confident and stale
+        ];
+        for (original, occurrences, days) in rows {
+            let mut correction = Correction::new(
+                original.to_string(),
+                original.to_uppercase(),
+                CorrectionSource::UserEdit,
+            );
+            correction.occurrences = occurrences;
+            correction.updated_at = now - chrono::Duration::days(days);
+            storage.save_correction(&correction).unwrap();
+        }
+
+        let engine = LearningEngine::new().with_max_cache_size(3);
+        engine.reload_from_storage(&storage).unwrap();
+
+        let mut kept: Vec<String> = engine
+            .get_all_corrections()
+            .into_iter()
+            .map(|(original, _, _)| original)
+            .collect();
+        kept.sort();
+        assert_eq!(kept, vec!["aaa", "ccc", "ddd"]);
+        assert_eq!(
+            engine.cache_occupancy(),
+            CacheOccupancy {
+                general: 3,
+                contextual: 0,
+                max: 3
+            }
+        );
+        // evicted corrections stay in storage
+        assert_eq!(storage.get_all_corrections().unwrap().len(), 5);
+
+        engine.set_max_cache_size(1);
+        assert_eq!(engine.get_correction("aaa"), Some("AAA".to_string()));
+        assert_eq!(engine.cache_size(), 1);
+    }
+
+    #[test]
+    fn test_learning_respects_cache_cap() {
+        let storage = Storage::in_memory().unwrap();
+        storage.delete_all_corrections().unwrap();
+        let engine = LearningEngine::new();
+        engine.set_min_confidence(0.0);
+        engine.set_max_cache_size(2);
+
+        engine
+            .learn_from_edit("teh recieve", "the receive", &storage)
+            .unwrap();
+        engine.learn_from_edit("wierd", "weird", &storage).unwrap();
+
+        let occupancy = engine.cache_occupancy();
+        assert_eq!(occupancy.general + occupancy.contextual, 2);
+    }
+
+    #[test]
+    fn test_code_tokens_are_left_alone() {
+        let storage = Storage::in_memory().unwrap();
+        let engine = LearningEngine::new();
+
+        engine
+            .learn_from_edit(
+                "we recieve data in recieveBuffer",
+                "we receive data in receiveBuffer",
+                &storage,
+            )
+            .unwrap();
+
+        let text = "recieve the recieveBuffer, `recieve` and recieve_buffer from src/recieve.rs then recieve()";
+        let (corrected, applied) = engine.apply_corrections_outside_code(text);
+        assert_eq!(
+            corrected,
+            "receive the recieveBuffer, `recieve` and recieve_buffer from src/recieve.rs then recieve()"
+        );
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].position, 0);
+
+        // multi-word backtick spans are skipped up to the closing backtick
+        assert_eq!(
+            engine
+                .apply_corrections_outside_code("`let x = recieve(); recieve` recieve")
+                .0,
+            "`let x = recieve(); recieve` receive"
+        );
+
+        // prose mode still fixes the identifier
+        assert_eq!(
+            engine.apply_corrections("the recieveBuffer").0,
+            "the receiveBuffer"
+        );
+    }
+
+    #[test]
+    fn test_is_code_like() {
+        for word in [
+            "recieveBuffer",
+            "RecieveBuffer",
+            "recieve_buffer",
+            "src/main.rs",
+            "https://example.com",
+            "config.toml",
+            "me@example.com",
+            "recieve()",
+        ] {
+            assert!(is_code_like(word), "{word}");
+        }
+        for word in [
+            "recieve",
+            "Recieve,",
+            "(recieve)",
+            "e.g.",
+            "3.5",
+            "NASA",
+            "end.",
+        ] {
+            assert!(!is_code_like(word), "{word}");
+        }
+    }
+
+    #[test]
+    fn test_homophone_corrections_depend_on_previous_word() {
+        let storage = Storage::in_memory().unwrap();
+        let engine = LearningEngine::new();
+
+        engine
+            .learn_from_edit("fix there code", "fix their code", &storage)
+            .unwrap();
+        engine
+            .learn_from_edit("go their now", "go there now", &storage)
+            .unwrap();
+
+        // each direction only applies after the word it was learned with
+        assert_eq!(
+            engine.apply_corrections("fix there code").0,
+            "fix their code"
+        );
+        assert_eq!(engine.apply_corrections("go their now").0, "go there now");
+        assert_eq!(engine.apply_corrections("go there now").0, "go there now");
+        assert_eq!(
+            engine.apply_corrections("their there was").0,
+            "their there was"
+        );
+
+        // the context survives a reload from storage
+        let reloaded = LearningEngine::from_storage(&storage).unwrap();
+        assert_eq!(
+            reloaded.apply_corrections("go there and fix there code").0,
+            "go there and fix their code"
+        );
+    }
+
+    #[test]
+    fn test_typo_learned_in_context_applies_everywhere() {
+        let storage = Storage::in_memory().unwrap();
+        let engine = LearningEngine::new();
+
+        engine
+            .learn_from_edit("I recieve mail", "I receive mail", &storage)
+            .unwrap();
+
+        let (corrected, applied) = engine.apply_corrections("we recieve it");
+        assert_eq!(corrected, "we receive it");
+        assert_eq!(applied.len(), 1);
+        assert!(
+            storage
+                .get_all_corrections()
+                .unwrap()
+                .iter()
+                .any(|c| c.original == "recieve" && c.context.as_deref() == Some("i"))
+        );
+    }
+
+    #[test]
+    fn test_align_joined_and_split_words() {
+        let original = vec!["work", "to", "gether", "alot"];
+        let edited = vec!["work", "together", "a", "lot"];
+
+        let alignments = align_word_indices(
+            &original,
+            &edited,
+            MIN_ALIGNMENT_SIMILARITY,
+            &WhitespaceTokenizer,
+        );
+
+        assert_eq!(
+            alignments,
+            vec![
+                Alignment::Word(0, 0),
+                Alignment::Joined(1, 1),
+                Alignment::Split(3, 2),
+            ]
+        );
+        assert!(is_joined("To", "gether,", "together,"));
+        assert!(!is_joined("to", "get", "together"));
+    }
+
+    #[test]
+    fn test_learns_joined_words() {
+        let storage = Storage::in_memory().unwrap();
+        let engine = LearningEngine::new();
+
+        for _ in 0..3 {
+            let learned = engine
+                .learn_from_edit(
+                    "let's work to gether on it.",
+                    "let's work together on it.",
+                    &storage,
+                )
+                .unwrap();
+            assert_eq!(learned.len(), 1);
+            assert_eq!(learned[0].original, "to gether");
+            assert_eq!(learned[0].corrected, "together");
+        }
+        // the parts aren't learned as corrections of their own
+        assert!(!engine.has_correction("to"));
+        assert!(!engine.has_correction("gether"));
+
+        let (text, applied) = engine.apply_corrections("To gether, we fixed it");
+        assert_eq!(text, "Together, we fixed it");
+        assert_eq!(applied[0].original, "To gether");
+        assert_eq!(
+            engine.revert_corrections(&text, &applied),
+            "To gether, we fixed it"
+        );
+
+        // legitimate phrases stay as they are
+        for phrase in [
+            "I went to get her",
+            "up to, gether",
+            "gether to",
+            "we went to the store",
+        ] {
+            assert_eq!(engine.apply_corrections(phrase).0, phrase);
+        }
+
+        // the learned join survives a reload from storage
+        let reloaded = LearningEngine::from_storage(&storage).unwrap();
+        assert_eq!(
+            reloaded.apply_corrections("all to gether now").0,
+            "all together now"
+        );
+    }
+
+    #[test]
+    fn test_learns_split_words() {
+        let storage = Storage::in_memory().unwrap();
+        let engine = LearningEngine::new();
+
+        for _ in 0..3 {
+            let learned = engine
+                .learn_from_edit("thanks alot!", "thanks a lot!", &storage)
+                .unwrap();
+            assert_eq!(learned.len(), 1);
+            assert_eq!(learned[0].original, "alot!");
+            assert_eq!(learned[0].corrected, "a lot!");
+        }
+
+        let input = "Alot of people said thanks alot, a lot";
+        let (text, applied) = engine.apply_corrections(input);
+        assert_eq!(text, "A lot of people said thanks a lot, a lot");
+        assert_eq!(applied.len(), 2);
+        assert_eq!(engine.revert_corrections(&text, &applied), input);
+
+        // words that merely resemble the split word are left alone
+        assert_eq!(engine.apply_corrections("allot a slot").0, "allot a slot");
+    }
+
+    #[test]
+    fn test_revert_mixed_joins_and_splits() {
+        let engine = LearningEngine::new();
+        {
+            let mut cache = engine.corrections.write();
+            for (original, corrected) in
+                [("to gether", "together"), ("alot", "a lot"), ("teh", "the")]
+            {
+                cache.insert(
+                    original.to_string(),
+                    CachedCorrection {
+                        corrected: corrected.to_string(),
+                        confidence: 0.95,
+                        updated_at: Utc::now(),
+                    },
+                );
+            }
+        }
+
+        let input = "alot of us to gether saw teh  cat to gether";
+        let (text, applied) = engine.apply_corrections(input);
+        assert_eq!(text, "a lot of us together saw the  cat together");
+        assert_eq!(
+            applied.iter().map(|c| c.position).collect::<Vec<_>>(),
+            vec![0, 3, 6, 8]
+        );
+        assert_eq!(engine.revert_corrections(&text, &applied), input);
+
+        // words inside code aren't joined
+        assert_eq!(
+            engine.apply_corrections_outside_code("to `gether`").0,
+            "to `gether`"
+        );
+    }
 }
@@ -6,17 +6,30 @@
 pub mod alignment;
 pub mod apps;
 pub mod audio;
+pub mod captions;
 pub mod contacts;
+pub mod dictation;
+pub mod edits;
 pub mod error;
 pub mod ffi;
+pub mod hallucination;
 pub mod learning;
+pub mod locale;
 pub mod macos_messages;
 pub mod metrics;
 pub mod migrations;
 pub mod modes;
+pub mod normalizer;
+pub mod numbers;
+pub mod profiles;
 pub mod providers;
+pub mod redaction;
 pub mod shortcuts;
+pub mod stats;
 pub mod storage;
+pub mod style;
+pub mod tokenizer;
+pub mod transforms;
 pub mod types;
 pub mod vad;
 pub mod voice_commands;
@@ -31,12 +44,22 @@ pub use ffi::*;
 /// Re-export the main engine components for convenience
 pub use alignment::{AlignmentResult, AlignmentStep, WordLabel, parse_alignment_steps};
 pub use apps::{AppRegistry, AppTracker};
-pub use audio::AudioCapture;
+pub use audio::{AudioCapture, OverflowPolicy};
 pub use contacts::ContactClassifier;
+pub use dictation::DictationProcessor;
+pub use hallucination::HallucinationFilter;
 pub use learning::LearningEngine;
+pub use locale::Locale;
 pub use macos_messages::MessagesDetector;
 pub use metrics::{MetricsCollector, SessionStats, UserStats};
 pub use modes::WritingModeEngine;
+pub use normalizer::TextNormalizer;
+pub use numbers::normalize_numbers;
 pub use providers::{CompletionProvider, TranscriptionProvider};
+pub use redaction::RedactionFilter;
 pub use shortcuts::ShortcutsEngine;
-pub use storage::Storage;
+pub use stats::Analytics;
+pub use storage::{Storage, StorageRecovery};
+pub use style::{enforce_locale_punctuation, enforce_style};
+pub use tokenizer::{CjkTokenizer, Tokenizer, WhitespaceTokenizer};
+pub use transforms::{TextTransform, TransformContext, TransformRegistry, TransformStage};
@@ -0,0 +1,122 @@
+//! Language-specific formatting
+//!
+//! The completion prompts and the deterministic style pass were written for
+//! English, so a Spanish "Formal" dictation came back with English capitalization
+//! and punctuation. A `Locale` picks the mode instructions for the language the user
+//! dictated in, taken from an explicit setting or the language the transcription
+//! provider detected. Languages without their own instructions use English.
+
+use crate::types::WritingMode;
+
+/// Language used for formatting instructions and punctuation rules
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Locale {
+    #[default]
+    English,
+    French,
+    Spanish,
+}
+
+impl Locale {
+    /// Parse an ISO 639-1 code with an optional region ("fr", "fr-CA", "es_MX") or
+    /// an English or native language name ("french", "Español"), as providers
+    /// report detected languages either way
+    pub fn parse(code: &str) -> Option<Self> {
+        let language = code
+            .trim()
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        match language.as_str() {
+            "en" | "english" => Some(Self::English),
+            "fr" | "french" | "français" | "francais" => Some(Self::French),
+            "es" | "spanish" | "español" | "espanol" => Some(Self::Spanish),
+            _ => None,
+        }
+    }
+
+    /// Locale for a detected language, English if it's missing or unsupported
+    pub fn from_detected(language: Option<&str>) -> Self {
+        language.and_then(Self::parse).unwrap_or_default()
+    }
+
+    /// ISO 639-1 code, also usable as a transcription language hint
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::French => "fr",
+            Self::Spanish => "es",
+        }
+    }
+
+    /// Completion prompt instructions for a writing mode in this language
+    pub fn prompt_modifier(&self, mode: WritingMode) -> &'static str {
+        match (self, mode) {
+            (Self::English, mode) => mode.prompt_modifier(),
+            (Self::French, WritingMode::Formal) => {
+                "Reformat in formal, professional French. Use \"vous\", complete sentences and polished vocabulary, and write spoken forms like \"ouais\", \"chais pas\" or a dropped \"ne\" properly. Follow French typography: capitalize only the start of sentences and proper nouns (not days, months or nationalities), put a space before ? ! : ; and use « » quotes. Keep the text in French, never translate it. Output EXACTLY as it would be typed—nothing more, nothing else."
+            }
+            (Self::French, WritingMode::Casual) => {
+                "Reformat in friendly, conversational French. Keep \"tu\" if the speaker uses it, natural phrasing and a warm tone, and preserve the intended meaning exactly. Follow French typography: a space before ? ! : ; and capitals only at the start of sentences and on proper nouns. Keep the text in French, never translate it. Output EXACTLY as it would be typed—do NOT add commentary, responses, or anything beyond the reformatted text."
+            }
+            (Self::French, WritingMode::VeryCasual) => {
+                "Reformat in casual French texting style. Use lowercase and common abbreviations like \"stp\", \"jsp\", \"mdr\". Keep it brief and informal like a text to a close friend. Keep the text in French, never translate it. Output EXACTLY as it would be typed—nothing else."
+            }
+            (Self::French, WritingMode::Excited) => {
+                "Reformat in enthusiastic, warm French. Add exclamation marks where appropriate, with a space before them as French typography requires, while preserving the intended meaning. Keep the text in French, never translate it. Output EXACTLY as it would be typed—nothing more."
+            }
+            (Self::Spanish, WritingMode::Formal) => {
+                "Reformat in formal, professional Spanish. Use \"usted\", complete sentences and polished vocabulary, and write colloquial forms like \"pa'\" or \"tá\" in full. Follow Spanish conventions: open questions and exclamations with ¿ and ¡, and capitalize only the start of sentences and proper nouns (not days, months or nationalities). Keep the text in Spanish, never translate it. Output EXACTLY as it would be typed—nothing more, nothing else."
+            }
+            (Self::Spanish, WritingMode::Casual) => {
+                "Reformat in friendly, conversational Spanish. Keep \"tú\" if the speaker uses it, natural phrasing and a warm tone, and preserve the intended meaning exactly. Open questions and exclamations with ¿ and ¡. Keep the text in Spanish, never translate it. Output EXACTLY as it would be typed—do NOT add commentary, responses, or anything beyond the reformatted text."
+            }
+            (Self::Spanish, WritingMode::VeryCasual) => {
+                "Reformat in casual Spanish texting style. Use lowercase and common abbreviations like \"q\", \"xq\", \"tmb\". Keep it brief and informal like a text to a close friend. Keep the text in Spanish, never translate it. Output EXACTLY as it would be typed—nothing else."
+            }
+            (Self::Spanish, WritingMode::Excited) => {
+                "Reformat in enthusiastic, warm Spanish. Add exclamations where appropriate, opened with ¡, while preserving the intended meaning. Keep the text in Spanish, never translate it. Output EXACTLY as it would be typed—nothing more."
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_codes_and_names() {
+        assert_eq!(Locale::parse("fr"), Some(Locale::French));
+        assert_eq!(Locale::parse("fr-CA"), Some(Locale::French));
+        assert_eq!(Locale::parse("es_MX"), Some(Locale::Spanish));
+        assert_eq!(Locale::parse("Spanish"), Some(Locale::Spanish));
+        assert_eq!(Locale::parse("english"), Some(Locale::English));
+        assert_eq!(Locale::parse("de"), None);
+
+        assert_eq!(Locale::from_detected(Some("de")), Locale::English);
+        assert_eq!(Locale::from_detected(None), Locale::English);
+        assert_eq!(Locale::from_detected(Some("french")), Locale::French);
+    }
+
+    #[test]
+    fn test_prompt_modifier_depends_on_locale() {
+        for &mode in WritingMode::all() {
+            assert_eq!(
+                Locale::English.prompt_modifier(mode),
+                mode.prompt_modifier()
+            );
+            let french = Locale::French.prompt_modifier(mode);
+            assert_ne!(french, Locale::English.prompt_modifier(mode));
+            assert!(french.contains("French"));
+            assert!(Locale::Spanish.prompt_modifier(mode).contains("Spanish"));
+        }
+        // English slang examples don't leak into other languages
+        assert!(
+            !Locale::French
+                .prompt_modifier(WritingMode::Formal)
+                .contains("gonna")
+        );
+    }
+}
@@ -16,6 +16,30 @@ const MIGRATIONS: &[(&str, &str)] = &[
         "002_add_edit_analytics.sql",
         include_str!("../migrations/002_add_edit_analytics.sql"),
     ),
+    (
+        "003_add_redaction_terms.sql",
+        include_str!("../migrations/003_add_redaction_terms.sql"),
+    ),
+    (
+        "004_add_app_model_overrides.sql",
+        include_str!("../migrations/004_add_app_model_overrides.sql"),
+    ),
+    (
+        "005_add_pending_transcriptions.sql",
+        include_str!("../migrations/005_add_pending_transcriptions.sql"),
+    ),
+    (
+        "006_add_shortcut_last_used.sql",
+        include_str!("../migrations/006_add_shortcut_last_used.sql"),
+    ),
+    (
+        "007_add_correction_context.sql",
+        include_str!("../migrations/007_add_correction_context.sql"),
+    ),
+    (
+        "008_add_vocabulary.sql",
+        include_str!("../migrations/008_add_vocabulary.sql"),
+    ),
 ];
 
 /// Run all pending migrations on the database
@@ -58,6 +82,11 @@ pub fn run_migrations(conn: &Connection) -> Result<usize, rusqlite::Error> {
                 applied_count += 1;
             }
             Err(e) => {
+                // A migration that failed inside BEGIN/COMMIT leaves its transaction open
+                if !conn.is_autocommit() {
+                    conn.execute_batch("ROLLBACK")?;
+                }
+
                 // Some migrations might have ALTER TABLE statements that fail
                 // if the column already exists. We handle this gracefully.
                 let err_str = e.to_string();
@@ -146,6 +175,9 @@ mod tests {
         assert!(tables.contains(&"shortcuts".to_string()));
         assert!(tables.contains(&"edit_analytics".to_string()));
         assert!(tables.contains(&"learned_words_sessions".to_string()));
+        assert!(tables.contains(&"redaction_terms".to_string()));
+        assert!(tables.contains(&"app_model_overrides".to_string()));
+        assert!(tables.contains(&"pending_transcriptions".to_string()));
         assert!(tables.contains(&"_migrations".to_string()));
     }
 
@@ -157,5 +189,48 @@ mod tests {
         let applied = get_applied_migrations(&conn).unwrap();
         assert!(applied.contains(&"001_initial_schema.sql".to_string()));
         assert!(applied.contains(&"002_add_edit_analytics.sql".to_string()));
+        assert!(applied.contains(&"003_add_redaction_terms.sql".to_string()));
+        assert!(applied.contains(&"004_add_app_model_overrides.sql".to_string()));
+        assert!(applied.contains(&"005_add_pending_transcriptions.sql".to_string()));
+        assert!(applied.contains(&"006_add_shortcut_last_used.sql".to_string()));
+        assert!(applied.contains(&"007_add_correction_context.sql".to_string()));
+        assert!(applied.contains(&"008_add_vocabulary.sql".to_string()));
+    }
+
+    #[test]
+    fn test_correction_context_keeps_observed_source() {
+        // Bring a database up to the migration before the rebuild
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE _migrations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                applied_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )",
+        )
+        .unwrap();
+        for (name, sql) in &MIGRATIONS[..6] {
+            conn.execute_batch(sql).unwrap();
+            conn.execute("INSERT INTO _migrations (name) VALUES (?1)", [name])
+                .unwrap();
+        }
+        conn.execute_batch(
+            "INSERT INTO corrections (id, original, corrected, source, created_at, updated_at, observed_source)
+             VALUES ('1', 'teh', 'the', 'user_edit', 'now', 'now', 'teh')",
+        )
+        .unwrap();
+
+        run_migrations(&conn).unwrap();
+
+        let (context, observed): (String, Option<String>) = conn
+            .query_row(
+                "SELECT context, observed_source FROM corrections WHERE id = '1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(context, "");
+        assert_eq!(observed.as_deref(), Some("teh"));
+        assert!(conn.is_autocommit());
     }
 }
@@ -6,35 +6,44 @@
 use std::collections::HashMap;
 
 use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 use crate::error::Result;
+use crate::providers::CompletionRequest;
 use crate::storage::Storage;
+use crate::types::AppModelOverride;
 
 // Re-export WritingMode from types for convenience
 pub use crate::types::WritingMode;
 
 /// Engine for managing writing modes per app
+///
+/// Caches use interior mutability so the engine can be shared across threads;
+/// lookups only take a read lock, and the write lock is taken only to insert.
 pub struct WritingModeEngine {
     /// Default mode when no app-specific mode is set
-    default_mode: WritingMode,
+    default_mode: RwLock<WritingMode>,
     /// In-memory cache of app modes
-    app_modes: HashMap<String, WritingMode>,
+    app_modes: RwLock<HashMap<String, WritingMode>>,
+    /// In-memory cache of per-app completion provider/model overrides
+    model_overrides: RwLock<HashMap<String, AppModelOverride>>,
 }
 
 impl WritingModeEngine {
     /// Create a new engine with the given default mode
     pub fn new(default_mode: WritingMode) -> Self {
         Self {
-            default_mode,
-            app_modes: HashMap::new(),
+            default_mode: RwLock::new(default_mode),
+            app_modes: RwLock::new(HashMap::new()),
+            model_overrides: RwLock::new(HashMap::new()),
         }
     }
 
-    /// Create engine and load app modes from storage
-    pub fn from_storage(_storage: &Storage, default_mode: WritingMode) -> Result<Self> {
-        let engine = Self::new(default_mode);
+    /// Create engine with the saved default mode, falling back to `default_mode`
+    pub fn from_storage(storage: &Storage, default_mode: WritingMode) -> Result<Self> {
+        let engine = Self::new(storage.get_default_mode()?.unwrap_or(default_mode));
         // load modes would need a get_all_app_modes method
         // for now we lazily load on demand
         Ok(engine)
@@ -43,35 +52,36 @@ impl WritingModeEngine {
     /// Get the writing mode for an app
     pub fn get_mode(&self, app_name: &str) -> WritingMode {
         self.app_modes
+            .read()
             .get(app_name)
             .copied()
-            .unwrap_or(self.default_mode)
+            .unwrap_or_else(|| self.default_mode())
     }
 
     /// Get mode for app, loading from storage if not cached
-    pub fn get_mode_with_storage(&mut self, app_name: &str, storage: &Storage) -> WritingMode {
-        if let Some(&mode) = self.app_modes.get(app_name) {
+    pub fn get_mode_with_storage(&self, app_name: &str, storage: &Storage) -> WritingMode {
+        if let Some(&mode) = self.app_modes.read().get(app_name) {
             return mode;
         }
 
         // try loading from storage
         if let Ok(Some(mode)) = storage.get_app_mode(app_name) {
-            self.app_modes.insert(app_name.to_string(), mode);
+            self.app_modes.write().insert(app_name.to_string(), mode);
             return mode;
         }
 
-        self.default_mode
+        self.default_mode()
     }
 
     /// Set the writing mode for an app
-    pub fn set_mode(&mut self, app_name: &str, mode: WritingMode) {
+    pub fn set_mode(&self, app_name: &str, mode: WritingMode) {
         debug!("Setting mode for {} to {:?}", app_name, mode);
-        self.app_modes.insert(app_name.to_string(), mode);
+        self.app_modes.write().insert(app_name.to_string(), mode);
     }
 
     /// Set mode and persist to storage
     pub fn set_mode_with_storage(
-        &mut self,
+        &self,
         app_name: &str,
         mode: WritingMode,
         storage: &Storage,
@@ -83,25 +93,120 @@ impl WritingModeEngine {
 
     /// Get the default mode
     pub fn default_mode(&self) -> WritingMode {
-        self.default_mode
+        *self.default_mode.read()
     }
 
     /// Set the default mode
-    pub fn set_default_mode(&mut self, mode: WritingMode) {
-        self.default_mode = mode;
+    pub fn set_default_mode(&self, mode: WritingMode) {
+        *self.default_mode.write() = mode;
+    }
+
+    /// Set the default mode and persist it
+    pub fn set_default_mode_with_storage(
+        &self,
+        mode: WritingMode,
+        storage: &Storage,
+    ) -> Result<()> {
+        self.set_default_mode(mode);
+        storage.set_default_mode(mode)
     }
 
     /// Clear the mode for an app (reverts to default)
-    pub fn clear_mode(&mut self, app_name: &str) {
-        self.app_modes.remove(app_name);
+    pub fn clear_mode(&self, app_name: &str) {
+        self.app_modes.write().remove(app_name);
     }
 
     /// Get all app-specific mode overrides
-    pub fn get_all_overrides(&self) -> &HashMap<String, WritingMode> {
-        &self.app_modes
+    pub fn get_all_overrides(&self) -> HashMap<String, WritingMode> {
+        self.app_modes.read().clone()
+    }
+
+    /// Get the completion provider/model override for an app
+    pub fn get_model_override(&self, app_name: &str) -> Option<AppModelOverride> {
+        self.model_overrides.read().get(app_name).cloned()
+    }
+
+    /// Get model override for app, loading from storage if not cached
+    pub fn get_model_override_with_storage(
+        &self,
+        app_name: &str,
+        storage: &Storage,
+    ) -> Option<AppModelOverride> {
+        if let Some(model_override) = self.get_model_override(app_name) {
+            return Some(model_override);
+        }
+
+        if let Ok(Some(model_override)) = storage.get_app_model_override(app_name) {
+            self.model_overrides
+                .write()
+                .insert(app_name.to_string(), model_override.clone());
+            return Some(model_override);
+        }
+
+        None
+    }
+
+    /// Set the completion provider/model override for an app
+    pub fn set_model_override(&self, app_name: &str, model_override: AppModelOverride) {
+        debug!(
+            "Setting model override for {} to {:?}",
+            app_name, model_override
+        );
+        self.model_overrides
+            .write()
+            .insert(app_name.to_string(), model_override);
+    }
+
+    /// Set model override and persist to storage
+    pub fn set_model_override_with_storage(
+        &self,
+        app_name: &str,
+        model_override: AppModelOverride,
+        storage: &Storage,
+    ) -> Result<()> {
+        storage.save_app_model_override(app_name, &model_override)?;
+        self.set_model_override(app_name, model_override);
+        Ok(())
+    }
+
+    /// Clear the model override for an app (reverts to the global provider) and persist
+    pub fn clear_model_override_with_storage(
+        &self,
+        app_name: &str,
+        storage: &Storage,
+    ) -> Result<()> {
+        self.model_overrides.write().remove(app_name);
+        storage.delete_app_model_override(app_name)?;
+        Ok(())
+    }
+
+    /// Build a completion request for an app, applying its model override if one is set
+    pub fn completion_request(
+        &self,
+        text: String,
+        mode: WritingMode,
+        app_name: Option<&str>,
+        storage: &Storage,
+    ) -> CompletionRequest {
+        let mut request = CompletionRequest::new(text, mode);
+
+        if let Some(app_name) = app_name {
+            request = request.with_app_context(app_name);
+            if let Some(model) = self
+                .get_model_override_with_storage(app_name, storage)
+                .and_then(|o| o.model)
+            {
+                request = request.with_model(model);
+            }
+        }
+
+        request
     }
 }
 
+/// Age at which a timestamped style sample counts half as much as the newest one
+pub const STYLE_SAMPLE_HALF_LIFE_DAYS: f64 = 14.0;
+
 /// Style analyzer for learning user preferences from their edits
 pub struct StyleAnalyzer;
 
@@ -141,23 +246,57 @@ impl StyleAnalyzer {
     }
 
     /// Analyze multiple samples and return the most common style
+    ///
+    /// Ties go to the mode listed first in `WritingMode::all()`, i.e. the more formal
+    /// one (Formal, Casual, VeryCasual, Excited), so the result never depends on
+    /// iteration order.
     pub fn analyze_samples(samples: &[String]) -> WritingMode {
-        if samples.is_empty() {
+        Self::most_weighted(
+            samples
+                .iter()
+                .map(|sample| (Self::analyze_style(sample), 1.0)),
+        )
+    }
+
+    /// Like `analyze_samples`, but recent samples count more
+    ///
+    /// A sample's weight halves every `STYLE_SAMPLE_HALF_LIFE_DAYS` it is older than
+    /// the newest sample. Ties are broken the same way as in `analyze_samples`.
+    pub fn analyze_timestamped_samples(samples: &[(String, DateTime<Utc>)]) -> WritingMode {
+        let Some(newest) = samples.iter().map(|(_, at)| *at).max() else {
             return WritingMode::default();
-        }
-
-        let mut counts: HashMap<WritingMode, usize> = HashMap::new();
+        };
 
-        for sample in samples {
-            let mode = Self::analyze_style(sample);
-            *counts.entry(mode).or_insert(0) += 1;
+        Self::most_weighted(samples.iter().map(|(sample, at)| {
+            let age_days = (newest - *at).num_seconds() as f64 / 86_400.0;
+            let weight = 0.5f64.powf(age_days / STYLE_SAMPLE_HALF_LIFE_DAYS);
+            (Self::analyze_style(sample), weight)
+        }))
+    }
+
+    /// The mode with the highest total weight, the more formal one on a tie
+    fn most_weighted(votes: impl Iterator<Item = (WritingMode, f64)>) -> WritingMode {
+        let modes = WritingMode::all();
+        let mut totals = vec![0.0; modes.len()];
+        let mut any = false;
+        for (mode, weight) in votes {
+            if let Some(i) = modes.iter().position(|m| *m == mode) {
+                totals[i] += weight;
+                any = true;
+            }
+        }
+        if !any {
+            return WritingMode::default();
         }
 
-        counts
-            .into_iter()
-            .max_by_key(|(_, count)| *count)
-            .map(|(mode, _)| mode)
-            .unwrap_or_default()
+        // strictly greater, so the earliest mode keeps a tie
+        let mut best = 0;
+        for (i, total) in totals.iter().enumerate() {
+            if *total > totals[best] {
+                best = i;
+            }
+        }
+        modes[best]
     }
 }
 
@@ -356,7 +495,7 @@ mod tests {
 
     #[test]
     fn test_engine() {
-        let mut engine = WritingModeEngine::new(WritingMode::Casual);
+        let engine = WritingModeEngine::new(WritingMode::Casual);
 
         assert_eq!(engine.get_mode("Slack"), WritingMode::Casual);
 
@@ -367,6 +506,135 @@ mod tests {
         assert_eq!(engine.get_mode("Mail"), WritingMode::Casual);
     }
 
+    #[test]
+    fn test_completion_request_uses_app_model_override() {
+        let storage = Storage::in_memory().unwrap();
+        let engine = WritingModeEngine::new(WritingMode::Casual);
+
+        engine
+            .set_model_override_with_storage(
+                "Mail",
+                AppModelOverride {
+                    provider: None,
+                    model: Some("gpt-4o".to_string()),
+                },
+                &storage,
+            )
+            .unwrap();
+
+        let request = engine.completion_request(
+            "hi".to_string(),
+            WritingMode::Formal,
+            Some("Mail"),
+            &storage,
+        );
+        assert_eq!(request.model, Some("gpt-4o".to_string()));
+        assert_eq!(request.app_context, Some("Mail".to_string()));
+        assert_eq!(request.mode, WritingMode::Formal);
+
+        // no override falls back to the provider's configured model
+        let request = engine.completion_request(
+            "hi".to_string(),
+            WritingMode::Casual,
+            Some("Slack"),
+            &storage,
+        );
+        assert_eq!(request.model, None);
+
+        let request =
+            engine.completion_request("hi".to_string(), WritingMode::Casual, None, &storage);
+        assert_eq!(request.model, None);
+        assert_eq!(request.app_context, None);
+    }
+
+    #[test]
+    fn test_default_mode_persists() {
+        let storage = Storage::in_memory().unwrap();
+
+        let engine = WritingModeEngine::from_storage(&storage, WritingMode::Casual).unwrap();
+        assert_eq!(engine.default_mode(), WritingMode::Casual);
+
+        engine
+            .set_default_mode_with_storage(WritingMode::Excited, &storage)
+            .unwrap();
+
+        let reloaded = WritingModeEngine::from_storage(&storage, WritingMode::Casual).unwrap();
+        assert_eq!(reloaded.default_mode(), WritingMode::Excited);
+        assert_eq!(reloaded.get_mode("Unknown App"), WritingMode::Excited);
+    }
+
+    #[test]
+    fn test_model_override_loaded_from_storage() {
+        let storage = Storage::in_memory().unwrap();
+        storage
+            .save_app_model_override(
+                "Slack",
+                &AppModelOverride {
+                    provider: Some("gemini".to_string()),
+                    model: Some("gemini-2.5-flash-lite".to_string()),
+                },
+            )
+            .unwrap();
+
+        let engine = WritingModeEngine::new(WritingMode::Casual);
+        assert!(engine.get_model_override("Slack").is_none());
+
+        let model_override = engine
+            .get_model_override_with_storage("Slack", &storage)
+            .unwrap();
+        assert_eq!(model_override.provider, Some("gemini".to_string()));
+        assert!(engine.get_model_override("Slack").is_some());
+
+        engine
+            .clear_model_override_with_storage("Slack", &storage)
+            .unwrap();
+        assert!(
+            engine
+                .get_model_override_with_storage("Slack", &storage)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_engine_concurrent_get_mode() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        storage.save_app_mode("Mail", WritingMode::Formal).unwrap();
+
+        let engine = Arc::new(WritingModeEngine::new(WritingMode::Casual));
+        engine.set_mode("Slack", WritingMode::VeryCasual);
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let engine = Arc::clone(&engine);
+                let storage = Arc::clone(&storage);
+                thread::spawn(move || {
+                    for j in 0..500 {
+                        assert_eq!(engine.get_mode("Slack"), WritingMode::VeryCasual);
+                        assert_eq!(
+                            engine.get_mode_with_storage("Mail", &storage),
+                            WritingMode::Formal
+                        );
+                        assert_eq!(engine.get_mode("Unknown"), WritingMode::Casual);
+                        // writers interleave with the readers
+                        if j % 100 == 0 {
+                            engine.set_mode(&format!("App{i}"), WritingMode::Excited);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(engine.get_mode("Mail"), WritingMode::Formal);
+        assert_eq!(engine.get_all_overrides().len(), 2 + 8);
+    }
+
     #[test]
     fn test_style_learner() {
         let mut learner = StyleLearner::new();
@@ -552,6 +820,51 @@ mod tests {
         assert_eq!(result, WritingMode::VeryCasual);
     }
 
+    #[test]
+    fn test_analyze_samples_tie_prefers_more_formal_mode() {
+        // one VeryCasual and one Excited sample, in both orders
+        let samples = vec!["hello how r u".to_string(), "Wow!! Amazing!!".to_string()];
+        let reversed: Vec<String> = samples.iter().rev().cloned().collect();
+
+        for _ in 0..20 {
+            assert_eq!(
+                StyleAnalyzer::analyze_samples(&samples),
+                WritingMode::VeryCasual
+            );
+            assert_eq!(
+                StyleAnalyzer::analyze_samples(&reversed),
+                WritingMode::VeryCasual
+            );
+        }
+    }
+
+    #[test]
+    fn test_timestamped_samples_favor_recent_style() {
+        let now = Utc::now();
+        let days_ago = |days| now - chrono::Duration::days(days);
+        let samples = vec![
+            ("hello how r u".to_string(), days_ago(60)),
+            ("sure thing".to_string(), days_ago(45)),
+            ("Wow!! Amazing!!".to_string(), now),
+        ];
+
+        // two old casual samples lose to one recent excited one
+        assert_eq!(
+            StyleAnalyzer::analyze_timestamped_samples(&samples),
+            WritingMode::Excited
+        );
+        assert_eq!(
+            StyleAnalyzer::analyze_samples(
+                &samples.iter().map(|(s, _)| s.clone()).collect::<Vec<_>>()
+            ),
+            WritingMode::VeryCasual
+        );
+        assert_eq!(
+            StyleAnalyzer::analyze_timestamped_samples(&[]),
+            WritingMode::default()
+        );
+    }
+
     #[test]
     fn test_engine_default_mode() {
         let engine = WritingModeEngine::new(WritingMode::Formal);
@@ -563,7 +876,7 @@ mod tests {
 
     #[test]
     fn test_engine_set_default_mode() {
-        let mut engine = WritingModeEngine::new(WritingMode::Casual);
+        let engine = WritingModeEngine::new(WritingMode::Casual);
         assert_eq!(engine.default_mode(), WritingMode::Casual);
 
         engine.set_default_mode(WritingMode::Formal);
@@ -575,7 +888,7 @@ mod tests {
 
     #[test]
     fn test_engine_get_all_overrides() {
-        let mut engine = WritingModeEngine::new(WritingMode::Casual);
+        let engine = WritingModeEngine::new(WritingMode::Casual);
         engine.set_mode("App1", WritingMode::Formal);
         engine.set_mode("App2", WritingMode::Excited);
 
@@ -587,7 +900,7 @@ mod tests {
 
     #[test]
     fn test_engine_clear_mode() {
-        let mut engine = WritingModeEngine::new(WritingMode::Casual);
+        let engine = WritingModeEngine::new(WritingMode::Casual);
         engine.set_mode("Mail", WritingMode::Formal);
         assert_eq!(engine.get_mode("Mail"), WritingMode::Formal);
 
@@ -597,7 +910,7 @@ mod tests {
 
     #[test]
     fn test_engine_clear_nonexistent_mode() {
-        let mut engine = WritingModeEngine::new(WritingMode::Casual);
+        let engine = WritingModeEngine::new(WritingMode::Casual);
         // clearing a mode that doesn't exist should be fine
         engine.clear_mode("NonexistentApp");
         assert_eq!(engine.get_mode("NonexistentApp"), WritingMode::Casual);
@@ -825,7 +1138,7 @@ mod tests {
 
     #[test]
     fn test_engine_same_app_multiple_sets() {
-        let mut engine = WritingModeEngine::new(WritingMode::Casual);
+        let engine = WritingModeEngine::new(WritingMode::Casual);
 
         engine.set_mode("App", WritingMode::Formal);
         assert_eq!(engine.get_mode("App"), WritingMode::Formal);
@@ -0,0 +1,245 @@
+//! Final-output text normalization
+//!
+//! Cleans up text returned by completion providers before it is pasted:
+//! collapses whitespace runs, straightens smart quotes, and trims trailing spaces.
+//! Each rule can be toggled independently; newlines are always preserved.
+
+/// Normalizer with independently toggleable rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextNormalizer {
+    /// Collapse runs of spaces, tabs and non-breaking spaces into a single space.
+    /// A lone non-breaking space is kept, as it was put there on purpose (e.g. French
+    /// spacing before "?").
+    pub collapse_whitespace: bool,
+    /// Convert curly quotes and apostrophes to straight ones
+    pub straighten_quotes: bool,
+    /// Remove trailing spaces at the end of each line
+    pub trim_trailing: bool,
+}
+
+impl TextNormalizer {
+    /// Create a normalizer with all rules enabled
+    pub fn new() -> Self {
+        Self {
+            collapse_whitespace: true,
+            straighten_quotes: true,
+            trim_trailing: true,
+        }
+    }
+
+    /// Create a normalizer with all rules disabled
+    pub fn disabled() -> Self {
+        Self {
+            collapse_whitespace: false,
+            straighten_quotes: false,
+            trim_trailing: false,
+        }
+    }
+
+    /// Enable or disable whitespace collapsing
+    pub fn with_collapse_whitespace(mut self, enabled: bool) -> Self {
+        self.collapse_whitespace = enabled;
+        self
+    }
+
+    /// Enable or disable smart quote straightening
+    pub fn with_straighten_quotes(mut self, enabled: bool) -> Self {
+        self.straighten_quotes = enabled;
+        self
+    }
+
+    /// Enable or disable trailing space trimming
+    pub fn with_trim_trailing(mut self, enabled: bool) -> Self {
+        self.trim_trailing = enabled;
+        self
+    }
+
+    /// Check whether any rule is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.collapse_whitespace || self.straighten_quotes || self.trim_trailing
+    }
+
+    /// Apply all enabled rules, line by line so newlines are kept intact
+    pub fn normalize(&self, text: &str) -> String {
+        if !self.is_enabled() {
+            return text.to_string();
+        }
+
+        let mut result = String::with_capacity(text.len());
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                result.push('\n');
+            }
+
+            let mut line = if self.straighten_quotes {
+                straighten_quotes(line)
+            } else {
+                line.to_string()
+            };
+            if self.collapse_whitespace {
+                line = collapse_whitespace(&line);
+            }
+            if self.trim_trailing {
+                line = trim_trailing(&line).to_string();
+            }
+            result.push_str(&line);
+        }
+        result
+    }
+}
+
+impl Default for TextNormalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Horizontal whitespace: anything whitespace except line breaks
+#[inline]
+fn is_horizontal_space(c: char) -> bool {
+    c.is_whitespace() && c != '\n' && c != '\r'
+}
+
+/// Replace curly quotes and apostrophes with their ASCII equivalents
+fn straighten_quotes(line: &str) -> String {
+    line.chars()
+        .map(|c| match c {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => '\'',
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' => '"',
+            _ => c,
+        })
+        .collect()
+}
+
+/// Non-breaking spaces kept when they stand alone
+fn is_no_break_space(c: char) -> bool {
+    matches!(c, '\u{00A0}' | '\u{2007}' | '\u{202F}')
+}
+
+/// Collapse runs of horizontal whitespace after the first word into a single space
+/// Leading indentation and lone non-breaking spaces are kept as-is
+fn collapse_whitespace(line: &str) -> String {
+    let content_start = line
+        .find(|c: char| !is_horizontal_space(c))
+        .unwrap_or(line.len());
+    let (indent, content) = line.split_at(content_start);
+
+    let mut result = String::with_capacity(line.len());
+    result.push_str(indent);
+
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !is_horizontal_space(c) {
+            result.push(c);
+            continue;
+        }
+        let mut run = 1;
+        while chars.next_if(|&n| is_horizontal_space(n)).is_some() {
+            run += 1;
+        }
+        result.push(if run == 1 && is_no_break_space(c) {
+            c
+        } else {
+            ' '
+        });
+    }
+    result
+}
+
+/// Remove trailing horizontal whitespace, keeping a carriage return if present
+fn trim_trailing(line: &str) -> String {
+    match line.strip_suffix('\r') {
+        Some(stripped) => format!("{}\r", stripped.trim_end_matches(is_horizontal_space)),
+        None => line.trim_end_matches(is_horizontal_space).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collapse_whitespace_only() {
+        let normalizer = TextNormalizer::disabled().with_collapse_whitespace(true);
+
+        assert_eq!(normalizer.normalize("hello   world"), "hello world");
+        assert_eq!(normalizer.normalize("hello\t\u{00A0} world"), "hello world");
+        // a lone no-break space is deliberate typography
+        assert_eq!(
+            normalizer.normalize("Tu viens\u{202F}?"),
+            "Tu viens\u{202F}?"
+        );
+        // trailing run collapses to one space but isn't trimmed
+        assert_eq!(normalizer.normalize("end  "), "end ");
+    }
+
+    #[test]
+    fn test_collapse_whitespace_keeps_indentation() {
+        let normalizer = TextNormalizer::disabled().with_collapse_whitespace(true);
+
+        assert_eq!(normalizer.normalize("    let  x = 1;"), "    let x = 1;");
+    }
+
+    #[test]
+    fn test_straighten_quotes_only() {
+        let normalizer = TextNormalizer::disabled().with_straighten_quotes(true);
+
+        assert_eq!(
+            normalizer.normalize("\u{201C}Don\u{2019}t,\u{201D} she said"),
+            "\"Don't,\" she said"
+        );
+        // whitespace untouched
+        assert_eq!(normalizer.normalize("a  b "), "a  b ");
+    }
+
+    #[test]
+    fn test_trim_trailing_only() {
+        let normalizer = TextNormalizer::disabled().with_trim_trailing(true);
+
+        assert_eq!(normalizer.normalize("hello  \nworld\t"), "hello\nworld");
+        assert_eq!(normalizer.normalize("a  b"), "a  b");
+        assert_eq!(normalizer.normalize("line \r\nnext"), "line\r\nnext");
+    }
+
+    #[test]
+    fn test_rules_compose() {
+        let normalizer = TextNormalizer::new();
+
+        let input = "\u{201C}Hi\u{201D}   there\u{00A0}\u{00A0}\nit\u{2019}s   me  ";
+        assert_eq!(normalizer.normalize(input), "\"Hi\" there\nit's me");
+    }
+
+    #[test]
+    fn test_preserves_newlines() {
+        let normalizer = TextNormalizer::new();
+
+        assert_eq!(
+            normalizer.normalize("first  line\n\nsecond   line\n"),
+            "first line\n\nsecond line\n"
+        );
+    }
+
+    #[test]
+    fn test_keep_smart_quotes_for_prose() {
+        let normalizer = TextNormalizer::new().with_straighten_quotes(false);
+
+        assert_eq!(
+            normalizer.normalize("\u{201C}Hello\u{201D}  world "),
+            "\u{201C}Hello\u{201D} world"
+        );
+    }
+
+    #[test]
+    fn test_disabled_is_identity() {
+        let normalizer = TextNormalizer::disabled();
+        assert!(!normalizer.is_enabled());
+
+        let input = "\u{201C}a\u{201D}   b  \n";
+        assert_eq!(normalizer.normalize(input), input);
+    }
+
+    #[test]
+    fn test_empty_text() {
+        assert_eq!(TextNormalizer::new().normalize(""), "");
+    }
+}
@@ -0,0 +1,614 @@
+//! Deterministic number and unit normalization per writing mode
+//!
+//! Dictated numbers come back from transcription in whatever form the model picked:
+//! "twenty five percent", "25 percent", "25%". `normalize_numbers` rewrites them
+//! consistently for the writing mode. Formal text spells out small numbers and
+//! writes "percent"; the other modes use digits and "%". Decimals ("two point
+//! five"), currency ("five dollars") and ranges ("three to five") are handled.
+//! A lone number word with no numeric context ("one of them") is left alone, and
+//! so are years and anything else written as a large number in digits.
+
+use crate::tokenizer::word_spans;
+use crate::types::WritingMode;
+
+/// Spelled-out forms of the numbers Formal mode writes as words
+const SMALL_NUMBERS: [&str; 10] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+const TEENS: [&str; 10] = [
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const TENS: [&str; 8] = [
+    "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+/// Words before a number that make it a label ("version 3"), which keeps its digits
+const LABELS: &[&str] = &[
+    "version", "chapter", "page", "step", "room", "number", "no", "level", "part", "section",
+    "figure", "table", "item", "episode", "season",
+];
+
+/// Words after a number that make it a time ("3 pm"), which keeps its digits
+const TIME_SUFFIXES: &[&str] = &["am", "pm", "a.m", "p.m", "o'clock"];
+
+/// How numbers are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberStyle {
+    /// Spell out whole numbers below this ("3 cats" -> "three cats"), 0 to never
+    pub spell_out_below: u64,
+    /// Write "%" instead of "percent"
+    pub percent_sign: bool,
+    /// Join ranges with "-" instead of " to "
+    pub dash_ranges: bool,
+}
+
+impl NumberStyle {
+    /// The style a writing mode uses
+    pub fn for_mode(mode: WritingMode) -> Self {
+        match mode {
+            WritingMode::Formal => Self {
+                spell_out_below: 10,
+                percent_sign: false,
+                dash_ranges: false,
+            },
+            WritingMode::Casual | WritingMode::VeryCasual | WritingMode::Excited => Self {
+                spell_out_below: 0,
+                percent_sign: true,
+                dash_ranges: true,
+            },
+        }
+    }
+}
+
+/// Normalize numbers and units in the style of a writing mode
+pub fn normalize_numbers(text: &str, mode: WritingMode) -> String {
+    normalize_numbers_with(text, NumberStyle::for_mode(mode))
+}
+
+/// Normalize numbers and units in the given style
+pub fn normalize_numbers_with(text: &str, style: NumberStyle) -> String {
+    let tokens = tokenize(text);
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut index = 0;
+
+    while index < tokens.len() {
+        let Some(phrase) = parse_phrase(text, &tokens, index) else {
+            index += 1;
+            continue;
+        };
+        // numbers run together ("twenty twenty four") are probably a year or a
+        // code, so leave the whole run alone
+        let run_before = index > 0
+            && tokens[index - 1].joins_next
+            && parse_number(text, &tokens, index - 1).is_some();
+        let run_after = tokens[phrase.end - 1].joins_next
+            && phrase.end < tokens.len()
+            && parse_number(text, &tokens, phrase.end).is_some();
+        if run_before || run_after {
+            index = phrase.end;
+            continue;
+        }
+
+        let label = index > 0
+            && tokens[index - 1].joins_next
+            && LABELS.contains(&tokens[index - 1].word.trim_end_matches('.'));
+        let time = phrase.unit.is_none()
+            && tokens[phrase.end - 1].joins_next
+            && tokens
+                .get(phrase.end)
+                .is_some_and(|next| TIME_SUFFIXES.contains(&next.word.trim_end_matches('.')));
+
+        let start = phrase.first.start;
+        let end = phrase.text_end;
+        let rendered = if label || time {
+            None
+        } else {
+            render(text, &phrase, style)
+        };
+        if let Some(rendered) = rendered {
+            result.push_str(&text[copied..start]);
+            result.push_str(&rendered);
+            copied = end;
+        }
+        index = phrase.end;
+    }
+    result.push_str(&text[copied..]);
+    result
+}
+
+/// A word with surrounding punctuation trimmed
+#[derive(Debug)]
+struct Token {
+    /// Byte range of the trimmed word
+    start: usize,
+    end: usize,
+    /// The trimmed word, lowercased
+    word: String,
+    /// Whether nothing but whitespace separates this word from the next one
+    joins_next: bool,
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let spans = word_spans(text);
+    let mut tokens: Vec<Token> = Vec::with_capacity(spans.len());
+    for (span_start, span_end) in spans {
+        let span = &text[span_start..span_end];
+        let leading = span.len()
+            - span
+                .trim_start_matches(|c: char| !c.is_alphanumeric() && c != '$' && c != '€')
+                .len();
+        let trimmed = span[leading..].trim_end_matches(|c: char| !c.is_alphanumeric() && c != '%');
+        let start = span_start + leading;
+        let end = start + trimmed.len();
+        if let Some(previous) = tokens.last_mut() {
+            previous.joins_next &= leading == 0;
+        }
+        tokens.push(Token {
+            start,
+            end,
+            word: trimmed.to_lowercase(),
+            joins_next: end == span_end,
+        });
+    }
+    tokens
+}
+
+/// A number, as it was written
+#[derive(Debug)]
+struct Number {
+    /// Digits, with the decimal part if there is one ("25", "3.5")
+    digits: String,
+    /// Value of a whole number
+    whole: Option<u64>,
+    /// Byte range in the text
+    start: usize,
+    end: usize,
+    /// Written as words
+    spelled: bool,
+    /// Written as more than one word ("twenty five", "twenty-five")
+    compound: bool,
+    /// Written with a "%" ("25%")
+    percent_sign: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Percent,
+    Currency(char),
+}
+
+/// A number or range with its unit, ending before token `end`
+#[derive(Debug)]
+struct Phrase {
+    first: Number,
+    second: Option<Number>,
+    unit: Option<Unit>,
+    end: usize,
+    /// Byte offset where the phrase's text ends
+    text_end: usize,
+}
+
+fn parse_phrase(text: &str, tokens: &[Token], index: usize) -> Option<Phrase> {
+    let (first, mut end) = parse_number(text, tokens, index)?;
+
+    let mut second = None;
+    if !first.percent_sign
+        && tokens[end - 1].joins_next
+        && tokens.get(end).is_some_and(|token| token.word == "to")
+        && tokens[end].joins_next
+        && let Some((number, after)) = parse_number(text, tokens, end + 1)
+    {
+        // "five to three people" isn't a range
+        let ascending = match (first.whole, number.whole) {
+            (Some(low), Some(high)) => low < high,
+            _ => false,
+        };
+        if ascending {
+            second = Some(number);
+            end = after;
+        }
+    }
+
+    let last = second.as_ref().unwrap_or(&first);
+    let mut unit = last.percent_sign.then_some(Unit::Percent);
+    if unit.is_none()
+        && tokens[end - 1].joins_next
+        && let Some((parsed, after)) = parse_unit(tokens, end)
+    {
+        unit = Some(parsed);
+        end = after;
+    }
+
+    Some(Phrase {
+        first,
+        second,
+        unit,
+        end,
+        text_end: tokens[end - 1].end,
+    })
+}
+
+fn parse_unit(tokens: &[Token], index: usize) -> Option<(Unit, usize)> {
+    let word = tokens.get(index)?.word.as_str();
+    let unit = match word {
+        "percent" | "%" => Unit::Percent,
+        "per"
+            if tokens[index].joins_next
+                && tokens.get(index + 1).is_some_and(|t| t.word == "cent") =>
+        {
+            return Some((Unit::Percent, index + 2));
+        }
+        "dollar" | "dollars" | "bucks" => Unit::Currency('$'),
+        "euro" | "euros" => Unit::Currency('€'),
+        _ => return None,
+    };
+    Some((unit, index + 1))
+}
+
+fn parse_number(text: &str, tokens: &[Token], index: usize) -> Option<(Number, usize)> {
+    parse_digits(tokens, index).or_else(|| parse_words(text, tokens, index))
+}
+
+/// "25", "3.5", "1,000" or "25%"
+fn parse_digits(tokens: &[Token], index: usize) -> Option<(Number, usize)> {
+    let token = tokens.get(index)?;
+    let (word, percent_sign) = match token.word.strip_suffix('%') {
+        Some(word) => (word, true),
+        None => (token.word.as_str(), false),
+    };
+    let (integer, fraction) = match word.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (word, None),
+    };
+
+    let mut groups = integer.split(',');
+    let first_group = groups.next()?;
+    let grouped_ok = groups.all(|group| group.len() == 3);
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let valid = all_digits(first_group)
+        && (!integer.contains(',') || first_group.len() <= 3)
+        && grouped_ok
+        && integer.split(',').all(all_digits)
+        && fraction.is_none_or(all_digits);
+    if !valid {
+        return None;
+    }
+
+    let whole = match fraction {
+        Some(_) => None,
+        None => integer.replace(',', "").parse().ok(),
+    };
+    Some((
+        Number {
+            digits: word.to_string(),
+            whole,
+            start: token.start,
+            end: token.end,
+            spelled: false,
+            compound: false,
+            percent_sign,
+        },
+        index + 1,
+    ))
+}
+
+/// Number words: "seven", "twenty-five", "three hundred and twelve", "two point five"
+fn parse_words(text: &str, tokens: &[Token], index: usize) -> Option<(Number, usize)> {
+    let mut cursor = WordCursor {
+        tokens,
+        index,
+        words: 0,
+    };
+    let whole = cursor.below_million()?;
+
+    // digits after "point" are read one word at a time ("three point one four")
+    let mut digits = whole.to_string();
+    if cursor.peek() == Some("point") {
+        let mut fraction = String::new();
+        let mut lookahead = cursor.index + 1;
+        while cursor.joins(lookahead - 1)
+            && let Some(digit) = tokens
+                .get(lookahead)
+                .and_then(|token| word_value(&token.word, &SMALL_NUMBERS))
+        {
+            fraction.push_str(&digit.to_string());
+            lookahead += 1;
+        }
+        if !fraction.is_empty() {
+            digits = format!("{digits}.{fraction}");
+            cursor.words += lookahead - cursor.index;
+            cursor.index = lookahead;
+        }
+    }
+
+    let start = tokens[index].start;
+    let end = tokens[cursor.index - 1].end;
+    let decimal = digits.contains('.');
+    Some((
+        Number {
+            digits,
+            whole: (!decimal).then_some(whole),
+            start,
+            end,
+            spelled: true,
+            compound: cursor.words > 1 || text[start..end].contains('-'),
+            percent_sign: false,
+        },
+        cursor.index,
+    ))
+}
+
+fn word_value(word: &str, words: &[&str]) -> Option<u64> {
+    words.iter().position(|w| *w == word).map(|i| i as u64)
+}
+
+/// Reads number words from consecutive tokens
+struct WordCursor<'a> {
+    tokens: &'a [Token],
+    index: usize,
+    words: usize,
+}
+
+impl WordCursor<'_> {
+    /// The next word, if it directly follows the words read so far
+    fn peek(&self) -> Option<&str> {
+        if self.words > 0 && !self.joins(self.index - 1) {
+            return None;
+        }
+        self.tokens.get(self.index).map(|token| token.word.as_str())
+    }
+
+    fn joins(&self, index: usize) -> bool {
+        self.tokens.get(index).is_some_and(|token| token.joins_next)
+    }
+
+    fn advance(&mut self) {
+        self.index += 1;
+        self.words += 1;
+    }
+
+    /// Consume "and" when a number follows it ("one hundred and five")
+    fn skip_and(&mut self) {
+        if self.peek() == Some("and") && self.joins(self.index) {
+            let after = self
+                .tokens
+                .get(self.index + 1)
+                .is_some_and(|token| below_hundred_value(&token.word).is_some());
+            if after {
+                self.advance();
+            }
+        }
+    }
+
+    fn below_hundred(&mut self) -> Option<u64> {
+        let value = below_hundred_value(self.peek()?)?;
+        self.advance();
+        if (20..100).contains(&value)
+            && value % 10 == 0
+            && let Some(unit) = self
+                .peek()
+                .and_then(|word| word_value(word, &SMALL_NUMBERS))
+            && unit > 0
+        {
+            self.advance();
+            return Some(value + unit);
+        }
+        Some(value)
+    }
+
+    fn below_thousand(&mut self) -> Option<u64> {
+        let mut value = self.below_hundred()?;
+        if self.peek() == Some("hundred") {
+            self.advance();
+            value *= 100;
+            let checkpoint = (self.index, self.words);
+            self.skip_and();
+            match self.below_hundred() {
+                Some(rest) => value += rest,
+                None => (self.index, self.words) = checkpoint,
+            }
+        }
+        Some(value)
+    }
+
+    fn below_million(&mut self) -> Option<u64> {
+        let mut value = self.below_thousand()?;
+        if self.peek() == Some("thousand") {
+            self.advance();
+            value *= 1000;
+            let checkpoint = (self.index, self.words);
+            self.skip_and();
+            match self.below_thousand() {
+                Some(rest) => value += rest,
+                None => (self.index, self.words) = checkpoint,
+            }
+        }
+        Some(value)
+    }
+}
+
+/// Value of a single number word below one hundred, including "twenty-five"
+fn below_hundred_value(word: &str) -> Option<u64> {
+    if let Some((tens, unit)) = word.split_once('-') {
+        let tens = word_value(tens, &TENS)?;
+        let unit = word_value(unit, &SMALL_NUMBERS).filter(|&unit| unit > 0)?;
+        return Some((tens + 2) * 10 + unit);
+    }
+    word_value(word, &SMALL_NUMBERS)
+        .or_else(|| word_value(word, &TEENS).map(|value| value + 10))
+        .or_else(|| word_value(word, &TENS).map(|value| (value + 2) * 10))
+}
+
+/// The phrase rewritten in the given style, or None to leave it as written
+fn render(text: &str, phrase: &Phrase, style: NumberStyle) -> Option<String> {
+    let context = phrase.second.is_some();
+    let first = render_number(text, &phrase.first, phrase.unit, context, style);
+    let second = phrase
+        .second
+        .as_ref()
+        .map(|number| render_number(text, number, phrase.unit, context, style));
+
+    let joiner = if style.dash_ranges { "-" } else { " to " };
+    let rendered = match (phrase.unit, second) {
+        (Some(Unit::Currency(symbol)), Some(second)) => {
+            format!("{symbol}{first}{joiner}{symbol}{second}")
+        }
+        (Some(Unit::Currency(symbol)), None) => format!("{symbol}{first}"),
+        (unit, second) => {
+            let mut rendered = match second {
+                Some(second) => format!("{first}{joiner}{second}"),
+                None => first,
+            };
+            if unit == Some(Unit::Percent) {
+                rendered.push_str(if style.percent_sign { "%" } else { " percent" });
+            }
+            rendered
+        }
+    };
+
+    let original = &text[phrase.first.start..phrase.text_end];
+    (rendered != original).then_some(rendered)
+}
+
+fn render_number(
+    text: &str,
+    number: &Number,
+    unit: Option<Unit>,
+    range: bool,
+    style: NumberStyle,
+) -> String {
+    // amounts and decimals always read best as digits
+    if unit.is_some() || number.whole.is_none() {
+        return number.digits.clone();
+    }
+    let whole = number.whole.unwrap_or_default();
+    if whole < style.spell_out_below {
+        return SMALL_NUMBERS[whole as usize].to_string();
+    }
+    if number.spelled && !number.compound && !range {
+        // a lone number word ("one of them") isn't clearly a number
+        return text[number.start..number.end].to_string();
+    }
+    number.digits.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_per_mode() {
+        assert_eq!(
+            normalize_numbers("about 25 percent of users", WritingMode::Formal),
+            "about 25 percent of users"
+        );
+        assert_eq!(
+            normalize_numbers("about 25 percent of users", WritingMode::Casual),
+            "about 25% of users"
+        );
+        assert_eq!(
+            normalize_numbers("about twenty five percent", WritingMode::Casual),
+            "about 25%"
+        );
+        assert_eq!(
+            normalize_numbers("about 25% of users", WritingMode::Formal),
+            "about 25 percent of users"
+        );
+    }
+
+    #[test]
+    fn test_years_are_untouched() {
+        for mode in [WritingMode::Formal, WritingMode::Casual] {
+            assert_eq!(
+                normalize_numbers("back in 2024, we shipped", mode),
+                "back in 2024, we shipped"
+            );
+            assert_eq!(
+                normalize_numbers("since twenty twenty four", mode),
+                "since twenty twenty four"
+            );
+        }
+    }
+
+    #[test]
+    fn test_small_numbers() {
+        assert_eq!(
+            normalize_numbers("I have 3 cats and 12 dogs", WritingMode::Formal),
+            "I have three cats and 12 dogs"
+        );
+        assert_eq!(
+            normalize_numbers("twenty-five people came", WritingMode::Formal),
+            "25 people came"
+        );
+        assert_eq!(
+            normalize_numbers("I have 3 cats", WritingMode::Casual),
+            "I have 3 cats"
+        );
+        // a lone number word could be a pronoun or an idiom
+        assert_eq!(
+            normalize_numbers("one of them said ten times", WritingMode::Casual),
+            "one of them said ten times"
+        );
+        // labels and times keep their digits
+        assert_eq!(
+            normalize_numbers("see version 3 at 5 pm", WritingMode::Formal),
+            "see version 3 at 5 pm"
+        );
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(
+            normalize_numbers("it takes three to five days", WritingMode::Casual),
+            "it takes 3-5 days"
+        );
+        assert_eq!(
+            normalize_numbers("it takes 3 to 5 days", WritingMode::Formal),
+            "it takes three to five days"
+        );
+        assert_eq!(
+            normalize_numbers("three to five percent", WritingMode::Casual),
+            "3-5%"
+        );
+        assert_eq!(
+            normalize_numbers("give five to three people", WritingMode::Casual),
+            "give five to three people"
+        );
+    }
+
+    #[test]
+    fn test_decimals_and_currency() {
+        assert_eq!(
+            normalize_numbers("it grew two point five percent", WritingMode::Formal),
+            "it grew 2.5 percent"
+        );
+        assert_eq!(
+            normalize_numbers("that costs twenty five dollars.", WritingMode::Formal),
+            "that costs $25."
+        );
+        assert_eq!(
+            normalize_numbers("between ten to twenty bucks", WritingMode::Casual),
+            "between $10-$20"
+        );
+        assert_eq!(
+            normalize_numbers("one hundred and five euros", WritingMode::Casual),
+            "€105"
+        );
+        assert_eq!(
+            normalize_numbers("it was 1,500 dollars", WritingMode::Casual),
+            "it was $1,500"
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Data directory and profiles
+//!
+//! Everything Flow stores lives in one SQLite database. Without a profile that is
+//! `flow.db` in the data directory; each named profile gets its own database under
+//! `profiles/<name>/`, so corrections, shortcuts, modes and stats never mix between
+//! e.g. a work and a personal profile. A handle is bound to one database for its
+//! whole life: to switch profiles, destroy the handle and open the other profile.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// Environment variable that overrides the data directory
+pub const DATA_DIR_ENV: &str = "FLOW_DATA_DIR";
+
+/// Longest accepted profile name, in characters
+pub const MAX_PROFILE_NAME_LEN: usize = 64;
+
+/// Subdirectory of the data directory that holds the profiles
+const PROFILES_DIR: &str = "profiles";
+
+/// Database file name, both for the default database and inside each profile
+const DB_FILE: &str = "flow.db";
+
+/// Directory Flow stores its data in: `$FLOW_DATA_DIR` if set, otherwise `flow`
+/// in the platform's local data directory
+pub fn data_dir() -> PathBuf {
+    match std::env::var_os(DATA_DIR_ENV) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => dirs::data_local_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("flow"),
+    }
+}
+
+/// Database used when no profile is given
+pub fn default_db_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(DB_FILE)
+}
+
+/// Check that a profile name is safe to use as a directory name
+///
+/// Names are 1-64 letters, digits, '-' or '_', which keeps them from escaping the
+/// profiles directory or clashing on case-insensitive file systems in odd ways.
+pub fn validate_profile_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(Error::Config("Profile name is empty".to_string()));
+    }
+    if name.chars().count() > MAX_PROFILE_NAME_LEN {
+        return Err(Error::Config(format!(
+            "Profile name is longer than {MAX_PROFILE_NAME_LEN} characters"
+        )));
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !c.is_alphanumeric() && *c != '-' && *c != '_')
+    {
+        return Err(Error::Config(format!(
+            "Profile name '{name}' contains '{c}', use letters, digits, '-' or '_'"
+        )));
+    }
+    Ok(())
+}
+
+/// Database of a profile, which may not exist yet
+pub fn profile_db_path(data_dir: &Path, name: &str) -> Result<PathBuf> {
+    validate_profile_name(name)?;
+    Ok(data_dir.join(PROFILES_DIR).join(name).join(DB_FILE))
+}
+
+/// Names of the profiles in a data directory, sorted
+///
+/// A profile exists once its directory does, i.e. after it was first opened.
+/// Directories with names that aren't valid profile names are skipped.
+pub fn list_profiles(data_dir: &Path) -> Result<Vec<String>> {
+    let entries = match std::fs::read_dir(data_dir.join(PROFILES_DIR)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut profiles = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if let Some(name) = entry.file_name().to_str()
+            && validate_profile_name(name).is_ok()
+        {
+            profiles.push(name.to_string());
+        }
+    }
+    profiles.sort();
+    Ok(profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_data_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("flow_profiles_{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_profile_names() {
+        assert!(validate_profile_name("work").is_ok());
+        assert!(validate_profile_name("side-project_2").is_ok());
+        assert!(validate_profile_name("").is_err());
+        assert!(validate_profile_name("..").is_err());
+        assert!(validate_profile_name("a/b").is_err());
+        assert!(validate_profile_name(&"x".repeat(MAX_PROFILE_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_list_profiles() {
+        let dir = temp_data_dir();
+        assert!(list_profiles(&dir).unwrap().is_empty());
+
+        for name in ["work", "personal"] {
+            let path = profile_db_path(&dir, name).unwrap();
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        }
+        std::fs::create_dir_all(dir.join(PROFILES_DIR).join("not a profile")).unwrap();
+        std::fs::write(dir.join(PROFILES_DIR).join("stray-file"), "").unwrap();
+
+        assert_eq!(list_profiles(&dir).unwrap(), vec!["personal", "work"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
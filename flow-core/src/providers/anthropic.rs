@@ -0,0 +1,517 @@
+//! Anthropic provider implementation for Claude completion via the Messages API
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{debug, error};
+
+use crate::error::{Error, Result};
+use crate::locale::Locale;
+use crate::types::WritingMode;
+
+use super::completion::TokenUsage;
+use super::health::check_endpoint;
+use super::rate_limit::RateLimiter;
+use super::response::parse_json;
+use super::streaming::{
+    AnthropicStreamEvent, CompletionChunk, CompletionStream, StreamingCompletionProvider,
+    parse_sse_line,
+};
+use super::{CompletionProvider, CompletionRequest, CompletionResponse};
+
+const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// The Messages API requires max_tokens, so use this when the request doesn't set one
+const DEFAULT_MAX_TOKENS: u32 = 1024;
+
+/// Anthropic completion provider
+pub struct AnthropicCompletionProvider {
+    client: Client,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl AnthropicCompletionProvider {
+    /// Create a new provider (API key loaded from environment if not provided)
+    pub fn new(api_key: Option<String>) -> Self {
+        let key = api_key.or_else(|| std::env::var("ANTHROPIC_API_KEY").ok());
+
+        Self {
+            client: Client::new(),
+            rate_limiter: None,
+            api_key: key,
+            model: "claude-3-5-haiku-latest".to_string(),
+        }
+    }
+
+    /// Set the model to use
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Queue requests to stay under `requests_per_minute`
+    pub fn with_rate_limit(mut self, requests_per_minute: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::per_minute(requests_per_minute)));
+        self
+    }
+
+    fn api_key(&self) -> Result<&str> {
+        self.api_key
+            .as_deref()
+            .ok_or_else(|| Error::ProviderNotConfigured("Anthropic API key not set".to_string()))
+    }
+
+    fn build_system_prompt(
+        &self,
+        mode: WritingMode,
+        locale: Locale,
+        app_context: Option<&str>,
+    ) -> String {
+        let mut prompt = String::from(
+            "You are a text formatter. The user will provide raw transcribed text wrapped in <TRANSCRIPTION> tags. \
+             Reformat ONLY the text inside according to the style below. Output the reformatted text exactly as it would \
+             be typed. Do NOT generate new content, do NOT add commentary or responses, do NOT say anything.\n\n",
+        );
+
+        prompt.push_str("Formatting style: ");
+        prompt.push_str(locale.prompt_modifier(mode));
+
+        if let Some(context) = app_context {
+            prompt.push_str("\n\nContext: User is typing in ");
+            prompt.push_str(context);
+            prompt.push_str(". Adjust formatting for this context.");
+        }
+
+        prompt
+    }
+
+    fn build_request(&self, request: CompletionRequest, stream: bool) -> MessagesRequest {
+        let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
+            self.build_system_prompt(request.mode, request.locale, request.app_context.as_deref())
+        });
+
+        // Add shortcut preservation instruction if present
+        if let Some(preservation) = request.shortcut_preservation {
+            system_prompt.push_str(&preservation);
+        }
+
+        MessagesRequest {
+            model: request.model.unwrap_or_else(|| self.model.clone()),
+            max_tokens: request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            system: system_prompt,
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", request.text),
+            }],
+            temperature: 0.3, // low temperature for consistent formatting
+            stream: stream.then_some(true),
+        }
+    }
+
+    async fn send(&self, request: &MessagesRequest) -> Result<reqwest::Response> {
+        let api_key = self.api_key()?;
+
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/messages", ANTHROPIC_API_BASE))
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .header("Content-Type", "application/json")
+            .json(request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            error!("Anthropic API error: {} - {}", status, error_text);
+            return Err(Error::from_status(
+                status,
+                format!("Anthropic API error: {} - {}", status, error_text),
+                Error::Completion,
+            ));
+        }
+
+        Ok(response)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct MessagesRequest {
+    model: String,
+    max_tokens: u32,
+    system: String,
+    messages: Vec<Message>,
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+struct Message {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    usage: Option<MessagesUsage>,
+    model: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    block_type: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesUsage {
+    input_tokens: u32,
+    output_tokens: u32,
+}
+
+impl MessagesResponse {
+    /// Join the text blocks into a single completion response
+    fn into_completion(self) -> Result<CompletionResponse> {
+        let text: String = self
+            .content
+            .into_iter()
+            .filter(|block| block.block_type == "text")
+            .map(|block| block.text)
+            .collect();
+
+        if text.is_empty() {
+            return Err(Error::Completion("No completion returned".to_string()));
+        }
+
+        Ok(CompletionResponse {
+            text,
+            usage: self
+                .usage
+                .map(|u| token_usage(u.input_tokens, u.output_tokens)),
+            model: Some(self.model),
+        })
+    }
+}
+
+fn token_usage(input_tokens: u32, output_tokens: u32) -> TokenUsage {
+    TokenUsage {
+        prompt_tokens: input_tokens,
+        completion_tokens: output_tokens,
+        total_tokens: input_tokens + output_tokens,
+    }
+}
+
+/// State for turning a Messages API SSE byte stream into completion chunks
+struct EventStreamState<S> {
+    inner: S,
+    buffer: Vec<u8>,
+    input_tokens: u32,
+    output_tokens: u32,
+    done: bool,
+}
+
+impl<S> EventStreamState<S> {
+    /// Handle one SSE line, returning a chunk if it carries text or ends the message
+    fn handle_line(&mut self, line: &str) -> Result<Option<CompletionChunk>> {
+        let Some(event) = parse_sse_line(line) else {
+            return Ok(None);
+        };
+        // "event:" lines repeat the type that is also in the data payload
+        if event.event.is_some() {
+            return Ok(None);
+        }
+
+        let event: AnthropicStreamEvent = match serde_json::from_str(&event.data) {
+            Ok(event) => event,
+            Err(e) => {
+                debug!("Skipping unrecognized Anthropic stream event: {}", e);
+                return Ok(None);
+            }
+        };
+
+        let chunk = match event {
+            AnthropicStreamEvent::MessageStart { message } => {
+                self.input_tokens = message.usage.input_tokens;
+                self.output_tokens = message.usage.output_tokens;
+                None
+            }
+            AnthropicStreamEvent::ContentBlockStart { content_block, .. } => {
+                (!content_block.text.is_empty()).then(|| text_chunk(content_block.text))
+            }
+            AnthropicStreamEvent::ContentBlockDelta { delta, .. } => {
+                (delta.delta_type == "text_delta").then(|| text_chunk(delta.text))
+            }
+            AnthropicStreamEvent::MessageDelta { usage, .. } => {
+                self.output_tokens = usage.output_tokens;
+                None
+            }
+            AnthropicStreamEvent::MessageStop => {
+                self.done = true;
+                Some(CompletionChunk {
+                    text: String::new(),
+                    is_final: true,
+                    usage: Some(token_usage(self.input_tokens, self.output_tokens)),
+                    final_response: None,
+                })
+            }
+            AnthropicStreamEvent::ContentBlockStop { .. } | AnthropicStreamEvent::Ping => None,
+            AnthropicStreamEvent::Error { error } => {
+                return Err(Error::Completion(format!(
+                    "Anthropic stream error: {} - {}",
+                    error.error_type, error.message
+                )));
+            }
+        };
+
+        Ok(chunk)
+    }
+}
+
+fn text_chunk(text: String) -> CompletionChunk {
+    CompletionChunk {
+        text,
+        is_final: false,
+        usage: None,
+        final_response: None,
+    }
+}
+
+/// Convert a Messages API SSE byte stream into a stream of completion chunks
+fn event_stream<S, B, E>(inner: S) -> CompletionStream
+where
+    S: Stream<Item = std::result::Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]>,
+    Error: From<E>,
+{
+    let state = EventStreamState {
+        inner: Box::pin(inner),
+        buffer: Vec::new(),
+        input_tokens: 0,
+        output_tokens: 0,
+        done: false,
+    };
+
+    Box::pin(futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if state.done {
+                return None;
+            }
+
+            // Handle complete lines already buffered before reading more
+            if let Some(pos) = state.buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = state.buffer.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line);
+                match state.handle_line(&line) {
+                    Ok(Some(chunk)) => return Some((Ok(chunk), state)),
+                    Ok(None) => continue,
+                    Err(e) => {
+                        state.done = true;
+                        return Some((Err(e), state));
+                    }
+                }
+            }
+
+            match state.inner.next().await {
+                Some(Ok(bytes)) => state.buffer.extend_from_slice(bytes.as_ref()),
+                Some(Err(e)) => {
+                    state.done = true;
+                    return Some((Err(e.into()), state));
+                }
+                None => {
+                    state.done = true;
+                    return Some((
+                        Err(Error::Completion(
+                            "Anthropic stream ended before message_stop".to_string(),
+                        )),
+                        state,
+                    ));
+                }
+            }
+        }
+    }))
+}
+
+#[async_trait]
+impl CompletionProvider for AnthropicCompletionProvider {
+    fn name(&self) -> &'static str {
+        "Anthropic"
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let messages_request = self.build_request(request, false);
+
+        debug!("Sending completion request to Anthropic");
+
+        let response = self.send(&messages_request).await?;
+        let messages_response: MessagesResponse = parse_json(response).await?;
+        messages_response.into_completion()
+    }
+
+    fn is_configured(&self) -> bool {
+        self.api_key.is_some()
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        let api_key = self.api_key()?;
+        let request = self
+            .client
+            .get(format!("{}/models", ANTHROPIC_API_BASE))
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION);
+        check_endpoint(CompletionProvider::name(self), request).await
+    }
+}
+
+#[async_trait]
+impl StreamingCompletionProvider for AnthropicCompletionProvider {
+    fn name(&self) -> &'static str {
+        "Anthropic"
+    }
+
+    async fn complete_stream(&self, request: CompletionRequest) -> Result<CompletionStream> {
+        let messages_request = self.build_request(request, true);
+
+        debug!("Sending streaming completion request to Anthropic");
+
+        let response = self.send(&messages_request).await?;
+        Ok(event_stream(response.bytes_stream()))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.api_key.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::collect_stream;
+
+    #[test]
+    fn test_request_shape() {
+        let provider = AnthropicCompletionProvider::new(Some("key".to_string()));
+        let request = CompletionRequest::new("hello world".to_string(), WritingMode::Formal)
+            .with_app_context("Mail");
+
+        let body = serde_json::to_value(provider.build_request(request, false)).unwrap();
+
+        // system prompt is top-level, not a message
+        assert!(body["system"].as_str().unwrap().contains("Mail"));
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(body["messages"][0]["role"], "user");
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+        assert!(body.get("stream").is_none());
+
+        let request = CompletionRequest::new("hi".to_string(), WritingMode::Casual)
+            .with_max_tokens(64)
+            .with_model("custom-model");
+        let body = serde_json::to_value(provider.build_request(request, true)).unwrap();
+        assert_eq!(body["max_tokens"], 64);
+        assert_eq!(body["model"], "custom-model");
+        assert_eq!(body["stream"], true);
+    }
+
+    #[test]
+    fn test_parse_messages_response() {
+        let json = r#"{
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-3-5-haiku-latest",
+            "content": [
+                {"type": "text", "text": "Hello, "},
+                {"type": "text", "text": "world."}
+            ],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 12, "output_tokens": 4}
+        }"#;
+
+        let response: MessagesResponse = serde_json::from_str(json).unwrap();
+        let completion = response.into_completion().unwrap();
+
+        assert_eq!(completion.text, "Hello, world.");
+        assert_eq!(completion.model.as_deref(), Some("claude-3-5-haiku-latest"));
+        let usage = completion.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 12);
+        assert_eq!(usage.completion_tokens, 4);
+        assert_eq!(usage.total_tokens, 16);
+    }
+
+    #[test]
+    fn test_parse_empty_messages_response() {
+        let json = r#"{"model": "m", "content": [], "usage": null}"#;
+
+        let response: MessagesResponse = serde_json::from_str(json).unwrap();
+        assert!(response.into_completion().is_err());
+    }
+
+    fn byte_stream(
+        parts: Vec<&'static str>,
+    ) -> impl Stream<Item = std::result::Result<&'static [u8], Error>> + Send + 'static {
+        futures::stream::iter(parts.into_iter().map(|p| Ok(p.as_bytes())))
+    }
+
+    #[tokio::test]
+    async fn test_stream_events_through_collect_stream() {
+        // split mid-line to check buffering across network chunks
+        let parts = vec![
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_01\",\"model\":\"m\",\"usage\":{\"input_tokens\":9,\"output_tokens\":1}}}\n\n",
+            "event: content_block_start\n",
+            "data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: ping\ndata: {\"type\":\"ping\"}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,",
+            "\"delta\":{\"type\":\"text_delta\",\"text\":\", world.\"}}\n\n",
+            "data: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":5}}\n\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+        ];
+
+        let response = collect_stream(event_stream(byte_stream(parts)))
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "Hello, world.");
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 9);
+        assert_eq!(usage.completion_tokens, 5);
+        assert_eq!(usage.total_tokens, 14);
+    }
+
+    #[tokio::test]
+    async fn test_stream_error_event() {
+        let parts = vec![
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n",
+            "data: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n",
+        ];
+
+        let result = collect_stream(event_stream(byte_stream(parts))).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_truncated() {
+        let parts = vec![
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n",
+        ];
+
+        let result = collect_stream(event_stream(byte_stream(parts))).await;
+        assert!(result.is_err());
+    }
+}
@@ -8,19 +8,25 @@ use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use tracing::{debug, error};
 
 use crate::error::{Error, Result};
 
+use super::health::check_endpoint;
+use super::rate_limit::RateLimiter;
+use super::response::parse_json;
 use super::{TranscriptionProvider, TranscriptionRequest, TranscriptionResponse};
 
 const FLOW_WORKER_URL: &str = "https://flow-worker.test-j.workers.dev";
 const FLOW_WORKER_VALIDATE_URL: &str =
     "https://flow-worker.test-j.workers.dev/validate-corrections";
+const FLOW_WORKER_HEALTH_URL: &str = "https://flow-worker.test-j.workers.dev/health";
 
 /// Auto transcription provider (with integrated completion)
 pub struct AutoTranscriptionProvider {
     client: Client,
+    rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 /// A correction pair to validate
@@ -77,13 +83,14 @@ pub async fn validate_corrections(
         let status = response.status();
         let error_text = response.text().await.unwrap_or_default();
         error!("Validation worker error: {} - {}", status, error_text);
-        return Err(Error::Transcription(format!(
-            "Validation error: {} - {}",
-            status, error_text
-        )));
+        return Err(Error::from_status(
+            status,
+            format!("Validation error: {} - {}", status, error_text),
+            Error::Transcription,
+        ));
     }
 
-    let validation_response: ValidateCorrectionsResponse = response.json().await?;
+    let validation_response: ValidateCorrectionsResponse = parse_json(response).await?;
     Ok(validation_response.results)
 }
 
@@ -91,8 +98,15 @@ impl AutoTranscriptionProvider {
     pub fn new(_api_key: Option<String>) -> Self {
         Self {
             client: Client::new(),
+            rate_limiter: None,
         }
     }
+
+    /// Queue requests to stay under `requests_per_minute`
+    pub fn with_rate_limit(mut self, requests_per_minute: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::per_minute(requests_per_minute)));
+        self
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -115,6 +129,8 @@ struct AudioInput {
 #[derive(Debug, Serialize)]
 struct WhisperParams {
     audio_language: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    initial_prompt: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -125,6 +141,8 @@ struct WorkerCompletionParams {
     shortcuts_triggered: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     voice_instruction: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    locale: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -158,6 +176,7 @@ impl TranscriptionProvider for AutoTranscriptionProvider {
                 },
                 whisper_params: WhisperParams {
                     audio_language: language,
+                    initial_prompt: request.prompt,
                 },
             },
             completion: WorkerCompletionParams {
@@ -165,11 +184,16 @@ impl TranscriptionProvider for AutoTranscriptionProvider {
                 app_context: completion.app_context,
                 shortcuts_triggered: completion.shortcuts_triggered,
                 voice_instruction: completion.voice_instruction,
+                locale: completion.locale,
             },
         };
 
         debug!("Sending combined transcription+completion request to worker");
 
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+
         let response = self
             .client
             .post(FLOW_WORKER_URL)
@@ -181,13 +205,14 @@ impl TranscriptionProvider for AutoTranscriptionProvider {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("Worker error: {} - {}", status, error_text);
-            return Err(Error::Transcription(format!(
-                "Worker error: {} - {}",
-                status, error_text
-            )));
+            return Err(Error::from_status(
+                status,
+                format!("Worker error: {} - {}", status, error_text),
+                Error::Transcription,
+            ));
         }
 
-        let worker_response: WorkerResponse = response.json().await?;
+        let worker_response: WorkerResponse = parse_json(response).await?;
 
         let samples = request.audio.len() / 2;
         let duration_ms = (samples as u64 * 1000) / request.sample_rate as u64;
@@ -205,6 +230,15 @@ impl TranscriptionProvider for AutoTranscriptionProvider {
     fn is_configured(&self) -> bool {
         true
     }
+
+    fn includes_completion(&self) -> bool {
+        true
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        // a transcription would be billed, the health route only proves the worker is up
+        check_endpoint(self.name(), self.client.get(FLOW_WORKER_HEALTH_URL)).await
+    }
 }
 
 fn pcm_to_wav(pcm: &[u8], sample_rate: u32, channels: u16) -> Vec<u8> {
@@ -254,5 +288,6 @@ mod tests {
     fn test_provider_always_configured() {
         let provider = AutoTranscriptionProvider::new(None);
         assert!(provider.is_configured());
+        assert!(provider.includes_completion());
     }
 }
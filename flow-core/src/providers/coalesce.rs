@@ -0,0 +1,241 @@
+//! Coalescing for streamed completions
+//!
+//! Token-level streaming yields a chunk per token, and every chunk becomes an FFI
+//! callback. `coalesce_stream` batches chunks until enough text has built up or a
+//! time window has passed, whichever comes first. Text is only regrouped, never
+//! changed, so the coalesced chunks concatenate to exactly the same string.
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::time::Instant;
+
+use super::{CompletionChunk, CompletionStream};
+use crate::error::Error;
+
+/// When to emit buffered text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoalesceConfig {
+    /// Emit once at least this many characters are buffered
+    pub min_chars: usize,
+    /// Emit text that has been buffered this long, however short it is
+    pub max_delay: Duration,
+}
+
+impl Default for CoalesceConfig {
+    /// About one callback per few words, and never more than 50ms behind
+    fn default() -> Self {
+        Self {
+            min_chars: 24,
+            max_delay: Duration::from_millis(50),
+        }
+    }
+}
+
+struct CoalesceState {
+    inner: CompletionStream,
+    config: CoalesceConfig,
+    buffer: String,
+    /// When the buffered text must be emitted, set when the buffer starts filling
+    deadline: Option<Instant>,
+    /// Error to yield after the text buffered before it
+    pending_error: Option<Error>,
+    done: bool,
+}
+
+impl CoalesceState {
+    fn take_chunk(&mut self) -> CompletionChunk {
+        self.deadline = None;
+        CompletionChunk {
+            text: std::mem::take(&mut self.buffer),
+            is_final: false,
+            usage: None,
+            final_response: None,
+        }
+    }
+}
+
+/// Batch a completion stream into larger chunks
+///
+/// Buffered text is flushed when the stream ends, and the final chunk carries any
+/// text still buffered along with its usage and final response. An error is yielded
+/// after the text that arrived before it.
+pub fn coalesce_stream(stream: CompletionStream, config: CoalesceConfig) -> CompletionStream {
+    let state = CoalesceState {
+        inner: stream,
+        config,
+        buffer: String::new(),
+        deadline: None,
+        pending_error: None,
+        done: false,
+    };
+
+    Box::pin(futures::stream::unfold(state, |mut state| async move {
+        if let Some(error) = state.pending_error.take() {
+            state.done = true;
+            return Some((Err(error), state));
+        }
+
+        while !state.done {
+            let next = match state.deadline {
+                Some(deadline) => tokio::select! {
+                    item = state.inner.next() => Some(item),
+                    _ = tokio::time::sleep_until(deadline) => None,
+                },
+                None => Some(state.inner.next().await),
+            };
+
+            match next {
+                // the window closed with text still buffered
+                None => {
+                    let chunk = state.take_chunk();
+                    return Some((Ok(chunk), state));
+                }
+                Some(None) => {
+                    state.done = true;
+                    if !state.buffer.is_empty() {
+                        let chunk = state.take_chunk();
+                        return Some((Ok(chunk), state));
+                    }
+                }
+                Some(Some(Err(error))) => {
+                    if state.buffer.is_empty() {
+                        state.done = true;
+                        return Some((Err(error), state));
+                    }
+                    state.pending_error = Some(error);
+                    let chunk = state.take_chunk();
+                    return Some((Ok(chunk), state));
+                }
+                Some(Some(Ok(mut chunk))) if chunk.is_final => {
+                    state.buffer.push_str(&chunk.text);
+                    chunk.text = state.take_chunk().text;
+                    return Some((Ok(chunk), state));
+                }
+                Some(Some(Ok(chunk))) => {
+                    if state.buffer.is_empty() {
+                        state.deadline = Some(Instant::now() + state.config.max_delay);
+                    }
+                    state.buffer.push_str(&chunk.text);
+                    if state.buffer.chars().count() >= state.config.min_chars {
+                        let chunk = state.take_chunk();
+                        return Some((Ok(chunk), state));
+                    }
+                }
+            }
+        }
+        None
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{CompletionResponse, TokenUsage, collect_stream};
+
+    fn chunk(text: &str) -> CompletionChunk {
+        CompletionChunk {
+            text: text.to_string(),
+            is_final: false,
+            usage: None,
+            final_response: None,
+        }
+    }
+
+    fn single_chars(text: &str) -> Vec<crate::error::Result<CompletionChunk>> {
+        text.chars().map(|c| Ok(chunk(&c.to_string()))).collect()
+    }
+
+    fn config(min_chars: usize) -> CoalesceConfig {
+        CoalesceConfig {
+            min_chars,
+            max_delay: Duration::from_secs(60),
+        }
+    }
+
+    async fn texts(stream: CompletionStream) -> Vec<String> {
+        stream.map(|chunk| chunk.unwrap().text).collect().await
+    }
+
+    #[tokio::test]
+    async fn test_groups_single_characters() {
+        let stream = Box::pin(futures::stream::iter(single_chars("Hello, world!")));
+
+        let chunks = texts(coalesce_stream(stream, config(4))).await;
+
+        assert_eq!(chunks, vec!["Hell", "o, w", "orld", "!"]);
+        assert_eq!(chunks.concat(), "Hello, world!");
+    }
+
+    #[tokio::test]
+    async fn test_final_chunk_carries_buffered_text() {
+        let mut items = single_chars("Hi there");
+        items.push(Ok(CompletionChunk {
+            text: ".".to_string(),
+            is_final: true,
+            usage: Some(TokenUsage {
+                prompt_tokens: 3,
+                completion_tokens: 2,
+                total_tokens: 5,
+            }),
+            final_response: Some(CompletionResponse {
+                text: "Hi there.".to_string(),
+                usage: None,
+                model: None,
+            }),
+        }));
+        let stream = Box::pin(futures::stream::iter(items));
+
+        let chunks: Vec<CompletionChunk> = coalesce_stream(stream, config(5))
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].text, "Hi th");
+        assert_eq!(chunks[1].text, "ere.");
+        assert!(chunks[1].is_final);
+        assert_eq!(chunks[1].usage.as_ref().unwrap().total_tokens, 5);
+        assert!(chunks[1].final_response.is_some());
+
+        let stream = Box::pin(futures::stream::iter(single_chars("Same text either way")));
+        let response = collect_stream(coalesce_stream(stream, config(6)))
+            .await
+            .unwrap();
+        assert_eq!(response.text, "Same text either way");
+    }
+
+    #[tokio::test]
+    async fn test_flushes_after_time_window() {
+        let delays = [("a", 0), ("b", 0), ("c", 300), ("d", 0)];
+        let stream = Box::pin(
+            futures::stream::iter(delays).then(|(text, delay)| async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                Ok(chunk(text))
+            }),
+        );
+        let config = CoalesceConfig {
+            min_chars: 100,
+            max_delay: Duration::from_millis(50),
+        };
+
+        let chunks = texts(coalesce_stream(stream, config)).await;
+
+        assert_eq!(chunks, vec!["ab", "cd"]);
+    }
+
+    #[tokio::test]
+    async fn test_error_follows_buffered_text() {
+        let stream: CompletionStream = Box::pin(futures::stream::iter(vec![
+            Ok(chunk("partial")),
+            Err(Error::Completion("boom".to_string())),
+            Ok(chunk("never seen")),
+        ]));
+
+        let results: Vec<_> = coalesce_stream(stream, config(100)).collect().await;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().text, "partial");
+        assert!(results[1].is_err());
+    }
+}
@@ -4,6 +4,7 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
 use crate::error::Result;
+use crate::locale::Locale;
 use crate::modes::WritingMode;
 
 /// Request for text completion/formatting
@@ -13,6 +14,8 @@ pub struct CompletionRequest {
     pub text: String,
     /// Writing mode to apply
     pub mode: WritingMode,
+    /// Language of the text, which picks the mode's instructions
+    pub locale: Locale,
     /// Optional system prompt override
     pub system_prompt: Option<String>,
     /// Context about the target application
@@ -21,6 +24,8 @@ pub struct CompletionRequest {
     pub max_tokens: Option<u32>,
     /// Instruction to preserve shortcut text word-for-word
     pub shortcut_preservation: Option<String>,
+    /// Model override (uses the provider's configured model when None)
+    pub model: Option<String>,
 }
 
 impl CompletionRequest {
@@ -28,13 +33,20 @@ impl CompletionRequest {
         Self {
             text,
             mode,
+            locale: Locale::default(),
             system_prompt: None,
             app_context: None,
             max_tokens: None,
             shortcut_preservation: None,
+            model: None,
         }
     }
 
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
     pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
         self.system_prompt = Some(prompt.into());
         self
@@ -54,6 +66,11 @@ impl CompletionRequest {
         self.shortcut_preservation = Some(instruction.into());
         self
     }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
 }
 
 /// Response from completion
@@ -86,4 +103,12 @@ pub trait CompletionProvider: Send + Sync {
 
     /// Check if the provider is configured and ready
     fn is_configured(&self) -> bool;
+
+    /// Verify the provider actually works (key valid, endpoint reachable)
+    /// Defaults to a 1-token completion; providers with a dedicated endpoint override this
+    async fn health_check(&self) -> Result<()> {
+        let request =
+            CompletionRequest::new("ok".to_string(), WritingMode::default()).with_max_tokens(1);
+        self.complete(request).await.map(|_| ())
+    }
 }
@@ -5,23 +5,70 @@ use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use tracing::{debug, error};
 
 use crate::error::{Error, Result};
+use crate::locale::Locale;
 use crate::types::WritingMode;
 
 use super::completion::TokenUsage;
+use super::health::check_endpoint;
+use super::rate_limit::RateLimiter;
+use super::response::parse_json;
 use super::{
     CompletionProvider, CompletionRequest, CompletionResponse, TranscriptionProvider,
     TranscriptionRequest, TranscriptionResponse,
 };
 
 const GEMINI_API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta";
-const GEMINI_OPENAI_COMPAT_BASE: &str = "https://generativelanguage.googleapis.com/v1beta/openai";
+
+/// Harm category a Gemini safety setting applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum GeminiHarmCategory {
+    #[serde(rename = "HARM_CATEGORY_HARASSMENT")]
+    Harassment,
+    #[serde(rename = "HARM_CATEGORY_HATE_SPEECH")]
+    HateSpeech,
+    #[serde(rename = "HARM_CATEGORY_SEXUALLY_EXPLICIT")]
+    SexuallyExplicit,
+    #[serde(rename = "HARM_CATEGORY_DANGEROUS_CONTENT")]
+    DangerousContent,
+    #[serde(rename = "HARM_CATEGORY_CIVIC_INTEGRITY")]
+    CivicIntegrity,
+}
+
+/// How likely content must be to be harmful before Gemini blocks it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GeminiBlockThreshold {
+    BlockLowAndAbove,
+    BlockMediumAndAbove,
+    BlockOnlyHigh,
+    BlockNone,
+    Off,
+}
+
+/// A `safetySettings` entry overriding Gemini's default threshold for one category
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct GeminiSafetySetting {
+    pub category: GeminiHarmCategory,
+    pub threshold: GeminiBlockThreshold,
+}
+
+impl GeminiSafetySetting {
+    pub fn new(category: GeminiHarmCategory, threshold: GeminiBlockThreshold) -> Self {
+        Self {
+            category,
+            threshold,
+        }
+    }
+}
 
 /// Gemini transcription provider (using native API with audio input)
 pub struct GeminiTranscriptionProvider {
     client: Client,
+    rate_limiter: Option<Arc<RateLimiter>>,
     api_key: Option<String>,
     model: String,
 }
@@ -33,6 +80,7 @@ impl GeminiTranscriptionProvider {
 
         Self {
             client: Client::new(),
+            rate_limiter: None,
             api_key: key,
             model: "gemini-3-flash-preview".to_string(),
         }
@@ -44,6 +92,12 @@ impl GeminiTranscriptionProvider {
         self
     }
 
+    /// Queue requests to stay under `requests_per_minute`
+    pub fn with_rate_limit(mut self, requests_per_minute: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::per_minute(requests_per_minute)));
+        self
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
@@ -52,14 +106,21 @@ impl GeminiTranscriptionProvider {
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiGenerateContentRequest {
     contents: Vec<GeminiContent>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<GeminiContent>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    safety_settings: Vec<GeminiSafetySetting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     generation_config: Option<GeminiGenerationConfig>,
 }
 
 #[derive(Debug, Serialize)]
 struct GeminiContent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
     parts: Vec<GeminiPart>,
 }
 
@@ -82,27 +143,74 @@ struct GeminiInlineData {
     data: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiGenerationConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking_config: Option<GeminiThinkingConfig>,
+    /// Caller-supplied `generationConfig` fields passed through as-is
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiThinkingConfig {
+    thinking_budget: i32,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiGenerateContentResponse {
+    // absent when the prompt itself was blocked
+    #[serde(default)]
     candidates: Vec<GeminiCandidate>,
+    #[serde(default)]
+    prompt_feedback: Option<GeminiPromptFeedback>,
+    #[serde(default)]
+    usage_metadata: Option<GeminiUsageMetadata>,
+    #[serde(default)]
+    model_version: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiCandidate {
+    // absent when the candidate was blocked by a safety filter
+    #[serde(default)]
     content: GeminiContentResponse,
+    #[serde(default)]
+    finish_reason: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct GeminiContentResponse {
+    #[serde(default)]
     parts: Vec<GeminiPartResponse>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiPromptFeedback {
+    #[serde(default)]
+    block_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiUsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u32,
+    #[serde(default)]
+    candidates_token_count: u32,
+    #[serde(default)]
+    total_token_count: u32,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 enum GeminiPartResponse {
@@ -143,19 +251,23 @@ impl TranscriptionProvider for GeminiTranscriptionProvider {
             },
         }];
 
-        // Add prompt if provided
-        let prompt_text = if let Some(prompt) = &request.prompt {
-            prompt.clone()
-        } else {
-            "Transcribe this audio accurately. Output only the transcribed text, nothing else."
-                .to_string()
-        };
+        // The prompt primes spellings of names and jargon, like Whisper's
+        let mut prompt_text = String::from(
+            "Transcribe this audio accurately. Output only the transcribed text, nothing else.",
+        );
+        if let Some(prompt) = &request.prompt {
+            prompt_text.push_str("\n\nContext, including spellings of names and terms: ");
+            prompt_text.push_str(prompt);
+        }
         parts.insert(0, GeminiPart::Text { text: prompt_text });
 
         let generate_request = GeminiGenerateContentRequest {
-            contents: vec![GeminiContent { parts }],
+            contents: vec![GeminiContent { role: None, parts }],
+            system_instruction: None,
+            safety_settings: Vec::new(),
             generation_config: Some(GeminiGenerationConfig {
                 temperature: Some(0.0), // Low temperature for accurate transcription
+                ..Default::default()
             }),
         };
 
@@ -165,6 +277,10 @@ impl TranscriptionProvider for GeminiTranscriptionProvider {
             "{}/models/{}:generateContent?key={}",
             GEMINI_API_BASE, self.model, api_key
         );
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+
         let response = self
             .client
             .post(&url)
@@ -177,13 +293,14 @@ impl TranscriptionProvider for GeminiTranscriptionProvider {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("Gemini API error: {} - {}", status, error_text);
-            return Err(Error::Transcription(format!(
-                "Gemini API error: {} - {}",
-                status, error_text
-            )));
+            return Err(Error::from_status(
+                status,
+                format!("Gemini API error: {} - {}", status, error_text),
+                Error::Transcription,
+            ));
         }
 
-        let gemini_response: GeminiGenerateContentResponse = response.json().await?;
+        let gemini_response: GeminiGenerateContentResponse = parse_json(response).await?;
 
         let text = gemini_response
             .candidates
@@ -214,13 +331,22 @@ impl TranscriptionProvider for GeminiTranscriptionProvider {
     fn is_configured(&self) -> bool {
         self.api_key.is_some()
     }
+
+    async fn health_check(&self) -> Result<()> {
+        let api_key = self.api_key()?;
+        check_endpoint(self.name(), get_model(&self.client, &self.model, api_key)).await
+    }
 }
 
-/// Gemini completion provider (using OpenAI-compatible endpoint)
+/// Gemini completion provider (using the native generateContent API)
 pub struct GeminiCompletionProvider {
     client: Client,
+    rate_limiter: Option<Arc<RateLimiter>>,
     api_key: Option<String>,
     model: String,
+    safety_settings: Vec<GeminiSafetySetting>,
+    thinking_budget: Option<i32>,
+    generation_config: serde_json::Map<String, serde_json::Value>,
 }
 
 impl GeminiCompletionProvider {
@@ -230,8 +356,12 @@ impl GeminiCompletionProvider {
 
         Self {
             client: Client::new(),
+            rate_limiter: None,
             api_key: key,
             model: "gemini-3-flash-preview".to_string(),
+            safety_settings: Vec::new(),
+            thinking_budget: None,
+            generation_config: serde_json::Map::new(),
         }
     }
 
@@ -241,13 +371,82 @@ impl GeminiCompletionProvider {
         self
     }
 
+    /// Queue requests to stay under `requests_per_minute`
+    pub fn with_rate_limit(mut self, requests_per_minute: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::per_minute(requests_per_minute)));
+        self
+    }
+
+    /// Override Gemini's default safety thresholds, e.g. to stop medical dictation
+    /// being blocked as dangerous content. Categories not listed keep their defaults.
+    pub fn with_safety_settings(mut self, settings: Vec<GeminiSafetySetting>) -> Self {
+        self.safety_settings = settings;
+        self
+    }
+
+    /// Set the thinking budget in tokens for thinking-capable models
+    /// (0 disables thinking where the model allows it, -1 lets the model decide)
+    pub fn with_thinking_budget(mut self, budget: i32) -> Self {
+        self.thinking_budget = Some(budget);
+        self
+    }
+
+    /// Extra `generationConfig` fields sent as-is (e.g. `topP`, `stopSequences`)
+    pub fn with_generation_config(
+        mut self,
+        config: serde_json::Map<String, serde_json::Value>,
+    ) -> Self {
+        self.generation_config = config;
+        self
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
             .ok_or_else(|| Error::ProviderNotConfigured("Gemini API key not set".to_string()))
     }
 
-    fn build_system_prompt(&self, mode: WritingMode, app_context: Option<&str>) -> String {
+    fn build_request(&self, request: CompletionRequest) -> GeminiGenerateContentRequest {
+        let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
+            self.build_system_prompt(request.mode, request.locale, request.app_context.as_deref())
+        });
+
+        // Add shortcut preservation instruction if present
+        if let Some(preservation) = request.shortcut_preservation {
+            system_prompt.push_str(&preservation);
+        }
+
+        GeminiGenerateContentRequest {
+            contents: vec![GeminiContent {
+                role: Some("user".to_string()),
+                parts: vec![GeminiPart::Text {
+                    text: format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", request.text),
+                }],
+            }],
+            system_instruction: Some(GeminiContent {
+                role: None,
+                parts: vec![GeminiPart::Text {
+                    text: system_prompt,
+                }],
+            }),
+            safety_settings: self.safety_settings.clone(),
+            generation_config: Some(GeminiGenerationConfig {
+                temperature: Some(0.3), // low temperature for consistent formatting
+                max_output_tokens: request.max_tokens,
+                thinking_config: self
+                    .thinking_budget
+                    .map(|thinking_budget| GeminiThinkingConfig { thinking_budget }),
+                extra: self.generation_config.clone(),
+            }),
+        }
+    }
+
+    fn build_system_prompt(
+        &self,
+        mode: WritingMode,
+        locale: Locale,
+        app_context: Option<&str>,
+    ) -> String {
         let mut prompt = String::from(
             "You are a text formatter. The user will provide raw transcribed text wrapped in <TRANSCRIPTION> tags. \
              Reformat ONLY the text inside according to the style below. Output the reformatted text exactly as it would \
@@ -255,7 +454,7 @@ impl GeminiCompletionProvider {
         );
 
         prompt.push_str("Formatting style: ");
-        prompt.push_str(mode.prompt_modifier());
+        prompt.push_str(locale.prompt_modifier(mode));
 
         if let Some(context) = app_context {
             prompt.push_str("\n\nContext: User is typing in ");
@@ -267,45 +466,6 @@ impl GeminiCompletionProvider {
     }
 }
 
-#[derive(Debug, Serialize)]
-struct ChatRequest {
-    model: String,
-    messages: Vec<ChatMessage>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    max_tokens: Option<u32>,
-    temperature: f32,
-}
-
-#[derive(Debug, Serialize)]
-struct ChatMessage {
-    role: String,
-    content: String,
-}
-
-#[derive(Debug, Deserialize)]
-struct ChatResponse {
-    choices: Vec<ChatChoice>,
-    usage: Option<ChatUsage>,
-    model: String,
-}
-
-#[derive(Debug, Deserialize)]
-struct ChatChoice {
-    message: ChatMessageResponse,
-}
-
-#[derive(Debug, Deserialize)]
-struct ChatMessageResponse {
-    content: String,
-}
-
-#[derive(Debug, Deserialize)]
-struct ChatUsage {
-    prompt_tokens: u32,
-    completion_tokens: u32,
-    total_tokens: u32,
-}
-
 #[async_trait]
 impl CompletionProvider for GeminiCompletionProvider {
     fn name(&self) -> &'static str {
@@ -314,40 +474,24 @@ impl CompletionProvider for GeminiCompletionProvider {
 
     async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
         let api_key = self.api_key()?;
+        let model = request.model.clone().unwrap_or_else(|| self.model.clone());
+        let generate_request = self.build_request(request);
 
-        let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
-            self.build_system_prompt(request.mode, request.app_context.as_deref())
-        });
+        debug!("Sending completion request to Gemini");
 
-        // Add shortcut preservation instruction if present
-        if let Some(preservation) = request.shortcut_preservation {
-            system_prompt.push_str(&preservation);
+        let url = format!(
+            "{}/models/{}:generateContent?key={}",
+            GEMINI_API_BASE, model, api_key
+        );
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
         }
 
-        let chat_request = ChatRequest {
-            model: self.model.clone(),
-            messages: vec![
-                ChatMessage {
-                    role: "system".to_string(),
-                    content: system_prompt,
-                },
-                ChatMessage {
-                    role: "user".to_string(),
-                    content: format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", request.text),
-                },
-            ],
-            max_tokens: request.max_tokens,
-            temperature: 0.3, // low temperature for consistent formatting
-        };
-
-        debug!("Sending completion request to Gemini");
-
         let response = self
             .client
-            .post(format!("{}/chat/completions", GEMINI_OPENAI_COMPAT_BASE))
-            .header("Authorization", format!("Bearer {}", api_key))
+            .post(&url)
             .header("Content-Type", "application/json")
-            .json(&chat_request)
+            .json(&generate_request)
             .send()
             .await?;
 
@@ -355,35 +499,77 @@ impl CompletionProvider for GeminiCompletionProvider {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("Gemini API error: {} - {}", status, error_text);
+            return Err(Error::from_status(
+                status,
+                format!("Gemini API error: {} - {}", status, error_text),
+                Error::Completion,
+            ));
+        }
+
+        let gemini_response: GeminiGenerateContentResponse = parse_json(response).await?;
+        gemini_response.into_completion(model)
+    }
+
+    fn is_configured(&self) -> bool {
+        self.api_key.is_some()
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        let api_key = self.api_key()?;
+        check_endpoint(self.name(), get_model(&self.client, &self.model, api_key)).await
+    }
+}
+
+impl GeminiGenerateContentResponse {
+    /// Join the first candidate's text parts, reporting why nothing came back if it was blocked
+    fn into_completion(self, model: String) -> Result<CompletionResponse> {
+        if let Some(reason) = self.prompt_feedback.and_then(|f| f.block_reason) {
             return Err(Error::Completion(format!(
-                "Gemini API error: {} - {}",
-                status, error_text
+                "Gemini blocked the request: {reason}"
             )));
         }
 
-        let chat_response: ChatResponse = response.json().await?;
-
-        let text = chat_response
-            .choices
+        let candidate = self
+            .candidates
             .into_iter()
             .next()
-            .map(|c| c.message.content)
             .ok_or_else(|| Error::Completion("No completion returned".to_string()))?;
 
+        let text: String = candidate
+            .content
+            .parts
+            .into_iter()
+            .filter_map(|part| match part {
+                GeminiPartResponse::Text { text } => Some(text),
+                _ => None,
+            })
+            .collect();
+
+        if text.is_empty() {
+            let reason = candidate.finish_reason.as_deref().unwrap_or("unknown");
+            return Err(Error::Completion(format!(
+                "No completion returned (finish reason: {reason})"
+            )));
+        }
+
         Ok(CompletionResponse {
             text,
-            usage: chat_response.usage.map(|u| TokenUsage {
-                prompt_tokens: u.prompt_tokens,
-                completion_tokens: u.completion_tokens,
-                total_tokens: u.total_tokens,
+            usage: self.usage_metadata.map(|u| TokenUsage {
+                prompt_tokens: u.prompt_token_count,
+                completion_tokens: u.candidates_token_count,
+                total_tokens: u.total_token_count,
             }),
-            model: Some(chat_response.model),
+            model: Some(self.model_version.unwrap_or(model)),
         })
     }
+}
 
-    fn is_configured(&self) -> bool {
-        self.api_key.is_some()
-    }
+/// Fetch a model's metadata, which validates both the key and the model name for free
+fn get_model(client: &Client, model: &str, api_key: &str) -> reqwest::RequestBuilder {
+    client.get(format!(
+        "{}/models/{}?key={}",
+        GEMINI_API_BASE, model, api_key
+    ))
 }
 
 /// Convert raw PCM data to WAV format
@@ -443,13 +629,14 @@ mod tests {
     fn test_system_prompt_building() {
         let provider = GeminiCompletionProvider::new(None);
 
-        let prompt = provider.build_system_prompt(WritingMode::Formal, None);
+        let prompt = provider.build_system_prompt(WritingMode::Formal, Locale::English, None);
         assert!(prompt.contains("professional"));
         assert!(prompt.contains("Transform slang into professional alternatives"));
         assert!(prompt.contains("<TRANSCRIPTION>"));
         assert!(prompt.contains("Do NOT generate new content"));
 
-        let prompt = provider.build_system_prompt(WritingMode::VeryCasual, Some("Slack"));
+        let prompt =
+            provider.build_system_prompt(WritingMode::VeryCasual, Locale::English, Some("Slack"));
         assert!(prompt.contains("texting style"));
         assert!(prompt.contains("Slack"));
         assert!(prompt.contains("exactly as it would be typed"));
@@ -462,4 +649,93 @@ mod tests {
         // but in tests the env might be set, so we just verify the method works
         let _ = provider.is_configured();
     }
+
+    #[test]
+    fn test_request_includes_safety_settings() {
+        let provider = GeminiCompletionProvider::new(Some("key".to_string()))
+            .with_safety_settings(vec![
+                GeminiSafetySetting::new(
+                    GeminiHarmCategory::DangerousContent,
+                    GeminiBlockThreshold::BlockNone,
+                ),
+                GeminiSafetySetting::new(
+                    GeminiHarmCategory::Harassment,
+                    GeminiBlockThreshold::BlockOnlyHigh,
+                ),
+            ])
+            .with_thinking_budget(0);
+
+        let request = CompletionRequest::new("take two tablets".to_string(), WritingMode::Formal)
+            .with_max_tokens(256);
+        let body = serde_json::to_value(provider.build_request(request)).unwrap();
+
+        assert_eq!(
+            body["safetySettings"],
+            serde_json::json!([
+                {"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "BLOCK_NONE"},
+                {"category": "HARM_CATEGORY_HARASSMENT", "threshold": "BLOCK_ONLY_HIGH"},
+            ])
+        );
+        assert_eq!(
+            body["generationConfig"]["thinkingConfig"]["thinkingBudget"],
+            0
+        );
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 256);
+        assert_eq!(body["contents"][0]["role"], "user");
+        assert!(
+            body["systemInstruction"]["parts"][0]["text"]
+                .as_str()
+                .unwrap()
+                .contains("<TRANSCRIPTION>")
+        );
+    }
+
+    #[test]
+    fn test_request_omits_unset_options() {
+        let provider = GeminiCompletionProvider::new(Some("key".to_string()));
+
+        let request = CompletionRequest::new("hello".to_string(), WritingMode::Casual);
+        let body = serde_json::to_value(provider.build_request(request)).unwrap();
+
+        assert!(body.get("safetySettings").is_none());
+        let config = body["generationConfig"].as_object().unwrap();
+        assert!(config.get("thinkingConfig").is_none());
+        assert!(config.get("maxOutputTokens").is_none());
+        assert_eq!(config.len(), 1); // just the temperature
+    }
+
+    #[test]
+    fn test_generation_config_passthrough() {
+        let mut extra = serde_json::Map::new();
+        extra.insert("topP".to_string(), serde_json::json!(0.8));
+        let provider =
+            GeminiCompletionProvider::new(Some("key".to_string())).with_generation_config(extra);
+
+        let request = CompletionRequest::new("hello".to_string(), WritingMode::Casual);
+        let body = serde_json::to_value(provider.build_request(request)).unwrap();
+
+        assert_eq!(body["generationConfig"]["topP"], 0.8);
+        assert!(body["generationConfig"]["temperature"].is_number());
+    }
+
+    #[test]
+    fn test_parse_blocked_response() {
+        let blocked: GeminiGenerateContentResponse =
+            serde_json::from_str(r#"{"promptFeedback": {"blockReason": "SAFETY"}}"#).unwrap();
+        let err = blocked.into_completion("m".to_string()).unwrap_err();
+        assert!(err.to_string().contains("SAFETY"));
+
+        let ok: GeminiGenerateContentResponse = serde_json::from_str(
+            r#"{
+                "candidates": [{"content": {"parts": [{"text": "Take two "}, {"text": "tablets."}]}, "finishReason": "STOP"}],
+                "usageMetadata": {"promptTokenCount": 10, "candidatesTokenCount": 4, "totalTokenCount": 14},
+                "modelVersion": "gemini-3-flash-preview"
+            }"#,
+        )
+        .unwrap();
+        let completion = ok.into_completion("m".to_string()).unwrap();
+        assert_eq!(completion.text, "Take two tablets.");
+        assert_eq!(completion.usage.unwrap().total_tokens, 14);
+        assert_eq!(completion.model.as_deref(), Some("gemini-3-flash-preview"));
+    }
 }
@@ -0,0 +1,68 @@
+//! Shared helpers for provider health checks
+
+use reqwest::RequestBuilder;
+use tracing::error;
+
+use crate::error::{Error, Result};
+
+/// Send a lightweight request (e.g. list models) and fail unless it succeeds
+///
+/// A rejected key (401/403) surfaces as `Error::Auth` and any other rejection as
+/// `Error::Config`, which almost always means a bad base URL; an unreachable endpoint
+/// stays `Error::Network`.
+pub(crate) async fn check_endpoint(provider: &str, request: RequestBuilder) -> Result<()> {
+    let response = request.send().await?;
+    if response.status().is_success() {
+        return Ok(());
+    }
+
+    let status = response.status();
+    let error_text = response.text().await.unwrap_or_default();
+    error!(
+        "{} health check failed: {} - {}",
+        provider, status, error_text
+    );
+    Err(Error::from_status(
+        status,
+        format!(
+            "{} health check failed: {} - {}",
+            provider, status, error_text
+        ),
+        Error::Config,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Answer one request with the given status line
+    async fn respond(listener: tokio::net::TcpListener, status: &str) {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 4096];
+        let _ = socket.read(&mut buf).await.unwrap();
+        let response =
+            format!("HTTP/1.1 {status}\r\ncontent-length: 4\r\nconnection: close\r\n\r\nnope");
+        socket.write_all(response.as_bytes()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rejected_key_is_an_auth_error() {
+        for (status, code) in [
+            ("401 Unauthorized", ErrorCode::Auth),
+            ("403 Forbidden", ErrorCode::Auth),
+            ("404 Not Found", ErrorCode::InvalidInput),
+        ] {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/models", listener.local_addr().unwrap());
+            let server = tokio::spawn(respond(listener, status));
+
+            let request = reqwest::Client::new().get(url);
+            let error = check_endpoint("Test", request).await.unwrap_err();
+            assert_eq!(error.code(), code, "{status}");
+            server.await.unwrap();
+        }
+    }
+}
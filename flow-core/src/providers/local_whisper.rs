@@ -1,11 +1,14 @@
 //! Local Whisper provider using Candle with Metal + Accelerate acceleration
 //!
-//! Model tiers (fastest to best quality):
-//! - Turbo: Quantized tiny (~15MB) - ultra-fast, lowest memory, good for drafts
-//! - Fast: Tiny (~39MB) - fast, lower accuracy
-//! - Balanced: Base (~142MB) - good speed/accuracy balance
-//! - Quality: Distilled medium (~400MB) - great accuracy, still fast (recommended)
-//! - Best: Distilled large-v3 (~750MB) - best quality available
+//! Model presets (fastest to best quality):
+//! - Turbo: 8-bit quantized tiny - ultra-fast, lowest memory, good for drafts
+//! - Fast: Tiny - fast, lower accuracy
+//! - Balanced: Base - good speed/accuracy balance
+//! - Quality: Distilled medium - great accuracy, still fast (recommended)
+//! - Best: Distilled large-v3 - best quality available
+//!
+//! Quantized GGUF weights are only published for tiny (8-bit), so Turbo is the only
+//! quantized preset. No 5-bit weights are published, so they aren't offered.
 
 use crate::error::{Error, Result};
 use async_trait::async_trait;
@@ -15,128 +18,400 @@ use candle_transformers::models::whisper::{self as m, Config, audio};
 use candle_transformers::quantized_var_builder;
 use hf_hub::{Repo, RepoType, api::sync::Api};
 use parking_lot::Mutex;
+use std::future::Future;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
 use tokenizers::Tokenizer;
 use tracing::{debug, info};
 
-use super::{TranscriptionProvider, TranscriptionRequest, TranscriptionResponse};
+use super::{
+    TranscriptionProvider, TranscriptionRequest, TranscriptionResponse, TranscriptionSegment,
+};
 
 // Include the mel filter bytes (80 mel bins for Whisper)
 const MEL_FILTER_BYTES: &[u8] = include_bytes!("../../melfilters.bytes");
 
-/// Whisper model variants with clear speed/quality tradeoffs
+/// Marks the prompt as text from a previous window
+const SOT_PREV_TOKEN: &str = "<|startofprev|>";
+
+/// Half a second of silence at 16kHz, decoded once by `warm_up`
+const WARM_UP_SAMPLES: usize = 8000;
+
+/// Base URL for direct model file downloads
+const HF_BASE_URL: &str = "https://huggingface.co";
+
+/// Repo hosting GGUF-quantized Whisper weights for Candle, which only publishes them
+/// for tiny
+const QUANTIZED_REPO: &str = "lmz/candle-whisper";
+
+/// 8-bit tiny.en weights in [`QUANTIZED_REPO`]
+const QUANTIZED_TINY_WEIGHTS: &str = "model-tiny-en-q80.gguf";
+
+/// Whisper checkpoint size, smallest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WhisperSize {
+    /// tiny.en, 39M parameters
+    Tiny,
+    /// base.en, 74M parameters
+    Base,
+    /// distil-medium.en, 394M parameters
+    DistilMedium,
+    /// distil-large-v3, 756M parameters
+    DistilLargeV3,
+}
+
+impl WhisperSize {
+    /// Get all sizes, smallest first
+    pub fn all() -> &'static [WhisperSize] {
+        &[
+            WhisperSize::Tiny,
+            WhisperSize::Base,
+            WhisperSize::DistilMedium,
+            WhisperSize::DistilLargeV3,
+        ]
+    }
+
+    /// Canonical string name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WhisperSize::Tiny => "tiny",
+            WhisperSize::Base => "base",
+            WhisperSize::DistilMedium => "distil-medium",
+            WhisperSize::DistilLargeV3 => "distil-large-v3",
+        }
+    }
+
+    /// Parse a size name
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "tiny" | "tiny.en" => Some(WhisperSize::Tiny),
+            "base" | "base.en" => Some(WhisperSize::Base),
+            "distil-medium" | "distil-medium.en" | "medium" => Some(WhisperSize::DistilMedium),
+            "distil-large-v3" | "distil-large" | "large" => Some(WhisperSize::DistilLargeV3),
+            _ => None,
+        }
+    }
+
+    /// HuggingFace model ID and revision of the full-precision checkpoint
+    pub fn model_id(&self) -> (&'static str, &'static str) {
+        match self {
+            WhisperSize::Tiny => ("openai/whisper-tiny.en", "refs/pr/15"),
+            WhisperSize::Base => ("openai/whisper-base.en", "refs/pr/13"),
+            WhisperSize::DistilMedium => ("distil-whisper/distil-medium.en", "main"),
+            WhisperSize::DistilLargeV3 => ("distil-whisper/distil-large-v3", "main"),
+        }
+    }
+
+    /// Parameter count in millions
+    pub fn params_millions(&self) -> usize {
+        match self {
+            WhisperSize::Tiny => 39,
+            WhisperSize::Base => 74,
+            WhisperSize::DistilMedium => 394,
+            WhisperSize::DistilLargeV3 => 756,
+        }
+    }
+
+    /// Size of the full-precision safetensors download in MB
+    fn full_download_mb(&self) -> usize {
+        match self {
+            // openai checkpoints are stored as f32, distil-whisper as f16
+            WhisperSize::Tiny => 151,
+            WhisperSize::Base => 290,
+            WhisperSize::DistilMedium => 789,
+            WhisperSize::DistilLargeV3 => 1513,
+        }
+    }
+}
+
+/// Weight precision: quantized weights are smaller and faster but slightly less accurate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WhisperQuantization {
+    /// Full-precision safetensors weights
+    Full,
+    /// 8-bit GGUF weights, close to full accuracy
+    Q8_0,
+}
+
+impl WhisperQuantization {
+    /// Get all quantizations, most precise first
+    pub fn all() -> &'static [WhisperQuantization] {
+        &[WhisperQuantization::Full, WhisperQuantization::Q8_0]
+    }
+
+    /// Canonical string name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WhisperQuantization::Full => "full",
+            WhisperQuantization::Q8_0 => "q8_0",
+        }
+    }
+
+    /// Parse a quantization name
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "full" | "f32" | "f16" | "fp" => Some(WhisperQuantization::Full),
+            "q8_0" | "q8" | "q80" => Some(WhisperQuantization::Q8_0),
+            _ => None,
+        }
+    }
+
+    /// Bytes per weight once loaded (GGUF blocks of 32 weights share one f16 scale)
+    fn bytes_per_weight(&self) -> f64 {
+        match self {
+            // candle runs full-precision whisper in f32
+            WhisperQuantization::Full => 4.0,
+            WhisperQuantization::Q8_0 => 34.0 / 32.0,
+        }
+    }
+}
+
+/// A Whisper model: checkpoint size plus weight quantization
+///
+/// The app offers five presets, ordered by speed (fastest first):
+/// - Turbo: 8-bit quantized tiny - ultra-fast, lowest memory
+/// - Fast: Tiny - fast, lower accuracy
+/// - Balanced: Base - good speed/accuracy balance
+/// - Quality: Distilled medium - great accuracy, still fast (recommended)
+/// - Best: Distilled large-v3 - best quality available
 ///
-/// Models ordered by speed (fastest first):
-/// - Turbo: Quantized tiny (~15MB) - ultra-fast, lowest memory
-/// - Fast: Tiny (~39MB) - fast, lower accuracy
-/// - Balanced: Base (~142MB) - good speed/accuracy balance
-/// - Quality: Distilled medium (~400MB) - great accuracy, still fast (recommended)
-/// - Best: Distilled large-v3 (~750MB) - best quality available
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum WhisperModel {
-    /// Quantized tiny model (~15MB) - Ultra-fast, lowest memory usage
+/// Quantized weights are only published for tiny, so [`WhisperModel::new`] only
+/// accepts full precision for the other sizes. 5-bit (q5_0) weights aren't
+/// published for any size, so there's no 5-bit quantization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WhisperModel {
+    size: WhisperSize,
+    quantization: WhisperQuantization,
+}
+
+impl WhisperModel {
+    /// Quantized tiny model - Ultra-fast, lowest memory usage
     /// Speed: ⚡⚡⚡⚡⚡⚡ | Quality: ⭐⭐ | Memory: 💾
-    Turbo,
+    pub const TURBO: Self = Self {
+        size: WhisperSize::Tiny,
+        quantization: WhisperQuantization::Q8_0,
+    };
 
-    /// Tiny model (~39MB) - Fast, suitable for quick drafts
+    /// Tiny model - Fast, suitable for quick drafts
     /// Speed: ⚡⚡⚡⚡⚡ | Quality: ⭐⭐
-    Fast,
+    pub const FAST: Self = Self {
+        size: WhisperSize::Tiny,
+        quantization: WhisperQuantization::Full,
+    };
 
-    /// Base model (~142MB) - Good balance of speed and accuracy
+    /// Base model - Good balance of speed and accuracy
     /// Speed: ⚡⚡⚡⚡ | Quality: ⭐⭐⭐
-    Balanced,
+    pub const BALANCED: Self = Self {
+        size: WhisperSize::Base,
+        quantization: WhisperQuantization::Full,
+    };
 
-    /// Distilled medium.en (~400MB) - Great accuracy, still fast (recommended)
+    /// Distilled medium.en - Great accuracy, still fast (recommended)
     /// Speed: ⚡⚡⚡⚡ | Quality: ⭐⭐⭐⭐
-    Quality,
+    pub const QUALITY: Self = Self {
+        size: WhisperSize::DistilMedium,
+        quantization: WhisperQuantization::Full,
+    };
 
-    /// Distilled large-v3 (~750MB) - Best quality available
+    /// Distilled large-v3 - Best quality available
     /// Speed: ⚡⚡⚡ | Quality: ⭐⭐⭐⭐⭐
-    Best,
-}
+    pub const BEST: Self = Self {
+        size: WhisperSize::DistilLargeV3,
+        quantization: WhisperQuantization::Full,
+    };
+
+    /// Create a model from a size and quantization, None if no weights are published
+    /// for the pair
+    pub const fn new(size: WhisperSize, quantization: WhisperQuantization) -> Option<Self> {
+        match (size, quantization) {
+            (_, WhisperQuantization::Full) | (WhisperSize::Tiny, WhisperQuantization::Q8_0) => {
+                Some(Self { size, quantization })
+            }
+            _ => None,
+        }
+    }
 
-impl WhisperModel {
-    /// Parse model from string (supports various naming conventions)
+    /// Checkpoint size
+    pub fn size(&self) -> WhisperSize {
+        self.size
+    }
+
+    /// Weight precision
+    pub fn quantization(&self) -> WhisperQuantization {
+        self.quantization
+    }
+
+    /// Parse model from string: a preset name ("turbo", "quality"), a size ("base"),
+    /// or a size and quantization ("tiny-q8_0", "base-full")
     pub fn parse(s: &str) -> Option<Self> {
-        match s.to_lowercase().as_str() {
-            "turbo" | "quantized" | "q" => Some(WhisperModel::Turbo),
-            "fast" | "tiny" => Some(WhisperModel::Fast),
-            "balanced" | "base" => Some(WhisperModel::Balanced),
-            "quality" | "small" | "distil_balanced" | "distil-balanced" => {
-                Some(WhisperModel::Quality)
-            }
-            "best" | "distil_quality" | "distil-quality" => Some(WhisperModel::Best),
+        let s = s.to_lowercase();
+        let preset = match s.as_str() {
+            "turbo" | "quantized" | "q" => Some(Self::TURBO),
+            "fast" => Some(Self::FAST),
+            "balanced" => Some(Self::BALANCED),
+            "quality" | "small" | "distil_balanced" | "distil-balanced" => Some(Self::QUALITY),
+            "best" | "distil_quality" | "distil-quality" => Some(Self::BEST),
             _ => None,
+        };
+        if preset.is_some() {
+            return preset;
+        }
+
+        if let Some(size) = WhisperSize::parse(&s) {
+            return Self::new(size, WhisperQuantization::Full);
         }
+        let (size, quantization) = s.rsplit_once('-')?;
+        Self::new(
+            WhisperSize::parse(size)?,
+            WhisperQuantization::parse(quantization)?,
+        )
     }
 
-    /// Get the canonical string name for this model
+    /// Get the canonical string name, which is always a preset name
     pub fn as_str(&self) -> &'static str {
-        match self {
-            WhisperModel::Turbo => "turbo",
-            WhisperModel::Fast => "fast",
-            WhisperModel::Balanced => "balanced",
-            WhisperModel::Quality => "quality",
-            WhisperModel::Best => "best",
+        use WhisperQuantization::*;
+        use WhisperSize::*;
+        match (self.size, self.quantization) {
+            // only tiny is published quantized
+            (_, Q8_0) => "turbo",
+            (Tiny, Full) => "fast",
+            (Base, Full) => "balanced",
+            (DistilMedium, Full) => "quality",
+            (DistilLargeV3, Full) => "best",
         }
     }
 
-    /// HuggingFace model ID and revision
+    /// HuggingFace model ID and revision of the checkpoint (config and tokenizer
+    /// always come from here)
     pub fn model_id(&self) -> (&'static str, &'static str) {
-        match self {
-            // Quantized model from lmz/candle-whisper
-            WhisperModel::Turbo => ("lmz/candle-whisper", "main"),
-            // Standard models
-            WhisperModel::Fast => ("openai/whisper-tiny.en", "refs/pr/15"),
-            WhisperModel::Balanced => ("openai/whisper-base.en", "refs/pr/13"),
-            WhisperModel::Quality => ("distil-whisper/distil-medium.en", "main"),
-            WhisperModel::Best => ("distil-whisper/distil-large-v3", "main"),
+        self.size.model_id()
+    }
+
+    /// HuggingFace repo and revision hosting the weights file
+    pub fn weights_repo(&self) -> (&'static str, &'static str) {
+        if self.is_quantized() {
+            (QUANTIZED_REPO, "main")
+        } else {
+            self.size.model_id()
+        }
+    }
+
+    /// Name of the weights file in [`Self::weights_repo`]
+    pub fn weights_filename(&self) -> String {
+        match self.quantization {
+            WhisperQuantization::Full => "model.safetensors".to_string(),
+            WhisperQuantization::Q8_0 => QUANTIZED_TINY_WEIGHTS.to_string(),
+        }
+    }
+
+    /// Name the weights are cached under in the models directory
+    pub fn cached_weights_filename(&self) -> String {
+        if self.is_quantized() {
+            self.weights_filename()
+        } else {
+            format!("{}-model.safetensors", self.checkpoint_name())
         }
     }
 
+    /// Direct download URL of the weights file
+    pub fn download_url(&self) -> String {
+        let (repo, revision) = self.weights_repo();
+        format!(
+            "{}/{}/resolve/{}/{}",
+            HF_BASE_URL,
+            repo,
+            revision.replace('/', "%2F"),
+            self.weights_filename()
+        )
+    }
+
+    /// Paths of the cached config, tokenizer and weights in the models directory
+    pub fn cached_files(&self, models_dir: &Path) -> [PathBuf; 3] {
+        let name = self.checkpoint_name();
+        [
+            models_dir.join(format!("{}-config.json", name)),
+            models_dir.join(format!("{}-tokenizer.json", name)),
+            models_dir.join(self.cached_weights_filename()),
+        ]
+    }
+
+    /// Checkpoint name used to prefix cached config, tokenizer and weights
+    fn checkpoint_name(&self) -> &'static str {
+        let (model_id, _) = self.model_id();
+        model_id.rsplit('/').next().unwrap_or(model_id)
+    }
+
     /// Approximate download size in MB
     pub fn size_mb(&self) -> usize {
-        match self {
-            WhisperModel::Turbo => 15,
-            WhisperModel::Fast => 39,
-            WhisperModel::Balanced => 142,
-            WhisperModel::Quality => 400,
-            WhisperModel::Best => 750,
+        match self.quantization {
+            WhisperQuantization::Full => self.size.full_download_mb(),
+            quantization => (self.size.params_millions() as f64 * quantization.bytes_per_weight())
+                .round() as usize,
         }
     }
 
+    /// Rough memory needed to load and run the model, in MB
+    ///
+    /// Weights at their loaded precision, plus ~20% for activations and the decoder
+    /// cache and a fixed overhead for the mel spectrogram, tokenizer and runtime.
+    /// Meant for warning before loading a model that won't fit, not for exact budgeting.
+    pub fn estimated_ram_mb(&self) -> usize {
+        let weights_mb = self.size.params_millions() as f64 * self.quantization.bytes_per_weight();
+        (weights_mb * 1.2 + 100.0).round() as usize
+    }
+
     /// Human-readable description
     pub fn description(&self) -> &'static str {
-        match self {
-            WhisperModel::Turbo => "Ultra-fast, lowest memory (~15MB)",
-            WhisperModel::Fast => "Fast, lower accuracy (~39MB)",
-            WhisperModel::Balanced => "Good speed/accuracy balance (~142MB)",
-            WhisperModel::Quality => "Great accuracy, still fast (~400MB) [recommended]",
-            WhisperModel::Best => "Best quality available (~750MB)",
+        use WhisperQuantization::*;
+        use WhisperSize::*;
+        match (self.size, self.quantization) {
+            (_, Q8_0) => "Ultra-fast, lowest memory",
+            (Tiny, Full) => "Fast, lower accuracy",
+            (Base, Full) => "Good speed/accuracy balance",
+            (DistilMedium, Full) => "Great accuracy, still fast [recommended]",
+            (DistilLargeV3, Full) => "Best quality available",
         }
     }
 
     /// Whether this is a quantized model
     pub fn is_quantized(&self) -> bool {
-        matches!(self, WhisperModel::Turbo)
+        self.quantization != WhisperQuantization::Full
     }
 
     /// Whether this is a distilled model variant
     pub fn is_distilled(&self) -> bool {
-        matches!(self, WhisperModel::Quality | WhisperModel::Best)
+        matches!(
+            self.size,
+            WhisperSize::DistilMedium | WhisperSize::DistilLargeV3
+        )
     }
 
-    /// Get all available models
+    /// Get the app's presets, fastest first
     pub fn all() -> &'static [WhisperModel] {
         &[
-            WhisperModel::Turbo,
-            WhisperModel::Fast,
-            WhisperModel::Balanced,
-            WhisperModel::Quality,
-            WhisperModel::Best,
+            WhisperModel::TURBO,
+            WhisperModel::FAST,
+            WhisperModel::BALANCED,
+            WhisperModel::QUALITY,
+            WhisperModel::BEST,
         ]
     }
+
+    /// Get every size and quantization combination with published weights
+    pub fn variants() -> Vec<WhisperModel> {
+        WhisperSize::all()
+            .iter()
+            .flat_map(|&size| {
+                WhisperQuantization::all()
+                    .iter()
+                    .filter_map(move |&quantization| WhisperModel::new(size, quantization))
+            })
+            .collect()
+    }
+}
+
+/// Audio time covered by a number of mel frames
+fn frames_to_ms(frames: usize) -> u64 {
+    (frames * m::HOP_LENGTH * 1000 / m::SAMPLE_RATE) as u64
 }
 
 /// Model can be either quantized or full-precision
@@ -209,18 +484,8 @@ impl WhisperEngine {
         device: &Device,
     ) -> Result<(Model, Config, Tokenizer)> {
         let (config_path, tokenizer_path, weights_path) =
-            Self::ensure_normal_model_files(model_size, models_dir).await?;
-
-        // Load config
-        let config: Config = serde_json::from_str(
-            &std::fs::read_to_string(&config_path)
-                .map_err(|e| Error::Transcription(format!("Failed to read config: {}", e)))?,
-        )
-        .map_err(|e| Error::Transcription(format!("Failed to parse config: {}", e)))?;
-
-        // Load tokenizer
-        let tokenizer = Tokenizer::from_file(tokenizer_path)
-            .map_err(|e| Error::Transcription(format!("Failed to load tokenizer: {}", e)))?;
+            Self::ensure_model_files(model_size, models_dir).await?;
+        let (config, tokenizer) = Self::load_config_and_tokenizer(&config_path, &tokenizer_path)?;
 
         // Load model weights
         info!("Loading model weights...");
@@ -240,11 +505,26 @@ impl WhisperEngine {
         device: &Device,
     ) -> Result<(Model, Config, Tokenizer)> {
         let (config_path, tokenizer_path, weights_path) =
-            Self::ensure_quantized_model_files(model_size, models_dir).await?;
+            Self::ensure_model_files(model_size, models_dir).await?;
+        let (config, tokenizer) = Self::load_config_and_tokenizer(&config_path, &tokenizer_path)?;
+
+        // Load quantized model weights (GGUF format)
+        info!("Loading quantized model weights...");
+        let vb = quantized_var_builder::VarBuilder::from_gguf(&weights_path, device)
+            .map_err(|e| Error::Transcription(format!("Failed to load GGUF weights: {}", e)))?;
+        let model = m::quantized_model::Whisper::load(&vb, config.clone())
+            .map_err(|e| Error::Transcription(format!("Failed to load quantized model: {}", e)))?;
 
+        Ok((Model::Quantized(model), config, tokenizer))
+    }
+
+    fn load_config_and_tokenizer(
+        config_path: &Path,
+        tokenizer_path: &Path,
+    ) -> Result<(Config, Tokenizer)> {
         // Load config
         let config: Config = serde_json::from_str(
-            &std::fs::read_to_string(&config_path)
+            &std::fs::read_to_string(config_path)
                 .map_err(|e| Error::Transcription(format!("Failed to read config: {}", e)))?,
         )
         .map_err(|e| Error::Transcription(format!("Failed to parse config: {}", e)))?;
@@ -253,26 +533,14 @@ impl WhisperEngine {
         let tokenizer = Tokenizer::from_file(tokenizer_path)
             .map_err(|e| Error::Transcription(format!("Failed to load tokenizer: {}", e)))?;
 
-        // Load quantized model weights (GGUF format)
-        info!("Loading quantized model weights...");
-        let vb = quantized_var_builder::VarBuilder::from_gguf(&weights_path, device)
-            .map_err(|e| Error::Transcription(format!("Failed to load GGUF weights: {}", e)))?;
-        let model = m::quantized_model::Whisper::load(&vb, config.clone())
-            .map_err(|e| Error::Transcription(format!("Failed to load quantized model: {}", e)))?;
-
-        Ok((Model::Quantized(model), config, tokenizer))
+        Ok((config, tokenizer))
     }
 
-    async fn ensure_normal_model_files(
+    async fn ensure_model_files(
         model_size: WhisperModel,
         models_dir: &Path,
     ) -> Result<(PathBuf, PathBuf, PathBuf)> {
-        let (model_id, revision) = model_size.model_id();
-        let model_name = model_id.split('/').next_back().unwrap();
-
-        let config_path = models_dir.join(format!("{}-config.json", model_name));
-        let tokenizer_path = models_dir.join(format!("{}-tokenizer.json", model_name));
-        let weights_path = models_dir.join(format!("{}-model.safetensors", model_name));
+        let [config_path, tokenizer_path, weights_path] = model_size.cached_files(models_dir);
 
         // Check if all files exist
         if config_path.exists() && tokenizer_path.exists() && weights_path.exists() {
@@ -280,102 +548,70 @@ impl WhisperEngine {
             return Ok((config_path, tokenizer_path, weights_path));
         }
 
-        // Download from HuggingFace
-        info!(
-            "Downloading {} model files ({}MB)...",
-            model_id,
-            model_size.size_mb()
-        );
-
         let api = Api::new()
             .map_err(|e| Error::Transcription(format!("Failed to init HuggingFace API: {}", e)))?;
+
+        // Config and tokenizer are shared by every quantization of a checkpoint
+        let (model_id, revision) = model_size.model_id();
         let repo = api.repo(Repo::with_revision(
             model_id.to_string(),
             RepoType::Model,
             revision.to_string(),
         ));
 
-        // Download config
-        info!("Downloading config.json");
-        let config_file = repo
-            .get("config.json")
-            .map_err(|e| Error::Transcription(format!("Failed to download config: {}", e)))?;
-        std::fs::copy(&config_file, &config_path)
-            .map_err(|e| Error::Transcription(format!("Failed to save config: {}", e)))?;
-
-        // Download tokenizer
-        info!("Downloading tokenizer.json");
-        let tokenizer_file = repo
-            .get("tokenizer.json")
-            .map_err(|e| Error::Transcription(format!("Failed to download tokenizer: {}", e)))?;
-        std::fs::copy(&tokenizer_file, &tokenizer_path)
-            .map_err(|e| Error::Transcription(format!("Failed to save tokenizer: {}", e)))?;
-
-        // Download model weights
-        info!("Downloading model weights (this may take a while)");
-        let weights_file = repo
-            .get("model.safetensors")
-            .map_err(|e| Error::Transcription(format!("Failed to download weights: {}", e)))?;
-        std::fs::copy(&weights_file, &weights_path)
-            .map_err(|e| Error::Transcription(format!("Failed to save weights: {}", e)))?;
-
-        info!("Model downloaded successfully");
-
-        Ok((config_path, tokenizer_path, weights_path))
-    }
-
-    async fn ensure_quantized_model_files(
-        _model_size: WhisperModel,
-        models_dir: &Path,
-    ) -> Result<(PathBuf, PathBuf, PathBuf)> {
-        // Quantized models use different file naming from lmz/candle-whisper
-        let config_path = models_dir.join("config-tiny-en.json");
-        let tokenizer_path = models_dir.join("tokenizer-tiny-en.json");
-        let weights_path = models_dir.join("model-tiny-en-q80.gguf");
+        if !config_path.exists() {
+            info!("Downloading {} config.json", model_id);
+            let config_file = repo
+                .get("config.json")
+                .map_err(|e| Error::Transcription(format!("Failed to download config: {}", e)))?;
+            std::fs::copy(&config_file, &config_path)
+                .map_err(|e| Error::Transcription(format!("Failed to save config: {}", e)))?;
+        }
 
-        // Check if all files exist
-        if config_path.exists() && tokenizer_path.exists() && weights_path.exists() {
-            info!("Quantized model files already cached");
-            return Ok((config_path, tokenizer_path, weights_path));
+        if !tokenizer_path.exists() {
+            info!("Downloading {} tokenizer.json", model_id);
+            let tokenizer_file = repo.get("tokenizer.json").map_err(|e| {
+                Error::Transcription(format!("Failed to download tokenizer: {}", e))
+            })?;
+            std::fs::copy(&tokenizer_file, &tokenizer_path)
+                .map_err(|e| Error::Transcription(format!("Failed to save tokenizer: {}", e)))?;
         }
 
-        // Download from lmz/candle-whisper
-        info!("Downloading quantized model files (~15MB)...");
+        if !weights_path.exists() {
+            let (weights_id, weights_revision) = model_size.weights_repo();
+            let weights_name = model_size.weights_filename();
+            info!(
+                "Downloading {} from {} ({}MB, this may take a while)",
+                weights_name,
+                weights_id,
+                model_size.size_mb()
+            );
+            let weights_repo = api.repo(Repo::with_revision(
+                weights_id.to_string(),
+                RepoType::Model,
+                weights_revision.to_string(),
+            ));
+            let weights_file = weights_repo.get(&weights_name).map_err(|e| {
+                Error::Transcription(format!("Failed to download {}: {}", weights_name, e))
+            })?;
+            std::fs::copy(&weights_file, &weights_path)
+                .map_err(|e| Error::Transcription(format!("Failed to save weights: {}", e)))?;
+        }
 
-        let api = Api::new()
-            .map_err(|e| Error::Transcription(format!("Failed to init HuggingFace API: {}", e)))?;
-        let repo = api.repo(Repo::new("lmz/candle-whisper".to_string(), RepoType::Model));
-
-        // Download config
-        info!("Downloading config-tiny-en.json");
-        let config_file = repo
-            .get("config-tiny-en.json")
-            .map_err(|e| Error::Transcription(format!("Failed to download config: {}", e)))?;
-        std::fs::copy(&config_file, &config_path)
-            .map_err(|e| Error::Transcription(format!("Failed to save config: {}", e)))?;
-
-        // Download tokenizer
-        info!("Downloading tokenizer-tiny-en.json");
-        let tokenizer_file = repo
-            .get("tokenizer-tiny-en.json")
-            .map_err(|e| Error::Transcription(format!("Failed to download tokenizer: {}", e)))?;
-        std::fs::copy(&tokenizer_file, &tokenizer_path)
-            .map_err(|e| Error::Transcription(format!("Failed to save tokenizer: {}", e)))?;
-
-        // Download quantized weights (GGUF format)
-        info!("Downloading quantized model weights");
-        let weights_file = repo
-            .get("model-tiny-en-q80.gguf")
-            .map_err(|e| Error::Transcription(format!("Failed to download GGUF weights: {}", e)))?;
-        std::fs::copy(&weights_file, &weights_path)
-            .map_err(|e| Error::Transcription(format!("Failed to save weights: {}", e)))?;
-
-        info!("Quantized model downloaded successfully");
+        info!("Model downloaded successfully");
 
         Ok((config_path, tokenizer_path, weights_path))
     }
 
-    fn transcribe_pcm(&mut self, pcm_data: &[f32]) -> Result<String> {
+    /// Transcribe 16kHz mono audio into one segment per 30 second window
+    ///
+    /// A prompt (names, jargon) is fed in as previous-window text, which biases the
+    /// model towards its spellings.
+    fn transcribe_pcm(
+        &mut self,
+        pcm_data: &[f32],
+        prompt: Option<&str>,
+    ) -> Result<Vec<TranscriptionSegment>> {
         debug!("Transcribing {} samples", pcm_data.len());
 
         // Convert to mel spectrogram
@@ -398,6 +634,20 @@ impl WhisperEngine {
         let eot_token = self.token_id(m::EOT_TOKEN)?;
         let no_timestamps_token = self.token_id(m::NO_TIMESTAMPS_TOKEN)?;
 
+        let mut start_tokens = Vec::new();
+        if let Some(prompt) = prompt.filter(|p| !p.trim().is_empty()) {
+            let encoding = self
+                .tokenizer
+                .encode(format!(" {}", prompt.trim()), false)
+                .map_err(|e| Error::Transcription(format!("Failed to encode prompt: {}", e)))?;
+            // leave room for the start tokens and a full window of output
+            let max_prompt_tokens = self.config.max_target_positions / 2 - 4;
+            let ids = encoding.get_ids();
+            start_tokens.push(self.token_id(SOT_PREV_TOKEN)?);
+            start_tokens.extend_from_slice(&ids[ids.len().saturating_sub(max_prompt_tokens)..]);
+        }
+        start_tokens.extend([sot_token, transcribe_token, no_timestamps_token]);
+
         // Decode audio based on model type
         let segments = match &mut self.model {
             Model::Normal(model) => Self::decode_audio_normal(
@@ -406,10 +656,8 @@ impl WhisperEngine {
                 &self.tokenizer,
                 &self.config,
                 &self.device,
-                sot_token,
-                transcribe_token,
+                &start_tokens,
                 eot_token,
-                no_timestamps_token,
             )?,
             Model::Quantized(model) => Self::decode_audio_quantized(
                 model,
@@ -417,30 +665,23 @@ impl WhisperEngine {
                 &self.tokenizer,
                 &self.config,
                 &self.device,
-                sot_token,
-                transcribe_token,
+                &start_tokens,
                 eot_token,
-                no_timestamps_token,
             )?,
         };
 
-        // Join segments
-        let text = segments.join(" ");
-        Ok(text.trim().to_string())
+        Ok(segments)
     }
 
-    #[allow(clippy::too_many_arguments)]
     fn decode_audio_normal(
         model: &mut m::model::Whisper,
         mel: &Tensor,
         tokenizer: &Tokenizer,
         config: &Config,
         device: &Device,
-        sot_token: u32,
-        transcribe_token: u32,
+        start_tokens: &[u32],
         eot_token: u32,
-        no_timestamps_token: u32,
-    ) -> Result<Vec<String>> {
+    ) -> Result<Vec<TranscriptionSegment>> {
         let (_, _, content_frames) = mel
             .dims3()
             .map_err(|e| Error::Transcription(format!("Invalid mel dimensions: {}", e)))?;
@@ -458,7 +699,7 @@ impl WhisperEngine {
                 .forward(&mel_segment, true)
                 .map_err(|e| Error::Transcription(format!("Encoder failed: {}", e)))?;
 
-            let mut tokens = vec![sot_token, transcribe_token, no_timestamps_token];
+            let mut tokens = start_tokens.to_vec();
             let max_tokens = config.max_target_positions / 2;
 
             for i in 0..max_tokens {
@@ -502,11 +743,16 @@ impl WhisperEngine {
             }
 
             let text = tokenizer
-                .decode(&tokens[3..], true)
+                .decode(&tokens[start_tokens.len()..], true)
                 .map_err(|e| Error::Transcription(format!("Failed to decode tokens: {}", e)))?;
 
             if !text.trim().is_empty() {
-                segments.push(text.trim().to_string());
+                segments.push(TranscriptionSegment {
+                    text: text.trim().to_string(),
+                    start_ms: frames_to_ms(seek),
+                    end_ms: frames_to_ms(seek + segment_size),
+                    confidence: None,
+                });
             }
             seek += segment_size;
         }
@@ -514,18 +760,15 @@ impl WhisperEngine {
         Ok(segments)
     }
 
-    #[allow(clippy::too_many_arguments)]
     fn decode_audio_quantized(
         model: &mut m::quantized_model::Whisper,
         mel: &Tensor,
         tokenizer: &Tokenizer,
         config: &Config,
         device: &Device,
-        sot_token: u32,
-        transcribe_token: u32,
+        start_tokens: &[u32],
         eot_token: u32,
-        no_timestamps_token: u32,
-    ) -> Result<Vec<String>> {
+    ) -> Result<Vec<TranscriptionSegment>> {
         let (_, _, content_frames) = mel
             .dims3()
             .map_err(|e| Error::Transcription(format!("Invalid mel dimensions: {}", e)))?;
@@ -543,7 +786,7 @@ impl WhisperEngine {
                 .forward(&mel_segment, true)
                 .map_err(|e| Error::Transcription(format!("Encoder failed: {}", e)))?;
 
-            let mut tokens = vec![sot_token, transcribe_token, no_timestamps_token];
+            let mut tokens = start_tokens.to_vec();
             let max_tokens = config.max_target_positions / 2;
 
             for i in 0..max_tokens {
@@ -587,11 +830,16 @@ impl WhisperEngine {
             }
 
             let text = tokenizer
-                .decode(&tokens[3..], true)
+                .decode(&tokens[start_tokens.len()..], true)
                 .map_err(|e| Error::Transcription(format!("Failed to decode tokens: {}", e)))?;
 
             if !text.trim().is_empty() {
-                segments.push(text.trim().to_string());
+                segments.push(TranscriptionSegment {
+                    text: text.trim().to_string(),
+                    start_ms: frames_to_ms(seek),
+                    end_ms: frames_to_ms(seek + segment_size),
+                    confidence: None,
+                });
             }
             seek += segment_size;
         }
@@ -606,33 +854,153 @@ impl WhisperEngine {
     }
 }
 
+/// A value loaded on first use, at most once however many callers ask at the same time
+struct LoadOnce<T> {
+    state: Mutex<LoadState<T>>,
+    /// Held while loading so concurrent callers wait for the first load instead of
+    /// starting their own
+    loading: tokio::sync::Mutex<()>,
+}
+
+/// The loaded value and how many leases are using it, behind one lock so an unload
+/// never lands between another caller's load and its use
+struct LoadState<T> {
+    value: Option<T>,
+    users: usize,
+}
+
+impl<T> LoadOnce<T> {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(LoadState {
+                value: None,
+                users: 0,
+            }),
+            loading: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.state.lock().value.is_some()
+    }
+
+    /// Load the value unless it's already loaded
+    async fn get_or_load<F, Fut>(&self, load: F) -> Result<()>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.acquire(load, false).await.map(|_| ())
+    }
+
+    /// Load the value unless it's already loaded and hold it until the lease drops
+    ///
+    /// With `unload_after` the value is dropped once the last lease is gone, so
+    /// callers that need it in the meantime keep it loaded.
+    async fn acquire<F, Fut>(&self, load: F, unload_after: bool) -> Result<Lease<'_, T>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if let Some(lease) = self.lease_if_loaded(unload_after) {
+            return Ok(lease);
+        }
+
+        let _loading = self.loading.lock().await;
+        // another caller may have finished loading while we waited
+        if let Some(lease) = self.lease_if_loaded(unload_after) {
+            return Ok(lease);
+        }
+        let value = load().await?;
+        let mut state = self.state.lock();
+        state.value = Some(value);
+        state.users += 1;
+        Ok(Lease {
+            owner: self,
+            unload_after,
+        })
+    }
+
+    fn lease_if_loaded(&self, unload_after: bool) -> Option<Lease<'_, T>> {
+        let mut state = self.state.lock();
+        state.value.as_ref()?;
+        state.users += 1;
+        Some(Lease {
+            owner: self,
+            unload_after,
+        })
+    }
+}
+
+/// A loaded value that stays loaded while the lease is held
+struct Lease<'a, T> {
+    owner: &'a LoadOnce<T>,
+    unload_after: bool,
+}
+
+impl<T> Lease<'_, T> {
+    /// Use the value; calls are serialized
+    fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut state = self.owner.state.lock();
+        let value = state
+            .value
+            .as_mut()
+            .expect("leased value is only unloaded once every lease is gone");
+        f(value)
+    }
+}
+
+impl<T> Drop for Lease<'_, T> {
+    fn drop(&mut self) {
+        let mut state = self.owner.state.lock();
+        state.users -= 1;
+        if self.unload_after && state.users == 0 {
+            state.value = None;
+        }
+    }
+}
+
 /// Local Whisper transcription provider with Metal + Accelerate acceleration
 pub struct LocalWhisperTranscriptionProvider {
-    engine: Arc<Mutex<Option<WhisperEngine>>>,
+    engine: LoadOnce<WhisperEngine>,
     model_size: WhisperModel,
     models_dir: PathBuf,
+    keep_alive: bool,
 }
 
 impl LocalWhisperTranscriptionProvider {
     /// Create a new provider with a model size
     pub fn new(model_size: WhisperModel, models_dir: PathBuf) -> Self {
         Self {
-            engine: Arc::new(Mutex::new(None)),
+            engine: LoadOnce::new(),
             model_size,
             models_dir,
+            keep_alive: true,
         }
     }
 
-    /// Load the model (call once before first use)
-    pub async fn load_model(&self) -> Result<()> {
-        let engine = WhisperEngine::new(self.model_size, &self.models_dir).await?;
-        *self.engine.lock() = Some(engine);
-        Ok(())
+    /// Keep the model in memory between transcriptions (the default)
+    ///
+    /// Turning this off frees the model's memory after every transcription, at the
+    /// cost of loading it again for the next one.
+    pub fn with_keep_alive(mut self, keep_alive: bool) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Load the model into memory ahead of the first transcription
+    ///
+    /// Does nothing if it's already loaded; concurrent calls (and transcriptions)
+    /// share a single load.
+    pub async fn preload(&self) -> Result<()> {
+        self.engine
+            .get_or_load(|| WhisperEngine::new(self.model_size, &self.models_dir))
+            .await
     }
 
     /// Check if model is loaded
     pub fn is_model_loaded(&self) -> bool {
-        self.engine.lock().is_some()
+        self.engine.is_loaded()
     }
 
     /// Resample audio using linear interpolation
@@ -679,10 +1047,14 @@ impl TranscriptionProvider for LocalWhisperTranscriptionProvider {
     }
 
     async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
-        // Ensure model is loaded
-        if !self.is_model_loaded() {
-            self.load_model().await?;
-        }
+        // without keep-alive the last transcription using the model frees it
+        let engine = self
+            .engine
+            .acquire(
+                || WhisperEngine::new(self.model_size, &self.models_dir),
+                !self.keep_alive,
+            )
+            .await?;
 
         // Convert audio bytes to f32 format expected by whisper (mono at 16kHz)
         let mut audio_data = Self::pcm_bytes_to_f32(&request.audio);
@@ -693,12 +1065,15 @@ impl TranscriptionProvider for LocalWhisperTranscriptionProvider {
         }
 
         // Transcribe
-        let mut engine_guard = self.engine.lock();
-        let engine = engine_guard
-            .as_mut()
-            .ok_or_else(|| Error::Transcription("Whisper engine not initialized".to_string()))?;
+        let segments =
+            engine.with(|engine| engine.transcribe_pcm(&audio_data, request.prompt.as_deref()))?;
+        drop(engine);
 
-        let text = engine.transcribe_pcm(&audio_data)?;
+        let text = segments
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
 
         debug!("Local Whisper transcription: {}", text);
 
@@ -707,7 +1082,7 @@ impl TranscriptionProvider for LocalWhisperTranscriptionProvider {
             confidence: None,
             language: Some("en".to_string()),
             duration_ms: request.audio.len() as u64 * 1000 / request.sample_rate as u64,
-            segments: None,
+            segments: Some(segments),
             completed_text: None,
         })
     }
@@ -715,4 +1090,202 @@ impl TranscriptionProvider for LocalWhisperTranscriptionProvider {
     fn is_configured(&self) -> bool {
         self.models_dir.exists()
     }
+
+    /// Loading the model doubles as a warm-up, so the first dictation isn't delayed
+    async fn health_check(&self) -> Result<()> {
+        self.preload().await
+    }
+
+    /// Load the model and decode a moment of silence, so the first real transcription
+    /// doesn't pay for loading or for Metal compiling its kernels
+    async fn warm_up(&self) -> Result<()> {
+        let engine = self
+            .engine
+            .acquire(
+                || WhisperEngine::new(self.model_size, &self.models_dir),
+                false,
+            )
+            .await?;
+        engine.with(|engine| engine.transcribe_pcm(&[0.0; WARM_UP_SAMPLES], None))?;
+        debug!("Whisper model warmed up");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_loads_share_one_load() {
+        let model = Arc::new(LoadOnce::new());
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let model = Arc::clone(&model);
+                let loads = Arc::clone(&loads);
+                tokio::spawn(async move {
+                    model
+                        .get_or_load(|| async {
+                            loads.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                            Ok("engine")
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert!(model.is_loaded());
+
+        // a lease that frees the model on release, then a fresh load
+        drop(
+            model
+                .acquire(|| async { Ok("unused") }, true)
+                .await
+                .unwrap(),
+        );
+        assert!(!model.is_loaded());
+        model
+            .get_or_load(|| async { Ok("reloaded") })
+            .await
+            .unwrap();
+        assert_eq!(model.state.lock().value, Some("reloaded"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_load_is_retried() {
+        let model = LoadOnce::<&str>::new();
+
+        let result = model
+            .get_or_load(|| async { Err(Error::Transcription("download failed".into())) })
+            .await;
+        assert!(result.is_err());
+        assert!(!model.is_loaded());
+
+        model.get_or_load(|| async { Ok("engine") }).await.unwrap();
+        assert!(model.is_loaded());
+    }
+
+    #[tokio::test]
+    async fn test_unload_waits_for_every_lease() {
+        let model = LoadOnce::new();
+
+        // one caller frees the model when done while another is still using it
+        let unloading = model.acquire(|| async { Ok(1) }, true).await.unwrap();
+        let kept = model
+            .acquire(|| async { panic!("already loaded") }, false)
+            .await
+            .unwrap();
+        drop(unloading);
+        assert_eq!(kept.with(|value| *value), 1);
+
+        drop(kept);
+        assert!(model.is_loaded());
+        let last = model.acquire(|| async { Ok(2) }, true).await.unwrap();
+        drop(last);
+        assert!(!model.is_loaded());
+    }
+
+    #[test]
+    fn test_weights_filename_per_variant() {
+        use WhisperQuantization::*;
+        use WhisperSize::*;
+        let expected = [
+            (
+                Tiny,
+                Full,
+                "model.safetensors",
+                "whisper-tiny.en-model.safetensors",
+            ),
+            (
+                Tiny,
+                Q8_0,
+                "model-tiny-en-q80.gguf",
+                "model-tiny-en-q80.gguf",
+            ),
+            (
+                Base,
+                Full,
+                "model.safetensors",
+                "whisper-base.en-model.safetensors",
+            ),
+            (
+                DistilMedium,
+                Full,
+                "model.safetensors",
+                "distil-medium.en-model.safetensors",
+            ),
+            (
+                DistilLargeV3,
+                Full,
+                "model.safetensors",
+                "distil-large-v3-model.safetensors",
+            ),
+        ];
+        assert_eq!(expected.len(), WhisperModel::variants().len());
+
+        for (size, quantization, remote, cached) in expected {
+            let model = WhisperModel::new(size, quantization).unwrap();
+            assert_eq!(model.weights_filename(), remote, "{:?}", model);
+            assert_eq!(model.cached_weights_filename(), cached, "{:?}", model);
+        }
+
+        // nothing is published for the larger sizes quantized
+        for size in [Base, DistilMedium, DistilLargeV3] {
+            assert_eq!(WhisperModel::new(size, Q8_0), None);
+        }
+    }
+
+    #[test]
+    fn test_download_url() {
+        assert_eq!(
+            WhisperModel::FAST.download_url(),
+            "https://huggingface.co/openai/whisper-tiny.en/resolve/refs%2Fpr%2F15/model.safetensors"
+        );
+        assert_eq!(
+            WhisperModel::TURBO.download_url(),
+            "https://huggingface.co/lmz/candle-whisper/resolve/main/model-tiny-en-q80.gguf"
+        );
+    }
+
+    #[test]
+    fn test_ram_estimate_ordering() {
+        assert!(WhisperModel::FAST.estimated_ram_mb() > WhisperModel::TURBO.estimated_ram_mb());
+
+        let ram: Vec<usize> = WhisperSize::all()
+            .iter()
+            .filter_map(|&size| WhisperModel::new(size, WhisperQuantization::Full))
+            .map(|model| model.estimated_ram_mb())
+            .collect();
+        assert!(ram.windows(2).all(|w| w[0] < w[1]), "{:?}", ram);
+
+        // in the right ballpark: tiny fits anywhere, full large-v3 needs a few GB
+        assert!(WhisperModel::TURBO.estimated_ram_mb() < 300);
+        let best = WhisperModel::BEST.estimated_ram_mb();
+        assert!((3_000..5_000).contains(&best), "{}", best);
+    }
+
+    #[test]
+    fn test_parse_round_trips() {
+        for model in WhisperModel::variants() {
+            assert_eq!(WhisperModel::parse(model.as_str()), Some(model));
+        }
+        assert_eq!(WhisperModel::parse("tiny"), Some(WhisperModel::FAST));
+        assert_eq!(WhisperModel::parse("tiny-q8"), Some(WhisperModel::TURBO));
+        assert_eq!(
+            WhisperModel::parse("base-full"),
+            Some(WhisperModel::BALANCED)
+        );
+        assert_eq!(WhisperModel::parse("distil-medium-q8_0"), None);
+        assert_eq!(WhisperModel::parse("base-q5_0"), None);
+        assert_eq!(WhisperModel::parse("huge"), None);
+    }
 }
@@ -0,0 +1,501 @@
+//! Scriptable mock providers for tests
+//!
+//! Available with the `testing` feature, and always in this crate's own tests.
+//! Each mock plays back a script of responses, errors and delays, one entry per
+//! call, and records the requests it received so tests can assert on them:
+//!
+//! ```ignore
+//! use std::time::Duration;
+//! use flow::providers::MockTranscriptionProvider;
+//!
+//! let provider = MockTranscriptionProvider::new()
+//!     .with_error(flow::Error::Transcription("first try fails".into()))
+//!     .with_delayed_response(Duration::from_millis(50), "hello world");
+//!
+//! // ... run the code under test ...
+//! provider.assert_calls(2);
+//! ```
+//!
+//! Once the script runs out every call gets the fallback set with `with_fallback`,
+//! or an error if there is none, so unexpected extra calls fail loudly.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use parking_lot::Mutex;
+
+use super::{
+    CompletionChunk, CompletionProvider, CompletionRequest, CompletionResponse, CompletionStream,
+    StreamingCompletionProvider, TranscriptionProvider, TranscriptionRequest,
+    TranscriptionResponse,
+};
+use crate::error::{Error, Result};
+
+/// Name every mock reports
+const MOCK_NAME: &str = "mock";
+
+/// One scripted call
+struct Step<T> {
+    outcome: Result<T>,
+    delay: Duration,
+}
+
+/// Responses played back in order, shared by the mocks
+struct Script<T, R> {
+    steps: Mutex<VecDeque<Step<T>>>,
+    fallback: Option<T>,
+    latency: Duration,
+    requests: Mutex<Vec<R>>,
+    calls: AtomicUsize,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+/// Counts a call as in flight until dropped, so cancelled calls are counted out too
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<T: Clone, R> Script<T, R> {
+    fn new() -> Self {
+        Self {
+            steps: Mutex::new(VecDeque::new()),
+            fallback: None,
+            latency: Duration::ZERO,
+            requests: Mutex::new(Vec::new()),
+            calls: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    fn push(&mut self, outcome: Result<T>, delay: Duration) {
+        self.steps.get_mut().push_back(Step { outcome, delay });
+    }
+
+    /// Record the request, wait out the step's delay and return its outcome
+    async fn next(&self, request: R) -> Result<T> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.requests.lock().push(request);
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+        let _in_flight = InFlight(&self.in_flight);
+
+        let step = self.steps.lock().pop_front();
+        let (outcome, delay) = match step {
+            Some(step) => (step.outcome, step.delay),
+            None => match &self.fallback {
+                Some(fallback) => (Ok(fallback.clone()), Duration::ZERO),
+                None => (
+                    Err(Error::Config("mock script has no responses left".into())),
+                    Duration::ZERO,
+                ),
+            },
+        };
+
+        let delay = delay + self.latency;
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        outcome
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    fn remaining(&self) -> usize {
+        self.steps.lock().len()
+    }
+}
+
+/// Implements the builder and inspection methods shared by every mock
+macro_rules! script_methods {
+    ($request:ty, $response:ty, $to_response:expr) => {
+        /// Queue a successful response
+        pub fn with_response(self, text: impl Into<String>) -> Self {
+            self.with_delayed_response(Duration::ZERO, text)
+        }
+
+        /// Queue a successful response that arrives after `delay`
+        pub fn with_delayed_response(mut self, delay: Duration, text: impl Into<String>) -> Self {
+            let to_response: fn(String) -> $response = $to_response;
+            self.script.push(Ok(to_response(text.into())), delay);
+            self
+        }
+
+        /// Queue a failed call
+        pub fn with_error(self, error: Error) -> Self {
+            self.with_delayed_error(Duration::ZERO, error)
+        }
+
+        /// Queue a failed call that fails after `delay`
+        pub fn with_delayed_error(mut self, delay: Duration, error: Error) -> Self {
+            self.script.push(Err(error), delay);
+            self
+        }
+
+        /// Respond with `text` once the script runs out, instead of failing
+        pub fn with_fallback(mut self, text: impl Into<String>) -> Self {
+            let to_response: fn(String) -> $response = $to_response;
+            self.script.fallback = Some(to_response(text.into()));
+            self
+        }
+
+        /// Add `latency` to every call, on top of any scripted delay
+        pub fn with_latency(mut self, latency: Duration) -> Self {
+            self.script.latency = latency;
+            self
+        }
+
+        /// Report the provider as not configured
+        pub fn unconfigured(mut self) -> Self {
+            self.configured = false;
+            self
+        }
+
+        /// Number of calls made so far
+        pub fn calls(&self) -> usize {
+            self.script.calls()
+        }
+
+        /// Scripted steps not yet used
+        pub fn remaining(&self) -> usize {
+            self.script.remaining()
+        }
+
+        /// Most calls that were in flight at the same time
+        pub fn max_in_flight(&self) -> usize {
+            self.script.max_in_flight.load(Ordering::SeqCst)
+        }
+
+        /// Requests received so far, in order
+        pub fn requests(&self) -> Vec<$request> {
+            self.script.requests.lock().clone()
+        }
+
+        /// Panic unless exactly `expected` calls were made
+        #[track_caller]
+        pub fn assert_calls(&self, expected: usize) {
+            let calls = self.calls();
+            assert_eq!(
+                calls, expected,
+                "expected {expected} calls to the mock provider, got {calls}"
+            );
+        }
+    };
+}
+
+/// Transcription provider that plays back scripted responses
+pub struct MockTranscriptionProvider {
+    script: Script<TranscriptionResponse, TranscriptionRequest>,
+    configured: bool,
+    language: Option<String>,
+    completed_text: Option<String>,
+}
+
+impl MockTranscriptionProvider {
+    /// A configured provider with an empty script
+    pub fn new() -> Self {
+        Self {
+            script: Script::new(),
+            configured: true,
+            language: None,
+            completed_text: None,
+        }
+    }
+
+    /// A provider that transcribes every recording as `text`
+    pub fn returning(text: impl Into<String>) -> Self {
+        Self::new().with_fallback(text)
+    }
+
+    /// Report `language` as the detected language of every response
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Format every request that asks for completion as `text`, like the worker
+    pub fn with_completed_text(mut self, text: impl Into<String>) -> Self {
+        self.completed_text = Some(text.into());
+        self
+    }
+
+    script_methods!(TranscriptionRequest, TranscriptionResponse, |text| {
+        TranscriptionResponse {
+            text,
+            confidence: Some(1.0),
+            language: None,
+            duration_ms: 0,
+            segments: None,
+            completed_text: None,
+        }
+    });
+}
+
+impl Default for MockTranscriptionProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl TranscriptionProvider for MockTranscriptionProvider {
+    fn name(&self) -> &'static str {
+        MOCK_NAME
+    }
+
+    async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
+        let samples = request.audio.len() as u64 / 2;
+        let duration_ms = samples * 1000 / u64::from(request.sample_rate.max(1));
+        let wants_completion = request.completion.is_some();
+        let mut response = self.script.next(request).await?;
+        response.duration_ms = duration_ms;
+        if wants_completion {
+            response.completed_text = self.completed_text.clone();
+        }
+        if self.language.is_some() {
+            response.language = self.language.clone();
+        }
+        Ok(response)
+    }
+
+    fn is_configured(&self) -> bool {
+        self.configured
+    }
+}
+
+/// Completion provider that plays back scripted responses
+pub struct MockCompletionProvider {
+    script: Script<CompletionResponse, CompletionRequest>,
+    configured: bool,
+}
+
+impl MockCompletionProvider {
+    /// A configured provider with an empty script
+    pub fn new() -> Self {
+        Self {
+            script: Script::new(),
+            configured: true,
+        }
+    }
+
+    /// A provider that completes every request as `text`
+    pub fn returning(text: impl Into<String>) -> Self {
+        Self::new().with_fallback(text)
+    }
+
+    script_methods!(CompletionRequest, CompletionResponse, |text| {
+        CompletionResponse {
+            text,
+            usage: None,
+            model: Some(MOCK_NAME.to_string()),
+        }
+    });
+}
+
+impl Default for MockCompletionProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for MockCompletionProvider {
+    fn name(&self) -> &'static str {
+        MOCK_NAME
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        self.script.next(request).await
+    }
+
+    fn is_configured(&self) -> bool {
+        self.configured
+    }
+}
+
+/// Streaming completion provider that plays back scripted chunks
+///
+/// Each response is streamed as its chunks followed by a final chunk carrying the
+/// whole text. A scripted delay holds back the start of the stream.
+pub struct MockStreamingCompletionProvider {
+    script: Script<Vec<String>, CompletionRequest>,
+    configured: bool,
+    chunk_delay: Duration,
+}
+
+impl MockStreamingCompletionProvider {
+    /// A configured provider with an empty script
+    pub fn new() -> Self {
+        Self {
+            script: Script::new(),
+            configured: true,
+            chunk_delay: Duration::ZERO,
+        }
+    }
+
+    /// Queue a response streamed as the given chunks
+    pub fn with_chunks<S: Into<String>>(mut self, chunks: impl IntoIterator<Item = S>) -> Self {
+        let chunks = chunks.into_iter().map(Into::into).collect();
+        self.script.push(Ok(chunks), Duration::ZERO);
+        self
+    }
+
+    /// Wait `delay` before each chunk
+    pub fn with_chunk_delay(mut self, delay: Duration) -> Self {
+        self.chunk_delay = delay;
+        self
+    }
+
+    script_methods!(CompletionRequest, Vec<String>, |text| vec![text]);
+}
+
+impl Default for MockStreamingCompletionProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl StreamingCompletionProvider for MockStreamingCompletionProvider {
+    fn name(&self) -> &'static str {
+        MOCK_NAME
+    }
+
+    async fn complete_stream(&self, request: CompletionRequest) -> Result<CompletionStream> {
+        let chunks = self.script.next(request).await?;
+        let final_chunk = CompletionChunk {
+            text: String::new(),
+            is_final: true,
+            usage: None,
+            final_response: Some(CompletionResponse {
+                text: chunks.concat(),
+                usage: None,
+                model: Some(MOCK_NAME.to_string()),
+            }),
+        };
+        let delay = self.chunk_delay;
+
+        let chunks = chunks.into_iter().map(|text| CompletionChunk {
+            text,
+            is_final: false,
+            usage: None,
+            final_response: None,
+        });
+        let stream =
+            futures::stream::iter(chunks.chain([final_chunk])).then(move |chunk| async move {
+                if !delay.is_zero() && !chunk.is_final {
+                    tokio::time::sleep(delay).await;
+                }
+                Ok(chunk)
+            });
+        Ok(Box::pin(stream))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.configured
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::providers::collect_stream;
+    use crate::types::WritingMode;
+
+    fn transcription_request() -> TranscriptionRequest {
+        TranscriptionRequest::new(vec![0; 32_000], 16_000)
+    }
+
+    fn completion_request(text: &str) -> CompletionRequest {
+        CompletionRequest::new(text.to_string(), WritingMode::Casual)
+    }
+
+    #[tokio::test]
+    async fn test_scripted_errors_then_responses() {
+        let provider = MockTranscriptionProvider::new()
+            .with_error(Error::Transcription("first try fails".to_string()))
+            .with_response("hello world");
+
+        let first = provider.transcribe(transcription_request()).await;
+        assert!(matches!(first, Err(Error::Transcription(_))));
+        let second = provider.transcribe(transcription_request()).await.unwrap();
+        assert_eq!(second.text, "hello world");
+        assert_eq!(second.duration_ms, 1000);
+
+        // the script is used up and there is no fallback
+        assert!(provider.transcribe(transcription_request()).await.is_err());
+        provider.assert_calls(3);
+        assert_eq!(provider.remaining(), 0);
+        assert_eq!(provider.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_scripted_delays() {
+        let provider = MockCompletionProvider::new()
+            .with_delayed_response(Duration::from_millis(80), "slow")
+            .with_fallback("fast");
+
+        let start = Instant::now();
+        let slow = provider.complete(completion_request("a")).await.unwrap();
+        assert_eq!(slow.text, "slow");
+        assert!(start.elapsed() >= Duration::from_millis(80));
+
+        // a slow scripted call loses a race against a fast one
+        let slow_provider =
+            MockCompletionProvider::new().with_delayed_response(Duration::from_secs(5), "too late");
+        let winner = tokio::select! {
+            response = slow_provider.complete(completion_request("b")) => response,
+            response = provider.complete(completion_request("b")) => response,
+        };
+        assert_eq!(winner.unwrap().text, "fast");
+        assert_eq!(provider.requests()[1].text, "b");
+        provider.assert_calls(2);
+    }
+
+    #[tokio::test]
+    async fn test_max_in_flight() {
+        let provider =
+            MockCompletionProvider::returning("done").with_latency(Duration::from_millis(20));
+
+        let (a, b) = tokio::join!(
+            provider.complete(completion_request("a")),
+            provider.complete(completion_request("b")),
+        );
+        assert!(a.is_ok() && b.is_ok());
+        provider.complete(completion_request("c")).await.unwrap();
+        assert_eq!(provider.max_in_flight(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_mock() {
+        let provider = MockStreamingCompletionProvider::new()
+            .with_chunks(["Hel", "lo", "!"])
+            .with_error(Error::Completion("overloaded".to_string()))
+            .with_chunk_delay(Duration::from_millis(1));
+
+        let stream = provider
+            .complete_stream(completion_request("hi"))
+            .await
+            .unwrap();
+        assert_eq!(collect_stream(stream).await.unwrap().text, "Hello!");
+        assert!(
+            provider
+                .complete_stream(completion_request("hi"))
+                .await
+                .is_err()
+        );
+        provider.assert_calls(2);
+    }
+}
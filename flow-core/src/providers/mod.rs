@@ -1,27 +1,48 @@
 //! Provider abstraction layer for transcription and completion services
 //!
 //! Supports pluggable providers for cloud (OpenAI, ElevenLabs, Anthropic, Gemini) and local services.
+mod anthropic;
 mod auto;
+mod coalesce;
 mod completion;
 mod gemini;
+mod health;
 mod local_whisper;
+#[cfg(any(test, feature = "testing"))]
+mod mock;
 mod openai;
 mod openrouter;
+mod rate_limit;
+mod response;
 mod streaming;
 mod transcription;
 
+pub use anthropic::AnthropicCompletionProvider;
 pub use auto::{
     AutoTranscriptionProvider, CorrectionPair, CorrectionValidation, validate_corrections,
 };
+pub use coalesce::{CoalesceConfig, coalesce_stream};
 pub use completion::{CompletionProvider, CompletionRequest, CompletionResponse, TokenUsage};
-pub use gemini::{GeminiCompletionProvider, GeminiTranscriptionProvider};
-pub use local_whisper::{LocalWhisperTranscriptionProvider, WhisperModel};
+pub use gemini::{
+    GeminiBlockThreshold, GeminiCompletionProvider, GeminiHarmCategory, GeminiSafetySetting,
+    GeminiTranscriptionProvider,
+};
+pub use local_whisper::{
+    LocalWhisperTranscriptionProvider, WhisperModel, WhisperQuantization, WhisperSize,
+};
+#[cfg(any(test, feature = "testing"))]
+pub use mock::{
+    MockCompletionProvider, MockStreamingCompletionProvider, MockTranscriptionProvider,
+};
 pub use openai::{OpenAICompletionProvider, OpenAITranscriptionProvider};
 pub use openrouter::OpenRouterCompletionProvider;
+pub use rate_limit::{Clock, RateLimiter, SystemClock};
 pub use streaming::{
-    CompletionChunk, CompletionStream, StreamingCompletionProvider, collect_stream,
+    CompletionChunk, CompletionStream, ReconciledCompletion, StreamingCompletionProvider,
+    collect_stream, collect_stream_with_final,
 };
 pub use transcription::{
-    CompletionParams as TranscriptionCompletionParams, TranscriptionProvider, TranscriptionRequest,
-    TranscriptionResponse,
+    CompletionParams as TranscriptionCompletionParams, MAX_VOCABULARY_PROMPT_CHARS,
+    StreamingTranscriptionProvider, TranscriptionProvider, TranscriptionRequest,
+    TranscriptionResponse, TranscriptionSegment,
 };
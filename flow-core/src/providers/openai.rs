@@ -3,15 +3,20 @@
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use tracing::{debug, error};
 
 use crate::error::{Error, Result};
+use crate::locale::Locale;
 use crate::types::WritingMode;
 
 use super::completion::TokenUsage;
+use super::health::check_endpoint;
+use super::rate_limit::RateLimiter;
+use super::response::parse_json;
 use super::{
     CompletionProvider, CompletionRequest, CompletionResponse, TranscriptionProvider,
-    TranscriptionRequest, TranscriptionResponse,
+    TranscriptionRequest, TranscriptionResponse, TranscriptionSegment,
 };
 
 const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
@@ -19,6 +24,7 @@ const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
 /// OpenAI Whisper transcription provider
 pub struct OpenAITranscriptionProvider {
     client: Client,
+    rate_limiter: Option<Arc<RateLimiter>>,
     api_key: Option<String>,
     model: String,
     base_url: String,
@@ -31,6 +37,7 @@ impl OpenAITranscriptionProvider {
 
         Self {
             client: Client::new(),
+            rate_limiter: None,
             api_key: key,
             model: "whisper-1".to_string(),
             base_url: base_url.unwrap_or_else(|| OPENAI_API_BASE.to_string()),
@@ -43,6 +50,12 @@ impl OpenAITranscriptionProvider {
         self
     }
 
+    /// Queue requests to stay under `requests_per_minute`
+    pub fn with_rate_limit(mut self, requests_per_minute: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::per_minute(requests_per_minute)));
+        self
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
@@ -57,6 +70,38 @@ struct WhisperResponse {
     language: Option<String>,
     #[serde(default)]
     duration: Option<f64>,
+    /// Only present with `response_format=verbose_json`
+    #[serde(default)]
+    segments: Option<Vec<WhisperSegment>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WhisperSegment {
+    text: String,
+    /// Seconds from the start of the audio
+    start: f64,
+    end: f64,
+    #[serde(default)]
+    avg_logprob: Option<f64>,
+}
+
+impl From<WhisperSegment> for TranscriptionSegment {
+    fn from(segment: WhisperSegment) -> Self {
+        Self {
+            text: segment.text.trim().to_string(),
+            start_ms: (segment.start * 1000.0) as u64,
+            end_ms: (segment.end * 1000.0) as u64,
+            confidence: segment
+                .avg_logprob
+                .map(|logprob| logprob.exp().clamp(0.0, 1.0) as f32),
+        }
+    }
+}
+
+/// Whether a transcription model supports `verbose_json` (segment timings);
+/// the gpt-4o transcription models only return plain json
+fn supports_verbose_json(model: &str) -> bool {
+    model.starts_with("whisper")
 }
 
 #[async_trait]
@@ -80,7 +125,14 @@ impl TranscriptionProvider for OpenAITranscriptionProvider {
         let mut form = reqwest::multipart::Form::new()
             .part("file", file_part)
             .text("model", self.model.clone())
-            .text("response_format", "json");
+            .text(
+                "response_format",
+                if supports_verbose_json(&self.model) {
+                    "verbose_json"
+                } else {
+                    "json"
+                },
+            );
 
         if let Some(lang) = &request.language {
             form = form.text("language", lang.clone());
@@ -92,6 +144,10 @@ impl TranscriptionProvider for OpenAITranscriptionProvider {
 
         debug!("Sending transcription request to OpenAI Whisper");
 
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+
         let response = self
             .client
             .post(format!("{}/audio/transcriptions", self.base_url))
@@ -104,13 +160,14 @@ impl TranscriptionProvider for OpenAITranscriptionProvider {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("Whisper API error: {} - {}", status, error_text);
-            return Err(Error::Transcription(format!(
-                "Whisper API error: {} - {}",
-                status, error_text
-            )));
+            return Err(Error::from_status(
+                status,
+                format!("Whisper API error: {} - {}", status, error_text),
+                Error::Transcription,
+            ));
         }
 
-        let whisper_response: WhisperResponse = response.json().await?;
+        let whisper_response: WhisperResponse = parse_json(response).await?;
 
         // estimate duration from audio size if not provided
         let duration_ms = whisper_response
@@ -127,7 +184,9 @@ impl TranscriptionProvider for OpenAITranscriptionProvider {
             confidence: None, // Whisper doesn't provide confidence
             language: whisper_response.language,
             duration_ms,
-            segments: None,
+            segments: whisper_response
+                .segments
+                .map(|segments| segments.into_iter().map(Into::into).collect()),
             completed_text: None,
         })
     }
@@ -135,11 +194,21 @@ impl TranscriptionProvider for OpenAITranscriptionProvider {
     fn is_configured(&self) -> bool {
         self.api_key.is_some()
     }
+
+    async fn health_check(&self) -> Result<()> {
+        let api_key = self.api_key()?;
+        let request = self
+            .client
+            .get(format!("{}/models", self.base_url))
+            .header("Authorization", format!("Bearer {}", api_key));
+        check_endpoint(self.name(), request).await
+    }
 }
 
 /// OpenAI GPT completion provider
 pub struct OpenAICompletionProvider {
     client: Client,
+    rate_limiter: Option<Arc<RateLimiter>>,
     api_key: Option<String>,
     model: String,
     base_url: String,
@@ -152,6 +221,7 @@ impl OpenAICompletionProvider {
 
         Self {
             client: Client::new(),
+            rate_limiter: None,
             api_key: key,
             model: "gpt-4o-mini".to_string(),
             base_url: base_url.unwrap_or_else(|| OPENAI_API_BASE.to_string()),
@@ -164,13 +234,24 @@ impl OpenAICompletionProvider {
         self
     }
 
+    /// Queue requests to stay under `requests_per_minute`
+    pub fn with_rate_limit(mut self, requests_per_minute: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::per_minute(requests_per_minute)));
+        self
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
             .ok_or_else(|| Error::ProviderNotConfigured("OpenAI API key not set".to_string()))
     }
 
-    fn build_system_prompt(&self, mode: WritingMode, app_context: Option<&str>) -> String {
+    fn build_system_prompt(
+        &self,
+        mode: WritingMode,
+        locale: Locale,
+        app_context: Option<&str>,
+    ) -> String {
         let mut prompt = String::from(
             "You are a text formatter. The user will provide raw transcribed text wrapped in <TRANSCRIPTION> tags. \
              Reformat ONLY the text inside according to the style below. Output the reformatted text exactly as it would \
@@ -178,7 +259,7 @@ impl OpenAICompletionProvider {
         );
 
         prompt.push_str("Formatting style: ");
-        prompt.push_str(mode.prompt_modifier());
+        prompt.push_str(locale.prompt_modifier(mode));
 
         if let Some(context) = app_context {
             prompt.push_str("\n\nContext: User is typing in ");
@@ -239,7 +320,7 @@ impl CompletionProvider for OpenAICompletionProvider {
         let api_key = self.api_key()?;
 
         let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
-            self.build_system_prompt(request.mode, request.app_context.as_deref())
+            self.build_system_prompt(request.mode, request.locale, request.app_context.as_deref())
         });
 
         // Add shortcut preservation instruction if present
@@ -248,7 +329,7 @@ impl CompletionProvider for OpenAICompletionProvider {
         }
 
         let chat_request = ChatRequest {
-            model: self.model.clone(),
+            model: request.model.unwrap_or_else(|| self.model.clone()),
             messages: vec![
                 ChatMessage {
                     role: "system".to_string(),
@@ -265,6 +346,10 @@ impl CompletionProvider for OpenAICompletionProvider {
 
         debug!("Sending completion request to OpenAI");
 
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+
         let response = self
             .client
             .post(format!("{}/chat/completions", self.base_url))
@@ -278,13 +363,14 @@ impl CompletionProvider for OpenAICompletionProvider {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("OpenAI API error: {} - {}", status, error_text);
-            return Err(Error::Completion(format!(
-                "OpenAI API error: {} - {}",
-                status, error_text
-            )));
+            return Err(Error::from_status(
+                status,
+                format!("OpenAI API error: {} - {}", status, error_text),
+                Error::Completion,
+            ));
         }
 
-        let chat_response: ChatResponse = response.json().await?;
+        let chat_response: ChatResponse = parse_json(response).await?;
 
         let text = chat_response
             .choices
@@ -307,6 +393,15 @@ impl CompletionProvider for OpenAICompletionProvider {
     fn is_configured(&self) -> bool {
         self.api_key.is_some()
     }
+
+    async fn health_check(&self) -> Result<()> {
+        let api_key = self.api_key()?;
+        let request = self
+            .client
+            .get(format!("{}/models", self.base_url))
+            .header("Authorization", format!("Bearer {}", api_key));
+        check_endpoint(self.name(), request).await
+    }
 }
 
 /// Convert raw PCM data to WAV format
@@ -362,22 +457,119 @@ mod tests {
         assert_eq!(wav.len(), 44 + 32000);
     }
 
+    /// Accept one HTTP request, reply with a JSON body, and return the raw request
+    async fn capture_request(listener: tokio::net::TcpListener, response_body: &str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).to_lowercase();
+            let Some(header_end) = text.find("\r\n\r\n") else {
+                continue;
+            };
+            let content_length = text[..header_end]
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length: "))
+                .and_then(|len| len.trim().parse::<usize>().ok());
+            let complete = match content_length {
+                Some(len) => request.len() >= header_end + 4 + len,
+                None => text.ends_with("0\r\n\r\n"),
+            };
+            if complete || n == 0 {
+                break;
+            }
+        }
+
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            response_body.len(),
+            response_body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        String::from_utf8_lossy(&request).into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_prompt_is_sent_in_request_body() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            capture_request(listener, r#"{"text": "Deploy FlowWhispr to Kubernetes"}"#).await
+        });
+
+        let provider = OpenAITranscriptionProvider::new(Some("test-key".into()), Some(base_url));
+        let request = TranscriptionRequest::new(vec![0; 3200], 16000)
+            .with_vocabulary(&["Kubernetes", "FlowWhispr"]);
+        let response = provider.transcribe(request).await.unwrap();
+        assert_eq!(response.text, "Deploy FlowWhispr to Kubernetes");
+
+        let body = server.await.unwrap();
+        assert!(
+            body.contains("name=\"prompt\"\r\n\r\nKubernetes, FlowWhispr.\r\n"),
+            "{body}"
+        );
+    }
+
+    #[test]
+    fn test_verbose_json_segments() {
+        let response: WhisperResponse = serde_json::from_str(
+            r#"{"text": "Hello there. General Kenobi.", "language": "english", "duration": 3.2,
+                "segments": [
+                    {"id": 0, "start": 0.0, "end": 1.5, "text": " Hello there.", "avg_logprob": -0.1},
+                    {"id": 1, "start": 1.5, "end": 3.2, "text": " General Kenobi."}
+                ]}"#,
+        )
+        .unwrap();
+
+        let segments: Vec<TranscriptionSegment> = response
+            .segments
+            .unwrap()
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        assert_eq!(segments[0].text, "Hello there.");
+        assert_eq!((segments[1].start_ms, segments[1].end_ms), (1500, 3200));
+        assert!(segments[0].confidence.unwrap() > 0.9);
+        assert_eq!(segments[1].confidence, None);
+        assert!(supports_verbose_json("whisper-1"));
+        assert!(!supports_verbose_json("gpt-4o-transcribe"));
+    }
+
     #[test]
     fn test_system_prompt_building() {
         let provider = OpenAICompletionProvider::new(None, None);
 
-        let prompt = provider.build_system_prompt(WritingMode::Formal, None);
+        let prompt = provider.build_system_prompt(WritingMode::Formal, Locale::English, None);
         assert!(prompt.contains("professional"));
         assert!(prompt.contains("Transform slang into professional alternatives"));
         assert!(prompt.contains("<TRANSCRIPTION>"));
         assert!(prompt.contains("Do NOT generate new content"));
 
-        let prompt = provider.build_system_prompt(WritingMode::VeryCasual, Some("Slack"));
+        let prompt =
+            provider.build_system_prompt(WritingMode::VeryCasual, Locale::English, Some("Slack"));
         assert!(prompt.contains("texting style"));
         assert!(prompt.contains("Slack"));
         assert!(prompt.contains("exactly as it would be typed"));
     }
 
+    #[test]
+    fn test_system_prompt_follows_locale() {
+        let provider = OpenAICompletionProvider::new(None, None);
+
+        let english = provider.build_system_prompt(WritingMode::Formal, Locale::English, None);
+        let french = provider.build_system_prompt(WritingMode::Formal, Locale::French, None);
+        assert_ne!(english, french);
+        assert!(french.contains("formal, professional French"));
+        assert!(french.contains("space before ? ! : ;"));
+        assert!(!french.contains("gonna"));
+        // the formatter framing is the same in every language
+        assert!(french.contains("<TRANSCRIPTION>"));
+    }
+
     #[test]
     fn test_provider_not_configured() {
         let provider = OpenAITranscriptionProvider::new(None, None);
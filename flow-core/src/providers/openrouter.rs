@@ -3,12 +3,17 @@
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use tracing::{debug, error};
 
 use crate::error::{Error, Result};
+use crate::locale::Locale;
 use crate::types::WritingMode;
 
 use super::completion::TokenUsage;
+use super::health::check_endpoint;
+use super::rate_limit::RateLimiter;
+use super::response::parse_json;
 use super::{CompletionProvider, CompletionRequest, CompletionResponse};
 
 const OPENROUTER_API_BASE: &str = "https://openrouter.ai/api/v1";
@@ -16,6 +21,7 @@ const OPENROUTER_API_BASE: &str = "https://openrouter.ai/api/v1";
 /// OpenRouter completion provider
 pub struct OpenRouterCompletionProvider {
     client: Client,
+    rate_limiter: Option<Arc<RateLimiter>>,
     api_key: Option<String>,
     models: Vec<String>,
 }
@@ -27,6 +33,7 @@ impl OpenRouterCompletionProvider {
 
         Self {
             client: Client::new(),
+            rate_limiter: None,
             api_key: key,
             models: vec![
                 "meta-llama/llama-4-maverick:nitro".to_string(),
@@ -47,13 +54,24 @@ impl OpenRouterCompletionProvider {
         self
     }
 
+    /// Queue requests to stay under `requests_per_minute`
+    pub fn with_rate_limit(mut self, requests_per_minute: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::per_minute(requests_per_minute)));
+        self
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
             .ok_or_else(|| Error::ProviderNotConfigured("OpenRouter API key not set".to_string()))
     }
 
-    fn build_system_prompt(&self, mode: WritingMode, app_context: Option<&str>) -> String {
+    fn build_system_prompt(
+        &self,
+        mode: WritingMode,
+        locale: Locale,
+        app_context: Option<&str>,
+    ) -> String {
         let mut prompt = String::from(
             "You are a text formatter. The user will provide raw transcribed text wrapped in <TRANSCRIPTION> tags. \
              Reformat ONLY the text inside according to the style below. Output the reformatted text exactly as it would \
@@ -61,7 +79,7 @@ impl OpenRouterCompletionProvider {
         );
 
         prompt.push_str("Formatting style: ");
-        prompt.push_str(mode.prompt_modifier());
+        prompt.push_str(locale.prompt_modifier(mode));
 
         if let Some(context) = app_context {
             prompt.push_str("\n\nContext: User is typing in ");
@@ -138,7 +156,7 @@ impl CompletionProvider for OpenRouterCompletionProvider {
         let api_key = self.api_key()?;
 
         let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
-            self.build_system_prompt(request.mode, request.app_context.as_deref())
+            self.build_system_prompt(request.mode, request.locale, request.app_context.as_deref())
         });
 
         // Add shortcut preservation instruction if present
@@ -147,7 +165,10 @@ impl CompletionProvider for OpenRouterCompletionProvider {
         }
 
         let chat_request = ChatRequest {
-            models: self.models.clone(),
+            models: request
+                .model
+                .map(|model| vec![model])
+                .unwrap_or_else(|| self.models.clone()),
             messages: vec![
                 ChatMessage {
                     role: "system".to_string(),
@@ -174,6 +195,10 @@ impl CompletionProvider for OpenRouterCompletionProvider {
             self.models
         );
 
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+
         let response = self
             .client
             .post(format!("{}/chat/completions", OPENROUTER_API_BASE))
@@ -190,13 +215,14 @@ impl CompletionProvider for OpenRouterCompletionProvider {
                 .await
                 .unwrap_or_else(|_| String::from("Unknown error"));
             error!("OpenRouter API error ({}): {}", status, error_text);
-            return Err(Error::Completion(format!(
-                "OpenRouter API error ({}): {}",
-                status, error_text
-            )));
+            return Err(Error::from_status(
+                status,
+                format!("OpenRouter API error ({}): {}", status, error_text),
+                Error::Completion,
+            ));
         }
 
-        let chat_response: ChatResponse = response.json().await?;
+        let chat_response: ChatResponse = parse_json(response).await?;
 
         let text = chat_response
             .choices
@@ -222,4 +248,14 @@ impl CompletionProvider for OpenRouterCompletionProvider {
     fn is_configured(&self) -> bool {
         self.api_key.is_some()
     }
+
+    async fn health_check(&self) -> Result<()> {
+        // key info endpoint: validates the key without spending credits
+        let api_key = self.api_key()?;
+        let request = self
+            .client
+            .get(format!("{}/key", OPENROUTER_API_BASE))
+            .header("Authorization", format!("Bearer {}", api_key));
+        check_endpoint(self.name(), request).await
+    }
 }
@@ -0,0 +1,170 @@
+//! Client-side rate limiting for provider requests
+//!
+//! A token bucket holding a single request: each request reserves the next free
+//! slot, `60 / requests_per_minute` seconds after the previous one, and waits for
+//! it. Callers queue instead of failing, and concurrent callers each get their own
+//! slot, so total throughput stays under the limit however many run at once.
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Time source for the rate limiter, swappable in tests
+#[async_trait]
+pub trait Clock: Send + Sync {
+    /// Current time
+    fn now(&self) -> Instant;
+
+    /// Wait for the given duration
+    async fn sleep(&self, duration: Duration);
+}
+
+/// Wall clock backed by tokio's timer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+/// Spaces out requests to stay under a requests-per-minute limit
+pub struct RateLimiter {
+    interval: Duration,
+    /// Earliest time the next request may be sent
+    next_slot: Mutex<Option<Instant>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RateLimiter {
+    /// Limit to `requests_per_minute` (at least 1)
+    pub fn per_minute(requests_per_minute: u32) -> Self {
+        Self::with_clock(requests_per_minute, Arc::new(SystemClock))
+    }
+
+    /// Limit to `requests_per_minute` using a custom clock
+    pub fn with_clock(requests_per_minute: u32, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            interval: Duration::from_secs(60) / requests_per_minute.max(1),
+            next_slot: Mutex::new(None),
+            clock,
+        }
+    }
+
+    /// Minimum time between two requests
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Wait until a request may be sent
+    pub async fn acquire(&self) {
+        let wait = self.reserve();
+        if !wait.is_zero() {
+            self.clock.sleep(wait).await;
+        }
+    }
+
+    /// Claim the next free slot and return how long to wait for it
+    fn reserve(&self) -> Duration {
+        let now = self.clock.now();
+        let mut next_slot = self.next_slot.lock();
+        let slot = next_slot.map_or(now, |next| next.max(now));
+        *next_slot = Some(slot + self.interval);
+        slot - now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clock that only moves when slept on
+    struct MockClock {
+        start: Instant,
+        elapsed: Mutex<Duration>,
+    }
+
+    impl MockClock {
+        fn new() -> Self {
+            Self {
+                start: Instant::now(),
+                elapsed: Mutex::new(Duration::ZERO),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            self.start + *self.elapsed.lock()
+        }
+
+        async fn sleep(&self, duration: Duration) {
+            *self.elapsed.lock() += duration;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_spaced_at_limit() {
+        let clock = Arc::new(MockClock::new());
+        let limiter = RateLimiter::with_clock(60, clock.clone());
+
+        let mut sent = Vec::new();
+        for _ in 0..5 {
+            limiter.acquire().await;
+            sent.push(clock.now());
+        }
+
+        for pair in sent.windows(2) {
+            assert!(pair[1] - pair[0] >= Duration::from_secs(1));
+        }
+        assert_eq!(sent[4] - sent[0], Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn test_idle_time_is_not_banked() {
+        let clock = Arc::new(MockClock::new());
+        let limiter = RateLimiter::with_clock(60, clock.clone());
+
+        limiter.acquire().await;
+        clock.sleep(Duration::from_secs(10)).await;
+
+        // the first request after a pause goes straight out, the next one waits
+        let before = clock.now();
+        limiter.acquire().await;
+        assert_eq!(clock.now(), before);
+        limiter.acquire().await;
+        assert_eq!(clock.now() - before, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_concurrent_callers_queue() {
+        let limiter = RateLimiter::with_clock(120, Arc::new(MockClock::new()));
+
+        // callers arriving together each get the next slot instead of an error
+        let waits: Vec<_> = (0..3).map(|_| limiter.reserve()).collect();
+        assert_eq!(
+            waits,
+            vec![
+                Duration::ZERO,
+                Duration::from_millis(500),
+                Duration::from_secs(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zero_limit_is_clamped() {
+        assert_eq!(
+            RateLimiter::per_minute(0).interval(),
+            Duration::from_secs(60)
+        );
+    }
+}
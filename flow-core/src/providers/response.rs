@@ -0,0 +1,125 @@
+//! Shared parsing of provider response bodies
+//!
+//! `reqwest::Response::json` fails with a bare serde error when a proxy returns a
+//! truncated body or an HTML error page with a 200 status. These helpers keep the
+//! status and the start of the body so the error says what actually came back.
+
+use serde::de::DeserializeOwned;
+
+use crate::error::{BodyIssue, Error, Result};
+
+/// Most characters of a response body kept in an error
+pub(crate) const MAX_BODY_SNIPPET_CHARS: usize = 200;
+
+/// Read a successful response and parse its body as JSON
+pub(crate) async fn parse_json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+    let status = response.status().as_u16();
+    let body = response.text().await?;
+    parse_body(status, &body)
+}
+
+/// Parse a response body as JSON, or explain why it isn't usable
+pub(crate) fn parse_body<T: DeserializeOwned>(status: u16, body: &str) -> Result<T> {
+    serde_json::from_str(body).map_err(|e| {
+        let start = body.trim_start();
+        let issue = if start.is_empty() {
+            BodyIssue::Empty
+        } else if start.starts_with('<') {
+            BodyIssue::Html
+        } else {
+            BodyIssue::InvalidJson(e.to_string())
+        };
+        Error::Provider {
+            status,
+            issue,
+            snippet: snippet(body),
+        }
+    })
+}
+
+/// The start of a body on one line, marked with "…" if it was cut
+fn snippet(body: &str) -> String {
+    let body = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    match body.char_indices().nth(MAX_BODY_SNIPPET_CHARS) {
+        Some((cut, _)) => format!("{}…", &body[..cut]),
+        None => body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::error::ErrorCode;
+
+    #[derive(Debug, Deserialize)]
+    struct Reply {
+        #[allow(dead_code)]
+        text: String,
+    }
+
+    fn parse_error(status: u16, body: &str) -> (u16, BodyIssue, String) {
+        match parse_body::<Reply>(status, body).unwrap_err() {
+            Error::Provider {
+                status,
+                issue,
+                snippet,
+            } => (status, issue, snippet),
+            other => panic!("expected a provider error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_valid_body_parses() {
+        assert!(parse_body::<Reply>(200, r#"{"text": "hi"}"#).is_ok());
+    }
+
+    #[test]
+    fn test_empty_body() {
+        let (status, issue, snippet) = parse_error(200, "  \n");
+        assert_eq!(status, 200);
+        assert_eq!(issue, BodyIssue::Empty);
+        assert_eq!(snippet, "");
+    }
+
+    #[test]
+    fn test_html_error_page() {
+        let page = "<!DOCTYPE html>\n<html><head><title>502 Bad Gateway</title></head></html>";
+        let (status, issue, snippet) = parse_error(200, page);
+        assert_eq!(status, 200);
+        assert_eq!(issue, BodyIssue::Html);
+        assert_eq!(
+            snippet,
+            "<!DOCTYPE html> <html><head><title>502 Bad Gateway</title></head></html>"
+        );
+    }
+
+    #[test]
+    fn test_truncated_json() {
+        let (status, issue, snippet) = parse_error(200, r#"{"text": "hello wo"#);
+        assert_eq!(status, 200);
+        assert!(matches!(issue, BodyIssue::InvalidJson(_)));
+        assert_eq!(snippet, r#"{"text": "hello wo"#);
+
+        // well-formed but not the expected shape
+        let (_, issue, _) = parse_error(200, r#"{"error": "overloaded"}"#);
+        assert!(matches!(issue, BodyIssue::InvalidJson(reason) if reason.contains("text")));
+    }
+
+    #[test]
+    fn test_long_body_is_cut() {
+        let body = format!("not json {}", "é".repeat(500));
+        let (_, _, snippet) = parse_error(200, &body);
+        assert_eq!(snippet.chars().count(), MAX_BODY_SNIPPET_CHARS + 1);
+        assert!(snippet.ends_with('…'));
+
+        let error = parse_body::<Reply>(200, &body).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::Provider);
+        assert!(
+            error
+                .to_string()
+                .starts_with("Unusable provider response (HTTP 200")
+        );
+    }
+}
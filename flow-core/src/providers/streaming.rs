@@ -21,6 +21,19 @@ pub struct CompletionChunk {
     pub is_final: bool,
     /// Token usage (only available on final chunk)
     pub usage: Option<TokenUsage>,
+    /// Authoritative full response (only on final chunk, if the provider sends one)
+    pub final_response: Option<CompletionResponse>,
+}
+
+/// A streamed completion reconciled against the provider's final payload
+#[derive(Debug, Clone)]
+pub struct ReconciledCompletion {
+    /// The final response (the provider's payload if sent, otherwise the streamed text)
+    pub response: CompletionResponse,
+    /// Concatenation of all streamed chunk text
+    pub streamed_text: String,
+    /// Whether the streamed text differed from the final payload
+    pub diverged: bool,
 }
 
 /// Type alias for the boxed stream of completion chunks
@@ -204,6 +217,52 @@ pub async fn collect_stream(stream: CompletionStream) -> Result<CompletionRespon
     Ok(CompletionResponse { text, usage, model })
 }
 
+/// Collect a stream and reconcile it with the final response, if the provider sends one
+///
+/// Providers may revise earlier tokens, so the final payload wins over the streamed
+/// concatenation. `diverged` is set when the two differ.
+pub async fn collect_stream_with_final(stream: CompletionStream) -> Result<ReconciledCompletion> {
+    use futures::StreamExt;
+
+    let mut streamed_text = String::new();
+    let mut usage = None;
+    let mut final_response = None;
+
+    let mut stream = stream;
+    while let Some(chunk_result) = stream.next().await {
+        let chunk = chunk_result?;
+        streamed_text.push_str(&chunk.text);
+        if chunk.is_final {
+            usage = chunk.usage;
+            final_response = chunk.final_response;
+        }
+    }
+
+    let (response, diverged) = match final_response {
+        Some(mut response) => {
+            let diverged = response.text != streamed_text;
+            if response.usage.is_none() {
+                response.usage = usage;
+            }
+            (response, diverged)
+        }
+        None => (
+            CompletionResponse {
+                text: streamed_text.clone(),
+                usage,
+                model: None,
+            },
+            false,
+        ),
+    };
+
+    Ok(ReconciledCompletion {
+        response,
+        streamed_text,
+        diverged,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -252,4 +311,98 @@ mod tests {
             _ => panic!("Wrong event type"),
         }
     }
+
+    fn chunk(text: &str) -> CompletionChunk {
+        CompletionChunk {
+            text: text.to_string(),
+            is_final: false,
+            usage: None,
+            final_response: None,
+        }
+    }
+
+    fn usage() -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: 10,
+            completion_tokens: 5,
+            total_tokens: 15,
+        }
+    }
+
+    fn stream_of(chunks: Vec<CompletionChunk>) -> CompletionStream {
+        Box::pin(futures::stream::iter(chunks.into_iter().map(Ok)))
+    }
+
+    #[tokio::test]
+    async fn test_collect_stream_with_final_prefers_final() {
+        let final_chunk = CompletionChunk {
+            text: String::new(),
+            is_final: true,
+            usage: Some(usage()),
+            final_response: Some(CompletionResponse {
+                text: "Hello, world.".to_string(),
+                usage: Some(usage()),
+                model: Some("test-model".to_string()),
+            }),
+        };
+        let stream = stream_of(vec![chunk("Hello "), chunk("wrld"), final_chunk]);
+
+        let reconciled = collect_stream_with_final(stream).await.unwrap();
+
+        assert_eq!(reconciled.response.text, "Hello, world.");
+        assert_eq!(reconciled.streamed_text, "Hello wrld");
+        assert!(reconciled.diverged);
+        assert_eq!(reconciled.response.model, Some("test-model".to_string()));
+        assert_eq!(reconciled.response.usage.unwrap().total_tokens, 15);
+    }
+
+    #[tokio::test]
+    async fn test_collect_stream_with_final_matching() {
+        let final_chunk = CompletionChunk {
+            text: "world".to_string(),
+            is_final: true,
+            usage: Some(usage()),
+            final_response: Some(CompletionResponse {
+                text: "Hello world".to_string(),
+                usage: None,
+                model: None,
+            }),
+        };
+        let stream = stream_of(vec![chunk("Hello "), final_chunk]);
+
+        let reconciled = collect_stream_with_final(stream).await.unwrap();
+
+        assert_eq!(reconciled.response.text, "Hello world");
+        assert!(!reconciled.diverged);
+        // usage from the final chunk fills in when the payload has none
+        assert_eq!(reconciled.response.usage.unwrap().prompt_tokens, 10);
+    }
+
+    #[tokio::test]
+    async fn test_collect_stream_with_final_without_payload() {
+        let final_chunk = CompletionChunk {
+            text: "!".to_string(),
+            is_final: true,
+            usage: Some(usage()),
+            final_response: None,
+        };
+        let stream = stream_of(vec![chunk("Hi"), final_chunk]);
+
+        let reconciled = collect_stream_with_final(stream).await.unwrap();
+
+        assert_eq!(reconciled.response.text, "Hi!");
+        assert_eq!(reconciled.streamed_text, "Hi!");
+        assert!(!reconciled.diverged);
+        assert!(reconciled.response.usage.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_collect_stream_with_final_propagates_error() {
+        let stream: CompletionStream = Box::pin(futures::stream::iter(vec![
+            Ok(chunk("partial")),
+            Err(crate::error::Error::Completion("boom".to_string())),
+        ]));
+
+        assert!(collect_stream_with_final(stream).await.is_err());
+    }
 }
@@ -4,8 +4,15 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
 use crate::AudioData;
+use crate::audio::AudioFrameReceiver;
 use crate::error::Result;
 
+/// Sample rate of the silent clip used by the default health check
+const HEALTH_CHECK_SAMPLE_RATE: u32 = 16000;
+
+/// Longest vocabulary added to a prompt; Whisper only reads the last 224 tokens of it
+pub const MAX_VOCABULARY_PROMPT_CHARS: usize = 800;
+
 /// Request for transcription
 #[derive(Debug, Clone)]
 pub struct TranscriptionRequest {
@@ -15,7 +22,7 @@ pub struct TranscriptionRequest {
     pub sample_rate: u32,
     /// Optional language hint (ISO 639-1 code, e.g., "en")
     pub language: Option<String>,
-    /// Optional prompt to guide transcription
+    /// Optional prompt to guide transcription, e.g. spellings of names and jargon
     pub prompt: Option<String>,
     /// Optional completion parameters for combined transcription+completion
     pub completion: Option<CompletionParams>,
@@ -33,6 +40,8 @@ pub struct CompletionParams {
     /// Voice instruction (e.g., "reject him politely", "translate to Spanish")
     /// When present, worker uses instruction mode instead of normal formatting
     pub voice_instruction: Option<String>,
+    /// Language code to format for (e.g. "fr"), None to follow the spoken language
+    pub locale: Option<String>,
 }
 
 impl TranscriptionRequest {
@@ -60,6 +69,40 @@ impl TranscriptionRequest {
         self.completion = Some(params);
         self
     }
+
+    /// Prime the model with domain words and names ("Kubernetes, FlowWhispr."),
+    /// after any existing prompt. Words past `MAX_VOCABULARY_PROMPT_CHARS` are dropped.
+    pub fn with_vocabulary<S: AsRef<str>>(mut self, words: &[S]) -> Self {
+        let mut vocabulary = String::new();
+        for word in words.iter().map(|w| w.as_ref().trim()) {
+            if word.is_empty() {
+                continue;
+            }
+            if vocabulary.len() + word.len() + 2 > MAX_VOCABULARY_PROMPT_CHARS {
+                break;
+            }
+            if !vocabulary.is_empty() {
+                vocabulary.push_str(", ");
+            }
+            vocabulary.push_str(word);
+        }
+        if vocabulary.is_empty() {
+            return self;
+        }
+        vocabulary.push('.');
+
+        self.prompt = Some(match self.prompt.take() {
+            Some(prompt) => format!("{} {}", prompt.trim_end(), vocabulary),
+            None => vocabulary,
+        });
+        self
+    }
+}
+
+/// A tenth of a second of silence, the cheapest request every provider accepts
+pub(crate) fn health_check_request() -> TranscriptionRequest {
+    let samples = HEALTH_CHECK_SAMPLE_RATE as usize / 10;
+    TranscriptionRequest::new(vec![0; samples * 2], HEALTH_CHECK_SAMPLE_RATE)
 }
 
 /// Response from transcription
@@ -73,13 +116,33 @@ pub struct TranscriptionResponse {
     pub language: Option<String>,
     /// Duration of audio in milliseconds
     pub duration_ms: u64,
-    /// Individual word segments if available
+    /// Timed segments if the provider reports them
     pub segments: Option<Vec<TranscriptionSegment>>,
     /// Completed/formatted text if worker performed completion
     #[serde(default)]
     pub completed_text: Option<String>,
 }
 
+impl TranscriptionResponse {
+    /// Timed segments, synthesized evenly over the audio if the provider gave none
+    pub fn caption_segments(&self) -> Vec<TranscriptionSegment> {
+        match &self.segments {
+            Some(segments) if !segments.is_empty() => segments.clone(),
+            _ => crate::captions::synthesize_segments(&self.text, self.duration_ms),
+        }
+    }
+
+    /// The transcription as SubRip (.srt) subtitles
+    pub fn to_srt(&self) -> String {
+        crate::captions::to_srt(&self.caption_segments())
+    }
+
+    /// The transcription as WebVTT (.vtt) captions
+    pub fn to_vtt(&self) -> String {
+        crate::captions::to_vtt(&self.caption_segments())
+    }
+}
+
 /// A segment of transcribed text with timing
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TranscriptionSegment {
@@ -100,4 +163,81 @@ pub trait TranscriptionProvider: Send + Sync {
 
     /// Check if the provider is configured and ready
     fn is_configured(&self) -> bool;
+
+    /// Verify the provider actually works (key valid, endpoint reachable)
+    /// Defaults to transcribing a short silent clip; providers with a cheaper
+    /// dedicated endpoint override this
+    async fn health_check(&self) -> Result<()> {
+        self.transcribe(health_check_request()).await.map(|_| ())
+    }
+
+    /// Get ready for the first transcription, e.g. by loading a local model into memory
+    /// Cloud providers have nothing to prepare
+    async fn warm_up(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether transcriptions come back already formatted (`completed_text`), so no
+    /// separate completion provider is needed
+    fn includes_completion(&self) -> bool {
+        false
+    }
+}
+
+/// Trait for transcription providers that consume audio while it is being recorded
+#[async_trait]
+pub trait StreamingTranscriptionProvider: Send + Sync {
+    /// Get the provider name
+    fn name(&self) -> &'static str;
+
+    /// Transcribe PCM frames from `AudioCapture::frame_stream` as they arrive,
+    /// finishing once the channel closes
+    async fn transcribe_stream(
+        &self,
+        frames: AudioFrameReceiver,
+        sample_rate: u32,
+    ) -> Result<TranscriptionResponse>;
+
+    /// Check if the provider is configured and ready
+    fn is_configured(&self) -> bool;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vocabulary_prompt() {
+        let request = TranscriptionRequest::new(Vec::new(), 16000);
+        assert_eq!(request.clone().with_vocabulary::<&str>(&[]).prompt, None);
+        assert_eq!(
+            request
+                .clone()
+                .with_vocabulary(&["Kubernetes", " ", "FlowWhispr"])
+                .prompt
+                .as_deref(),
+            Some("Kubernetes, FlowWhispr.")
+        );
+        assert_eq!(
+            request
+                .with_prompt("Meeting notes.")
+                .with_vocabulary(&["Kubernetes"])
+                .prompt
+                .as_deref(),
+            Some("Meeting notes. Kubernetes.")
+        );
+    }
+
+    #[test]
+    fn test_vocabulary_prompt_is_capped() {
+        let words: Vec<String> = (0..500).map(|i| format!("term{i}")).collect();
+        let prompt = TranscriptionRequest::new(Vec::new(), 16000)
+            .with_vocabulary(&words)
+            .prompt
+            .unwrap();
+
+        assert!(prompt.len() <= MAX_VOCABULARY_PROMPT_CHARS);
+        assert!(prompt.starts_with("term0, term1, "));
+        assert!(prompt.ends_with('.'));
+    }
 }
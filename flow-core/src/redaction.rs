@@ -0,0 +1,427 @@
+//! Redaction filter for masking user-specified words in transcriptions
+//!
+//! Matches a word list (and optional regex patterns) case-insensitively on word
+//! boundaries and replaces every match with a mask of the same length.
+//! Example: "darn it" -> "**** it"
+
+use parking_lot::RwLock;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use tracing::debug;
+
+use crate::error::{Error, Result};
+use crate::storage::Storage;
+
+/// Default character used to mask redacted text
+pub const DEFAULT_MASK_CHAR: char = '*';
+
+/// Where in the transcription pipeline the filter runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RedactionStage {
+    /// Right after corrections, before any formatting passes
+    BeforeFormatting,
+    /// On the final text, after all formatting passes
+    #[default]
+    AfterFormatting,
+}
+
+impl RedactionStage {
+    /// Parse a stage from its stored string form
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "before_formatting" => Some(Self::BeforeFormatting),
+            "after_formatting" => Some(Self::AfterFormatting),
+            _ => None,
+        }
+    }
+
+    /// String form used for persistence
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BeforeFormatting => "before_formatting",
+            Self::AfterFormatting => "after_formatting",
+        }
+    }
+}
+
+/// Masks user-supplied words and patterns in text
+pub struct RedactionFilter {
+    /// Combined matcher for all words and patterns (None when nothing is configured)
+    matcher: RwLock<Option<Regex>>,
+    /// Masked words (lowercased)
+    words: RwLock<Vec<String>>,
+    /// Custom regex patterns
+    patterns: RwLock<Vec<String>>,
+    /// Character used to mask each redacted character
+    mask_char: char,
+    /// Pipeline stage the filter runs at
+    stage: RwLock<RedactionStage>,
+}
+
+impl RedactionFilter {
+    /// Create an empty filter
+    pub fn new() -> Self {
+        Self {
+            matcher: RwLock::new(None),
+            words: RwLock::new(Vec::new()),
+            patterns: RwLock::new(Vec::new()),
+            mask_char: DEFAULT_MASK_CHAR,
+            stage: RwLock::new(RedactionStage::default()),
+        }
+    }
+
+    /// Create a filter and load masked words and patterns from storage
+    pub fn from_storage(storage: &Storage) -> Result<Self> {
+        let filter = Self::new();
+        let words = storage.get_redacted_words()?;
+        let patterns = storage.get_redaction_patterns()?;
+        filter.load(words, patterns)?;
+        Ok(filter)
+    }
+
+    /// Use a custom mask character
+    pub fn with_mask_char(mut self, mask_char: char) -> Self {
+        self.mask_char = mask_char;
+        self
+    }
+
+    /// Get the pipeline stage the filter runs at
+    pub fn stage(&self) -> RedactionStage {
+        *self.stage.read()
+    }
+
+    /// Set the pipeline stage the filter runs at
+    pub fn set_stage(&self, stage: RedactionStage) {
+        *self.stage.write() = stage;
+    }
+
+    /// Replace all words and patterns and rebuild the matcher
+    pub fn load(&self, words: Vec<String>, patterns: Vec<String>) -> Result<()> {
+        let words: Vec<String> = words
+            .iter()
+            .map(|w| w.trim().to_lowercase())
+            .filter(|w| !w.is_empty())
+            .collect();
+        let matcher = build_matcher(&words, &patterns)?;
+
+        *self.words.write() = words;
+        *self.patterns.write() = patterns;
+        *self.matcher.write() = matcher;
+
+        debug!(
+            "Loaded {} redacted words and {} patterns",
+            self.words.read().len(),
+            self.patterns.read().len()
+        );
+        Ok(())
+    }
+
+    /// Add a masked word or phrase
+    pub fn add_word(&self, word: &str) -> Result<()> {
+        let word = word.trim().to_lowercase();
+        if word.is_empty() {
+            return Err(Error::Config("Redacted word cannot be empty".to_string()));
+        }
+
+        let mut words = self.words.write();
+        if !words.contains(&word) {
+            words.push(word);
+        }
+        drop(words);
+        self.rebuild_matcher()
+    }
+
+    /// Remove a masked word or phrase
+    pub fn remove_word(&self, word: &str) -> Result<()> {
+        let word = word.trim().to_lowercase();
+        self.words.write().retain(|w| *w != word);
+        self.rebuild_matcher()
+    }
+
+    /// Add a custom regex pattern (validated before it is added)
+    pub fn add_pattern(&self, pattern: &str) -> Result<()> {
+        Regex::new(pattern)
+            .map_err(|e| Error::Config(format!("Invalid redaction pattern: {e}")))?;
+
+        let mut patterns = self.patterns.write();
+        if !patterns.iter().any(|p| p == pattern) {
+            patterns.push(pattern.to_string());
+        }
+        drop(patterns);
+        self.rebuild_matcher()
+    }
+
+    /// Remove a custom regex pattern
+    pub fn remove_pattern(&self, pattern: &str) -> Result<()> {
+        self.patterns.write().retain(|p| p != pattern);
+        self.rebuild_matcher()
+    }
+
+    /// Get all masked words
+    pub fn words(&self) -> Vec<String> {
+        self.words.read().clone()
+    }
+
+    /// Get all custom patterns
+    pub fn patterns(&self) -> Vec<String> {
+        self.patterns.read().clone()
+    }
+
+    /// Check whether the filter has nothing to mask
+    pub fn is_empty(&self) -> bool {
+        self.matcher.read().is_none()
+    }
+
+    /// Mask all matches in text
+    /// Returns the input unchanged (borrowed) when nothing matches
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let matcher = self.matcher.read();
+        let Some(regex) = matcher.as_ref() else {
+            return Cow::Borrowed(text);
+        };
+
+        let mask_char = self.mask_char;
+        regex.replace_all(text, |caps: &Captures| mask(&caps[0], mask_char))
+    }
+
+    /// Mask text only if the filter runs at the given stage
+    pub fn apply_at<'a>(&self, stage: RedactionStage, text: &'a str) -> Cow<'a, str> {
+        if stage != self.stage() {
+            return Cow::Borrowed(text);
+        }
+        self.apply(text)
+    }
+
+    /// Rebuild the matcher from current words and patterns
+    fn rebuild_matcher(&self) -> Result<()> {
+        let matcher = build_matcher(&self.words.read(), &self.patterns.read())?;
+        *self.matcher.write() = matcher;
+        Ok(())
+    }
+}
+
+impl Default for RedactionFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Build a single case-insensitive regex that matches any word or pattern
+fn build_matcher(words: &[String], patterns: &[String]) -> Result<Option<Regex>> {
+    if words.is_empty() && patterns.is_empty() {
+        return Ok(None);
+    }
+
+    // longest words first so phrases win over the words they contain
+    let mut sorted: Vec<&String> = words.iter().collect();
+    sorted.sort_by_key(|w| std::cmp::Reverse(w.len()));
+
+    let alternatives: Vec<String> = sorted
+        .into_iter()
+        .map(|w| word_pattern(w))
+        .chain(patterns.iter().map(|p| format!("(?:{p})")))
+        .collect();
+
+    let source = format!("(?i){}", alternatives.join("|"));
+    Regex::new(&source)
+        .map(Some)
+        .map_err(|e| Error::Config(format!("Invalid redaction pattern: {e}")))
+}
+
+/// Escape a word or phrase and anchor it on word boundaries
+fn word_pattern(word: &str) -> String {
+    let body = word
+        .split_whitespace()
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(r"\s+");
+
+    // \b only works next to word characters, so skip it for words like "f@#k!"
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let start = if word.chars().next().is_some_and(is_word_char) {
+        r"\b"
+    } else {
+        ""
+    };
+    let end = if word.chars().next_back().is_some_and(is_word_char) {
+        r"\b"
+    } else {
+        ""
+    };
+
+    format!("{start}{body}{end}")
+}
+
+/// Replace every non-whitespace character with the mask character
+fn mask(matched: &str, mask_char: char) -> String {
+    matched
+        .chars()
+        .map(|c| if c.is_whitespace() { c } else { mask_char })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter_with(words: &[&str]) -> RedactionFilter {
+        let filter = RedactionFilter::new();
+        for word in words {
+            filter.add_word(word).unwrap();
+        }
+        filter
+    }
+
+    #[test]
+    fn test_empty_filter_is_noop() {
+        let filter = RedactionFilter::new();
+        assert!(filter.is_empty());
+
+        let result = filter.apply("nothing to see here");
+        assert!(matches!(result, Cow::Borrowed(_)));
+        assert_eq!(result, "nothing to see here");
+    }
+
+    #[test]
+    fn test_masks_word_preserving_surrounding_text() {
+        let filter = filter_with(&["darn"]);
+
+        let result = filter.apply("Oh darn, I forgot my homework.");
+        assert_eq!(result, "Oh ****, I forgot my homework.");
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let filter = filter_with(&["Darn"]);
+
+        assert_eq!(filter.apply("DARN it"), "**** it");
+        assert_eq!(filter.apply("darn it"), "**** it");
+    }
+
+    #[test]
+    fn test_respects_word_boundaries() {
+        let filter = filter_with(&["ass"]);
+
+        assert_eq!(
+            filter.apply("pass the class assignment"),
+            "pass the class assignment"
+        );
+        assert_eq!(filter.apply("you ass!"), "you ***!");
+    }
+
+    #[test]
+    fn test_no_match_returns_borrowed() {
+        let filter = filter_with(&["darn"]);
+
+        let result = filter.apply("all clean");
+        assert!(matches!(result, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_masks_phrase_keeping_whitespace() {
+        let filter = filter_with(&["shut up"]);
+
+        assert_eq!(filter.apply("please shut  up now"), "please ****  ** now");
+    }
+
+    #[test]
+    fn test_non_word_characters_in_word() {
+        let filter = filter_with(&["f@#k"]);
+
+        assert_eq!(filter.apply("what the f@#k"), "what the ****");
+    }
+
+    #[test]
+    fn test_regex_pattern() {
+        let filter = RedactionFilter::new();
+        filter.add_pattern(r"\b\d{3}-\d{4}\b").unwrap();
+
+        assert_eq!(filter.apply("call 555-1234 now"), "call ******** now");
+    }
+
+    #[test]
+    fn test_invalid_pattern_rejected() {
+        let filter = RedactionFilter::new();
+
+        assert!(filter.add_pattern("(unclosed").is_err());
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    fn test_remove_word() {
+        let filter = filter_with(&["darn", "heck"]);
+        filter.remove_word("DARN").unwrap();
+
+        assert_eq!(filter.words(), vec!["heck".to_string()]);
+        assert_eq!(filter.apply("darn heck"), "darn ****");
+
+        filter.remove_word("heck").unwrap();
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_words_ignored() {
+        let filter = filter_with(&["darn", "Darn"]);
+        assert_eq!(filter.words().len(), 1);
+    }
+
+    #[test]
+    fn test_empty_word_rejected() {
+        let filter = RedactionFilter::new();
+        assert!(filter.add_word("   ").is_err());
+    }
+
+    #[test]
+    fn test_custom_mask_char() {
+        let filter = RedactionFilter::new().with_mask_char('#');
+        filter.add_word("darn").unwrap();
+
+        assert_eq!(filter.apply("darn"), "####");
+    }
+
+    #[test]
+    fn test_apply_at_stage() {
+        let filter = filter_with(&["darn"]);
+
+        assert_eq!(filter.stage(), RedactionStage::AfterFormatting);
+        assert_eq!(
+            filter.apply_at(RedactionStage::BeforeFormatting, "darn"),
+            "darn"
+        );
+        assert_eq!(
+            filter.apply_at(RedactionStage::AfterFormatting, "darn"),
+            "****"
+        );
+
+        filter.set_stage(RedactionStage::BeforeFormatting);
+        assert_eq!(
+            filter.apply_at(RedactionStage::BeforeFormatting, "darn"),
+            "****"
+        );
+        assert_eq!(
+            filter.apply_at(RedactionStage::AfterFormatting, "darn"),
+            "darn"
+        );
+    }
+
+    #[test]
+    fn test_stage_roundtrip() {
+        for stage in [
+            RedactionStage::BeforeFormatting,
+            RedactionStage::AfterFormatting,
+        ] {
+            assert_eq!(RedactionStage::parse(stage.as_str()), Some(stage));
+        }
+        assert_eq!(RedactionStage::parse("unknown"), None);
+    }
+
+    #[test]
+    fn test_from_storage() {
+        let storage = Storage::in_memory().unwrap();
+        storage.add_redacted_word("darn").unwrap();
+        storage.add_redaction_pattern(r"\d{4}").unwrap();
+
+        let filter = RedactionFilter::from_storage(&storage).unwrap();
+        assert_eq!(filter.apply("darn 1234"), "**** ****");
+    }
+}
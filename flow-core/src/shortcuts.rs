@@ -2,21 +2,34 @@
 //!
 //! Allows users to define trigger phrases that expand to replacement text.
 //! Example: "my linkedin" -> "jsn.cam/li"
+//!
+//! Fuzzy matching can optionally catch triggers the transcription got slightly
+//! wrong ("insert signatures" for "insert signature"). It compares whole phrases of
+//! the trigger's word count by normalized edit distance and is off by default, since
+//! a loose threshold turns ordinary speech into shortcuts.
 
 use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
 use parking_lot::RwLock;
+use strsim::normalized_levenshtein;
 use tracing::debug;
 
 use crate::error::Result;
 use crate::storage::Storage;
+use crate::tokenizer::word_spans;
 use crate::types::Shortcut;
 
+/// Similarity a phrase needs to fuzzily match a trigger, when fuzzy matching is on
+/// One wrong letter in a two-word trigger scores about 0.94.
+pub const DEFAULT_FUZZY_THRESHOLD: f64 = 0.85;
+
 /// Engine for processing voice shortcuts with O(n) multi-pattern matching
 pub struct ShortcutsEngine {
     /// Aho-Corasick automaton for pattern matching
     automaton: RwLock<Option<AhoCorasick>>,
     /// Map from pattern index to shortcut
     shortcuts: RwLock<Vec<Shortcut>>,
+    /// Minimum similarity for fuzzy matches (None = exact matches only)
+    fuzzy_threshold: RwLock<Option<f64>>,
 }
 
 impl ShortcutsEngine {
@@ -25,9 +38,21 @@ impl ShortcutsEngine {
         Self {
             automaton: RwLock::new(None),
             shortcuts: RwLock::new(Vec::new()),
+            fuzzy_threshold: RwLock::new(None),
         }
     }
 
+    /// Also match phrases within `threshold` similarity (0.0-1.0) of a trigger,
+    /// or only exact triggers with None
+    pub fn set_fuzzy_threshold(&self, threshold: Option<f64>) {
+        *self.fuzzy_threshold.write() = threshold.map(|t| t.clamp(0.0, 1.0));
+    }
+
+    /// Minimum similarity for fuzzy matches, None when fuzzy matching is off
+    pub fn fuzzy_threshold(&self) -> Option<f64> {
+        *self.fuzzy_threshold.read()
+    }
+
     /// Create engine and load shortcuts from storage
     pub fn from_storage(storage: &Storage) -> Result<Self> {
         let engine = Self::new();
@@ -126,8 +151,26 @@ impl ShortcutsEngine {
         // work with lowercase for matching but preserve original positions
         let text_lower = text.to_lowercase();
 
-        // find all matches
-        let matches: Vec<_> = ac.find_iter(&text_lower).collect();
+        // find all matches as (start, end, shortcut index)
+        let mut matches: Vec<(usize, usize, usize)> = ac
+            .find_iter(&text_lower)
+            .map(|m| (m.start(), m.end(), m.pattern().as_usize()))
+            .collect();
+
+        // fuzzy matches can only fill the gaps between exact ones. An exact match
+        // inside a longer word ("signature" in "signatures") is left to the fuzzy
+        // pass, which replaces the whole word.
+        if let Some(threshold) = self.fuzzy_threshold() {
+            matches.retain(|&(start, end, _)| is_word_boundary(text, start, end));
+            let mut fuzzy = Vec::new();
+            let mut gap_start = 0;
+            for &(start, end, _) in matches.iter().chain([(text.len(), text.len(), 0)].iter()) {
+                fuzzy.extend(fuzzy_matches(text, gap_start, start, &shortcuts, threshold));
+                gap_start = end;
+            }
+            matches.extend(fuzzy);
+            matches.sort_by_key(|&(start, _, _)| start);
+        }
 
         if matches.is_empty() {
             return (text.to_string(), Vec::new());
@@ -137,11 +180,11 @@ impl ShortcutsEngine {
         let mut result = String::with_capacity(text.len());
         let mut last_end = 0;
 
-        for m in &matches {
-            let shortcut = &shortcuts[m.pattern().as_usize()];
+        for &(start, end, index) in &matches {
+            let shortcut = &shortcuts[index];
 
             // add text before this match
-            result.push_str(&text[last_end..m.start()]);
+            result.push_str(&text[last_end..start]);
 
             // add replacement
             result.push_str(&shortcut.replacement);
@@ -149,10 +192,10 @@ impl ShortcutsEngine {
             triggered.push(TriggeredShortcut {
                 trigger: shortcut.trigger.clone(),
                 replacement: shortcut.replacement.clone(),
-                position: m.start(),
+                position: start,
             });
 
-            last_end = m.end();
+            last_end = end;
         }
 
         // add remaining text
@@ -183,6 +226,79 @@ impl ShortcutsEngine {
     }
 }
 
+/// Whether `text[start..end]` neither starts nor ends in the middle of a word
+fn is_word_boundary(text: &str, start: usize, end: usize) -> bool {
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+    !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+}
+
+/// Fuzzy trigger matches in `text[start..end]`, as (start, end, shortcut index)
+///
+/// Each run of words is compared, punctuation trimmed, to every case-insensitive
+/// trigger with the same number of words. The most similar trigger at or above the
+/// threshold wins and matching continues after it.
+fn fuzzy_matches(
+    text: &str,
+    start: usize,
+    end: usize,
+    shortcuts: &[Shortcut],
+    threshold: f64,
+) -> Vec<(usize, usize, usize)> {
+    let words: Vec<(usize, usize)> = word_spans(&text[start..end])
+        .into_iter()
+        .filter_map(|(s, e)| {
+            let word = &text[start + s..start + e];
+            let core = word.trim_matches(|c: char| !c.is_alphanumeric());
+            let offset = word.find(core)?;
+            (!core.is_empty()).then(|| (start + s + offset, start + s + offset + core.len()))
+        })
+        .collect();
+    let triggers: Vec<Option<(String, usize)>> = shortcuts
+        .iter()
+        .map(|s| {
+            let words: Vec<String> = s
+                .trigger
+                .split_whitespace()
+                .map(str::to_lowercase)
+                .collect();
+            (!s.case_sensitive && !words.is_empty()).then(|| (words.join(" "), words.len()))
+        })
+        .collect();
+
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let mut best: Option<(f64, usize, usize)> = None;
+        for (index, trigger) in triggers.iter().enumerate() {
+            let Some((trigger, len)) = trigger else {
+                continue;
+            };
+            let Some(window) = words.get(i..i + len) else {
+                continue;
+            };
+            let phrase = window
+                .iter()
+                .map(|&(s, e)| text[s..e].to_lowercase())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let score = normalized_levenshtein(&phrase, trigger);
+            if score >= threshold && best.is_none_or(|(best_score, _, _)| score > best_score) {
+                best = Some((score, *len, index));
+            }
+        }
+
+        match best {
+            Some((_, len, index)) => {
+                matches.push((words[i].0, words[i + len - 1].1, index));
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+    matches
+}
+
 impl Default for ShortcutsEngine {
     fn default() -> Self {
         Self::new()
@@ -519,6 +635,51 @@ mod tests {
         assert!(engine.contains_shortcuts("TEST"));
     }
 
+    #[test]
+    fn test_fuzzy_matching() {
+        let engine = ShortcutsEngine::new();
+        engine.add_shortcut(Shortcut::new(
+            "insert signature".to_string(),
+            "Best,\nJason".to_string(),
+        ));
+
+        // off by default
+        let (result, triggered) = engine.process("please insert signiture");
+        assert_eq!(result, "please insert signiture");
+        assert!(triggered.is_empty());
+
+        engine.set_fuzzy_threshold(Some(DEFAULT_FUZZY_THRESHOLD));
+        let (result, triggered) = engine.process("please Insert signatures.");
+        assert_eq!(result, "please Best,\nJason.");
+        assert_eq!(triggered.len(), 1);
+        assert_eq!(triggered[0].trigger, "insert signature");
+        assert_eq!(triggered[0].position, 7);
+        let (result, _) = engine.process("insert signiture");
+        assert_eq!(result, "Best,\nJason");
+
+        for unrelated in ["insert a picture", "insert signal", "signature insert"] {
+            let (result, triggered) = engine.process(unrelated);
+            assert_eq!(result, unrelated);
+            assert!(triggered.is_empty(), "{unrelated} triggered a shortcut");
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_matching_keeps_exact_matches() {
+        let engine = ShortcutsEngine::new();
+        engine.add_shortcut(Shortcut::new("my email".to_string(), "a@b.c".to_string()));
+        engine.add_shortcut(Shortcut::new(
+            "my linkedin".to_string(),
+            "jsn.cam/li".to_string(),
+        ));
+        engine.set_fuzzy_threshold(Some(DEFAULT_FUZZY_THRESHOLD));
+
+        let (result, triggered) = engine.process("my email and my linkdin");
+
+        assert_eq!(result, "a@b.c and jsn.cam/li");
+        assert_eq!(triggered.len(), 2);
+    }
+
     #[test]
     fn test_shortcut_partial_word_match() {
         // BUG EXPOSURE: Shortcuts match anywhere in text, not just word boundaries
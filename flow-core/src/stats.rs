@@ -0,0 +1,223 @@
+//! Local usage analytics
+//!
+//! Aggregates for in-app insights (speaking rate, most-corrected words, busiest
+//! app), computed on demand with read-only queries over what's already stored.
+//! Nothing here records new data or sends anything off the device.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::storage::Storage;
+
+/// Number of corrected words reported in `Analytics::top_corrected_words`
+pub const TOP_CORRECTED_WORDS: usize = 10;
+
+/// Usage aggregates over everything stored for a profile
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Analytics {
+    pub total_transcriptions: u64,
+    /// Transcriptions that failed, from the history
+    pub failed_transcriptions: u64,
+    pub total_words: u64,
+    pub total_duration_ms: u64,
+    /// Words per minute of recorded audio, over transcriptions with a duration
+    /// (0 if there are none)
+    pub words_per_minute: f64,
+    /// Words the user corrected most, most occurrences first
+    pub top_corrected_words: Vec<CorrectedWord>,
+    /// Usage per app, most transcriptions first
+    pub apps: Vec<AppUsage>,
+    /// App with the most transcriptions
+    pub busiest_app: Option<String>,
+}
+
+/// A learned correction and how often it was made
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CorrectedWord {
+    pub original: String,
+    pub corrected: String,
+    pub occurrences: u32,
+}
+
+/// Transcriptions dictated into one app
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AppUsage {
+    pub app_name: String,
+    pub transcriptions: u64,
+    pub total_duration_ms: u64,
+    pub words: u64,
+}
+
+impl Analytics {
+    /// Compute the aggregates from storage
+    pub fn from_storage(storage: &Storage) -> Result<Self> {
+        let usage = storage.get_transcription_usage()?;
+
+        let mut total_words = 0u64;
+        let mut total_duration_ms = 0u64;
+        let mut timed_words = 0u64;
+        let mut apps: HashMap<String, AppUsage> = HashMap::new();
+        for (app_name, duration_ms, words) in &usage {
+            total_words += words;
+            total_duration_ms += duration_ms;
+            if *duration_ms > 0 {
+                timed_words += words;
+            }
+
+            if let Some(app_name) = app_name {
+                let app = apps.entry(app_name.clone()).or_insert_with(|| AppUsage {
+                    app_name: app_name.clone(),
+                    transcriptions: 0,
+                    total_duration_ms: 0,
+                    words: 0,
+                });
+                app.transcriptions += 1;
+                app.total_duration_ms += duration_ms;
+                app.words += words;
+            }
+        }
+
+        let mut apps: Vec<AppUsage> = apps.into_values().collect();
+        apps.sort_by(|a, b| {
+            b.transcriptions
+                .cmp(&a.transcriptions)
+                .then(b.total_duration_ms.cmp(&a.total_duration_ms))
+                .then(a.app_name.cmp(&b.app_name))
+        });
+
+        let top_corrected_words = storage
+            .get_top_corrected_words(TOP_CORRECTED_WORDS)?
+            .into_iter()
+            .map(|(original, corrected, occurrences)| CorrectedWord {
+                original,
+                corrected,
+                occurrences,
+            })
+            .collect();
+
+        Ok(Self {
+            total_transcriptions: usage.len() as u64,
+            failed_transcriptions: storage.get_failed_transcription_count()?,
+            total_words,
+            total_duration_ms,
+            words_per_minute: words_per_minute(timed_words, total_duration_ms),
+            top_corrected_words,
+            busiest_app: apps.first().map(|app| app.app_name.clone()),
+            apps,
+        })
+    }
+}
+
+/// Speaking rate for `words` spoken over `duration_ms`, 0 for no duration
+pub fn words_per_minute(words: u64, duration_ms: u64) -> f64 {
+    if duration_ms == 0 {
+        return 0.0;
+    }
+    words as f64 / (duration_ms as f64 / 60_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        AppCategory, AppContext, Correction, CorrectionSource, Transcription,
+        TranscriptionHistoryEntry,
+    };
+
+    fn save(storage: &Storage, text: &str, duration_ms: u64, app: Option<&str>) {
+        let mut transcription =
+            Transcription::new(text.to_string(), text.to_string(), 0.9, duration_ms);
+        transcription.app_context = app.map(|name| AppContext {
+            app_name: name.to_string(),
+            bundle_id: None,
+            window_title: None,
+            category: AppCategory::Unknown,
+        });
+        storage.save_transcription(&transcription).unwrap();
+    }
+
+    #[test]
+    fn test_analytics_from_storage() {
+        let storage = Storage::in_memory().unwrap();
+        // 30 words in 15s and 10 words in 5s: 40 words in 20s is 120 wpm
+        save(&storage, &["word"; 30].join(" "), 15_000, Some("Slack"));
+        save(&storage, &["word"; 10].join(" "), 5_000, Some("Mail"));
+        save(&storage, "hi there", 0, Some("Slack"));
+        save(&storage, "untracked app", 0, None);
+        storage
+            .save_history_entry(&TranscriptionHistoryEntry::failure(
+                "offline".to_string(),
+                1_000,
+            ))
+            .unwrap();
+
+        let mut kubernetes = Correction::new(
+            "cooper netties".to_string(),
+            "Kubernetes".to_string(),
+            CorrectionSource::UserEdit,
+        );
+        kubernetes.occurrences = 5;
+        storage.save_correction(&kubernetes).unwrap();
+        let mut postgres = Correction::new(
+            "post grass".to_string(),
+            "Postgres".to_string(),
+            CorrectionSource::UserEdit,
+        );
+        postgres.occurrences = 2;
+        storage.save_correction(&postgres).unwrap();
+        // the contextual copy of the same edit isn't counted again
+        let mut in_context = postgres.clone();
+        in_context.id = uuid::Uuid::new_v4();
+        in_context.context = Some("the".to_string());
+        storage.save_correction(&in_context).unwrap();
+
+        let analytics = Analytics::from_storage(&storage).unwrap();
+        assert_eq!(analytics.total_transcriptions, 4);
+        assert_eq!(analytics.failed_transcriptions, 1);
+        assert_eq!(analytics.total_words, 44);
+        assert_eq!(analytics.total_duration_ms, 20_000);
+        assert_eq!(analytics.words_per_minute, 120.0);
+
+        // seeded corrections ("gonna") aren't the user's
+        assert_eq!(
+            analytics.top_corrected_words,
+            vec![
+                CorrectedWord {
+                    original: "cooper netties".to_string(),
+                    corrected: "Kubernetes".to_string(),
+                    occurrences: 5,
+                },
+                CorrectedWord {
+                    original: "post grass".to_string(),
+                    corrected: "Postgres".to_string(),
+                    occurrences: 2,
+                },
+            ]
+        );
+
+        assert_eq!(analytics.busiest_app.as_deref(), Some("Slack"));
+        assert_eq!(
+            analytics.apps[0],
+            AppUsage {
+                app_name: "Slack".to_string(),
+                transcriptions: 2,
+                total_duration_ms: 15_000,
+                words: 32,
+            }
+        );
+        assert_eq!(analytics.apps.len(), 2);
+    }
+
+    #[test]
+    fn test_empty_analytics() {
+        let storage = Storage::in_memory().unwrap();
+        let analytics = Analytics::from_storage(&storage).unwrap();
+        assert_eq!(analytics.total_transcriptions, 0);
+        assert_eq!(analytics.words_per_minute, 0.0);
+        assert!(analytics.top_corrected_words.is_empty());
+        assert!(analytics.busiest_app.is_none());
+        assert_eq!(words_per_minute(150, 60_000), 150.0);
+    }
+}
@@ -3,21 +3,67 @@
 use chrono::{DateTime, Utc};
 use parking_lot::Mutex;
 use rusqlite::{Connection, OptionalExtension, params};
-use std::path::Path;
-use tracing::{debug, info};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
 use crate::error::Result;
 use crate::migrations;
 use crate::types::{
-    AnalyticsEvent, AppCategory, AppContext, Contact, ContactCategory, Correction,
-    CorrectionSource, EventType, Shortcut, Transcription, TranscriptionHistoryEntry,
-    TranscriptionStatus, WritingMode,
+    AnalyticsEvent, AppCategory, AppContext, AppModelOverride, ConfidenceCurve, Contact,
+    ContactCategory, Correction, CorrectionSource, EventType, PendingTranscription, Shortcut,
+    Transcription, TranscriptionHistoryEntry, TranscriptionStatus, WritingMode,
 };
 
 /// Storage backend using SQLite
 pub struct Storage {
     conn: Mutex<Connection>,
+    /// Set when `open` found the database corrupt and replaced it
+    recovery: Option<StorageRecovery>,
+}
+
+/// What `Storage::open` did with a database it found corrupt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StorageRecovery {
+    /// Why the database couldn't be used
+    pub reason: String,
+    /// Where the corrupt file was moved
+    pub backup_path: PathBuf,
+    /// Tables whose rows were copied into the new database
+    pub recovered_tables: Vec<String>,
+    /// Tables that couldn't be read back from the corrupt file
+    pub lost_tables: Vec<String>,
+}
+
+/// Why a database couldn't be opened
+enum OpenError {
+    /// The file is damaged and should be replaced
+    Corrupt(String),
+    /// Anything else (permissions, disk full), which replacing the file won't fix
+    Failed(crate::error::Error),
+}
+
+impl From<rusqlite::Error> for OpenError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::DatabaseCorrupt | rusqlite::ErrorCode::NotADatabase) => {
+                Self::Corrupt(e.to_string())
+            }
+            _ => Self::Failed(e.into()),
+        }
+    }
+}
+
+impl From<crate::error::Error> for OpenError {
+    fn from(e: crate::error::Error) -> Self {
+        match e {
+            crate::error::Error::Storage(e) => e.into(),
+            e => Self::Failed(e),
+        }
+    }
 }
 
 pub const SETTING_OPENAI_API_KEY: &str = "openai_api_key";
@@ -34,28 +80,188 @@ pub const SETTING_CLOUD_TRANSCRIPTION_PROVIDER: &str = "cloud_transcription_prov
 pub const SETTING_AUTO_REWRITING_ENABLED: &str = "auto_rewriting_enabled";
 /// Custom OpenAI-compatible base URL for transcription (empty = use default https://api.openai.com/v1)
 pub const SETTING_OPENAI_BASE_URL: &str = "openai_base_url";
+/// Minimum Jaro-Winkler similarity for learning a correction (default 0.7)
+pub const SETTING_MIN_CORRECTION_SIMILARITY: &str = "min_correction_similarity";
+/// Redaction filter stage: "before_formatting" | "after_formatting" (default)
+pub const SETTING_REDACTION_STAGE: &str = "redaction_stage";
+/// Output normalization rules ("true" | "false", all default to enabled)
+pub const SETTING_NORMALIZE_WHITESPACE: &str = "normalize_whitespace";
+pub const SETTING_NORMALIZE_QUOTES: &str = "normalize_quotes";
+pub const SETTING_TRIM_TRAILING_SPACES: &str = "trim_trailing_spaces";
+/// Spoken formatting commands ("new line", "bullet", ...): "true" (default) | "false"
+pub const SETTING_DICTATION_COMMANDS_ENABLED: &str = "dictation_commands_enabled";
+/// Drop transcriptions that are only a known silence hallucination: "true" (default) | "false"
+pub const SETTING_HALLUCINATION_FILTER_ENABLED: &str = "hallucination_filter_enabled";
+/// JSON array of hallucination phrases (unset = built-in list)
+pub const SETTING_HALLUCINATION_PHRASES: &str = "hallucination_phrases";
+/// Back-to-back repeats of a phrase allowed before it's collapsed as a loop (default 6, 0 = off)
+pub const SETTING_MAX_PHRASE_REPEATS: &str = "max_phrase_repeats";
+/// Language code dictation is formatted for, e.g. "fr" (unset = detected language)
+pub const SETTING_LOCALE: &str = "locale";
+/// Recordings shorter than this many milliseconds aren't transcribed (default 300)
+pub const SETTING_MIN_RECORDING_MS: &str = "min_recording_ms";
+/// Prime transcription with the stored vocabulary ("true"/"false", default false)
+pub const SETTING_VOCABULARY_PROMPT_ENABLED: &str = "vocabulary_prompt_enabled";
+/// Writing mode for apps without their own mode (default Casual)
+pub const SETTING_DEFAULT_WRITING_MODE: &str = "default_writing_mode";
+/// Lowest confidence at which a learned correction is applied (default 0.55)
+pub const SETTING_MIN_CORRECTION_CONFIDENCE: &str = "min_correction_confidence";
+/// Most learned corrections kept in memory (default 10000)
+pub const SETTING_MAX_CORRECTION_CACHE_SIZE: &str = "max_correction_cache_size";
+/// Seconds before a transcription or completion request gives up (unset or 0 = no limit)
+pub const SETTING_REQUEST_TIMEOUT_SECS: &str = "request_timeout_secs";
+/// Similarity for fuzzy shortcut matches, 0.0-1.0 (unset or 0 = exact matches only)
+pub const SETTING_FUZZY_SHORTCUT_THRESHOLD: &str = "fuzzy_shortcut_threshold";
+/// Longest recording kept in memory, in seconds (default 600)
+pub const SETTING_MAX_RECORDING_SECS: &str = "max_recording_secs";
+/// What a recording does at its maximum length: "stop" (default) | "drop_oldest"
+pub const SETTING_RECORDING_OVERFLOW_POLICY: &str = "recording_overflow_policy";
 
 impl Storage {
     /// Open or create a database at the given path
+    ///
+    /// The database is checked with `PRAGMA quick_check` and switched to WAL
+    /// journaling, so a write cut short by the app being killed can't leave it half
+    /// written. If the file is corrupt anyway, it is moved aside as
+    /// `<name>.corrupt-<timestamp>`, a fresh database is created in its place and
+    /// every table that can still be read is copied over. `recovery()` then reports
+    /// what happened so the app can tell the user.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let conn = Connection::open(path)?;
+        let path = path.as_ref();
+        match Self::open_checked(path) {
+            Ok(storage) => Ok(storage),
+            Err(OpenError::Corrupt(reason)) => Self::recover(path, reason),
+            Err(OpenError::Failed(e)) => Err(e),
+        }
+    }
+
+    /// Create an in-memory database (useful for testing)
+    pub fn in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory()?;
         let storage = Self {
             conn: Mutex::new(conn),
+            recovery: None,
         };
         storage.init_schema()?;
         Ok(storage)
     }
 
-    /// Create an in-memory database (useful for testing)
-    pub fn in_memory() -> Result<Self> {
-        let conn = Connection::open_in_memory()?;
+    /// The recovery `open` performed, if the database was corrupt
+    pub fn recovery(&self) -> Option<&StorageRecovery> {
+        self.recovery.as_ref()
+    }
+
+    fn open_checked(path: &Path) -> std::result::Result<Self, OpenError> {
+        let conn = Connection::open(path)?;
+        let check: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+        if check != "ok" {
+            return Err(OpenError::Corrupt(format!(
+                "Integrity check failed: {check}"
+            )));
+        }
+        enable_wal(&conn)?;
+
         let storage = Self {
             conn: Mutex::new(conn),
+            recovery: None,
+        };
+        storage.init_schema()?;
+        Ok(storage)
+    }
+
+    /// Move a corrupt database aside and salvage what it can into a new one
+    fn recover(path: &Path, reason: String) -> Result<Self> {
+        warn!(
+            "Database {} is corrupt ({}), recovering",
+            path.display(),
+            reason
+        );
+
+        let mut backup_name = path.as_os_str().to_owned();
+        backup_name.push(format!(
+            ".corrupt-{}",
+            Utc::now().format("%Y%m%dT%H%M%S%.3f")
+        ));
+        let backup_path = PathBuf::from(backup_name);
+        std::fs::rename(path, &backup_path)?;
+        // the WAL may hold the most recent writes, keep it next to its database
+        for suffix in ["-wal", "-shm"] {
+            let mut sidecar = path.as_os_str().to_owned();
+            sidecar.push(suffix);
+            let mut sidecar_backup = backup_path.as_os_str().to_owned();
+            sidecar_backup.push(suffix);
+            if Path::new(&sidecar).exists() {
+                std::fs::rename(&sidecar, &sidecar_backup)?;
+            }
+        }
+
+        let conn = Connection::open(path)?;
+        enable_wal(&conn)?;
+        let mut storage = Self {
+            conn: Mutex::new(conn),
+            recovery: None,
         };
         storage.init_schema()?;
+
+        let (recovered_tables, lost_tables) = storage.salvage(&backup_path);
+        warn!(
+            "Recovered {} table(s) from {}, lost {}",
+            recovered_tables.len(),
+            backup_path.display(),
+            lost_tables.len()
+        );
+        storage.recovery = Some(StorageRecovery {
+            reason,
+            backup_path,
+            recovered_tables,
+            lost_tables,
+        });
         Ok(storage)
     }
 
+    /// Copy every readable table of the database at `backup` into this one,
+    /// returning (recovered, lost) table names
+    ///
+    /// Only columns both schemas have are copied, so an older database still
+    /// recovers. Rows that clash with seeded ones are skipped.
+    fn salvage(&self, backup: &Path) -> (Vec<String>, Vec<String>) {
+        let conn = self.conn.lock();
+        let tables: Vec<String> = conn
+            .prepare(
+                "SELECT name FROM main.sqlite_master WHERE type = 'table'
+                 AND name NOT LIKE 'sqlite_%' AND name != '_migrations' ORDER BY name",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<_>>()
+            })
+            .unwrap_or_default();
+
+        if let Err(e) = conn.execute("ATTACH DATABASE ?1 AS corrupt", [backup.to_string_lossy()]) {
+            warn!("Couldn't attach corrupt database: {}", e);
+            return (Vec::new(), tables);
+        }
+
+        let mut recovered = Vec::new();
+        let mut lost = Vec::new();
+        for table in tables {
+            match copy_table(&conn, &table) {
+                Ok(true) => recovered.push(table),
+                // the old database didn't have the table yet
+                Ok(false) => {}
+                Err(e) => {
+                    warn!("Couldn't recover table {}: {}", table, e);
+                    lost.push(table);
+                }
+            }
+        }
+
+        if let Err(e) = conn.execute("DETACH DATABASE corrupt", []) {
+            warn!("Couldn't detach corrupt database: {}", e);
+        }
+        (recovered, lost)
+    }
+
     /// Initialize database schema using migration system
     fn init_schema(&self) -> Result<()> {
         let conn = self.conn.lock();
@@ -174,6 +380,36 @@ impl Storage {
         .map_err(Into::into)
     }
 
+    /// Get a setting parsed as `T`
+    /// Values that don't parse are treated like missing ones, so the caller's default applies.
+    pub fn get_setting_as<T: FromStr>(&self, key: &str) -> Result<Option<T>> {
+        Ok(self.get_setting(key)?.and_then(|s| s.parse().ok()))
+    }
+
+    /// Save a setting from any value with a string form
+    pub fn set_setting_as<T: ToString>(&self, key: &str, value: &T) -> Result<()> {
+        self.set_setting(key, &value.to_string())
+    }
+
+    /// Save the writing mode used for apps without their own mode
+    pub fn set_default_mode(&self, mode: WritingMode) -> Result<()> {
+        self.set_setting(SETTING_DEFAULT_WRITING_MODE, &format!("{:?}", mode))
+    }
+
+    /// Get the saved default writing mode
+    pub fn get_default_mode(&self) -> Result<Option<WritingMode>> {
+        Ok(self
+            .get_setting(SETTING_DEFAULT_WRITING_MODE)?
+            .and_then(|s| parse_writing_mode(&s)))
+    }
+
+    /// Remove a setting so its default applies again
+    pub fn delete_setting(&self, key: &str) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute("DELETE FROM settings WHERE key = ?1", params![key])?;
+        Ok(())
+    }
+
     /// Get recent transcriptions
     pub fn get_recent_transcriptions(&self, limit: usize) -> Result<Vec<Transcription>> {
         let conn = self.conn.lock();
@@ -330,8 +566,9 @@ impl Storage {
         conn.execute(
             r#"
             INSERT OR REPLACE INTO shortcuts (id, trigger, replacement, case_sensitive,
-                                              enabled, use_count, created_at, updated_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                                              enabled, use_count, last_used_at, created_at,
+                                              updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
             "#,
             params![
                 shortcut.id.to_string(),
@@ -340,6 +577,7 @@ impl Storage {
                 shortcut.case_sensitive as i32,
                 shortcut.enabled as i32,
                 shortcut.use_count,
+                shortcut.last_used_at.map(|t| t.to_rfc3339()),
                 shortcut.created_at.to_rfc3339(),
                 shortcut.updated_at.to_rfc3339(),
             ],
@@ -356,7 +594,8 @@ impl Storage {
         let conn = self.conn.lock();
         let mut stmt = conn.prepare(
             r#"
-            SELECT id, trigger, replacement, case_sensitive, enabled, use_count, created_at, updated_at
+            SELECT id, trigger, replacement, case_sensitive, enabled, use_count, created_at, updated_at,
+                   last_used_at
             FROM shortcuts
             WHERE enabled = 1
             ORDER BY trigger
@@ -368,6 +607,7 @@ impl Storage {
                 let id: String = row.get(0)?;
                 let created_at_str: String = row.get(6)?;
                 let updated_at_str: String = row.get(7)?;
+                let last_used_at_str: Option<String> = row.get(8)?;
 
                 Ok(Shortcut {
                     id: Uuid::parse_str(&id).unwrap_or_else(|_| Uuid::new_v4()),
@@ -376,6 +616,9 @@ impl Storage {
                     case_sensitive: row.get::<_, i32>(3)? != 0,
                     enabled: row.get::<_, i32>(4)? != 0,
                     use_count: row.get(5)?,
+                    last_used_at: last_used_at_str
+                        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                        .map(|dt| dt.with_timezone(&Utc)),
                     created_at: DateTime::parse_from_rfc3339(&created_at_str)
                         .map(|dt| dt.with_timezone(&Utc))
                         .unwrap_or_else(|_| Utc::now()),
@@ -394,7 +637,8 @@ impl Storage {
         let conn = self.conn.lock();
         let mut stmt = conn.prepare(
             r#"
-            SELECT id, trigger, replacement, case_sensitive, enabled, use_count, created_at, updated_at
+            SELECT id, trigger, replacement, case_sensitive, enabled, use_count, created_at, updated_at,
+                   last_used_at
             FROM shortcuts
             ORDER BY trigger
             "#,
@@ -405,6 +649,7 @@ impl Storage {
                 let id: String = row.get(0)?;
                 let created_at_str: String = row.get(6)?;
                 let updated_at_str: String = row.get(7)?;
+                let last_used_at_str: Option<String> = row.get(8)?;
 
                 Ok(Shortcut {
                     id: Uuid::parse_str(&id).unwrap_or_else(|_| Uuid::new_v4()),
@@ -413,6 +658,9 @@ impl Storage {
                     case_sensitive: row.get::<_, i32>(3)? != 0,
                     enabled: row.get::<_, i32>(4)? != 0,
                     use_count: row.get(5)?,
+                    last_used_at: last_used_at_str
+                        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                        .map(|dt| dt.with_timezone(&Utc)),
                     created_at: DateTime::parse_from_rfc3339(&created_at_str)
                         .map(|dt| dt.with_timezone(&Utc))
                         .unwrap_or_else(|_| Utc::now()),
@@ -426,11 +674,11 @@ impl Storage {
         Ok(shortcuts)
     }
 
-    /// Increment use count for a shortcut
+    /// Increment use count for a shortcut and stamp it as just used
     pub fn increment_shortcut_use(&self, trigger: &str) -> Result<()> {
         let conn = self.conn.lock();
         conn.execute(
-            r#"UPDATE shortcuts SET use_count = use_count + 1, updated_at = ?1 WHERE trigger = ?2"#,
+            r#"UPDATE shortcuts SET use_count = use_count + 1, last_used_at = ?1 WHERE trigger = ?2"#,
             params![Utc::now().to_rfc3339(), trigger],
         )?;
         Ok(())
@@ -454,23 +702,38 @@ impl Storage {
     /// confidence = 0.5 + 0.5 * (1.0 - 1.0 / ln(occurrences + e))
     /// This ensures corrections gain confidence as they're seen more often.
     pub fn save_correction(&self, correction: &Correction) -> Result<()> {
+        self.save_correction_with_curve(correction, ConfidenceCurve::default())
+            .map(|_| ())
+    }
+
+    /// Save or update a correction, computing confidence with the given curve
+    /// Returns the stored occurrence count after the update
+    pub fn save_correction_with_curve(
+        &self,
+        correction: &Correction,
+        curve: ConfidenceCurve,
+    ) -> Result<u32> {
         let conn = self.conn.lock();
 
-        let initial_confidence = Self::calculate_confidence(correction.occurrences);
+        let initial_confidence = curve.confidence(correction.occurrences);
+
+        // '' is stored for corrections without context so they still conflict with each other
+        let context = correction.context.as_deref().unwrap_or("");
 
         conn.execute(
             r#"
-            INSERT INTO corrections (id, original, corrected, occurrences, confidence, source, created_at, updated_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
-            ON CONFLICT(original, corrected) DO UPDATE SET
+            INSERT INTO corrections (id, original, corrected, context, occurrences, confidence, source, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            ON CONFLICT(original, corrected, context) DO UPDATE SET
                 occurrences = corrections.occurrences + 1,
-                confidence = ?5,
-                updated_at = ?8
+                confidence = ?6,
+                updated_at = ?9
             "#,
             params![
                 correction.id.to_string(),
                 correction.original,
                 correction.corrected,
+                context,
                 correction.occurrences as i64,
                 initial_confidence,
                 format!("{:?}", correction.source),
@@ -482,45 +745,44 @@ impl Storage {
         // Re-read to get the actual occurrences (may have been incremented) and update confidence
         if let Some((actual_occurrences,)) = conn
             .query_row(
-                "SELECT occurrences FROM corrections WHERE original = ?1 AND corrected = ?2",
-                params![&correction.original, &correction.corrected],
+                "SELECT occurrences FROM corrections WHERE original = ?1 AND corrected = ?2 AND context = ?3",
+                params![&correction.original, &correction.corrected, context],
                 |row| Ok((row.get::<_, i64>(0)?,)),
             )
             .optional()?
         {
-            let actual_confidence = Self::calculate_confidence(actual_occurrences as u32);
+            let actual_occurrences = actual_occurrences as u32;
+            let actual_confidence = curve.confidence(actual_occurrences);
             conn.execute(
-                "UPDATE corrections SET confidence = ?1 WHERE original = ?2 AND corrected = ?3",
+                "UPDATE corrections SET confidence = ?1 WHERE original = ?2 AND corrected = ?3 AND context = ?4",
                 params![
                     actual_confidence,
                     &correction.original,
-                    &correction.corrected
+                    &correction.corrected,
+                    context
                 ],
             )?;
             debug!(
-                "Saved correction {} -> {} (occurrences: {}, confidence: {:.2})",
-                correction.original, correction.corrected, actual_occurrences, actual_confidence
+                "Saved correction {} -> {} (context: {:?}, occurrences: {}, confidence: {:.2})",
+                correction.original,
+                correction.corrected,
+                correction.context,
+                actual_occurrences,
+                actual_confidence
             );
+            return Ok(actual_occurrences);
         }
-        Ok(())
-    }
-
-    /// Calculate confidence based on occurrence count
-    /// Formula: 0.5 + 0.5 * (1.0 - 1.0 / ln(occurrences + e)), capped at 0.99
-    fn calculate_confidence(occurrences: u32) -> f32 {
-        let e = std::f32::consts::E;
-        let confidence = 0.5 + 0.5 * (1.0 - 1.0 / (occurrences as f32 + e).ln());
-        confidence.min(0.99)
+        Ok(correction.occurrences)
     }
 
-    /// Get correction for a word if confidence is high enough
+    /// Get the context-free correction for a word if confidence is high enough
     pub fn get_correction(&self, original: &str, min_confidence: f32) -> Result<Option<String>> {
         let conn = self.conn.lock();
         let result: Option<String> = conn
             .query_row(
                 r#"
                 SELECT corrected FROM corrections
-                WHERE original = ?1 AND confidence >= ?2
+                WHERE original = ?1 AND context = '' AND confidence >= ?2
                 ORDER BY confidence DESC
                 LIMIT 1
                 "#,
@@ -536,7 +798,7 @@ impl Storage {
         let conn = self.conn.lock();
         let mut stmt = conn.prepare(
             r#"
-            SELECT id, original, corrected, occurrences, confidence, source, created_at, updated_at
+            SELECT id, original, corrected, context, occurrences, confidence, source, created_at, updated_at
             FROM corrections
             WHERE confidence >= ?1
             ORDER BY confidence DESC
@@ -546,16 +808,18 @@ impl Storage {
         let corrections = stmt
             .query_map([min_confidence], |row| {
                 let id: String = row.get(0)?;
-                let source_str: String = row.get(5)?;
-                let created_at_str: String = row.get(6)?;
-                let updated_at_str: String = row.get(7)?;
+                let context: String = row.get(3)?;
+                let source_str: String = row.get(6)?;
+                let created_at_str: String = row.get(7)?;
+                let updated_at_str: String = row.get(8)?;
 
                 Ok(Correction {
                     id: Uuid::parse_str(&id).unwrap_or_else(|_| Uuid::new_v4()),
                     original: row.get(1)?,
                     corrected: row.get(2)?,
-                    occurrences: row.get(3)?,
-                    confidence: row.get(4)?,
+                    context: (!context.is_empty()).then_some(context),
+                    occurrences: row.get(4)?,
+                    confidence: row.get(5)?,
                     source: parse_correction_source(&source_str),
                     created_at: DateTime::parse_from_rfc3339(&created_at_str)
                         .map(|dt| dt.with_timezone(&Utc))
@@ -575,7 +839,7 @@ impl Storage {
         let conn = self.conn.lock();
         let mut stmt = conn.prepare(
             r#"
-            SELECT id, original, corrected, occurrences, confidence, source, created_at, updated_at
+            SELECT id, original, corrected, context, occurrences, confidence, source, created_at, updated_at
             FROM corrections
             ORDER BY confidence DESC, occurrences DESC
             "#,
@@ -584,16 +848,18 @@ impl Storage {
         let corrections = stmt
             .query_map([], |row| {
                 let id: String = row.get(0)?;
-                let source_str: String = row.get(5)?;
-                let created_at_str: String = row.get(6)?;
-                let updated_at_str: String = row.get(7)?;
+                let context: String = row.get(3)?;
+                let source_str: String = row.get(6)?;
+                let created_at_str: String = row.get(7)?;
+                let updated_at_str: String = row.get(8)?;
 
                 Ok(Correction {
                     id: Uuid::parse_str(&id).unwrap_or_else(|_| Uuid::new_v4()),
                     original: row.get(1)?,
                     corrected: row.get(2)?,
-                    occurrences: row.get(3)?,
-                    confidence: row.get(4)?,
+                    context: (!context.is_empty()).then_some(context),
+                    occurrences: row.get(4)?,
+                    confidence: row.get(5)?,
                     source: parse_correction_source(&source_str),
                     created_at: DateTime::parse_from_rfc3339(&created_at_str)
                         .map(|dt| dt.with_timezone(&Utc))
@@ -609,10 +875,16 @@ impl Storage {
     }
 
     /// Delete a correction by ID
+    ///
+    /// Deleting a general correction also deletes the contextual copies learned
+    /// alongside it, so none of them keep applying.
     pub fn delete_correction(&self, id: &Uuid) -> Result<bool> {
         let conn = self.conn.lock();
         let rows_affected = conn.execute(
-            "DELETE FROM corrections WHERE id = ?1",
+            "DELETE FROM corrections WHERE id = ?1
+                OR (context != '' AND (original, corrected) IN (
+                    SELECT original, corrected FROM corrections WHERE id = ?1 AND context = ''
+                ))",
             params![id.to_string()],
         )?;
         debug!("Deleted correction {}: {} rows affected", id, rows_affected);
@@ -627,6 +899,87 @@ impl Storage {
         Ok(rows_affected)
     }
 
+    /// Merge corrections that only differ by the casing of their text
+    /// ("teh" -> "the" and "teh" -> "The"), see `dedupe_corrections_with_curve`
+    pub fn dedupe_corrections(&self) -> Result<usize> {
+        self.dedupe_corrections_with_curve(ConfidenceCurve::default())
+    }
+
+    /// Merge corrections sharing the same original and context and case-insensitively
+    /// equal corrected text
+    ///
+    /// Occurrences are summed into the most common casing (most recently updated on a tie),
+    /// confidence is recomputed with the given curve and the other rows are deleted.
+    /// Returns the number of rows removed.
+    pub fn dedupe_corrections_with_curve(&self, curve: ConfidenceCurve) -> Result<usize> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+
+        let rows = {
+            let mut stmt = tx.prepare(
+                r#"
+                SELECT id, original, corrected, context, occurrences
+                FROM corrections
+                ORDER BY occurrences DESC, updated_at DESC
+                "#,
+            )?;
+            stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, u32>(4)?,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+        };
+
+        // rows are sorted so the first of each group is the canonical casing
+        let mut groups: HashMap<(String, String, String), Vec<(String, u32)>> = HashMap::new();
+        for (id, original, corrected, context, occurrences) in rows {
+            groups
+                .entry((
+                    original.to_lowercase(),
+                    corrected.to_lowercase(),
+                    context.to_lowercase(),
+                ))
+                .or_default()
+                .push((id, occurrences));
+        }
+
+        let now = Utc::now().to_rfc3339();
+        let mut removed = 0;
+        for ((original, corrected, _), members) in groups {
+            if members.len() < 2 {
+                continue;
+            }
+
+            let total: u32 = members.iter().map(|(_, occurrences)| occurrences).sum();
+            let (canonical_id, _) = &members[0];
+            for (id, _) in &members[1..] {
+                removed += tx.execute("DELETE FROM corrections WHERE id = ?1", params![id])?;
+            }
+            tx.execute(
+                "UPDATE corrections SET occurrences = ?1, confidence = ?2, updated_at = ?3 WHERE id = ?4",
+                params![total, curve.confidence(total), now, canonical_id],
+            )?;
+            debug!(
+                "Merged {} duplicate corrections for {} -> {} (occurrences: {})",
+                members.len(),
+                original,
+                corrected,
+                total
+            );
+        }
+
+        tx.commit()?;
+        if removed > 0 {
+            info!("Removed {} duplicate corrections", removed);
+        }
+        Ok(removed)
+    }
+
     // ========== Analytics event methods ==========
 
     /// Save an analytics event
@@ -744,6 +1097,207 @@ impl Storage {
         Ok(result.and_then(|s| parse_writing_mode(&s)))
     }
 
+    /// Save a completion provider/model override for an app
+    pub fn save_app_model_override(
+        &self,
+        app_name: &str,
+        model_override: &AppModelOverride,
+    ) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            r#"
+            INSERT OR REPLACE INTO app_model_overrides (app_name, provider, model, updated_at)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+            params![
+                app_name,
+                model_override.provider,
+                model_override.model,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get the completion provider/model override for an app
+    pub fn get_app_model_override(&self, app_name: &str) -> Result<Option<AppModelOverride>> {
+        let conn = self.conn.lock();
+        let result = conn
+            .query_row(
+                "SELECT provider, model FROM app_model_overrides WHERE app_name = ?1",
+                params![app_name],
+                |row| {
+                    Ok(AppModelOverride {
+                        provider: row.get(0)?,
+                        model: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(result)
+    }
+
+    /// Delete the completion provider/model override for an app
+    pub fn delete_app_model_override(&self, app_name: &str) -> Result<bool> {
+        let conn = self.conn.lock();
+        let rows = conn.execute(
+            "DELETE FROM app_model_overrides WHERE app_name = ?1",
+            params![app_name],
+        )?;
+        Ok(rows > 0)
+    }
+
+    // ========== Pending transcription methods ==========
+
+    /// Queue a recording for a later transcription retry
+    /// Evicts the oldest jobs beyond `max_jobs` and returns how many were evicted
+    pub fn enqueue_pending_transcription(
+        &self,
+        job: &PendingTranscription,
+        max_jobs: usize,
+    ) -> Result<usize> {
+        let conn = self.conn.lock();
+        conn.execute(
+            r#"
+            INSERT INTO pending_transcriptions (id, audio, sample_rate, app_name, attempts, last_error, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+            params![
+                job.id.to_string(),
+                job.audio,
+                job.sample_rate,
+                job.app_name,
+                job.attempts,
+                job.last_error,
+                job.created_at.to_rfc3339(),
+            ],
+        )?;
+
+        let evicted = conn.execute(
+            r#"
+            DELETE FROM pending_transcriptions WHERE id IN (
+                SELECT id FROM pending_transcriptions
+                ORDER BY created_at DESC, rowid DESC
+                LIMIT -1 OFFSET ?1
+            )
+            "#,
+            params![max_jobs as i64],
+        )?;
+        debug!(
+            "Queued pending transcription {} (evicted {})",
+            job.id, evicted
+        );
+        Ok(evicted)
+    }
+
+    /// Get all pending transcriptions, oldest first
+    pub fn get_pending_transcriptions(&self) -> Result<Vec<PendingTranscription>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, audio, sample_rate, app_name, attempts, last_error, created_at
+            FROM pending_transcriptions
+            ORDER BY created_at ASC, rowid ASC
+            "#,
+        )?;
+
+        let jobs = stmt
+            .query_map([], |row| {
+                let id: String = row.get(0)?;
+                let created_at_str: String = row.get(6)?;
+
+                Ok(PendingTranscription {
+                    id: Uuid::parse_str(&id).unwrap_or_else(|_| Uuid::new_v4()),
+                    audio: row.get(1)?,
+                    sample_rate: row.get(2)?,
+                    app_name: row.get(3)?,
+                    attempts: row.get(4)?,
+                    last_error: row.get(5)?,
+                    created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                        .map(|dt| dt.with_timezone(&Utc))
+                        .unwrap_or_else(|_| Utc::now()),
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(jobs)
+    }
+
+    /// Get the ids of the pending transcriptions, oldest first, without their audio
+    pub fn get_pending_transcription_ids(&self) -> Result<Vec<Uuid>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare("SELECT id FROM pending_transcriptions ORDER BY created_at ASC, rowid ASC")?;
+
+        let ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter_map(|id| Uuid::parse_str(&id).ok())
+            .collect();
+
+        Ok(ids)
+    }
+
+    /// Get a single pending transcription, if it's still queued
+    pub fn get_pending_transcription(&self, id: &Uuid) -> Result<Option<PendingTranscription>> {
+        let conn = self.conn.lock();
+        let job = conn
+            .query_row(
+                r#"
+                SELECT audio, sample_rate, app_name, attempts, last_error, created_at
+                FROM pending_transcriptions WHERE id = ?1
+                "#,
+                params![id.to_string()],
+                |row| {
+                    let created_at_str: String = row.get(5)?;
+                    Ok(PendingTranscription {
+                        id: *id,
+                        audio: row.get(0)?,
+                        sample_rate: row.get(1)?,
+                        app_name: row.get(2)?,
+                        attempts: row.get(3)?,
+                        last_error: row.get(4)?,
+                        created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                            .map(|dt| dt.with_timezone(&Utc))
+                            .unwrap_or_else(|_| Utc::now()),
+                    })
+                },
+            )
+            .optional()?;
+        Ok(job)
+    }
+
+    /// Record a failed retry attempt for a pending transcription
+    pub fn record_pending_transcription_failure(&self, id: &Uuid, error: &str) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "UPDATE pending_transcriptions SET attempts = attempts + 1, last_error = ?1 WHERE id = ?2",
+            params![error, id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Remove a pending transcription from the queue
+    pub fn delete_pending_transcription(&self, id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock();
+        let rows = conn.execute(
+            "DELETE FROM pending_transcriptions WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Get the number of queued pending transcriptions
+    pub fn get_pending_transcription_count(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+        let count: i64 =
+            conn.query_row("SELECT COUNT(*) FROM pending_transcriptions", [], |row| {
+                row.get(0)
+            })?;
+        Ok(count as usize)
+    }
+
     // ========== Style sample methods ==========
 
     /// Save a style sample for learning user's writing style in an app
@@ -817,16 +1371,78 @@ impl Storage {
 
         for row in rows {
             let (raw_text, processed_text) = row?;
-            let text = if raw_text.trim().is_empty() {
-                processed_text
-            } else {
-                raw_text
-            };
-            total = total.saturating_add(text.split_whitespace().count() as u64);
+            total = total.saturating_add(dictated_words(&raw_text, &processed_text));
         }
 
         Ok(total)
     }
+
+    /// Get (app name, duration in ms, words dictated) for every saved transcription
+    pub fn get_transcription_usage(&self) -> Result<Vec<(Option<String>, u64, u64)>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT app_name, duration_ms, raw_text, processed_text FROM transcriptions",
+        )?;
+        let usage = stmt
+            .query_map([], |row| {
+                let duration_ms: i64 = row.get(1)?;
+                let raw_text: String = row.get(2)?;
+                let processed_text: String = row.get(3)?;
+                Ok((
+                    row.get(0)?,
+                    duration_ms.max(0) as u64,
+                    dictated_words(&raw_text, &processed_text),
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(usage)
+    }
+
+    /// Get the words the user corrected most, as (original, corrected, occurrences)
+    /// Seeded corrections aren't the user's and are left out. Every edit is also
+    /// recorded as a contextual copy, so only the general rows are counted.
+    pub fn get_top_corrected_words(&self, limit: usize) -> Result<Vec<(String, String, u32)>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT original, corrected, occurrences
+            FROM corrections
+            WHERE source != 'Seeded' AND context = ''
+            ORDER BY occurrences DESC, original ASC
+            LIMIT ?1
+            "#,
+        )?;
+        let words = stmt
+            .query_map(params![limit as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(words)
+    }
+
+    /// Get the number of transcriptions that failed, from the history
+    pub fn get_failed_transcription_count(&self) -> Result<u64> {
+        let conn = self.conn.lock();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM transcription_history WHERE status = 'failed'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+}
+
+/// Words the user dictated: the raw transcription, or the processed text for rows
+/// saved without one
+fn dictated_words(raw_text: &str, processed_text: &str) -> u64 {
+    let text = if raw_text.trim().is_empty() {
+        processed_text
+    } else {
+        raw_text
+    };
+    text.split_whitespace().count() as u64
 }
 
 fn parse_app_category(s: &str) -> Option<AppCategory> {
@@ -883,6 +1499,47 @@ fn serialize_contact_category(category: ContactCategory) -> &'static str {
     }
 }
 
+/// Switch to write-ahead logging, so a crash mid-write leaves the last committed
+/// state intact
+fn enable_wal(conn: &Connection) -> rusqlite::Result<()> {
+    let mode: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
+    if !mode.eq_ignore_ascii_case("wal") {
+        warn!("Couldn't enable WAL journaling, using {}", mode);
+    }
+    Ok(())
+}
+
+/// Copy the rows of `table` from the attached `corrupt` database, using the columns
+/// both versions share. Returns false if the corrupt database has no such table.
+fn copy_table(conn: &Connection, table: &str) -> rusqlite::Result<bool> {
+    let columns = |schema: &str| -> rusqlite::Result<Vec<String>> {
+        conn.prepare(&format!("PRAGMA {schema}.table_info(\"{table}\")"))?
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect()
+    };
+    let old_columns = columns("corrupt")?;
+    if old_columns.is_empty() {
+        return Ok(false);
+    }
+    let shared: Vec<String> = columns("main")?
+        .into_iter()
+        .filter(|column| old_columns.contains(column))
+        .map(|column| format!("\"{column}\""))
+        .collect();
+    if shared.is_empty() {
+        return Ok(false);
+    }
+
+    let shared = shared.join(", ");
+    conn.execute(
+        &format!(
+            "INSERT OR IGNORE INTO main.\"{table}\" ({shared}) SELECT {shared} FROM corrupt.\"{table}\""
+        ),
+        [],
+    )?;
+    Ok(true)
+}
+
 impl Storage {
     // ============ Contact Management ============
 
@@ -1182,6 +1839,105 @@ impl Storage {
         );
         Ok(rows > 0)
     }
+
+    // ========== Redaction Terms ==========
+
+    /// Add a word to the redaction list (stored lowercased)
+    pub fn add_redacted_word(&self, word: &str) -> Result<()> {
+        self.save_redaction_term(&word.trim().to_lowercase(), false)
+    }
+
+    /// Remove a word from the redaction list
+    pub fn remove_redacted_word(&self, word: &str) -> Result<bool> {
+        self.delete_redaction_term(&word.trim().to_lowercase(), false)
+    }
+
+    /// Get all redacted words
+    pub fn get_redacted_words(&self) -> Result<Vec<String>> {
+        self.get_redaction_terms(false)
+    }
+
+    /// Add a regex pattern to the redaction list
+    pub fn add_redaction_pattern(&self, pattern: &str) -> Result<()> {
+        self.save_redaction_term(pattern, true)
+    }
+
+    /// Remove a regex pattern from the redaction list
+    pub fn remove_redaction_pattern(&self, pattern: &str) -> Result<bool> {
+        self.delete_redaction_term(pattern, true)
+    }
+
+    /// Get all redaction regex patterns
+    pub fn get_redaction_patterns(&self) -> Result<Vec<String>> {
+        self.get_redaction_terms(true)
+    }
+
+    fn save_redaction_term(&self, term: &str, is_regex: bool) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT OR IGNORE INTO redaction_terms (term, is_regex) VALUES (?1, ?2)",
+            params![term, is_regex as i32],
+        )?;
+        debug!("Saved redaction term: {}", term);
+        Ok(())
+    }
+
+    fn delete_redaction_term(&self, term: &str, is_regex: bool) -> Result<bool> {
+        let conn = self.conn.lock();
+        let rows = conn.execute(
+            "DELETE FROM redaction_terms WHERE term = ?1 AND is_regex = ?2",
+            params![term, is_regex as i32],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn get_redaction_terms(&self, is_regex: bool) -> Result<Vec<String>> {
+        let conn = self.conn.lock();
+        let mut stmt =
+            conn.prepare("SELECT term FROM redaction_terms WHERE is_regex = ?1 ORDER BY id")?;
+        let terms = stmt
+            .query_map([is_regex as i32], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+        Ok(terms)
+    }
+
+    // ========== Vocabulary ==========
+
+    /// Add a word or name to the transcription vocabulary
+    /// Returns false if it's blank or already there (ignoring case)
+    pub fn add_vocabulary_word(&self, word: &str) -> Result<bool> {
+        let word = word.trim();
+        if word.is_empty() {
+            return Ok(false);
+        }
+        let conn = self.conn.lock();
+        let rows = conn.execute(
+            "INSERT OR IGNORE INTO vocabulary (term) VALUES (?1)",
+            params![word],
+        )?;
+        debug!("Saved vocabulary word: {}", word);
+        Ok(rows > 0)
+    }
+
+    /// Remove a word from the transcription vocabulary (ignoring case)
+    pub fn remove_vocabulary_word(&self, word: &str) -> Result<bool> {
+        let conn = self.conn.lock();
+        let rows = conn.execute(
+            "DELETE FROM vocabulary WHERE term = ?1",
+            params![word.trim()],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Get the transcription vocabulary, oldest first
+    pub fn get_vocabulary(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare("SELECT term FROM vocabulary ORDER BY id")?;
+        let words = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+        Ok(words)
+    }
 }
 
 #[cfg(test)]
@@ -1239,6 +1995,107 @@ mod tests {
         assert_eq!(mode, None);
     }
 
+    #[test]
+    fn test_app_model_overrides() {
+        let storage = Storage::in_memory().unwrap();
+
+        let model_override = AppModelOverride {
+            provider: Some("openrouter".to_string()),
+            model: Some("anthropic/claude-sonnet-4".to_string()),
+        };
+        storage
+            .save_app_model_override("Mail", &model_override)
+            .unwrap();
+
+        assert_eq!(
+            storage.get_app_model_override("Mail").unwrap(),
+            Some(model_override)
+        );
+        assert_eq!(storage.get_app_model_override("Slack").unwrap(), None);
+
+        assert!(storage.delete_app_model_override("Mail").unwrap());
+        assert!(!storage.delete_app_model_override("Mail").unwrap());
+        assert_eq!(storage.get_app_model_override("Mail").unwrap(), None);
+    }
+
+    #[test]
+    fn test_pending_transcription_queue() {
+        let storage = Storage::in_memory().unwrap();
+
+        let first = PendingTranscription::new(vec![1, 2, 3, 4], 16_000, Some("Mail".to_string()));
+        let second = PendingTranscription::new(vec![5, 6], 48_000, None);
+        assert_eq!(
+            storage.enqueue_pending_transcription(&first, 10).unwrap(),
+            0
+        );
+        assert_eq!(
+            storage.enqueue_pending_transcription(&second, 10).unwrap(),
+            0
+        );
+
+        let jobs = storage.get_pending_transcriptions().unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].id, first.id);
+        assert_eq!(jobs[0].audio, vec![1, 2, 3, 4]);
+        assert_eq!(jobs[0].sample_rate, 16_000);
+        assert_eq!(jobs[0].app_name, Some("Mail".to_string()));
+        assert_eq!(
+            storage.get_pending_transcription_ids().unwrap(),
+            vec![first.id, second.id]
+        );
+        let job = storage
+            .get_pending_transcription(&second.id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(job.audio, vec![5, 6]);
+        assert_eq!(job.sample_rate, 48_000);
+
+        storage
+            .record_pending_transcription_failure(&first.id, "offline")
+            .unwrap();
+        let jobs = storage.get_pending_transcriptions().unwrap();
+        assert_eq!(jobs[0].attempts, 1);
+        assert_eq!(jobs[0].last_error, Some("offline".to_string()));
+
+        assert!(storage.delete_pending_transcription(&first.id).unwrap());
+        assert!(!storage.delete_pending_transcription(&first.id).unwrap());
+        assert!(
+            storage
+                .get_pending_transcription(&first.id)
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(storage.get_pending_transcription_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_pending_transcription_evicts_oldest() {
+        let storage = Storage::in_memory().unwrap();
+
+        let jobs: Vec<PendingTranscription> = (0..5)
+            .map(|i| PendingTranscription::new(vec![i], 16_000, None))
+            .collect();
+        for job in &jobs[..3] {
+            storage.enqueue_pending_transcription(job, 3).unwrap();
+        }
+        assert_eq!(
+            storage.enqueue_pending_transcription(&jobs[3], 3).unwrap(),
+            1
+        );
+        assert_eq!(
+            storage.enqueue_pending_transcription(&jobs[4], 3).unwrap(),
+            1
+        );
+
+        let remaining: Vec<Vec<u8>> = storage
+            .get_pending_transcriptions()
+            .unwrap()
+            .into_iter()
+            .map(|job| job.audio)
+            .collect();
+        assert_eq!(remaining, vec![vec![2], vec![3], vec![4]]);
+    }
+
     #[test]
     fn test_settings_roundtrip() {
         let storage = Storage::in_memory().unwrap();
@@ -1251,6 +2108,39 @@ mod tests {
         assert_eq!(value, Some("test-key".to_string()));
     }
 
+    #[test]
+    fn test_typed_settings() {
+        let storage = Storage::in_memory().unwrap();
+
+        assert_eq!(storage.get_default_mode().unwrap(), None);
+        storage.set_default_mode(WritingMode::Formal).unwrap();
+        assert_eq!(
+            storage.get_default_mode().unwrap(),
+            Some(WritingMode::Formal)
+        );
+
+        storage
+            .set_setting_as(SETTING_MIN_CORRECTION_CONFIDENCE, &0.8f32)
+            .unwrap();
+        assert_eq!(
+            storage
+                .get_setting_as::<f32>(SETTING_MIN_CORRECTION_CONFIDENCE)
+                .unwrap(),
+            Some(0.8)
+        );
+
+        // unparseable values fall back to the caller's default
+        storage
+            .set_setting(SETTING_REQUEST_TIMEOUT_SECS, "soon")
+            .unwrap();
+        assert_eq!(
+            storage
+                .get_setting_as::<u64>(SETTING_REQUEST_TIMEOUT_SECS)
+                .unwrap(),
+            None
+        );
+    }
+
     #[test]
     fn test_correction_deletion() {
         let storage = Storage::in_memory().unwrap();
@@ -1287,6 +2177,25 @@ mod tests {
         assert_eq!(remaining.len(), initial_count + 1);
         assert!(remaining.iter().any(|c| c.original == "recieve"));
 
+        // Deleting a general correction takes its contextual copies with it
+        let general = Correction::new(
+            "there".to_string(),
+            "their".to_string(),
+            CorrectionSource::UserEdit,
+        );
+        let contextual = Correction::new(
+            "there".to_string(),
+            "their".to_string(),
+            CorrectionSource::UserEdit,
+        )
+        .with_context("fix");
+        storage.save_correction(&general).unwrap();
+        storage.save_correction(&contextual).unwrap();
+        assert!(storage.delete_correction(&general.id).unwrap());
+        let remaining = storage.get_all_corrections().unwrap();
+        assert_eq!(remaining.len(), initial_count + 1);
+        assert!(!remaining.iter().any(|c| c.original == "there"));
+
         // Delete non-existent correction
         let not_deleted = storage.delete_correction(&uuid::Uuid::new_v4()).unwrap();
         assert!(!not_deleted);
@@ -1299,4 +2208,177 @@ mod tests {
         let empty = storage.get_all_corrections().unwrap();
         assert!(empty.is_empty());
     }
+
+    #[test]
+    fn test_dedupe_corrections_merges_casing_variants() {
+        let storage = Storage::in_memory().unwrap();
+        let curve = ConfidenceCurve::sigmoid();
+        let threshold = 0.55;
+
+        let mut lower = Correction::new(
+            "teh".to_string(),
+            "the".to_string(),
+            CorrectionSource::UserEdit,
+        );
+        lower.occurrences = 3;
+        let capitalized = Correction::new(
+            "teh".to_string(),
+            "The".to_string(),
+            CorrectionSource::UserEdit,
+        );
+        storage.save_correction_with_curve(&lower, curve).unwrap();
+        storage
+            .save_correction_with_curve(&capitalized, curve)
+            .unwrap();
+
+        // split between two rows, neither is trusted yet
+        assert_eq!(storage.get_correction("teh", threshold).unwrap(), None);
+
+        assert_eq!(storage.dedupe_corrections_with_curve(curve).unwrap(), 1);
+
+        let merged: Vec<_> = storage
+            .get_all_corrections()
+            .unwrap()
+            .into_iter()
+            .filter(|c| c.original == "teh")
+            .collect();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].corrected, "the");
+        assert_eq!(merged[0].occurrences, 4);
+        assert!(merged[0].confidence >= threshold);
+        assert_eq!(
+            storage.get_correction("teh", threshold).unwrap(),
+            Some("the".to_string())
+        );
+
+        // nothing left to merge
+        assert_eq!(storage.dedupe_corrections_with_curve(curve).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_redaction_terms() {
+        let storage = Storage::in_memory().unwrap();
+
+        storage.add_redacted_word("Darn").unwrap();
+        storage.add_redacted_word("darn").unwrap(); // duplicate ignored
+        storage.add_redaction_pattern(r"\d+").unwrap();
+
+        assert_eq!(
+            storage.get_redacted_words().unwrap(),
+            vec!["darn".to_string()]
+        );
+        assert_eq!(
+            storage.get_redaction_patterns().unwrap(),
+            vec![r"\d+".to_string()]
+        );
+
+        assert!(storage.remove_redacted_word("DARN").unwrap());
+        assert!(!storage.remove_redacted_word("darn").unwrap());
+        assert!(storage.get_redacted_words().unwrap().is_empty());
+
+        // patterns and words are tracked separately
+        assert!(!storage.remove_redacted_word(r"\d+").unwrap());
+        assert!(storage.remove_redaction_pattern(r"\d+").unwrap());
+    }
+
+    #[test]
+    fn test_vocabulary() {
+        let storage = Storage::in_memory().unwrap();
+
+        assert!(storage.add_vocabulary_word(" Kubernetes ").unwrap());
+        assert!(storage.add_vocabulary_word("FlowWhispr").unwrap());
+        assert!(!storage.add_vocabulary_word("kubernetes").unwrap());
+        assert!(!storage.add_vocabulary_word("  ").unwrap());
+
+        // the casing it was added with is kept
+        assert_eq!(
+            storage.get_vocabulary().unwrap(),
+            vec!["Kubernetes".to_string(), "FlowWhispr".to_string()]
+        );
+
+        assert!(storage.remove_vocabulary_word("KUBERNETES").unwrap());
+        assert!(!storage.remove_vocabulary_word("Kubernetes").unwrap());
+        assert_eq!(storage.get_vocabulary().unwrap(), vec!["FlowWhispr"]);
+    }
+
+    fn temp_db_path() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("flow_storage_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("flow.db")
+    }
+
+    fn backups(path: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|p| p.to_string_lossy().contains(".corrupt-"))
+            .collect()
+    }
+
+    #[test]
+    fn test_open_healthy_database_uses_wal() {
+        let path = temp_db_path();
+        {
+            let storage = Storage::open(&path).unwrap();
+            storage.add_vocabulary_word("FlowWhispr").unwrap();
+            let mode: String = storage
+                .conn
+                .lock()
+                .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(mode, "wal");
+        }
+
+        let storage = Storage::open(&path).unwrap();
+        assert!(storage.recovery().is_none());
+        assert_eq!(storage.get_vocabulary().unwrap(), vec!["FlowWhispr"]);
+        assert!(backups(&path).is_empty());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_open_corrupt_database_backs_up_and_recovers() {
+        let path = temp_db_path();
+        {
+            let storage = Storage::open(&path).unwrap();
+            storage.add_vocabulary_word("FlowWhispr").unwrap();
+        }
+        // cut the file off mid-page, as a crash during a copy would
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() / 2 + 100]).unwrap();
+
+        let storage = Storage::open(&path).unwrap();
+        let recovery = storage.recovery().expect("corruption should be reported");
+        assert!(recovery.backup_path.exists());
+        assert_eq!(backups(&path), vec![recovery.backup_path.clone()]);
+        // the new database is usable
+        storage.add_vocabulary_word("Kubernetes").unwrap();
+        assert!(
+            storage
+                .get_vocabulary()
+                .unwrap()
+                .contains(&"Kubernetes".to_string())
+        );
+        drop(storage);
+
+        // and healthy on the next launch
+        assert!(Storage::open(&path).unwrap().recovery().is_none());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_open_garbage_file_recovers_empty() {
+        let path = temp_db_path();
+        std::fs::write(&path, b"this is not a database, just some text").unwrap();
+
+        let storage = Storage::open(&path).unwrap();
+        let recovery = storage.recovery().unwrap();
+        assert!(recovery.recovered_tables.is_empty());
+        assert_eq!(
+            std::fs::read(&recovery.backup_path).unwrap(),
+            b"this is not a database, just some text"
+        );
+        assert!(storage.get_vocabulary().unwrap().is_empty());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
 }
@@ -0,0 +1,372 @@
+//! Deterministic per-mode output style
+//!
+//! The completion model is asked to write in the style of the writing mode, but
+//! doesn't always follow it. These transforms run after formatting and guarantee
+//! the parts of a mode that can be checked mechanically: Formal text is sentence
+//! cased and ends in punctuation, VeryCasual text is lowercase, and Excited text
+//! doesn't stack exclamation marks. Casual text is left as the model wrote it.
+//! `enforce_locale_punctuation` then applies the punctuation rules of the language
+//! the text is in, whatever the mode.
+
+use crate::locale::Locale;
+use crate::tokenizer::word_spans;
+use crate::types::WritingMode;
+
+/// Longest run of exclamation marks kept in Excited mode ("wow!!!!!" -> "wow!!!")
+pub const MAX_EXCLAMATION_RUN: usize = 3;
+
+/// Punctuation that ends a sentence
+const SENTENCE_END: &[char] = &['.', '!', '?', '…'];
+
+/// Closing quotes and brackets that may follow a sentence's final punctuation
+const CLOSERS: &[char] = &['"', '\'', ')', ']', '\u{201D}', '\u{2019}'];
+
+/// No-break space French puts before ':' and inside « »
+const NO_BREAK_SPACE: char = '\u{00A0}';
+
+/// Narrow no-break space French puts before '?', '!' and ';'
+const NARROW_NO_BREAK_SPACE: char = '\u{202F}';
+
+/// Abbreviations whose period doesn't end a sentence
+const ABBREVIATIONS: &[&str] = &["e.g.", "i.e.", "vs.", "mr.", "mrs.", "ms.", "dr.", "st."];
+
+/// Enforce the mechanical parts of a writing mode's style
+pub fn enforce_style(text: &str, mode: WritingMode) -> String {
+    match mode {
+        WritingMode::Formal => enforce_formal(text),
+        WritingMode::Casual => text.to_string(),
+        WritingMode::VeryCasual => enforce_very_casual(text),
+        WritingMode::Excited => enforce_excited(text),
+    }
+}
+
+/// Capitalize the start of every sentence and end the text with punctuation
+///
+/// A period is only added after a letter or digit, so text ending in ":" or an
+/// emoji is left alone.
+pub fn enforce_formal(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 1);
+    let mut copied = 0;
+    let mut sentence_start = true;
+
+    for (start, end) in word_spans(text) {
+        let gap = &text[copied..start];
+        result.push_str(gap);
+        copied = end;
+
+        let word = &text[start..end];
+        // a new line starts a new sentence (paragraphs, list items)
+        let at_start = sentence_start || gap.contains('\n');
+        if at_start {
+            result.push_str(&capitalize_first(word));
+        } else {
+            result.push_str(word);
+        }
+        sentence_start = next_is_sentence_start(word, at_start);
+    }
+    result.push_str(&text[copied..]);
+
+    let content_end = result.trim_end().len();
+    let last = result[..content_end]
+        .trim_end_matches(CLOSERS)
+        .chars()
+        .next_back();
+    if last.is_some_and(|c| c.is_alphanumeric()) {
+        result.insert(content_end, '.');
+    }
+    result
+}
+
+/// Lowercase everything except words that look like acronyms or proper nouns
+///
+/// Kept as-is: all-caps words of two or more letters ("NASA"), words with a capital
+/// after the first letter ("iPhone", "GitHub") and capitalized words in the middle
+/// of a sentence ("see you in Paris"). "I" is always lowercased.
+pub fn enforce_very_casual(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut sentence_start = true;
+
+    for (start, end) in word_spans(text) {
+        let gap = &text[copied..start];
+        result.push_str(gap);
+        copied = end;
+
+        let word = &text[start..end];
+        let at_start = sentence_start || gap.contains('\n');
+        if keeps_case(word, at_start) {
+            result.push_str(word);
+        } else {
+            result.push_str(&word.to_lowercase());
+        }
+        sentence_start = next_is_sentence_start(word, at_start);
+    }
+    result.push_str(&text[copied..]);
+    result
+}
+
+/// Shorten runs of exclamation marks to `MAX_EXCLAMATION_RUN`
+pub fn enforce_excited(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut run = 0;
+    for c in text.chars() {
+        if c == '!' {
+            run += 1;
+            if run > MAX_EXCLAMATION_RUN {
+                continue;
+            }
+        } else {
+            run = 0;
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Apply a language's punctuation rules that English doesn't have
+///
+/// French gets a no-break space before `? ! : ;` and inside « » (replacing a
+/// plain space if there is one), so the marks never wrap onto their own line.
+/// Spanish questions and exclamations that don't open with ¿ or ¡ get one at the
+/// start of their sentence. English is returned unchanged.
+pub fn enforce_locale_punctuation(text: &str, locale: Locale) -> String {
+    match locale {
+        Locale::English => text.to_string(),
+        Locale::French => enforce_french_spacing(text),
+        Locale::Spanish => enforce_spanish_inverted_marks(text),
+    }
+}
+
+/// Put the no-break spaces French typography wants around `? ! : ;` and « »
+///
+/// Marks are only spaced when they follow a word or closing quote and end it, so
+/// "10:30", "https://" and a second mark in "?!" are left alone.
+fn enforce_french_spacing(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len() + 8);
+    let mut skip_space = false;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if std::mem::take(&mut skip_space) && c == ' ' {
+            continue;
+        }
+        let space = match c {
+            ':' | '»' => NO_BREAK_SPACE,
+            '?' | '!' | ';' => NARROW_NO_BREAK_SPACE,
+            '«' => {
+                result.push(c);
+                let next = chars.get(i + 1);
+                if next.is_some_and(|&n| n == ' ' || !n.is_whitespace()) {
+                    result.push(NO_BREAK_SPACE);
+                    skip_space = true;
+                }
+                continue;
+            }
+            _ => {
+                result.push(c);
+                continue;
+            }
+        };
+
+        // the word or quote the mark belongs to, skipping a plain space before it
+        let before = result.trim_end_matches(' ');
+        let attaches = before
+            .chars()
+            .next_back()
+            .is_some_and(|p| p.is_alphanumeric() || CLOSERS.contains(&p) || p == '»');
+        let ends_word = c == '»'
+            || chars
+                .get(i + 1)
+                .is_none_or(|n| n.is_whitespace() || "?!:;»".contains(*n) || CLOSERS.contains(n));
+        let already_spaced = result.ends_with([NO_BREAK_SPACE, NARROW_NO_BREAK_SPACE]);
+
+        if attaches && ends_word && !already_spaced {
+            result.truncate(before.len());
+            result.push(space);
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Open Spanish questions and exclamations with ¿ and ¡ where they're missing
+///
+/// A heuristic: the mark goes at the start of the sentence, which is wrong for
+/// questions that start mid-sentence ("Oye, ¿vienes?") but right far more often
+/// than leaving it out. Sentences that already contain an opening mark are left alone.
+fn enforce_spanish_inverted_marks(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 8);
+    // where the current sentence's first word starts in `result`
+    let mut sentence_start: Option<usize> = None;
+
+    for (i, c) in text.char_indices() {
+        if sentence_start.is_none() && !c.is_whitespace() {
+            sentence_start = Some(result.len());
+        }
+        result.push(c);
+
+        // followed by the end of the text or a space, maybe after a closing quote
+        let ends_sentence = SENTENCE_END.contains(&c)
+            && text[i + c.len_utf8()..]
+                .trim_start_matches(CLOSERS)
+                .chars()
+                .next()
+                .is_none_or(char::is_whitespace);
+        if c == '\n' {
+            sentence_start = None;
+        } else if ends_sentence && let Some(start) = sentence_start.take() {
+            let sentence = &result[start..];
+            let opening = match c {
+                '?' if !sentence.contains('¿') && !sentence.contains('!') => Some('¿'),
+                '!' if !sentence.contains('¡') && !sentence.contains('?') => Some('¡'),
+                _ => None,
+            };
+            if let Some(opening) = opening {
+                // inside an opening quote or bracket, not before it
+                let offset = sentence
+                    .char_indices()
+                    .find(|&(_, p)| !"\"'([\u{201C}\u{2018}«".contains(p))
+                    .map_or(0, |(i, _)| i);
+                result.insert(start + offset, opening);
+            }
+        }
+    }
+    result
+}
+
+/// Whether the word after this one starts a sentence
+///
+/// Bare punctuation ("-", "*") doesn't change anything, so "- item" is capitalized.
+fn next_is_sentence_start(word: &str, at_start: bool) -> bool {
+    if !word.contains(char::is_alphanumeric) {
+        return at_start || word.ends_with(SENTENCE_END);
+    }
+    let trimmed = word.trim_end_matches(CLOSERS);
+    trimmed.ends_with(SENTENCE_END) && !ABBREVIATIONS.contains(&trimmed.to_lowercase().as_str())
+}
+
+/// Uppercase the first letter of a word, after any leading punctuation
+///
+/// Words that start with a digit or already mix cases ("iPhone") are left alone.
+fn capitalize_first(word: &str) -> String {
+    let Some((i, first)) = word.char_indices().find(|(_, c)| c.is_alphanumeric()) else {
+        return word.to_string();
+    };
+    if !first.is_lowercase() || word[i..].chars().skip(1).any(char::is_uppercase) {
+        return word.to_string();
+    }
+
+    let mut result = String::with_capacity(word.len());
+    result.push_str(&word[..i]);
+    result.extend(first.to_uppercase());
+    result.push_str(&word[i + first.len_utf8()..]);
+    result
+}
+
+/// Whether a VeryCasual word keeps its capitals
+fn keeps_case(word: &str, sentence_start: bool) -> bool {
+    let core = word.trim_matches(|c: char| !c.is_alphanumeric());
+    let letters: Vec<char> = core.chars().filter(|c| c.is_alphabetic()).collect();
+    let Some(&first) = letters.first() else {
+        return true;
+    };
+
+    // "I", "I'm", "I'll"
+    if core == "I" || core.starts_with("I'") || core.starts_with("I\u{2019}") {
+        return false;
+    }
+    if letters.len() >= 2 && letters.iter().all(|c| c.is_uppercase()) {
+        return true;
+    }
+    if letters[1..].iter().any(|c| c.is_uppercase()) {
+        return true;
+    }
+    first.is_uppercase() && !sentence_start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formal_sentence_case_and_period() {
+        assert_eq!(
+            enforce_formal("thanks for the update. i'll review it tomorrow"),
+            "Thanks for the update. I'll review it tomorrow."
+        );
+        assert_eq!(
+            enforce_formal("see the notes, e.g. the budget? yes\n- check iPhone build"),
+            "See the notes, e.g. the budget? Yes\n- Check iPhone build."
+        );
+        // already punctuated text and trailing whitespace are kept
+        assert_eq!(enforce_formal("Done!\n"), "Done!\n");
+        assert_eq!(enforce_formal("he said \"hi\""), "He said \"hi\".");
+        assert_eq!(enforce_formal(""), "");
+    }
+
+    #[test]
+    fn test_very_casual_lowercases_except_acronyms_and_names() {
+        assert_eq!(
+            enforce_very_casual("Hey! I'm meeting Sarah at the NASA office. Bring your iPhone"),
+            "hey! i'm meeting Sarah at the NASA office. bring your iPhone"
+        );
+        assert_eq!(enforce_very_casual("OK sounds good"), "OK sounds good");
+        assert_eq!(enforce_very_casual("Sounds good"), "sounds good");
+    }
+
+    #[test]
+    fn test_excited_caps_exclamation_runs() {
+        assert_eq!(
+            enforce_excited("We won!!!!!! Best day ever!! Really?!"),
+            "We won!!! Best day ever!! Really?!"
+        );
+    }
+
+    #[test]
+    fn test_french_punctuation_spacing() {
+        assert_eq!(
+            enforce_locale_punctuation(
+                "Tu viens ? Oui: à 10:30! Voir https://flow.app;",
+                Locale::French
+            ),
+            "Tu viens\u{202F}? Oui\u{00A0}: à 10:30\u{202F}! Voir https://flow.app\u{202F};"
+        );
+        assert_eq!(
+            enforce_locale_punctuation("« oui » et «non»", Locale::French),
+            "«\u{00A0}oui\u{00A0}» et «\u{00A0}non\u{00A0}»"
+        );
+        assert_eq!(
+            enforce_locale_punctuation("Il a dit «bonjour» ?!", Locale::French),
+            "Il a dit «\u{00A0}bonjour\u{00A0}»\u{202F}?!"
+        );
+        // already spaced text is unchanged
+        let spaced = "Vraiment\u{202F}?";
+        assert_eq!(enforce_locale_punctuation(spaced, Locale::French), spaced);
+        assert_eq!(
+            enforce_locale_punctuation("Really? Yes: 10:30!", Locale::English),
+            "Really? Yes: 10:30!"
+        );
+    }
+
+    #[test]
+    fn test_spanish_inverted_marks() {
+        assert_eq!(
+            enforce_locale_punctuation("Vienes mañana? Qué bien! Hasta luego.", Locale::Spanish),
+            "¿Vienes mañana? ¡Qué bien! Hasta luego."
+        );
+        assert_eq!(
+            enforce_locale_punctuation("¿Ya llegaste? \"Dónde estás?\"\nno sé", Locale::Spanish),
+            "¿Ya llegaste? \"¿Dónde estás?\"\nno sé"
+        );
+    }
+
+    #[test]
+    fn test_casual_is_untouched() {
+        let text = "hey there. gonna be late!!!!";
+        assert_eq!(enforce_style(text, WritingMode::Casual), text);
+        assert_eq!(
+            enforce_style(text, WritingMode::Formal),
+            "Hey there. Gonna be late!!!!"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! Word tokenization for correction learning
+//!
+//! The learning engine aligns and rewrites text token by token. English and most
+//! other languages split on whitespace, but Chinese and Japanese don't separate
+//! words with spaces, so a whole utterance would be a single token and no
+//! correction could ever match. Tokenizers return byte spans so the text between
+//! tokens (spaces, newlines, or nothing at all) is kept verbatim.
+
+use strsim::jaro_winkler;
+
+/// Similarity given to two different single CJK characters, just above the default
+/// learning threshold so a one-character substitution (e.g. a homophone) can be learned
+const CJK_CHARACTER_SIMILARITY: f64 = 0.75;
+
+/// Splits text into the tokens corrections are learned and applied on
+pub trait Tokenizer: Send + Sync {
+    /// Byte ranges of the tokens in text, in order and non-overlapping
+    fn spans(&self, text: &str) -> Vec<(usize, usize)>;
+
+    /// Tokens of text as string slices
+    fn tokens<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        self.spans(text)
+            .into_iter()
+            .map(|(start, end)| &text[start..end])
+            .collect()
+    }
+
+    /// How alike two tokens are (0.0 - 1.0), used for alignment and typo detection
+    fn similarity(&self, a: &str, b: &str) -> f64 {
+        jaro_winkler(a, b)
+    }
+}
+
+/// Whitespace-separated words (the default)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WhitespaceTokenizer;
+
+impl Tokenizer for WhitespaceTokenizer {
+    fn spans(&self, text: &str) -> Vec<(usize, usize)> {
+        word_spans(text)
+    }
+}
+
+/// Each Chinese character or kana is its own token; other text splits on whitespace
+///
+/// A dictionary-free approximation of word segmentation: corrections are learned
+/// per character, which covers the common single-character ASR mistakes
+/// ("在" vs "再") without needing a segmentation model.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CjkTokenizer;
+
+impl Tokenizer for CjkTokenizer {
+    fn spans(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut spans = Vec::new();
+        let mut start = None;
+        for (i, c) in text.char_indices() {
+            if c.is_whitespace() || is_cjk(c) {
+                if let Some(s) = start.take() {
+                    spans.push((s, i));
+                }
+                if is_cjk(c) {
+                    spans.push((i, i + c.len_utf8()));
+                }
+            } else if start.is_none() {
+                start = Some(i);
+            }
+        }
+        if let Some(s) = start {
+            spans.push((s, text.len()));
+        }
+        spans
+    }
+
+    fn similarity(&self, a: &str, b: &str) -> f64 {
+        let mut a_chars = a.chars();
+        let mut b_chars = b.chars();
+        match (
+            a_chars.next(),
+            a_chars.next(),
+            b_chars.next(),
+            b_chars.next(),
+        ) {
+            // single characters have no spelling to compare
+            (Some(x), None, Some(y), None) if is_cjk(x) && is_cjk(y) => {
+                if x == y {
+                    1.0
+                } else {
+                    CJK_CHARACTER_SIMILARITY
+                }
+            }
+            _ => jaro_winkler(a, b),
+        }
+    }
+}
+
+/// Byte ranges of the whitespace-separated words in text
+pub(crate) fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+/// Chinese characters and Japanese kana, which aren't space-delimited
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'   // hiragana, katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+        | '\u{F900}'..='\u{FAFF}' // CJK compatibility ideographs
+        | '\u{20000}'..='\u{2FFFF}' // CJK extensions B and later
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whitespace_tokens() {
+        let tokens = WhitespaceTokenizer.tokens("  hello,  world\nagain ");
+        assert_eq!(tokens, vec!["hello,", "world", "again"]);
+        assert_eq!(
+            WhitespaceTokenizer.tokens("我们明天再见"),
+            vec!["我们明天再见"]
+        );
+    }
+
+    #[test]
+    fn test_cjk_tokens() {
+        assert_eq!(
+            CjkTokenizer.tokens("我们明天再见。"),
+            vec!["我", "们", "明", "天", "再", "见", "。"]
+        );
+        // latin runs inside CJK text stay whole
+        assert_eq!(
+            CjkTokenizer.tokens("用iPhone 15拍照"),
+            vec!["用", "iPhone", "15", "拍", "照"]
+        );
+        assert_eq!(
+            CjkTokenizer.tokens("ひらがなOK"),
+            vec!["ひ", "ら", "が", "な", "OK"]
+        );
+        assert!(CjkTokenizer.tokens("").is_empty());
+    }
+
+    #[test]
+    fn test_cjk_similarity() {
+        assert_eq!(CjkTokenizer.similarity("在", "在"), 1.0);
+        assert_eq!(
+            CjkTokenizer.similarity("在", "再"),
+            CJK_CHARACTER_SIMILARITY
+        );
+        assert_eq!(
+            CjkTokenizer.similarity("recieve", "receive"),
+            jaro_winkler("recieve", "receive")
+        );
+    }
+}
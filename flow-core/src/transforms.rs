@@ -0,0 +1,197 @@
+//! User-registered text transforms
+//!
+//! Apps embedding Flow can hook their own logic into the pipeline (an org glossary,
+//! analytics, custom cleanup) without changing the built-in stages. A
+//! `TextTransform` is registered for a `TransformStage`, which says which built-in
+//! stage it runs after. Transforms at the same stage run in the order they were
+//! registered, each seeing the output of the one before.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::RwLock;
+
+use crate::types::WritingMode;
+
+/// Where in the pipeline a transform runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransformStage {
+    /// On the raw transcription, before shortcuts
+    AfterTranscription,
+    /// After shortcuts and spoken formatting commands
+    AfterShortcuts,
+    /// After learned corrections, before the completion model formats the text.
+    /// In cloud mode the worker formats while transcribing, so these run on its
+    /// formatted text instead.
+    AfterCorrections,
+    /// After formatting and style enforcement, before redaction and normalization
+    AfterFormatting,
+}
+
+/// What a transform knows about the text it's given
+#[derive(Debug, Clone, Copy)]
+pub struct TransformContext<'a> {
+    /// App the text is being dictated into
+    pub app_name: Option<&'a str>,
+    /// Writing mode used for this transcription
+    pub mode: WritingMode,
+    /// Stage the transform runs at
+    pub stage: TransformStage,
+}
+
+/// A step in the pipeline that rewrites text
+pub trait TextTransform: Send + Sync {
+    /// Return the rewritten text, or the input unchanged
+    fn transform(&self, text: &str, ctx: &TransformContext<'_>) -> String;
+}
+
+impl<F> TextTransform for F
+where
+    F: Fn(&str, &TransformContext<'_>) -> String + Send + Sync,
+{
+    fn transform(&self, text: &str, ctx: &TransformContext<'_>) -> String {
+        self(text, ctx)
+    }
+}
+
+struct Registered {
+    id: u64,
+    stage: TransformStage,
+    transform: Arc<dyn TextTransform>,
+}
+
+/// Transforms registered with an engine, in registration order
+#[derive(Default)]
+pub struct TransformRegistry {
+    transforms: RwLock<Vec<Registered>>,
+    next_id: AtomicU64,
+}
+
+impl TransformRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a transform to run at `stage`, after the ones already there
+    ///
+    /// Returns an id for `remove`, never 0.
+    pub fn register(&self, stage: TransformStage, transform: impl TextTransform + 'static) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.transforms.write().push(Registered {
+            id,
+            stage,
+            transform: Arc::new(transform),
+        });
+        id
+    }
+
+    /// Unregister a transform, returning whether it was registered
+    pub fn remove(&self, id: u64) -> bool {
+        let mut transforms = self.transforms.write();
+        let before = transforms.len();
+        transforms.retain(|registered| registered.id != id);
+        transforms.len() != before
+    }
+
+    /// Unregister every transform
+    pub fn clear(&self) {
+        self.transforms.write().clear();
+    }
+
+    /// Number of registered transforms
+    pub fn len(&self) -> usize {
+        self.transforms.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.read().is_empty()
+    }
+
+    /// Run the transforms registered for `ctx.stage` over the text, in order
+    pub fn apply(&self, text: &str, ctx: &TransformContext<'_>) -> String {
+        // clone the list so a transform can register or remove transforms
+        // without deadlocking
+        let transforms: Vec<Arc<dyn TextTransform>> = self
+            .transforms
+            .read()
+            .iter()
+            .filter(|registered| registered.stage == ctx.stage)
+            .map(|registered| Arc::clone(&registered.transform))
+            .collect();
+
+        let mut text = text.to_string();
+        for transform in transforms {
+            text = transform.transform(&text, ctx);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(stage: TransformStage) -> TransformContext<'static> {
+        TransformContext {
+            app_name: Some("Slack"),
+            mode: WritingMode::Casual,
+            stage,
+        }
+    }
+
+    #[test]
+    fn test_transforms_run_in_order() {
+        let registry = TransformRegistry::new();
+        registry.register(
+            TransformStage::AfterCorrections,
+            |text: &str, _: &TransformContext<'_>| text.replace("k8s", "Kubernetes"),
+        );
+        registry.register(
+            TransformStage::AfterCorrections,
+            |text: &str, _: &TransformContext<'_>| {
+                // sees the glossary's output
+                format!(
+                    "{text} (mentions Kubernetes: {})",
+                    text.contains("Kubernetes")
+                )
+            },
+        );
+
+        assert_eq!(
+            registry.apply("deploy to k8s", &context(TransformStage::AfterCorrections)),
+            "deploy to Kubernetes (mentions Kubernetes: true)"
+        );
+        // other stages are untouched
+        assert_eq!(
+            registry.apply("deploy to k8s", &context(TransformStage::AfterShortcuts)),
+            "deploy to k8s"
+        );
+    }
+
+    #[test]
+    fn test_remove_transform() {
+        let registry = TransformRegistry::new();
+        let first = registry.register(
+            TransformStage::AfterFormatting,
+            |text: &str, _: &TransformContext<'_>| text.to_uppercase(),
+        );
+        let second = registry.register(
+            TransformStage::AfterFormatting,
+            |text: &str, ctx: &TransformContext<'_>| {
+                format!("{text} via {}", ctx.app_name.unwrap_or("?"))
+            },
+        );
+        assert_ne!(first, second);
+        assert_eq!(registry.len(), 2);
+
+        assert!(registry.remove(first));
+        assert!(!registry.remove(first));
+        assert_eq!(
+            registry.apply("hi", &context(TransformStage::AfterFormatting)),
+            "hi via Slack"
+        );
+
+        registry.clear();
+        assert!(registry.is_empty());
+    }
+}
@@ -142,6 +142,33 @@ impl TranscriptionHistoryEntry {
     }
 }
 
+/// A recording queued for transcription after a network failure
+#[derive(Debug, Clone)]
+pub struct PendingTranscription {
+    pub id: TranscriptionId,
+    pub audio: AudioData,
+    pub sample_rate: u32,
+    pub app_name: Option<String>,
+    /// Number of failed retry attempts
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PendingTranscription {
+    pub fn new(audio: AudioData, sample_rate: u32, app_name: Option<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            audio,
+            sample_rate,
+            app_name,
+            attempts: 0,
+            last_error: None,
+            created_at: Utc::now(),
+        }
+    }
+}
+
 impl Transcription {
     pub fn new(
         raw_text: String,
@@ -170,6 +197,15 @@ pub struct AppContext {
     pub category: AppCategory,
 }
 
+/// Per-app completion provider/model override
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppModelOverride {
+    /// Completion provider name ("openai", "gemini", "openrouter"), or None for the global provider
+    pub provider: Option<String>,
+    /// Model name, or None for the provider's default model
+    pub model: Option<String>,
+}
+
 /// Categories of applications for mode suggestions
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -292,6 +328,9 @@ pub struct Shortcut {
     pub case_sensitive: bool,
     pub enabled: bool,
     pub use_count: u32,
+    /// When the shortcut last fired, None if it never has
+    #[serde(default)]
+    pub last_used_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -306,6 +345,7 @@ impl Shortcut {
             case_sensitive: false,
             enabled: true,
             use_count: 0,
+            last_used_at: None,
             created_at: now,
             updated_at: now,
         }
@@ -318,6 +358,10 @@ pub struct Correction {
     pub id: CorrectionId,
     pub original: String,
     pub corrected: String,
+    /// Lowercased word that must precede `original` for the correction to apply
+    /// (None applies it anywhere)
+    #[serde(default)]
+    pub context: Option<String>,
     pub occurrences: u32,
     pub confidence: f32,
     pub source: CorrectionSource,
@@ -332,6 +376,7 @@ impl Correction {
             id: Uuid::new_v4(),
             original,
             corrected,
+            context: None,
             occurrences: 1,
             confidence: 0.5, // starts at 50%
             source,
@@ -340,12 +385,91 @@ impl Correction {
         }
     }
 
-    /// Update confidence using logarithmic scaling
+    /// Only apply the correction after the given preceding word
+    pub fn with_context(mut self, previous_word: impl Into<String>) -> Self {
+        self.context = Some(previous_word.into().to_lowercase());
+        self
+    }
+
+    /// Update confidence using the default (logarithmic) curve
     /// Formula: confidence = 0.5 + 0.5 * (1 - 1/ln(occurrences + e))
     pub fn update_confidence(&mut self) {
-        let e = std::f32::consts::E;
-        self.confidence = 0.5 + 0.5 * (1.0 - 1.0 / (self.occurrences as f32 + e).ln());
-        self.confidence = self.confidence.min(0.99); // cap at 99%
+        self.update_confidence_with(ConfidenceCurve::default());
+    }
+
+    /// Update confidence from the occurrence count using the given curve
+    pub fn update_confidence_with(&mut self, curve: ConfidenceCurve) {
+        self.confidence = curve.confidence(self.occurrences);
+    }
+}
+
+/// Maximum confidence any curve can reach
+pub const MAX_CORRECTION_CONFIDENCE: f32 = 0.99;
+
+/// How a correction's confidence grows with the number of times it was seen
+///
+/// Approximate confidence by occurrence count (with default parameters):
+///
+/// | occurrences | Logarithmic | Linear | Sigmoid |
+/// |-------------|-------------|--------|---------|
+/// | 1           | 0.62        | 0.30   | 0.12    |
+/// | 2           | 0.68        | 0.40   | 0.27    |
+/// | 3           | 0.71        | 0.50   | 0.50    |
+/// | 4           | 0.74        | 0.60   | 0.73    |
+/// | 5           | 0.76        | 0.70   | 0.88    |
+/// | 10          | 0.80        | 0.99   | 0.99    |
+///
+/// All curves are capped at 0.99.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ConfidenceCurve {
+    /// 0.5 + 0.5 * (1 - 1/ln(occurrences + e)): starts high and grows slowly
+    #[default]
+    Logarithmic,
+    /// start + step * (occurrences - 1)
+    Linear { start: f32, step: f32 },
+    /// 1 / (1 + exp(-steepness * (occurrences - midpoint))): stays low until
+    /// `midpoint` occurrences (where it reaches 0.5), then rises quickly
+    Sigmoid { midpoint: f32, steepness: f32 },
+}
+
+impl ConfidenceCurve {
+    /// Linear curve with default parameters (0.3 at one occurrence, +0.1 per occurrence)
+    pub fn linear() -> Self {
+        Self::Linear {
+            start: 0.3,
+            step: 0.1,
+        }
+    }
+
+    /// Sigmoid curve with default parameters (0.5 at three occurrences)
+    pub fn sigmoid() -> Self {
+        Self::Sigmoid {
+            midpoint: 3.0,
+            steepness: 1.0,
+        }
+    }
+
+    /// Confidence for a correction seen `occurrences` times
+    pub fn confidence(&self, occurrences: u32) -> f32 {
+        let n = occurrences as f32;
+        let confidence = match *self {
+            Self::Logarithmic => {
+                let e = std::f32::consts::E;
+                0.5 + 0.5 * (1.0 - 1.0 / (n + e).ln())
+            }
+            Self::Linear { start, step } => start + step * (n - 1.0),
+            Self::Sigmoid {
+                midpoint,
+                steepness,
+            } => 1.0 / (1.0 + (-steepness * (n - midpoint)).exp()),
+        };
+        confidence.clamp(0.0, MAX_CORRECTION_CONFIDENCE)
+    }
+
+    /// Smallest occurrence count whose confidence reaches `threshold`
+    /// Returns None if the curve never reaches it within `max_occurrences`
+    pub fn occurrences_to_reach(&self, threshold: f32, max_occurrences: u32) -> Option<u32> {
+        (1..=max_occurrences).find(|&n| self.confidence(n) >= threshold)
     }
 }
 
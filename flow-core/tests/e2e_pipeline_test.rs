@@ -183,7 +183,7 @@ fn test_learning_persists_across_instances() {
 
 #[test]
 fn test_mode_with_app_specific_override() {
-    let mut engine = WritingModeEngine::new(WritingMode::Casual);
+    let engine = WritingModeEngine::new(WritingMode::Casual);
 
     // default for an unknown app
     assert_eq!(engine.get_mode("MyApp"), WritingMode::Casual);
@@ -199,7 +199,7 @@ fn test_mode_with_app_specific_override() {
 #[test]
 fn test_mode_selection_with_storage() {
     let storage = Storage::in_memory().unwrap();
-    let mut engine = WritingModeEngine::new(WritingMode::Casual);
+    let engine = WritingModeEngine::new(WritingMode::Casual);
 
     // set and persist mode
     engine
@@ -207,7 +207,7 @@ fn test_mode_selection_with_storage() {
         .unwrap();
 
     // create new engine and load from storage
-    let mut engine2 = WritingModeEngine::new(WritingMode::Casual);
+    let engine2 = WritingModeEngine::new(WritingMode::Casual);
     let mode = engine2.get_mode_with_storage("Slack", &storage);
     assert_eq!(mode, WritingMode::VeryCasual);
 }
@@ -384,7 +384,7 @@ fn test_shortcut_persistence() {
 #[test]
 fn test_app_context_determines_mode() {
     let storage = Storage::in_memory().unwrap();
-    let mut engine = WritingModeEngine::new(WritingMode::Casual);
+    let engine = WritingModeEngine::new(WritingMode::Casual);
 
     // Without an explicit override, get_mode_with_storage returns the default
     let mode = engine.get_mode_with_storage("Mail", &storage);
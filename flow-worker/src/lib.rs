@@ -47,6 +47,9 @@ struct CompletionParams {
     shortcuts_triggered: Vec<String>,
     #[serde(default)]
     voice_instruction: Option<String>,
+    /// Language code the client formats for (e.g. "fr"), absent to keep the spoken language
+    #[serde(default)]
+    locale: Option<String>,
 }
 
 // ============ Base10 Types ============
@@ -184,7 +187,12 @@ struct CombinedResponse {
 
 // ============ Helper Functions ============
 
-fn build_system_prompt(mode: &str, app_context: Option<&str>, shortcuts: &[String]) -> String {
+fn build_system_prompt(
+    mode: &str,
+    app_context: Option<&str>,
+    shortcuts: &[String],
+    locale: Option<&str>,
+) -> String {
     let mut prompt = String::from(
         "You are a text formatter. The user will provide raw transcribed text wrapped in <TRANSCRIPTION> tags. \
          Reformat ONLY the text inside according to the style below. Output the reformatted text exactly as it would \
@@ -203,6 +211,12 @@ fn build_system_prompt(mode: &str, app_context: Option<&str>, shortcuts: &[Strin
         prompt.push_str(". Adjust formatting for this context.");
     }
 
+    if let Some(locale) = locale {
+        prompt.push_str("\n\nLanguage: the text is in the language with code ");
+        prompt.push_str(locale);
+        prompt.push_str(". Keep it in that language and follow its punctuation conventions.");
+    }
+
     if !shortcuts.is_empty() {
         let shortcuts_info: Vec<String> = shortcuts.iter().map(|s| format!("\"{}\"", s)).collect();
         prompt.push_str(&format!(
@@ -480,13 +494,14 @@ async fn call_openrouter(
     mode: &str,
     app_context: Option<&str>,
     shortcuts: &[String],
+    locale: Option<&str>,
 ) -> Result<String> {
     let api_key = env
         .var("OPENROUTER_API_KEY")
         .map_err(|_| worker::Error::RustError("Missing OPENROUTER_API_KEY".to_string()))?
         .to_string();
 
-    let system_prompt = build_system_prompt(mode, app_context, shortcuts);
+    let system_prompt = build_system_prompt(mode, app_context, shortcuts, locale);
 
     let request = OpenRouterRequest {
         models: vec![
@@ -807,6 +822,11 @@ async fn validate_corrections(
 
 #[event(fetch)]
 pub async fn main(mut req: Request, env: Env, _ctx: worker::Context) -> Result<Response> {
+    // Route: /health (reachability check for clients, runs no models)
+    if req.method() == Method::Get && req.path() == "/health" {
+        return Response::ok("ok");
+    }
+
     if req.method() != Method::Post {
         return Response::error("Method Not Allowed", 405);
     }
@@ -897,6 +917,7 @@ pub async fn main(mut req: Request, env: Env, _ctx: worker::Context) -> Result<R
             &request.completion.mode,
             request.completion.app_context.as_deref(),
             &request.completion.shortcuts_triggered,
+            request.completion.locale.as_deref(),
         )
         .await?
     };